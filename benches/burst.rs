@@ -0,0 +1,83 @@
+//! Gaps between the datagrams a client sends during a bulk transfer over
+//! the loopback, with its bursts limited to quiche's send quantum and with
+//! every packet available sent at once (`--burst-override` with a limit
+//! never reached).
+//!
+//! > cargo bench --bench burst
+
+use std::time::Duration;
+
+use quiche_test::{client::Workload, config::ClientOptions, shared::testing::UdpRun};
+
+const MESSAGE_LEN: usize = 4 << 20;
+const RUNS: usize = 3;
+
+fn config(server: bool) -> quiche::Config {
+    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+
+    config.set_application_protos(&[b"http/0.9"]).unwrap();
+
+    if server {
+        config
+            .load_cert_chain_from_pem_file("certs/localhost.crt")
+            .unwrap();
+        config
+            .load_priv_key_from_pem_file("certs/localhost.key")
+            .unwrap();
+    } else {
+        config.verify_peer(false);
+    }
+
+    config.set_initial_max_streams_bidi(100);
+    config.set_initial_max_data(100_000_000);
+    config.set_initial_max_stream_data_bidi_local(100_000_000);
+    config.set_initial_max_stream_data_bidi_remote(100_000_000);
+    config.set_active_connection_id_limit(20);
+
+    config
+}
+
+/// Mean and standard deviation of the gaps between the datagrams sent by
+/// the client while echoing one large message.
+fn gaps(burst_override: Option<usize>, run: usize) -> (Duration, Duration) {
+    let opts = ClientOptions {
+        messages: vec![vec![b'x'; MESSAGE_LEN]],
+        burst_override,
+        ..Default::default()
+    };
+    let workload = Workload::from_options(&opts, 1).unwrap();
+    let port_file =
+        std::env::temp_dir().join(format!("quiche-test-{}-burst-{}", std::process::id(), run));
+
+    let mut udp = UdpRun::start(config(true), &mut config(false), &workload, &port_file).unwrap();
+    let completed = udp.run(1, Duration::from_secs(120)).unwrap();
+    std::fs::remove_file(port_file).unwrap();
+    assert_eq!(completed.len(), 1, "the transfer did not complete");
+
+    let gaps: Vec<f64> = udp
+        .departures()
+        .windows(2)
+        .map(|w| (w[1] - w[0]).as_secs_f64())
+        .collect();
+
+    let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+    let variance = gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / gaps.len() as f64;
+
+    (
+        Duration::from_secs_f64(mean),
+        Duration::from_secs_f64(variance.sqrt()),
+    )
+}
+
+fn main() {
+    for (name, burst_override) in [("send quantum", None), ("unlimited", Some(usize::MAX))] {
+        for run in 0..RUNS {
+            let (mean, jitter) = gaps(burst_override, run);
+
+            println!(
+                "{:>12}, run {}: inter-packet gap {:>10.3?}, jitter {:>10.3?}",
+                name, run, mean, jitter
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Time taken by a transfer with every packet logged at trace level, and
+//! with one packet logged out of 1000 (`--log-sample-packets 1000`).
+//!
+//! The logs go to a sink, the difference is the cost of formatting them.
+//!
+//! > cargo bench --bench log_sample
+
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use quiche_test::{
+    client::{Client, Workload},
+    config::ClientOptions,
+    report::TargetReport,
+    shared::testing::{EchoServer, Link, Simulator},
+};
+
+const MESSAGE_LEN: usize = 8 << 20;
+const RUNS: u32 = 3;
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+fn config(server: bool) -> quiche::Config {
+    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+
+    config.set_application_protos(&[b"http/0.9"]).unwrap();
+
+    if server {
+        config
+            .load_cert_chain_from_pem_file("certs/localhost.crt")
+            .unwrap();
+        config
+            .load_priv_key_from_pem_file("certs/localhost.key")
+            .unwrap();
+    } else {
+        config.verify_peer(false);
+    }
+
+    config.set_initial_max_streams_bidi(100);
+    config.set_initial_max_data(100_000_000);
+    config.set_initial_max_stream_data_bidi_local(100_000_000);
+    config.set_initial_max_stream_data_bidi_remote(100_000_000);
+    config.set_active_connection_id_limit(20);
+
+    config
+}
+
+/// Echoes one large message and returns the wall clock time it took.
+fn transfer(log_sample_packets: u64) -> Duration {
+    let opts = ClientOptions {
+        messages: vec![vec![b'x'; MESSAGE_LEN]],
+        log_sample_packets,
+        ..Default::default()
+    };
+    let workload = Workload::from_options(&opts, 1).unwrap();
+
+    let scid = quiche::ConnectionId::from_ref(&[0xba; quiche::MAX_CONN_ID_LEN]);
+    let conn = quiche::connect(
+        Some("localhost"),
+        &scid,
+        addr(9000),
+        addr(8000),
+        &mut config(false),
+    )
+    .unwrap();
+
+    let now = Instant::now();
+    let report = TargetReport::new("bench", addr(8000), 1);
+    let client = Client::new(
+        conn,
+        &workload,
+        vec![addr(9000)],
+        vec![addr(8000)],
+        report,
+        now,
+    );
+    let server = EchoServer::new(config(true), vec![addr(8000)]);
+    let mut sim = Simulator::new(
+        client,
+        server,
+        vec![Link::new(Duration::from_millis(5))],
+        1,
+        now,
+    );
+
+    let start = Instant::now();
+    let completed = sim.run(1, Duration::from_secs(600));
+    assert_eq!(completed.len(), 1, "the transfer did not complete");
+
+    start.elapsed()
+}
+
+fn main() {
+    env_logger::builder()
+        .filter_module("quiche_test", log::LevelFilter::Trace)
+        .target(env_logger::Target::Pipe(Box::new(io::sink())))
+        .init();
+
+    for every in [1, 1000] {
+        let elapsed: Duration = (0..RUNS).map(|_| transfer(every)).sum::<Duration>() / RUNS;
+        let mbps = (2 * MESSAGE_LEN) as f64 * 8.0 / elapsed.as_secs_f64() / 1e6;
+
+        println!(
+            "trace, 1 packet logged out of {:>4}: {:>10.3?} per transfer, {:.1} Mbit/s",
+            every, elapsed, mbps
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Streams read per wakeup and longest wakeup of a client echoing 5000
+//! small messages at once, each on a stream of its own, with the default
+//! `--read-budget` and with an unlimited one. The client and the server are
+//! real quiche connections, linked in virtual time by the simulator.
+//!
+//! > cargo bench --bench read_budget
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use quiche_test::{
+    client::{Client, Workload},
+    config::ClientOptions,
+    report::TargetReport,
+    shared::{
+        read_budget::DEFAULT_READ_BUDGET,
+        testing::{EchoServer, Link, Simulator},
+    },
+};
+
+const STREAMS: usize = 5000;
+const RUNS: usize = 3;
+
+fn config(server: bool) -> quiche::Config {
+    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+
+    config.set_application_protos(&[b"http/0.9"]).unwrap();
+
+    if server {
+        config
+            .load_cert_chain_from_pem_file("certs/localhost.crt")
+            .unwrap();
+        config
+            .load_priv_key_from_pem_file("certs/localhost.key")
+            .unwrap();
+    } else {
+        config.verify_peer(false);
+    }
+
+    config.set_initial_max_streams_bidi(STREAMS as u64);
+    config.set_initial_max_data(100_000_000);
+    config.set_initial_max_stream_data_bidi_local(1_000_000);
+    config.set_initial_max_stream_data_bidi_remote(1_000_000);
+    config.set_active_connection_id_limit(20);
+
+    config
+}
+
+/// Echoes the messages with `read_budget`, and returns the report of the
+/// client, the longest step it took and the duration of the whole run.
+fn echo(read_budget: usize) -> (TargetReport, Duration, Duration) {
+    let opts = ClientOptions {
+        messages: (0..STREAMS).map(|i| i.to_string().into_bytes()).collect(),
+        read_budget,
+        ..Default::default()
+    };
+    let mut workload = Workload::from_options(&opts, 1).unwrap();
+    workload.fit_paths(1);
+
+    let local_addr = SocketAddr::from(([127, 0, 0, 1], 9000));
+    let peer_addr = SocketAddr::from(([127, 0, 0, 1], 8000));
+
+    let scid = quiche::ConnectionId::from_ref(&[0xba; quiche::MAX_CONN_ID_LEN]);
+    let conn = quiche::connect(None, &scid, local_addr, peer_addr, &mut config(false)).unwrap();
+
+    let now = Instant::now();
+    let report = TargetReport::new("bench", peer_addr, STREAMS);
+    let client = Client::new(
+        conn,
+        &workload,
+        vec![local_addr],
+        vec![peer_addr],
+        report,
+        now,
+    );
+    let server = EchoServer::new(config(true), vec![peer_addr]);
+    let mut sim = Simulator::new(
+        client,
+        server,
+        vec![Link::new(Duration::from_millis(10))],
+        1,
+        now,
+    );
+
+    let started = Instant::now();
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    let elapsed = started.elapsed();
+    assert_eq!(completed.len(), STREAMS, "the echoes did not complete");
+
+    let longest_step = sim.longest_step();
+
+    (sim.into_client().into_report(), longest_step, elapsed)
+}
+
+fn main() {
+    for (name, read_budget) in [("default", DEFAULT_READ_BUDGET), ("unlimited", usize::MAX)] {
+        for run in 0..RUNS {
+            let (report, longest_step, elapsed) = echo(read_budget);
+
+            println!(
+                "{:>9}, run {}: {:>10.3?} in all, longest wakeup {:>10.3?}, streams per wakeup {}",
+                name, run, elapsed, longest_step, report.stream_reads
+            );
+        }
+    }
+}
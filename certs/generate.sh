@@ -0,0 +1,35 @@
+#!/bin/sh
+# Generates the certificates used for local testing: a CA and a certificate
+# for localhost, 127.0.0.1 and ::1 signed by it. The key of the CA is
+# deleted once the certificate is signed, so that it cannot sign anything
+# else, and the key of the localhost certificate is only good for loopback
+# addresses.
+#
+# Run from anywhere, the files are written next to this script.
+set -eu
+
+cd "$(dirname "$0")"
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -keyout "$tmp/ca.key" -out ca.crt -days 36500 \
+    -subj "/CN=quiche-test local CA" \
+    -addext "basicConstraints=critical,CA:TRUE" \
+    -addext "keyUsage=critical,keyCertSign,cRLSign"
+
+openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -keyout localhost.key -out "$tmp/localhost.csr" \
+    -subj "/CN=localhost"
+
+cat > "$tmp/localhost.ext" <<EXT
+basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=serverAuth,clientAuth
+subjectAltName=DNS:localhost,IP:127.0.0.1,IP:::1
+EXT
+
+openssl x509 -req -in "$tmp/localhost.csr" -CA ca.crt -CAkey "$tmp/ca.key" \
+    -CAcreateserial -CAserial "$tmp/ca.srl" -out localhost.crt -days 36500 \
+    -extfile "$tmp/localhost.ext"
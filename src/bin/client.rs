@@ -1,8 +1,36 @@
-use core::str;
-use std::{collections::HashMap, env, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::File,
+    io::{BufRead, IsTerminal, Write},
+    net::{Ipv4Addr, SocketAddr},
+    os::fd::FromRawFd,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use quiche_test::shared::{
-    generate_cid_and_reset_token, read_loop, write_loop, MAX_NUMBER_SOCKETS,
+use quiche_test::{
+    client::{Client, StepOutput, Workload},
+    config::{ClientOptions, Target, Validate},
+    events::{ConnEvents, Event, Hooks, JsonEvents, LogEvents, ProgressLine},
+    path_cache::PathCache,
+    report::{
+        Comparison, CwndSampler, HandshakeTrials, Outcome, RecordWriter, Report, SocketStats,
+        TargetReport,
+    },
+    resume::{ResumeState, TargetProgress},
+    shared::{
+        control::RunId,
+        demux::Demux,
+        is_uni_stream, payload, ports,
+        rng::{random_seed, SeededRng},
+        run_clock::RunClock,
+        transport::{Closed, DatagramTransport, Socks5, Udp},
+        GREASE_VERSION,
+    },
 };
 
 #[macro_use]
@@ -13,229 +41,1328 @@ use ring::rand::*;
 
 const MAX_BUF_SIZE: usize = 65507;
 
-pub type ClientMap = HashMap<ConnectionId<'static>, quiche::Connection>;
+/// Token of the waker notified when a line is read from stdin, the sockets
+/// use their index.
+const STDIN: mio::Token = mio::Token(usize::MAX);
+
+/// Set once SIGINT or SIGTERM is received.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Datagrams received for each session since the last step.
+type Incoming = Vec<Vec<(Vec<u8>, quiche::RecvInfo)>>;
+
+/// Client of one target, with the hooks notified of its events.
+struct Session {
+    client: Client,
+    hooks: Hooks,
+    /// Time at which the client must be stepped if nothing is received.
+    timeout: Option<Instant>,
+    /// Used to re-establish the connection after an interruption.
+    server_name: Option<String>,
+    config: quiche::Config,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let opts = match ClientOptions::from_args(env::args().skip(1)) {
+        Ok(v) => v,
+
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let warnings = opts.warnings();
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let rng = SystemRandom::new();
+    let _ = ring::hmac::Key::generate(ring::hmac::HMAC_SHA256, &rng).unwrap();
 
-    let messages = &args[1..];
+    let seed = opts.seed.unwrap_or_else(|| random_seed(&rng));
 
-    let mut received = vec![false; messages.len()];
+    let mut workload = match Workload::from_options(&opts, seed) {
+        Ok(v) => v,
 
-    let mut idx_message: u64 = 0;
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Every run has an ID, so that the servers tell a completed run from an
+    // aborted one.
+    let run_id = workload
+        .run_id
+        .get_or_insert_with(|| RunId::random(random_seed(&rng)))
+        .clone();
+
+    let targets = if let Some(path) = &opts.port_file {
+        let peers = ports::read_port_file(path)
+            .unwrap_or_else(|e| abort(&format!("cannot read {}: {}", path, e)));
+
+        if peers.len() < workload.path_count() {
+            warn!(
+                "{} lists {} of {} server addresses",
+                path,
+                peers.len(),
+                workload.path_count()
+            );
+            workload.fit_paths(peers.len());
+        }
+
+        vec![Target::discovered(peers)]
+    } else if opts.targets.is_empty() {
+        vec![Target::default()]
+    } else {
+        opts.targets.clone()
+    };
 
     let mut buf = [0; MAX_BUF_SIZE];
-    let mut out = [0; MAX_BUF_SIZE];
 
     env_logger::builder().format_timestamp_nanos().init();
 
+    // The events own the standard output, so that a monitoring tool reading
+    // them is not confused by the messages and the report.
+    let json_stdout = opts.json_events.then(|| {
+        take_stdout().unwrap_or_else(|e| abort(&format!("cannot redirect stdout: {}", e)))
+    });
+
+    if opts.insecure {
+        eprintln!("WARNING: --insecure: the certificates of the servers are NOT verified");
+    }
+
+    if opts.shuffle {
+        info!("shuffling messages with seed {}", seed);
+    }
+
+    info!("run id {}", run_id);
+    info!("connection IDs of {} bytes", opts.cid_len);
+
+    let mut path_cache = opts.path_cache.as_ref().map(|path| {
+        let ttl = Duration::from_secs(opts.path_cache_ttl_s);
+
+        PathCache::load(path, ttl, SystemTime::now())
+            .unwrap_or_else(|e| abort(&format!("cannot read {}: {}", path, e)))
+    });
+
+    let mut resume_state = opts.resume_state.as_ref().map(|path| {
+        ResumeState::load(path).unwrap_or_else(|e| abort(&format!("cannot read {}: {}", path, e)))
+    });
+
+    let clock = RunClock::new(Instant::now());
+    info!("run clock {}", clock);
+
     // Setup the event loop.
     let mut poll = mio::Poll::new().unwrap();
     let mut events = mio::Events::with_capacity(1024);
 
     // Create the UDP listening socket, and register it with the event loop.
-    let mut sockets = vec![];
+    let mut sockets: Vec<Box<dyn DatagramTransport>> = vec![];
+
+    let local_base = SocketAddr::from(([127, 0, 0, 1], opts.local_port));
+
+    for i in 0..workload.path_count() {
+        let mut addr = ports::socket_addr(local_base, i);
+
+        // The interface picks the source address of the path.
+        let dev = opts.bind_devs.iter().find(|d| d.path == i);
+        if dev.is_some() {
+            addr.set_ip(Ipv4Addr::UNSPECIFIED.into());
+        }
+
+        // One association per socket, so that each path keeps its own
+        // address behind the proxy.
+        let mut socket: Box<dyn DatagramTransport> = match opts.socks5 {
+            Some(proxy) => match Socks5::associate(addr, proxy) {
+                Ok(v) => Box::new(v),
+
+                Err(e) => abort(&format!(
+                    "cannot associate {} through {}: {}",
+                    addr, proxy, e
+                )),
+            },
+
+            None => match dev {
+                Some(dev) => match Udp::bind_device(addr, &dev.ifname) {
+                    Ok(v) => Box::new(v),
 
-    for i in 0..messages.len() {
-        let port = 9000 + i;
-        let mut socket =
-            mio::net::UdpSocket::bind(format!("127.0.0.1:{port}").parse().unwrap()).unwrap();
-        poll.registry()
-            .register(&mut socket, mio::Token(i), mio::Interest::READABLE)
-            .unwrap();
+                    Err(e) => abort(&format!("cannot bind {} on {}: {}", addr, dev.ifname, e)),
+                },
+
+                None => match Udp::bind(addr) {
+                    Ok(v) => Box::new(v),
+
+                    Err(e) => abort(&format!("cannot bind {}: {}", addr, e)),
+                },
+            },
+        };
+
+        // Tokens are the indices of the sockets that could be registered.
+        if let Err(e) = socket.register(poll.registry(), mio::Token(sockets.len())) {
+            error!("cannot poll {}, skipping its path: {}", addr, e);
+            continue;
+        }
 
         sockets.push(socket);
     }
 
-    let local_addrs: Vec<SocketAddr> = sockets.iter().map(|s| s.local_addr().unwrap()).collect();
+    for dev in opts
+        .bind_devs
+        .iter()
+        .filter(|d| d.path >= workload.path_count())
+    {
+        warn!("--bind-dev {}: the workload has no path {}", dev, dev.path);
+    }
+
+    if sockets.is_empty() {
+        abort(&format!(
+            "none of the {} client sockets could be registered for polling",
+            workload.path_count()
+        ));
+    }
+
+    if sockets.len() < workload.path_count() {
+        warn!(
+            "running on {} of {} paths",
+            sockets.len(),
+            workload.path_count()
+        );
+        workload.fit_paths(sockets.len());
+    }
 
-    // Create the configuration for the QUIC connections.
-    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+    let local_addrs: Vec<SocketAddr> = sockets.iter().map(|s| s.local_addr()).collect();
 
-    config.set_application_protos(&[b"http/0.9"]).unwrap();
-    config.verify_peer(false);
-    config.set_initial_max_streams_bidi(100);
-    config.set_initial_max_streams_uni(100);
+    let bound: Vec<String> = local_addrs.iter().map(|a| a.to_string()).collect();
+    println!("Bound to {}", bound.join(" "));
 
-    config.set_initial_max_data(1000000);
-    config.set_initial_max_stream_data_bidi_local(1000000);
-    config.set_initial_max_stream_data_bidi_remote(1000000);
-    config.set_initial_max_stream_data_uni(1000000);
+    // Sockets are tokens and paths in the order they were registered.
+    for (i, addr) in local_addrs.iter().enumerate() {
+        info!("path {} bound to {}", i, addr);
 
-    config.set_active_connection_id_limit(20);
+        if opts.print_addrs {
+            println!("path {}: {}", i, addr);
+        }
+    }
+
+    let mut stdin = opts.interactive.map(|_| {
+        let waker = mio::Waker::new(poll.registry(), STDIN).unwrap();
+        read_stdin(waker)
+    });
 
     let mut keylog = None;
 
     if let Some(keylog_path) = std::env::var_os("SSLKEYLOGFILE") {
-        let file = std::fs::OpenOptions::new()
+        match std::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(keylog_path)
-            .unwrap();
+            .open(&keylog_path)
+        {
+            Ok(v) => keylog = Some(v),
+
+            Err(e) => abort(&format!("cannot open {:?}: {}", keylog_path, e)),
+        }
+    }
+
+    if opts.handshakes.is_some() {
+        catch_signals();
 
-        keylog = Some(file);
+        let trials = run_handshakes(
+            &targets[0],
+            &opts,
+            &workload,
+            &sockets,
+            &mut poll,
+            seed,
+            keylog.as_ref(),
+        );
 
-        config.log_keys();
+        trials.print(&targets[0].name, opts.loss);
+        std::process::exit(trials.outcome().exit_code());
     }
 
-    let rng = SystemRandom::new();
-    let _ = ring::hmac::Key::generate(ring::hmac::HMAC_SHA256, &rng).unwrap();
+    let mut cwnd_sampler = opts.cwnd_csv.as_ref().map(|path| {
+        let interval = Duration::from_millis(opts.cwnd_interval_ms);
 
-    let local = sockets[0].local_addr().unwrap();
+        CwndSampler::create(path, clock, interval)
+            .unwrap_or_else(|e| abort(&format!("cannot create {}: {}", path, e)))
+    });
 
-    let mut scid = [0; quiche::MAX_CONN_ID_LEN];
-    rng.fill(&mut scid[..]).unwrap();
-    let scid = quiche::ConnectionId::from_vec(scid.to_vec());
+    let mut report = Report {
+        clock: Some(clock),
+        sockets: local_addrs.iter().map(|a| SocketStats::new(*a)).collect(),
+        ..Default::default()
+    };
+    let mut sessions: Vec<Session> = vec![];
+    let mut session_ids = Demux::new(opts.cid_len);
 
-    let mut peer_addrs = vec![];
-    for i in 0..20 {
-        let port = 8000 + i % MAX_NUMBER_SOCKETS;
-        peer_addrs.push(format!("127.0.0.1:{port}").parse().unwrap())
-    }
+    for target in &targets {
+        let mut target_report =
+            TargetReport::new(&target.name, target.addr, workload.messages.len());
+        target_report.grease_version = opts.grease.then_some(GREASE_VERSION);
 
-    let mut conn = quiche::connect(
-        Some("127.0.0.1:8000"),
-        &scid,
-        local,
-        peer_addrs[0],
-        &mut config,
-    )
-    .unwrap();
+        let mut hooks = Hooks::default();
 
-    if let Some(keylog) = keylog {
-        conn.set_keylog(Box::new(keylog));
-    }
+        if targets.len() > 1 {
+            hooks.push(LogEvents::with_label(&target.name));
+        } else {
+            hooks.push(LogEvents::default());
+        }
+
+        if let Some(path) = &opts.events_json {
+            match JsonEvents::open(path, clock).map(|v| v.format(opts.events_format)) {
+                Ok(v) if targets.len() > 1 => hooks.push(v.label(&target.name)),
+
+                Ok(v) => hooks.push(v),
+
+                Err(e) => abort(&format!("cannot open {}: {}", path, e)),
+            }
+        }
+
+        if let Some(out) = &json_stdout {
+            match out.try_clone() {
+                Ok(v) if targets.len() > 1 => {
+                    hooks.push(JsonEvents::new(v, clock).label(&target.name))
+                }
+
+                Ok(v) => hooks.push(JsonEvents::new(v, clock)),
+
+                Err(e) => abort(&format!("cannot write events to stdout: {}", e)),
+            }
+        }
+
+        if let Some(path) = &opts.progress_json {
+            match JsonEvents::open(path, clock).map(|v| v.format(opts.events_format)) {
+                Ok(v) => {
+                    let v = v.only(&["transfer_progress"]);
+
+                    if targets.len() > 1 {
+                        hooks.push(v.label(&target.name));
+                    } else {
+                        hooks.push(v);
+                    }
+                }
+
+                Err(e) => abort(&format!("cannot open {}: {}", path, e)),
+            }
+        }
+
+        if opts.file.is_some() && !opts.quiet && std::io::stderr().is_terminal() {
+            if targets.len() > 1 {
+                hooks.push(ProgressLine::with_label(&target.name));
+            } else {
+                hooks.push(ProgressLine::default());
+            }
+        }
+
+        // Recorded with the events of each target, as the options apply to
+        // all of them.
+        for warning in &warnings {
+            hooks.emit(Event::ConfigWarning(warning.clone()));
+        }
+
+        if let Some(path) = &opts.records_json {
+            match RecordWriter::open(path, clock).map(|v| v.format(opts.report_format)) {
+                Ok(v) if targets.len() > 1 => target_report.records = Some(v.label(&target.name)),
+
+                Ok(v) => target_report.records = Some(v),
+
+                Err(e) => abort(&format!("cannot open {}: {}", path, e)),
+            }
+        }
+
+        let mut config = client_config(target, &opts, keylog.is_some());
+
+        // Path i of the target goes to the i-th port after its address.
+        let peer_addrs = target.peer_addrs();
+
+        let server_name = target.server_name().map(str::to_string);
+
+        let (mut conn, scid) = match connect(
+            server_name.as_deref(),
+            local_addrs[0],
+            peer_addrs[0],
+            &mut config,
+            &rng,
+            opts.cid_len,
+            keylog.as_ref(),
+        ) {
+            Ok(v) => v,
+
+            Err(e) => {
+                error!("{} connection failed: {:?}", target.name, e);
+                target_report.error = Some(format!("connection failed: {:?}", e));
+                target_report.failure = Some(Outcome::LocalError);
+                report.targets.push(target_report);
+                continue;
+            }
+        };
 
-    let (write, send_info) = conn.send(&mut out).expect("initial send failed");
+        let progress = resume_state
+            .as_ref()
+            .and_then(|state| state.get(&target.name, target.addr, workload.messages.len()));
 
-    while let Err(e) = sockets[0].send_to(&out[..write], send_info.to) {
-        if e.kind() == std::io::ErrorKind::WouldBlock {
-            trace!(
-                "{} -> {}: send() would block",
-                sockets[0].local_addr().unwrap(),
-                send_info.to
+        if let Some(session) = progress.and_then(|p| p.session.as_ref()) {
+            if let Err(e) = conn.set_session(session) {
+                warn!("{} cannot resume the saved session: {:?}", target.name, e);
+            }
+        }
+
+        let now = Instant::now();
+        let mut client = Client::new(
+            conn,
+            &workload,
+            local_addrs.clone(),
+            peer_addrs,
+            target_report,
+            now,
+        );
+
+        if let Some(progress) = progress {
+            info!(
+                "{} resuming with {}/{} messages delivered",
+                target.name,
+                progress.delivered.len(),
+                progress.messages
             );
+            client.skip_delivered(&progress.delivered);
+        }
+
+        if let Some(cache) = &path_cache {
+            let server_name = server_name.as_deref().unwrap_or_default();
+            client.prioritize_paths(&cache.rtts(server_name, target.addr));
+        }
+
+        let output = client.step(now, &mut []);
+
+        if let Err(e) = send_datagrams(&sockets, &mut report.sockets, &output.datagrams) {
+            error!("{} initial send failed: {:?}", target.name, e);
+
+            let mut target_report = client.into_report();
+            target_report.error = Some(format!("initial send failed: {}", e));
+            target_report.failure = Some(Outcome::LocalError);
+            report.targets.push(target_report);
             continue;
         }
 
-        return;
+        session_ids.insert(scid, sessions.len());
+
+        let mut session = Session {
+            client,
+            hooks,
+            timeout: None,
+            server_name,
+            config,
+        };
+        session.handle_output(output, sessions.len(), &mut session_ids);
+
+        sessions.push(session);
     }
 
-    loop {
-        if conn.is_closed() {
-            info!(
-                "connection closed, {:?} {:?}",
-                conn.stats(),
-                conn.path_stats().collect::<Vec<quiche::PathStats>>()
-            );
+    catch_signals();
+
+    // Why the connections still open were abandoned.
+    let mut stopped: Option<(Outcome, String)> = None;
+
+    // Sessions that closed the path of each local address after it was
+    // idle.
+    let mut idle_sockets: HashMap<SocketAddr, HashSet<usize>> = HashMap::new();
+
+    'run: loop {
+        if sessions.iter().all(|s| s.client.is_finished()) {
+            break;
+        }
+
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            // Connections only kept open by --keep-open are closed cleanly,
+            // a second signal abandons them.
+            let unfinished = sessions.iter().filter(|s| !s.client.is_finished());
+            if unfinished.clone().all(|s| s.client.is_kept_open()) {
+                INTERRUPTED.store(false, Ordering::Relaxed);
+
+                let now = Instant::now();
+                for session in sessions.iter_mut().filter(|s| s.client.is_kept_open()) {
+                    info!(
+                        "{} closing the connection kept open",
+                        session.client.report().name
+                    );
+                    session.client.close();
+                    session.timeout = Some(now);
+                }
 
-            return;
+                continue;
+            }
+
+            stopped = Some((Outcome::Interrupted, "interrupted by signal".to_string()));
+            break;
         }
 
-        poll.poll(&mut events, conn.timeout()).unwrap();
+        let now = Instant::now();
+        let timeout = sessions
+            .iter()
+            .filter_map(|s| s.timeout)
+            .chain(cwnd_sampler.as_ref().map(|c| c.next_sample()))
+            .min()
+            .map(|t| t.saturating_duration_since(now));
 
-        if events.is_empty() {
-            trace!("timed out");
+        if let Err(e) = poll.poll(&mut events, timeout) {
+            // A signal was received.
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
 
-            conn.on_timeout();
+            stopped = Some((Outcome::LocalError, format!("poll failed: {}", e)));
+            break;
         }
 
+        let mut incoming: Incoming = vec![vec![]; sessions.len()];
+
         for event in &events {
-            let socket = sockets.get(event.token().0).unwrap();
+            if event.token() == STDIN {
+                continue;
+            }
 
-            read_loop(&events, &mut conn, socket, &mut buf);
+            let id = event.token().0;
+            let socket = sockets.get(id).unwrap();
 
-            // core of the client
-            if conn.is_established() {
-                while conn.scids_left() > 0 {
-                    let (scid, reset_token) = generate_cid_and_reset_token(&rng);
+            if let Err(e) = read_socket(
+                socket.as_ref(),
+                &mut buf,
+                &session_ids,
+                &mut incoming,
+                &mut report.sockets[id],
+            ) {
+                stopped = Some((Outcome::LocalError, format!("recv failed: {}", e)));
+                break 'run;
+            }
+        }
 
-                    if conn.new_scid(&scid, reset_token, false).is_err() {
-                        break;
-                    }
-                }
+        let now = Instant::now();
 
-                for stream_id in conn.readable() {
-                    while let Ok((read, fin)) = conn.stream_recv(stream_id, &mut buf) {
-                        let msg = str::from_utf8(&buf[..read]).unwrap();
-                        println!("Received '{}' from server on stream {}", msg, stream_id);
-                        if fin {
-                            received[(stream_id / 4) as usize] = true
-                        }
-                    }
+        // Interactive mode runs against a single target.
+        if let (Some(lines), Some(session)) = (&stdin, sessions.first_mut()) {
+            if session.client.conn().is_established() && !session.run_commands(lines) {
+                stdin = None;
+            }
+        }
+
+        for (id, session) in sessions.iter_mut().enumerate() {
+            if session.client.is_finished() {
+                continue;
+            }
+
+            let output = session.client.step(now, &mut incoming[id]);
+
+            let sent = send_datagrams(&sockets, &mut report.sockets, &output.datagrams);
+
+            for event in &output.events {
+                if let Event::PathIdleClosed(local_addr, _) = event {
+                    idle_sockets.entry(*local_addr).or_default().insert(id);
                 }
-                if received.iter().all(|b| *b) {
-                    conn.close(true, 0x00, b"closing").unwrap();
+            }
+
+            session.handle_output(output, id, &mut session_ids);
+
+            if let Err(e) = sent {
+                stopped = Some((Outcome::LocalError, format!("send failed: {}", e)));
+                break 'run;
+            }
+
+            if session.client.can_reconnect() {
+                if let Some(scid) = session.reconnect(&rng, keylog.as_ref(), now) {
+                    session_ids.insert(scid, id);
                 }
+            }
+        }
 
-                send_stream_new_path(
-                    &mut conn,
-                    &local_addrs,
-                    &peer_addrs,
-                    messages,
-                    &mut idx_message,
+        // The targets share the sockets, one is closed once idle for all of
+        // them.
+        idle_sockets.retain(|local_addr, closed_by| {
+            let idle = sessions
+                .iter()
+                .enumerate()
+                .all(|(id, s)| s.client.is_finished() || closed_by.contains(&id));
+
+            if idle {
+                close_socket(&mut sockets, *local_addr, poll.registry());
+            }
+
+            !idle
+        });
+
+        if let (Some(state), Some(path)) = (&mut resume_state, &opts.resume_state) {
+            save_progress(state, path, &sessions);
+        }
+
+        // Sampled once the packets are sent, to stay off the send path.
+        if let Some(sampler) = &mut cwnd_sampler {
+            let conns = sessions
+                .iter()
+                .filter(|s| !s.client.is_finished())
+                .map(|s| {
+                    (
+                        s.client.report().name.as_str(),
+                        s.client.conn(),
+                        s.client.ptos(),
+                    )
+                });
+
+            if let Err(e) = sampler.sample(Instant::now(), conns) {
+                error!("writing cwnd sample failed: {}", e);
+            }
+        }
+    }
+
+    // A completed run starts over next time.
+    if let (Some(state), Some(path)) = (&mut resume_state, &opts.resume_state) {
+        let complete = sessions.len() == targets.len()
+            && sessions
+                .iter()
+                .all(|s| s.client.delivered().len() == workload.messages.len());
+
+        if complete {
+            if let Err(e) = std::fs::remove_file(path) {
+                error!("removing resume state {} failed: {}", path, e);
+            }
+        } else {
+            save_progress(state, path, &sessions);
+        }
+    }
+
+    for session in sessions {
+        let finished = session.client.is_finished();
+        let mut target_report = session.client.into_report();
+
+        if let Some(cache) = &mut path_cache {
+            for (path, rtt) in &target_report.path_rtts {
+                cache.record(
+                    session.server_name.as_deref().unwrap_or_default(),
+                    target_report.addr,
+                    *path,
+                    *rtt,
+                    SystemTime::now(),
                 );
             }
+        }
 
-            while let Some(qe) = conn.path_event_next() {
-                match qe {
-                    quiche::PathEvent::New(..) => unreachable!(),
+        if let (false, Some((outcome, reason))) = (finished, &stopped) {
+            target_report.failure = Some(*outcome);
+            target_report.error = Some(reason.clone());
+        }
 
-                    quiche::PathEvent::Validated(local_addr, peer_addr) => {
-                        info!("Path ({}, {}) is now validated", local_addr, peer_addr);
-                        conn.migrate(local_addr, peer_addr).unwrap();
-                    }
+        report.targets.push(target_report);
+    }
 
-                    quiche::PathEvent::FailedValidation(local_addr, peer_addr) => {
-                        info!("Path ({}, {}) failed validation", local_addr, peer_addr);
-                    }
+    // The baselines run once every multipath connection is closed, so that
+    // they do not compete for the first path.
+    if opts.baseline && stopped.is_none() {
+        let mut baselines = vec![];
 
-                    quiche::PathEvent::Closed(local_addr, peer_addr) => {
-                        info!(
-                            "Path ({}, {}) is now closed and unusable",
-                            local_addr, peer_addr
-                        );
-                    }
+        for multipath in &report.targets {
+            let Some(target) = targets
+                .iter()
+                .find(|t| t.name == multipath.name && t.addr == multipath.addr)
+            else {
+                continue;
+            };
+
+            info!("{} running the single-path baseline", target.name);
+
+            let baseline = run_baseline(
+                target,
+                &opts,
+                &workload,
+                &sockets,
+                &mut report.sockets,
+                &mut poll,
+                keylog.as_ref(),
+            );
+
+            baselines.push(Comparison::new(multipath, &baseline));
+        }
+
+        report.baselines = baselines;
+    }
+
+    if let (Some(cache), Some(path)) = (&path_cache, &opts.path_cache) {
+        if let Err(e) = cache.save(path) {
+            error!("writing path cache {} failed: {}", path, e);
+        }
+    }
+
+    report.print();
+
+    std::process::exit(report.outcome().0.exit_code());
+}
+
+/// Establishes `--handshakes` connections to `target` one after the other,
+/// each closed once its handshake completed, and returns their handshake
+/// times. Every datagram sent or received is dropped with probability
+/// `--loss`, drawn from a generator seeded with `seed`.
+fn run_handshakes(
+    target: &Target,
+    opts: &ClientOptions,
+    workload: &Workload,
+    sockets: &[Box<dyn DatagramTransport>],
+    poll: &mut mio::Poll,
+    seed: u64,
+    keylog: Option<&File>,
+) -> HandshakeTrials {
+    let trials = opts.handshakes.unwrap_or(0);
+    let mut config = client_config(target, opts, keylog.is_some());
+    let rng = SystemRandom::new();
+    let mut loss = SeededRng::new(seed);
+    let timeout = Duration::from_millis(opts.handshake_timeout_ms);
+
+    let mut events = mio::Events::with_capacity(1024);
+    let mut buf = [0; MAX_BUF_SIZE];
+
+    let local_addrs: Vec<SocketAddr> = sockets.iter().map(|s| s.local_addr()).collect();
+    let mut stats: Vec<SocketStats> = local_addrs.iter().map(|a| SocketStats::new(*a)).collect();
+
+    let peer_addrs = target.peer_addrs();
+
+    if opts.grease {
+        info!(
+            "starting the handshakes with version {:#010x}",
+            GREASE_VERSION
+        );
+    }
+
+    let mut results = HandshakeTrials::default();
+
+    for trial in 0..trials {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let conn = match connect(
+            target.server_name(),
+            local_addrs[0],
+            target.addr,
+            &mut config,
+            &rng,
+            opts.cid_len,
+            keylog,
+        ) {
+            Ok((conn, _)) => conn,
+
+            Err(e) => {
+                results.failures.push(format!("connection failed: {:?}", e));
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        let report = TargetReport::new(&target.name, target.addr, 0);
+        let mut client = Client::new(
+            conn,
+            workload,
+            local_addrs.clone(),
+            peer_addrs.clone(),
+            report,
+            start,
+        );
+
+        let mut incoming = vec![];
+        let failure = loop {
+            let now = Instant::now();
+            let output = client.step(now, &mut incoming);
+            incoming.clear();
+
+            let datagrams: Vec<_> = output
+                .datagrams
+                .into_iter()
+                .filter(|_| !loss.chance(opts.loss))
+                .collect();
+
+            if let Err(e) = send_datagrams(sockets, &mut stats, &datagrams) {
+                break Some(format!("send failed: {}", e));
+            }
+
+            // The client closes the connection once established, as it has
+            // no messages.
+            if client.report().handshake_time.is_some() {
+                break None;
+            }
+
+            if client.is_finished() {
+                let report = client.report();
+                break Some(
+                    report
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| report.outcome().name().to_string()),
+                );
+            }
+
+            let deadline = start + timeout;
+            if now >= deadline {
+                break Some("timed out".to_string());
+            }
+
+            let wake = output.timeout.map_or(deadline, |t| t.min(deadline));
+            if let Err(e) = poll.poll(&mut events, Some(wake.saturating_duration_since(now))) {
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    break Some("interrupted by signal".to_string());
+                }
 
-                    quiche::PathEvent::ReusedSourceConnectionId(cid_seq, old, new) => {
-                        info!(
-                            "Peer reused cid seq {} (initially {:?}) on {:?}",
-                            cid_seq, old, new
-                        );
+                break Some(format!("poll failed: {}", e));
+            }
+
+            for event in &events {
+                let socket = &sockets[event.token().0];
+
+                while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                    if loss.chance(opts.loss) {
+                        continue;
                     }
 
-                    quiche::PathEvent::PeerMigrated(..) => unreachable!(),
+                    let recv_info = quiche::RecvInfo {
+                        to: socket.local_addr(),
+                        from,
+                    };
+                    incoming.push((buf[..len].to_vec(), recv_info));
+                }
+            }
+        };
+
+        match (failure, client.report().handshake_time) {
+            (None, Some(time)) => {
+                info!("handshake {} completed in {:?}", trial, time);
+                results.times.push(time);
+            }
+
+            (reason, _) => {
+                let reason = reason.unwrap_or_default();
+                info!("handshake {} failed: {}", trial, reason);
+                results.failures.push(reason);
+            }
+        }
+    }
+
+    results
+}
+
+/// Runs the workload against `target` again over a new connection that only
+/// uses the first path, with `--baseline`, and returns its report.
+fn run_baseline(
+    target: &Target,
+    opts: &ClientOptions,
+    workload: &Workload,
+    sockets: &[Box<dyn DatagramTransport>],
+    stats: &mut [SocketStats],
+    poll: &mut mio::Poll,
+    keylog: Option<&File>,
+) -> TargetReport {
+    let workload = workload.single_path();
+    let mut config = client_config(target, opts, keylog.is_some());
+    let rng = SystemRandom::new();
+
+    let mut events = mio::Events::with_capacity(1024);
+    let mut buf = [0; MAX_BUF_SIZE];
+
+    let local_addrs = vec![sockets[0].local_addr()];
+    let peer_addrs = target.peer_addrs();
+
+    let mut report = TargetReport::new(&target.name, target.addr, workload.messages.len());
+
+    let conn = match connect(
+        target.server_name(),
+        local_addrs[0],
+        peer_addrs[0],
+        &mut config,
+        &rng,
+        opts.cid_len,
+        keylog,
+    ) {
+        Ok((conn, _)) => conn,
+
+        Err(e) => {
+            error!("{} baseline connection failed: {:?}", target.name, e);
+            report.error = Some(format!("connection failed: {:?}", e));
+            report.failure = Some(Outcome::LocalError);
+            return report;
+        }
+    };
+
+    let mut client = Client::new(
+        conn,
+        &workload,
+        local_addrs,
+        peer_addrs,
+        report,
+        Instant::now(),
+    );
+
+    let mut hooks = Hooks::default();
+    hooks.push(LogEvents::with_label(&format!("{} baseline", target.name)));
+
+    let mut incoming = vec![];
+    let failure = loop {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break Some((Outcome::Interrupted, "interrupted by signal".to_string()));
+        }
+
+        let now = Instant::now();
+        let output = client.step(now, &mut incoming);
+        incoming.clear();
+
+        for event in &output.events {
+            hooks.on_event(event);
+        }
+
+        if let Err(e) = send_datagrams(sockets, stats, &output.datagrams) {
+            break Some((Outcome::LocalError, format!("send failed: {}", e)));
+        }
+
+        if client.is_finished() {
+            break None;
+        }
+
+        let timeout = output.timeout.map(|t| t.saturating_duration_since(now));
+        if let Err(e) = poll.poll(&mut events, timeout) {
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            break Some((Outcome::LocalError, format!("poll failed: {}", e)));
+        }
+
+        // Datagrams of the multipath connection still arriving on the other
+        // sockets are dropped.
+        for event in events.iter().filter(|e| e.token().0 == 0) {
+            let socket = &sockets[event.token().0];
+
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                stats[0].on_recv(len);
+
+                let recv_info = quiche::RecvInfo {
+                    to: socket.local_addr(),
+                    from,
+                };
+                incoming.push((buf[..len].to_vec(), recv_info));
+            }
+        }
+    };
+
+    let mut report = client.into_report();
+
+    if let Some((outcome, reason)) = failure {
+        report.failure = Some(outcome);
+        report.error = Some(reason);
+    }
+
+    report
+}
+
+/// Records the session and the messages delivered of every target in
+/// `state`, and writes it to `path` if anything changed.
+fn save_progress(state: &mut ResumeState, path: &str, sessions: &[Session]) {
+    let mut changed = false;
+
+    for session in sessions {
+        let report = session.client.report();
+
+        // A new connection has no session until the server sends a ticket.
+        let saved = state.get(&report.name, report.addr, report.messages);
+        let ticket = session
+            .client
+            .conn()
+            .session()
+            .map(<[u8]>::to_vec)
+            .or_else(|| saved.and_then(|p| p.session.clone()));
+
+        changed |= state.update(TargetProgress {
+            name: report.name.clone(),
+            addr: report.addr,
+            messages: report.messages,
+            session: ticket,
+            delivered: session.client.delivered(),
+        });
+    }
+
+    if changed {
+        if let Err(e) = state.save(path) {
+            error!("writing resume state {} failed: {}", path, e);
+        }
+    }
+}
+
+/// Ends the run on a local error that happened before any connection was
+/// made.
+fn abort(reason: &str) -> ! {
+    let outcome = Outcome::LocalError;
+    println!("{}", outcome.banner(reason));
+    std::process::exit(outcome.exit_code());
+}
+
+/// Moves the standard output to a new file descriptor, returned, and makes
+/// the standard output an alias of the standard error, where everything
+/// printed afterwards goes.
+fn take_stdout() -> std::io::Result<File> {
+    std::io::stdout().flush()?;
+
+    // SAFETY: the descriptors are the standard ones, and the one returned by
+    // dup() is owned by the file created from it.
+    unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let file = File::from_raw_fd(fd);
+
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(file)
+    }
+}
+
+/// Sets `INTERRUPTED` on SIGINT and SIGTERM, the event loop is woken up as
+/// polling fails with `Interrupted`.
+fn catch_signals() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+    // SAFETY: the handler only stores to an atomic, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+impl Session {
+    /// Notifies the hooks of what a step produced and keeps the connection
+    /// IDs of the session up to date.
+    fn handle_output(&mut self, output: StepOutput, id: usize, session_ids: &mut Demux) {
+        for event in &output.events {
+            match event {
+                Event::CidIssued(cid) => {
+                    session_ids.insert(cid.clone(), id);
                 }
+
+                Event::CidRetired(cid) => {
+                    session_ids.remove(cid);
+                }
+
+                _ => (),
+            }
+
+            self.hooks.on_event(event);
+        }
+
+        for completed in &output.completed {
+            let echo = match payload::printable(&completed.echo) {
+                Some(text) => format!("'{}'", text),
+                None => payload::hex_dump(&completed.echo),
+            };
+            let stream_id = completed.record.stream_id;
+
+            // Delivered once acknowledged, without echo.
+            if is_uni_stream(stream_id) {
+                println!("Message on stream {} acknowledged by server", stream_id);
+                continue;
+            }
+
+            match completed.record.paths.paths().last() {
+                Some((local, peer)) => println!(
+                    "Received {} from server on stream {} on path ({}, {})",
+                    echo, stream_id, local, peer
+                ),
+
+                None => println!("Received {} from server on stream {}", echo, stream_id),
+            }
+        }
+
+        self.timeout = output.timeout;
+    }
+
+    /// Sends the lines read from stdin as messages, or runs them if they are
+    /// commands. Returns false once the client was closed, by `:close` or
+    /// the end of stdin.
+    fn run_commands(&mut self, lines: &Receiver<String>) -> bool {
+        loop {
+            let line = match lines.try_recv() {
+                Ok(v) => v,
+
+                Err(TryRecvError::Empty) => return true,
+
+                Err(TryRecvError::Disconnected) => {
+                    self.client.close();
+                    return false;
+                }
+            };
+
+            let mut words = line.split_whitespace();
+
+            let result = match words.next() {
+                Some(":close") => {
+                    self.client.close();
+                    return false;
+                }
+
+                Some(":migrate") => match words.next().and_then(|w| w.parse().ok()) {
+                    Some(path) => self.client.migrate(path),
+
+                    None => Err("usage: :migrate <index>".to_string()),
+                },
+
+                Some(":stats") => {
+                    print_stats(self.client.conn());
+                    Ok(())
+                }
+
+                Some(command) if command.starts_with(':') => {
+                    Err(format!("unknown command {}", command))
+                }
+
+                _ => self.client.push_message(line.as_bytes()).map(|index| {
+                    println!("Sending '{}' as message {}", line, index);
+                }),
+            };
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
             }
 
-            // write function
-            write_loop(&mut conn, &sockets, &mut out);
+            // Step right away to send what the line produced.
+            self.timeout = Some(Instant::now());
         }
     }
+
+    /// Replaces the interrupted connection of the client by a new one.
+    /// Returns the source connection ID of the new connection.
+    fn reconnect(
+        &mut self,
+        rng: &SystemRandom,
+        keylog: Option<&File>,
+        now: Instant,
+    ) -> Option<ConnectionId<'static>> {
+        let local_addr = self.client.local_addrs()[0];
+        let peer_addr = self.client.peer_addrs()[0];
+
+        let (conn, scid) = match connect(
+            self.server_name.as_deref(),
+            local_addr,
+            peer_addr,
+            &mut self.config,
+            rng,
+            self.client.cid_len(),
+            keylog,
+        ) {
+            Ok(v) => v,
+
+            Err(e) => {
+                error!("{} reconnection failed: {:?}", self.client.report().name, e);
+                return None;
+            }
+        };
+
+        self.client.reconnect(conn, now);
+
+        // Step the new connection right away to send its first packets.
+        self.timeout = Some(now);
+
+        Some(scid)
+    }
+}
+
+/// Reads stdin line by line on a thread, waking the event loop for each line.
+/// The channel is closed at the end of stdin.
+fn read_stdin(waker: mio::Waker) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            if tx.send(line).is_err() {
+                return;
+            }
+
+            waker.wake().ok();
+        }
+
+        drop(tx);
+        waker.wake().ok();
+    });
+
+    rx
 }
 
-fn send_stream_new_path(
-    conn: &mut quiche::Connection,
-    local_addrs: &Vec<SocketAddr>,
-    peer_addrs: &Vec<SocketAddr>,
-    messages: &[String],
-    idx_message: &mut u64,
+/// Prints the statistics of the connection and of each of its paths.
+fn print_stats(conn: &quiche::Connection) {
+    let stats = conn.stats();
+    println!(
+        "sent {} recv {} lost {} retrans {}",
+        stats.sent, stats.recv, stats.lost, stats.retrans
+    );
+
+    for path in conn.path_stats() {
+        println!(
+            "  ({}, {}): active {} rtt {:?} cwnd {} pmtu {} sent {} recv {} lost {}",
+            path.local_addr,
+            path.peer_addr,
+            path.active,
+            path.rtt,
+            path.cwnd,
+            path.pmtu,
+            path.sent,
+            path.recv,
+            path.lost
+        );
+    }
+}
+
+/// Creates a connection from `local_addr` to `peer_addr` with a random source
+/// connection ID, which is returned with it.
+fn connect(
+    server_name: Option<&str>,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    config: &mut quiche::Config,
+    rng: &SystemRandom,
+    cid_len: usize,
+    keylog: Option<&File>,
+) -> quiche::Result<(quiche::Connection, ConnectionId<'static>)> {
+    let mut scid = vec![0; cid_len];
+    rng.fill(&mut scid).unwrap();
+    let scid = quiche::ConnectionId::from_vec(scid);
+
+    let mut conn = quiche::connect(server_name, &scid, local_addr, peer_addr, config)?;
+
+    if let Some(keylog) = keylog {
+        if let Ok(keylog) = keylog.try_clone() {
+            conn.set_keylog(Box::new(keylog));
+        }
+    }
+
+    Ok((conn, scid))
+}
+
+/// Creates the configuration of the connection to `target`, exiting if it
+/// cannot be.
+fn client_config(target: &Target, opts: &ClientOptions, log_keys: bool) -> quiche::Config {
+    match opts.quiche_config(target, log_keys, |_| ()) {
+        Ok(config) => config,
+
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Closes the socket bound to `local_addr`, which keeps its index and token.
+fn close_socket(
+    sockets: &mut [Box<dyn DatagramTransport>],
+    local_addr: SocketAddr,
+    registry: &mio::Registry,
 ) {
-    let idx_message_us = *idx_message as usize;
-    if idx_message_us >= messages.len() {
+    let Some(socket) = sockets.iter_mut().find(|s| s.local_addr() == local_addr) else {
         return;
+    };
+
+    if let Err(e) = socket.deregister(registry) {
+        warn!("cannot stop polling {}: {}", local_addr, e);
     }
-    let local_addr = local_addrs[idx_message_us];
-    let peer_addr = peer_addrs[idx_message_us];
-    let path_validated = conn.is_path_validated(local_addr, peer_addr);
-    if path_validated.is_err() && conn.available_dcids() > 0 {
-        // path doesn't exist, first probe it
-        conn.probe_path(local_addr, peer_addr).unwrap();
-    } else if path_validated.is_ok_and(|validated| validated) {
-        // path is validated, send on this new path
-        let message = messages.get(idx_message_us).unwrap();
-        conn.stream_send(*idx_message * 4, message.as_bytes(), true)
-            .unwrap();
-        *idx_message += 1;
+
+    *socket = Box::new(Closed::new(local_addr));
+    info!("closed the socket of {}, its paths are idle", local_addr);
+}
+
+/// Sends each datagram from the socket bound to its source address.
+fn send_datagrams(
+    sockets: &[Box<dyn DatagramTransport>],
+    stats: &mut [SocketStats],
+    datagrams: &[(Vec<u8>, quiche::SendInfo)],
+) -> std::io::Result<()> {
+    for (pkt, send_info) in datagrams {
+        let Some(id) = stats.iter().position(|s| s.local_addr == send_info.from) else {
+            error!("no socket bound to {}", send_info.from);
+            continue;
+        };
+
+        if let Err(e) = sockets[id].send_to(pkt, send_info.to) {
+            if e.kind() == std::io::ErrorKind::WouldBlock {
+                trace!("{} -> {}: send() would block", send_info.from, send_info.to);
+                continue;
+            }
+
+            if e.kind() == std::io::ErrorKind::NotConnected {
+                debug!("{} -> {}: socket closed", send_info.from, send_info.to);
+                continue;
+            }
+
+            return Err(e);
+        }
+
+        stats[id].on_send(pkt.len());
+    }
+
+    Ok(())
+}
+
+/// Reads the pending packets of `socket` and queues each of them for the
+/// session it belongs to, based on its destination connection ID.
+fn read_socket(
+    socket: &dyn DatagramTransport,
+    buf: &mut [u8],
+    session_ids: &Demux,
+    incoming: &mut Incoming,
+    stats: &mut SocketStats,
+) -> std::io::Result<()> {
+    let local_addr = socket.local_addr();
+
+    loop {
+        let (len, from) = match socket.recv_from(buf) {
+            Ok(v) => v,
+
+            Err(e) => {
+                // There are no more UDP packets to read, so end the read
+                // loop.
+                if e.kind() == std::io::ErrorKind::WouldBlock {
+                    trace!("recv() would block");
+                    return Ok(());
+                }
+
+                return Err(e);
+            }
+        };
+
+        stats.on_recv(len);
+
+        let pkt_buf = &mut buf[..len];
+
+        let hdr = match quiche::Header::from_slice(pkt_buf, session_ids.cid_len()) {
+            Ok(v) => v,
+
+            Err(e) => {
+                error!("Parsing packet header failed: {:?}", e);
+                continue;
+            }
+        };
+
+        let Some(id) = session_ids.get(&hdr.dcid) else {
+            debug!("Packet for unknown connection {:?}", hdr.dcid);
+            continue;
+        };
+
+        let recv_info = quiche::RecvInfo {
+            from,
+            to: local_addr,
+        };
+
+        incoming[id].push((pkt_buf.to_vec(), recv_info));
     }
 }
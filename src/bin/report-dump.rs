@@ -0,0 +1,48 @@
+use std::{
+    env,
+    io::{self, Read, Write},
+};
+
+use quiche_test::shared::{cbor, json::JsonObject};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let data = match args.as_slice() {
+        [] => {
+            let mut data = vec![];
+            io::stdin().read_to_end(&mut data).map(|_| data)
+        }
+
+        [path] => std::fs::read(path),
+
+        _ => {
+            eprintln!("usage: report-dump [file]");
+            std::process::exit(1);
+        }
+    };
+
+    let data = data.unwrap_or_else(|e| {
+        eprintln!("cannot read the input: {}", e);
+        std::process::exit(1);
+    });
+
+    let mut out = io::stdout().lock();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        match cbor::decode::<JsonObject>(&data[pos..]) {
+            Ok((obj, len)) => {
+                if writeln!(out, "{}", obj.finish()).is_err() {
+                    return;
+                }
+                pos += len;
+            }
+
+            Err(e) => {
+                eprintln!("invalid item at byte {}: {}", pos, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
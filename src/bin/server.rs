@@ -1,23 +1,257 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
 #[macro_use]
 extern crate log;
 
+use log::Level;
 use quiche::{self, ConnectionId};
-use quiche_test::shared::{generate_cid_and_reset_token, write_loop, MAX_NUMBER_SOCKETS};
+use quiche_test::{
+    config::ServerOptions,
+    shared::{
+        amplification::PreValidation,
+        apps::{self, ConnHandler, FileHandler, H3Handler, Handler, StreamHandler},
+        burst::BurstLimit,
+        cids::CidIssuer,
+        control::{ConnRun, Control, RunTally},
+        fair_share::{Fairness, RoundRobin, WorkBudget},
+        frame::{self, FrameDecoder},
+        generate_cid_and_reset_token,
+        impair::TxImpair,
+        is_uni_stream,
+        log_sample::PacketLog,
+        loop_timing::{LoopTiming, Phase},
+        owd::{self, WallClock},
+        padding, payload, poll_events, ports,
+        read_budget::ReadBudget,
+        server_paths::ServerPaths,
+        shaper::EchoShaper,
+        transform::{StreamTransform, Transform},
+        write_loop, MAX_NUMBER_SOCKETS,
+    },
+};
 use ring::rand::*;
 
 const MAX_BUF_SIZE: usize = 65507;
 
+/// Payloads longer than this are printed as a byte count.
+const MAX_PRINTED_LEN: usize = 64;
+
 struct Client {
     conn: quiche::Connection,
     id: u64,
+    /// Echo data not yet accepted by each stream, and whether it ends with
+    /// a fin. Streams with pending data are not read until it is flushed.
+    pending: HashMap<u64, (Vec<u8>, bool)>,
+    /// Handler of the application protocol negotiated, once the handshake
+    /// completed or early data arrived.
+    handler: Option<ConnHandler>,
+    /// Delays and rate limits the echoes, with `--echo-delay` or
+    /// `--echo-rate`.
+    shaper: Option<EchoShaper>,
+    paths: ServerPaths,
+    reads: ReadBudget,
+    /// Packets received and not processed yet, and the stream bytes served
+    /// per wakeup.
+    work: WorkBudget<(Vec<u8>, quiche::RecvInfo)>,
+    /// Bytes exchanged until the handshake completed.
+    pre_validation: PreValidation,
+    /// Impairs the outgoing datagrams, with `--tx-impair`.
+    impair: Option<TxImpair>,
+    /// Samples the logs of the packets received, with
+    /// `--log-sample-packets`.
+    recv_log: PacketLog,
+    /// Samples the logs of the packets sent.
+    send_log: PacketLog,
+    /// Limits the packets sent per path and wakeup to the send quantum.
+    burst: BurstLimit,
+    /// Issues connection IDs for the paths of the client.
+    cids: CidIssuer,
+    /// Run of the client, from its control messages.
+    run: ConnRun,
+}
+
+impl Client {
+    /// Time until the connection or a shaped echo needs attention.
+    fn timeout(&self) -> Option<Duration> {
+        let echo = self
+            .shaper
+            .as_ref()
+            .and_then(|s| s.next_release())
+            .map(|t| t.saturating_duration_since(Instant::now()));
+
+        // Streams or packets are left, the next wakeup must not wait.
+        if self.reads.has_backlog() || self.work.has_backlog() {
+            return Some(Duration::ZERO);
+        }
+
+        // Packets are left by a burst that reached the send quantum.
+        let burst = self
+            .burst
+            .next_release()
+            .map(|t| t.saturating_duration_since(Instant::now()));
+
+        [self.conn.timeout(), echo, burst]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+}
+
+/// The echo handler: answers each stream with its data, padded,
+/// multiplexed, transformed or timestamped as the options say.
+struct Echo {
+    trace_id: String,
+    padded: bool,
+    multiplex: bool,
+    transform: Option<Transform>,
+    /// Clock of the timestamps, with `--timestamps`.
+    clock: Option<WallClock>,
+    /// Padded messages received so far on each stream, echoed once complete.
+    frames: HashMap<u64, Vec<u8>>,
+    /// Incomplete frames of each stream, with `--multiplex`.
+    decoders: HashMap<u64, FrameDecoder>,
+    /// Transformation of the message of each stream, with `--transform`.
+    transforms: HashMap<u64, StreamTransform>,
+    /// Streams whose echo started with a timestamp, until their fin, with
+    /// `--timestamps`.
+    stamped: HashSet<u64>,
+}
+
+impl Echo {
+    fn new(opts: &ServerOptions, clock: WallClock, conn: &quiche::Connection) -> Self {
+        Echo {
+            trace_id: conn.trace_id().to_string(),
+            padded: opts.padded,
+            multiplex: opts.multiplex,
+            transform: opts.transform,
+            clock: opts.timestamps.then_some(clock),
+            frames: HashMap::new(),
+            decoders: HashMap::new(),
+            transforms: HashMap::new(),
+            stamped: HashSet::new(),
+        }
+    }
+}
+
+impl StreamHandler for Echo {
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        data: &[u8],
+        fin: bool,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if self.padded {
+            let frame = self.frames.entry(stream_id).or_default();
+            frame.extend_from_slice(data);
+
+            if !fin {
+                return Ok(None);
+            }
+
+            let frame = self.frames.remove(&stream_id).unwrap();
+            let payload =
+                padding::unpad(&frame).ok_or_else(|| "invalid padded message".to_string())?;
+
+            print_payload(payload, stream_id);
+
+            let answer = match self.transform {
+                Some(transform) => transform.apply(payload),
+                None => payload.to_vec(),
+            };
+
+            return padding::pad(&answer, frame.len())
+                .map(Some)
+                .map_err(|e| format!("cannot pad the answer ({})", e));
+        }
+
+        if self.multiplex {
+            let decoder = self.decoders.entry(stream_id).or_default();
+            decoder.push(data);
+
+            // Echo only complete frames, so that their boundaries are kept.
+            let mut frames = Vec::new();
+            while let Some(payload) = decoder.next_frame() {
+                print_payload(&payload, stream_id);
+
+                let answer = match self.transform {
+                    Some(transform) => transform.apply(&payload),
+                    None => payload,
+                };
+                frames.extend_from_slice(&frame::encode(&answer));
+            }
+
+            if fin {
+                if decoder.buffered() > 0 {
+                    error!("{} truncated frame on stream {}", self.trace_id, stream_id);
+                }
+
+                self.decoders.remove(&stream_id);
+            } else if frames.is_empty() {
+                return Ok(None);
+            }
+
+            return Ok(Some(frames));
+        }
+
+        print_payload(data, stream_id);
+
+        if let Some(transform) = self.transform {
+            // Chunks are transformed as they are read, the rest of the
+            // answer comes with the fin.
+            let stream = self
+                .transforms
+                .entry(stream_id)
+                .or_insert_with(|| StreamTransform::new(transform));
+            let mut answer = stream.update(data);
+
+            if fin {
+                let stream = self.transforms.remove(&stream_id).unwrap();
+                answer.extend_from_slice(&stream.finish());
+            } else if answer.is_empty() {
+                return Ok(None);
+            }
+
+            return Ok(Some(answer));
+        }
+
+        if let Some(clock) = &self.clock {
+            // The echo of each stream starts with the time its first bytes
+            // arrived.
+            let answer = match self.stamped.insert(stream_id) {
+                true => owd::stamp(clock.micros(now), data),
+                false => data.to_vec(),
+            };
+
+            if fin {
+                self.stamped.remove(&stream_id);
+            }
+
+            return Ok(Some(answer));
+        }
+
+        Ok(Some(data.to_vec()))
+    }
 }
 
 type ClientMap = HashMap<u64, Client>;
 type ClientIDMap = HashMap<ConnectionId<'static>, u64>;
 
 fn main() {
+    let opts = match ServerOptions::from_args(env::args().skip(1)) {
+        Ok(v) => v,
+
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let mut buf = [0; MAX_BUF_SIZE];
     let mut out = [0; MAX_BUF_SIZE];
 
@@ -28,39 +262,33 @@ fn main() {
     let mut events = mio::Events::with_capacity(1024);
 
     // Create the UDP listening socket, and register it with the event loop.
-    let mut sockets = vec![];
-    for i in 0..MAX_NUMBER_SOCKETS {
-        let port = 8000 + i;
-        let mut socket =
-            mio::net::UdpSocket::bind(format!("127.0.0.1:{port}").parse().unwrap()).unwrap();
+    let mut sockets = match ports::bind_sockets(opts.listen, MAX_NUMBER_SOCKETS) {
+        Ok(v) => v,
+
+        Err(e) => {
+            eprintln!("cannot bind {}: {}", opts.listen, e);
+            std::process::exit(1);
+        }
+    };
+
+    for (i, socket) in sockets.iter_mut().enumerate() {
         poll.registry()
-            .register(&mut socket, mio::Token(i), mio::Interest::READABLE)
+            .register(socket, mio::Token(i), mio::Interest::READABLE)
             .unwrap();
-        sockets.push(socket);
     }
 
-    info!("listening on {:}", sockets[0].local_addr().unwrap());
-
-    // Create the configuration for the QUIC connections.
-    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
-
-    config.set_application_protos(&[b"http/0.9"]).unwrap();
-    config
-        .load_cert_chain_from_pem_file("src/bin/cert.crt")
-        .unwrap();
-    config
-        .load_priv_key_from_pem_file("src/bin/cert.key")
-        .unwrap();
-
-    config.set_initial_max_streams_bidi(100);
-    config.set_initial_max_streams_uni(100);
+    let local_addrs: Vec<SocketAddr> = sockets.iter().map(|s| s.local_addr().unwrap()).collect();
 
-    config.set_initial_max_data(1000000);
-    config.set_initial_max_stream_data_bidi_local(1000000);
-    config.set_initial_max_stream_data_bidi_remote(1000000);
-    config.set_initial_max_stream_data_uni(1000000);
+    let listening: Vec<String> = local_addrs.iter().map(|a| a.to_string()).collect();
+    println!("Listening on {}", listening.join(" "));
+    info!("connection IDs of {} bytes", opts.cid_len);
 
-    config.set_active_connection_id_limit(20);
+    if let Some(path) = &opts.port_file {
+        if let Err(e) = ports::write_port_file(path, &local_addrs) {
+            eprintln!("cannot write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
 
     let mut keylog = None;
 
@@ -72,10 +300,22 @@ fn main() {
             .unwrap();
 
         keylog = Some(file);
-
-        config.log_keys();
     }
 
+    let apps = opts.apps();
+
+    // Create the configuration for the QUIC connections.
+    let mut config = match opts.quiche_config(keylog.is_some(), |_| ()) {
+        Ok(config) => config,
+
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let clock = WallClock::new(Instant::now());
+
     let rng = SystemRandom::new();
     let _ = ring::hmac::Key::generate(ring::hmac::HMAC_SHA256, &rng).unwrap();
 
@@ -84,10 +324,22 @@ fn main() {
 
     let mut curr_id = 0;
 
+    let mut round_robin = RoundRobin::default();
+    let mut fairness = Fairness::default();
+    let mut runs = RunTally::default();
+    let mut loop_timing = opts.loop_timing.then(|| LoopTiming::new(Instant::now()));
+
     loop {
-        let timeout = clients.values().filter_map(|c| c.conn.timeout()).min();
+        let timeout = clients.values().filter_map(|c| c.timeout()).min();
+
+        if let Err(e) = poll_events(&mut poll, &mut events, timeout) {
+            eprintln!("poll failed: {}", e);
+            std::process::exit(1);
+        }
 
-        poll.poll(&mut events, timeout).unwrap();
+        if let Some(timing) = &mut loop_timing {
+            timing.begin(Instant::now());
+        }
 
         'read: loop {
             if events.is_empty() {
@@ -120,7 +372,7 @@ fn main() {
                 let pkt_buf = &mut buf[..len];
 
                 // Parse the QUIC packet's header.
-                let hdr = match quiche::Header::from_slice(pkt_buf, quiche::MAX_CONN_ID_LEN) {
+                let hdr = match quiche::Header::from_slice(pkt_buf, opts.cid_len) {
                     Ok(v) => v,
 
                     Err(e) => {
@@ -135,9 +387,27 @@ fn main() {
                         continue 'read;
                     }
 
-                    let mut scid = [0; quiche::MAX_CONN_ID_LEN];
-                    rng.fill(&mut scid[..]).unwrap();
-                    let scid = quiche::ConnectionId::from_vec(scid.to_vec());
+                    // Clients may offer a reserved version first, such as
+                    // the client with --grease.
+                    if !quiche::version_is_supported(hdr.version) {
+                        info!("Negotiating version {:#010x} from {}", hdr.version, from);
+
+                        let len =
+                            quiche::negotiate_version(&hdr.scid, &hdr.dcid, &mut out).unwrap();
+
+                        if let Err(e) = socket.send_to(&out[..len], from) {
+                            if e.kind() == std::io::ErrorKind::WouldBlock {
+                                trace!("send() would block");
+                                continue 'read;
+                            }
+
+                            panic!("send_to() failed: {:?}", e);
+                        }
+
+                        continue 'read;
+                    }
+
+                    let (scid, _) = generate_cid_and_reset_token(&rng, opts.cid_len);
 
                     debug!("New connection: dcid={:?} scid={:?}", hdr.dcid, scid);
 
@@ -153,8 +423,34 @@ fn main() {
 
                     let id = curr_id;
 
+                    let mut paths = ServerPaths::new(local_addrs.clone(), (local_addr, from));
+                    if opts.probe_client_paths {
+                        paths = paths.probe_client_paths();
+                    }
+
                     client_ids.insert(scid.clone(), id);
-                    clients.insert(id, Client { conn, id });
+                    clients.insert(
+                        id,
+                        Client {
+                            conn,
+                            id,
+                            pending: HashMap::new(),
+                            handler: None,
+                            shaper: opts.echo_shaper(),
+                            paths,
+                            reads: ReadBudget::new(opts.read_budget),
+                            work: WorkBudget::new(opts.packet_budget, opts.byte_budget),
+                            pre_validation: PreValidation::new(true),
+                            // Seeded by the connection, to replay a run.
+                            impair: opts.tx_impair.map(|spec| TxImpair::new(spec, id)),
+                            recv_log: PacketLog::new(opts.log_sample_packets),
+                            send_log: PacketLog::new(opts.log_sample_packets),
+                            burst: BurstLimit::new(opts.burst_override),
+                            cids: CidIssuer::new(opts.cid_len),
+                            run: ConnRun::default(),
+                        },
+                    );
+                    fairness.open(id, Instant::now());
                     curr_id += 1;
 
                     clients.get_mut(&id).unwrap()
@@ -168,48 +464,226 @@ fn main() {
                     from,
                 };
 
+                // Processed in turn with the packets of the other
+                // connections.
+                client.work.push((pkt_buf.to_vec(), recv_info));
+            }
+        }
+
+        if let Some(timing) = &mut loop_timing {
+            timing.lap(Phase::Read, Instant::now());
+        }
+
+        for id in round_robin.order(clients.keys().cloned()) {
+            let client = clients.get_mut(&id).unwrap();
+
+            client.work.begin();
+
+            while let Some((mut pkt, recv_info)) = client.work.next_packet() {
+                client.pre_validation.on_recv(pkt.len());
+
                 // Process potentially coalesced packets.
-                let read = match client.conn.recv(pkt_buf, recv_info) {
+                let read = match client.conn.recv(&mut pkt, recv_info) {
                     Ok(v) => v,
 
                     Err(e) => {
                         error!("{} recv failed: {:?}", client.conn.trace_id(), e);
-                        continue 'read;
+                        continue;
                     }
                 };
 
-                info!("{} processed {} bytes", client.conn.trace_id(), read);
+                if client.recv_log.sample(Level::Info, read) {
+                    info!("{} processed {} bytes", client.conn.trace_id(), read);
+                }
+
+                if client.conn.is_established() {
+                    let lost = client.conn.stats().lost_bytes;
+                    client.pre_validation.validate(lost);
+                }
             }
-        }
 
-        for client in clients.values_mut() {
-            handle_path_events(client);
+            if let Some((read, skipped)) = client.recv_log.end_burst() {
+                info!(
+                    "{} processed {} bytes ({} packets not logged before)",
+                    client.conn.trace_id(),
+                    read,
+                    skipped
+                );
+            }
 
-            for stream_id in client.conn.readable() {
-                // Stream is readable, read until there's no more data.
-                while let Ok((read, fin)) = client.conn.stream_recv(stream_id, &mut buf) {
-                    println!(
-                        "Received {} on stream {}",
-                        std::str::from_utf8(&buf[..read]).unwrap(),
-                        stream_id
-                    );
-                    client
-                        .conn
-                        .stream_send(stream_id, &buf[..read], fin)
-                        .unwrap();
+            // Streams are only readable once the protocol they speak is
+            // known, from the handshake or the early data.
+            if client.handler.is_none()
+                && (client.conn.is_established() || client.conn.is_in_early_data())
+            {
+                let alpn = client.conn.application_proto();
+                let handler = apps::handler(&apps, alpn).unwrap_or(Handler::Echo);
+
+                info!(
+                    "{} serving {} with the {} handler",
+                    client.conn.trace_id(),
+                    String::from_utf8_lossy(alpn),
+                    handler
+                );
+
+                client.handler = Some(match handler {
+                    Handler::Echo => {
+                        ConnHandler::Streams(Box::new(Echo::new(&opts, clock, &client.conn)))
+                    }
+                    Handler::File => {
+                        ConnHandler::Streams(Box::new(FileHandler::new(opts.spill_over)))
+                    }
+                    Handler::H3 => ConnHandler::H3(Box::new(H3Handler::new().unwrap())),
+                });
+            }
+
+            if let Some(timing) = &mut loop_timing {
+                timing.lap(Phase::Read, Instant::now());
+            }
+
+            client.paths.handle_events(&mut client.conn);
+
+            if let Some(timing) = &mut loop_timing {
+                timing.lap(Phase::PathEvents, Instant::now());
+            }
+
+            flush_pending(client);
+
+            let handler = match &mut client.handler {
+                Some(ConnHandler::Streams(handler)) => Some(handler),
+
+                Some(ConnHandler::H3(h3)) => {
+                    if let Err(e) = h3.poll(&mut client.conn, &mut buf) {
+                        error!("{} HTTP/3 failed: {:?}", client.conn.trace_id(), e);
+                    }
+
+                    None
                 }
+
+                None => None,
+            };
+
+            if let Some(handler) = handler {
+                client.reads.begin();
+
+                while let Some(stream_id) = client.reads.next(&mut client.conn) {
+                    // Read again once the echo is flushed.
+                    if client.pending.contains_key(&stream_id) {
+                        client.reads.defer(stream_id);
+                        continue;
+                    }
+
+                    // Stream is readable, read until there's no more data or
+                    // the connection has served its share of this wakeup.
+                    loop {
+                        if !client.work.can_serve() {
+                            client.reads.defer(stream_id);
+                            break;
+                        }
+
+                        let Ok((read, fin)) = client.conn.stream_recv(stream_id, &mut buf) else {
+                            break;
+                        };
+
+                        client.work.serve(read);
+                        fairness.on_served(client.id, read);
+
+                        // Neither echoed nor handled by the application.
+                        if let Some(control) = Control::from_read(stream_id, &buf[..read], fin) {
+                            println!("Received {} on stream {}", control, stream_id);
+                            client.run.on_control(control);
+                            continue;
+                        }
+
+                        let answer = match handler.on_stream_data(
+                            stream_id,
+                            &buf[..read],
+                            fin,
+                            Instant::now(),
+                        ) {
+                            Ok(Some(answer)) => answer,
+
+                            Ok(None) => continue,
+
+                            Err(e) => {
+                                error!("{} {} on stream {}", client.conn.trace_id(), e, stream_id);
+                                break;
+                            }
+                        };
+
+                        // Unidirectional streams cannot be echoed.
+                        if opts.no_echo || is_uni_stream(stream_id) {
+                            continue;
+                        }
+
+                        if let Some(shaper) = &mut client.shaper {
+                            shaper.push(stream_id, &answer, fin, Instant::now());
+                            continue;
+                        }
+
+                        if !echo_on_stream(
+                            &mut client.conn,
+                            &mut client.pending,
+                            stream_id,
+                            &answer,
+                            fin,
+                        ) {
+                            // quiche does not report the stream again until
+                            // new data arrives on it.
+                            client.reads.defer(stream_id);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if let Some(shaper) = &mut client.shaper {
+                shaper.poll(&mut client.conn, Instant::now());
             }
 
-            while client.conn.scids_left() > 0 {
-                let (scid, reset_token) = generate_cid_and_reset_token(&rng);
-                if client.conn.new_scid(&scid, reset_token, false).is_err() {
-                    break;
+            let issued = client.cids.issue_with(
+                &mut client.conn,
+                || generate_cid_and_reset_token(&rng, opts.cid_len),
+                |scid| client_ids.get(scid).is_some_and(|id| *id != client.id),
+            );
+
+            for scid in issued {
+                // quiche cannot retire a source ID, so the connection it
+                // was routed to keeps it.
+                if let Some(other) = client_ids.get(&scid).filter(|id| **id != client.id) {
+                    error!(
+                        "{} connection ID {:?} already routed to connection {}",
+                        client.conn.trace_id(),
+                        scid,
+                        other
+                    );
+                    continue;
                 }
 
                 client_ids.insert(scid, client.id);
             }
 
-            write_loop(&mut client.conn, &sockets, &mut out);
+            if let Some(timing) = &mut loop_timing {
+                timing.lap(Phase::Schedule, Instant::now());
+            }
+
+            let sent = write_loop(
+                &mut client.conn,
+                &sockets,
+                &mut out,
+                client.impair.as_mut(),
+                &mut client.burst,
+                &mut client.send_log,
+            );
+            client.pre_validation.on_sent(sent);
+
+            if let Some(timing) = &mut loop_timing {
+                timing.lap(Phase::Write, Instant::now());
+            }
+        }
+
+        if let Some(stats) = loop_timing.as_mut().and_then(|t| t.end(Instant::now())) {
+            info!("loop timing: {}", stats);
         }
 
         // Garbage collect closed connections.
@@ -218,6 +692,44 @@ fn main() {
 
             if c.conn.is_closed() {
                 println!("Closing connection to {}", c.conn.trace_id());
+                println!("  paths: {}", c.paths.stats);
+                println!("  streams read per wakeup: {}", c.reads.stats());
+                println!("  work per wakeup: {}", c.work.stats());
+
+                let now = Instant::now();
+                if let Some(rate) = fairness.rate(c.id, now) {
+                    println!("  served: {:.0} bytes/s", rate);
+                }
+                if let Some(ratio) = fairness.ratio(now) {
+                    println!(
+                        "  fairness: {:.2} (max/min bytes served per second per connection)",
+                        ratio
+                    );
+                }
+                fairness.close(c.id);
+                println!("  pre-validation: {}", c.pre_validation);
+                println!("  bursts: {}", c.burst.stats());
+                println!("  connection IDs: {}", c.cids.stats(&c.conn));
+
+                if let Some(timing) = &loop_timing {
+                    println!("  loop timing: {}", timing.stats());
+                }
+
+                runs.close(&c.run);
+                println!("  run: {}", c.run);
+                if let Some(outcomes) = c.run.run_id.as_ref().and_then(|id| runs.get(id)) {
+                    println!("    connections of the run: {}", outcomes);
+                }
+
+                if let Some(impair) = &c.impair {
+                    println!("  tx impairment: {}", impair.stats());
+                }
+
+                let mut mtus: Vec<_> = c.paths.mtus().iter().collect();
+                mtus.sort();
+                for ((local, peer), mtu) in mtus {
+                    println!("    ({}, {}): mtu {}", local, peer, mtu);
+                }
 
                 info!(
                     "{} connection collected {:?} {:?}",
@@ -232,69 +744,80 @@ fn main() {
     }
 }
 
-fn handle_path_events(client: &mut Client) {
-    while let Some(qe) = client.conn.path_event_next() {
-        match qe {
-            quiche::PathEvent::New(local_addr, peer_addr) => {
-                info!(
-                    "{} Seen new path ({}, {})",
-                    client.conn.trace_id(),
-                    local_addr,
-                    peer_addr
-                );
+fn print_payload(data: &[u8], stream_id: u64) {
+    match payload::printable(data) {
+        Some(text) if data.len() <= MAX_PRINTED_LEN => {
+            println!("Received {} on stream {}", text, stream_id)
+        }
 
-                // Directly probe the new path.
-                client
-                    .conn
-                    .probe_path(local_addr, peer_addr)
-                    .expect("cannot probe");
-            }
+        Some(_) => println!("Received {} bytes on stream {}", data.len(), stream_id),
 
-            quiche::PathEvent::Validated(local_addr, peer_addr) => {
-                info!(
-                    "{} Path ({}, {}) is now validated",
-                    client.conn.trace_id(),
-                    local_addr,
-                    peer_addr
-                );
-            }
+        None => println!(
+            "Received {} on stream {}",
+            payload::hex_dump(data),
+            stream_id
+        ),
+    }
+}
 
-            quiche::PathEvent::FailedValidation(local_addr, peer_addr) => {
-                info!(
-                    "{} Path ({}, {}) failed validation",
-                    client.conn.trace_id(),
-                    local_addr,
-                    peer_addr
-                );
-            }
+/// Echoes `data` on `stream_id`, keeping what the stream cannot accept yet
+/// in `pending` for `flush_pending`. Returns false if the stream should not
+/// be read until then.
+fn echo_on_stream(
+    conn: &mut quiche::Connection,
+    pending: &mut HashMap<u64, (Vec<u8>, bool)>,
+    stream_id: u64,
+    data: &[u8],
+    fin: bool,
+) -> bool {
+    let written = match conn.stream_send(stream_id, data, fin) {
+        Ok(v) => v,
+
+        Err(quiche::Error::Done) => 0,
+
+        Err(e) => {
+            error!(
+                "{} echo on stream {} failed: {:?}",
+                conn.trace_id(),
+                stream_id,
+                e
+            );
+            return false;
+        }
+    };
 
-            quiche::PathEvent::Closed(local_addr, peer_addr) => {
-                info!(
-                    "{} Path ({}, {}) is now closed and unusable",
-                    client.conn.trace_id(),
-                    local_addr,
-                    peer_addr
-                );
-            }
+    // The stream is flow control limited, keep the rest for later and stop
+    // reading until it is sent.
+    if written < data.len() {
+        pending.insert(stream_id, (data[written..].to_vec(), fin));
+        return false;
+    }
 
-            quiche::PathEvent::ReusedSourceConnectionId(cid_seq, old, new) => {
-                info!(
-                    "{} Peer reused cid seq {} (initially {:?}) on {:?}",
-                    client.conn.trace_id(),
-                    cid_seq,
-                    old,
-                    new
-                );
-            }
+    true
+}
 
-            quiche::PathEvent::PeerMigrated(local_addr, peer_addr) => {
-                info!(
-                    "{} Connection migrated to ({}, {})",
-                    client.conn.trace_id(),
-                    local_addr,
-                    peer_addr
+/// Writes the echo data that streams could not accept earlier.
+fn flush_pending(client: &mut Client) {
+    let conn = &mut client.conn;
+
+    client.pending.retain(|stream_id, (data, fin)| {
+        let written = match conn.stream_send(*stream_id, data, *fin) {
+            Ok(v) => v,
+
+            Err(quiche::Error::Done) => 0,
+
+            Err(e) => {
+                error!(
+                    "{} echo on stream {} failed: {:?}",
+                    conn.trace_id(),
+                    stream_id,
+                    e
                 );
+                return false;
             }
-        }
-    }
+        };
+
+        data.drain(..written);
+        !data.is_empty()
+    });
 }
@@ -0,0 +1,4534 @@
+//! Client state machine, driven by `Client::step` from any event loop.
+//!
+//! The client does not own sockets nor timers: the driver hands it the
+//! datagrams received on its local addresses and sends back the ones it
+//! produces, then calls `step` again at the latest when the returned timeout
+//! expires.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, Cursor, Read},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, trace, warn, Level};
+use ring::rand::SystemRandom;
+
+use crate::{
+    config::{ArgsError, ClientOptions, Confirm},
+    events::{Event, KeyPhase, StreamStages},
+    report::{
+        CompletionTimes, HandshakeRecovery, Histogram, KeepaliveStats, LoadSample, MessageRecord,
+        Outcome, PathDelivery, PathList, PathUsage, SendStall, StalledPath, TargetReport,
+        TransferRun as ReportedRun,
+    },
+    shared::{
+        admission::Admission,
+        amplification,
+        burst::BurstLimit,
+        cids::CidIssuer,
+        control::{Completion, Control, RunId},
+        demux::RejectTracker,
+        file_transfer::{self, RangeHeader, HEADER_LEN},
+        flow_block::{FlowBlocking, PendingStream},
+        frame::{self, FrameDecoder},
+        impair::{ImpairSpec, TxImpair},
+        is_uni_stream,
+        keepalive::ConnKeepalive,
+        liveness::{Liveness, SUSPECT_RTTS},
+        log_sample::PacketLog,
+        migration::Migrations,
+        owd::{self, OwdStats, WallClock},
+        padding,
+        path_quality::{self, PathQuality, PathSample, QualityWeights},
+        path_schedule::{PathCounters, PathSchedule, ScheduleTracker, SCHEDULE_LEAD},
+        progress::{ProgressMeter, TransferProgress},
+        pto::PtoCounter,
+        read_budget::ReadBudget,
+        rng::SeededRng,
+        sequence::{self, SequenceCheck},
+        stream_writer::{Progress, StreamWriter, WriteError},
+        transform::Transform,
+        MAX_NUMBER_SOCKETS,
+    },
+};
+
+const MAX_BUF_SIZE: usize = 65507;
+
+/// Interval between two keep-alive probes on the standby path.
+const STANDBY_KEEPALIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default max_ack_delay of quiche, in milliseconds.
+const DEFAULT_MAX_ACK_DELAY: u64 = 25;
+
+/// Minimum time to wait for the first connection ID of the peer before
+/// falling back to a single path, also at least 3 RTTs.
+const PEER_CID_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Size of the chunks of a striped file transfer without `--chunk-size`.
+const DEFAULT_STRIPE_CHUNK: usize = 64 * 1024;
+
+/// Time left to the paths of a striped file transfer to be validated,
+/// those still not validated are left out.
+const STRIPE_VALIDATION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of unanswered keep-alives after which a path is probed again.
+const KEEPALIVE_MAX_UNANSWERED: u32 = 3;
+
+/// Number of PTOs after which a message whose echo is not complete frees its
+/// slot under `--max-concurrent-streams`.
+const STUCK_MESSAGE_PTOS: u32 = 8;
+
+/// Time the client waits for the server to acknowledge the completion of
+/// the run before closing the connection anyway.
+const COMPLETION_WAIT: Duration = Duration::from_secs(1);
+
+/// Payload of the latency probes of the bufferbloat measurement.
+const PROBE: &[u8] = b"probe";
+
+/// What the client sends, the same for every target.
+#[derive(Clone, Debug, Default)]
+pub struct Workload {
+    pub messages: Vec<Vec<u8>>,
+
+    /// Bytes sent for each message, padded if requested.
+    pub payloads: Vec<Vec<u8>>,
+
+    /// Size to which the payloads are padded, the echoes must then be
+    /// unpadded.
+    pub pad_to: Option<usize>,
+
+    /// Path used by each message.
+    pub paths: Vec<usize>,
+
+    /// Order in which the messages are dispatched.
+    pub order: Vec<usize>,
+
+    /// Path kept validated without carrying data until the active one fails.
+    pub standby_path: Option<usize>,
+
+    /// Size of the bulk transfer and interval between two probes of the
+    /// bufferbloat measurement.
+    pub bufferbloat: Option<(u64, Duration)>,
+
+    /// Content of the file sent in byte ranges, and the number of streams
+    /// carrying them after the single stream baseline.
+    pub file_transfer: Option<(Arc<[u8]>, usize)>,
+
+    /// Number of paths the file is striped across, with `--stripe`, and
+    /// the size of its chunks.
+    pub stripe: Option<(usize, usize)>,
+
+    /// Path new stream data goes on during each window of time, with
+    /// `--path-schedule`.
+    pub path_schedule: Option<PathSchedule>,
+
+    /// Close the connection if nothing was received for this long.
+    pub stall_timeout: Option<Duration>,
+
+    /// Close the connection if the handshake did not complete after this
+    /// long.
+    pub handshake_timeout: Duration,
+
+    /// Weights of the quality score of the paths.
+    pub quality_weights: QualityWeights,
+
+    /// Whether the messages are timestamped to measure the one-way delays
+    /// of the paths.
+    pub owd: bool,
+
+    /// Whether the messages carry their sequence number, checked on their
+    /// echo.
+    pub sequence: bool,
+
+    /// Whether the flow control blocking of the connection and its streams
+    /// is reported.
+    pub flow_blocking: bool,
+
+    /// ID of the run announced to the server, which then tells a completed
+    /// run from an aborted one.
+    pub run_id: Option<RunId>,
+
+    /// Number of times the connection may be re-established after an
+    /// interruption, to deliver the outstanding messages.
+    pub reconnect: u32,
+
+    /// max_ack_delay advertised to the peer, in milliseconds.
+    pub max_ack_delay: u64,
+
+    /// Interval of the keep-alives sent on the validated paths on which
+    /// nothing is received.
+    pub path_keepalive: Option<Duration>,
+
+    /// Time without stream data after which a path other than the active
+    /// and standby ones is closed.
+    pub path_idle_timeout: Option<Duration>,
+
+    /// Time without anything received after which a PING is sent.
+    pub keepalive: Option<Duration>,
+
+    /// Destination connection IDs the probes of new paths leave unused.
+    pub dcid_reserve: usize,
+
+    /// Length of the source connection IDs issued to the server, which the
+    /// driver also gives to the first one.
+    pub cid_len: usize,
+
+    /// Impairment of the outgoing datagrams, and the seed of its draws.
+    pub tx_impair: Option<(ImpairSpec, u64)>,
+
+    /// Number of paths over which the messages pushed once connected are
+    /// spread, keeps the connection open until `Client::close`.
+    pub interactive: Option<usize>,
+
+    /// Whether the connection stays open once every message is delivered,
+    /// until `Client::close`.
+    pub keep_open: bool,
+
+    /// One packet received out of this many is logged.
+    pub log_sample_packets: u64,
+
+    /// Interval between two `PathStats` events, none are emitted if `None`.
+    pub stats_interval: Option<Duration>,
+
+    /// Packets per burst replacing the send quantum of quiche.
+    pub burst_override: Option<usize>,
+
+    /// Number of PTOs without any packet sent, while there is stream data to
+    /// send and room in the congestion window, after which the connection is
+    /// reported as stalled.
+    pub send_watchdog: Option<u32>,
+
+    /// Close the connection once reported as stalled.
+    pub strict: bool,
+
+    /// Close the connection instead of falling back to the initial path.
+    pub require_multipath: bool,
+
+    /// Maximum number of bytes of a message handed to its stream per write.
+    pub chunk_size: Option<usize>,
+
+    /// Maximum number of messages in flight at once.
+    pub max_concurrent_streams: Option<usize>,
+
+    /// Maximum number of streams read per step.
+    pub read_budget: usize,
+
+    /// Number of buckets of the histogram of completion times, if printed.
+    pub hist_buckets: Option<usize>,
+
+    /// When a message counts as delivered.
+    pub confirm: Confirm,
+
+    /// Transformation of the messages expected as their echoes.
+    pub expect_transform: Option<Transform>,
+
+    /// Number of streams per path carrying the messages as length-prefixed
+    /// frames, instead of a stream per message.
+    pub multiplex: Option<usize>,
+}
+
+impl Workload {
+    /// Builds the workload of the client options, `seed` is used to shuffle
+    /// the messages if requested.
+    pub fn from_options(opts: &ClientOptions, seed: u64) -> Result<Self, ArgsError> {
+        let mut messages = opts.messages.clone();
+
+        if opts.multiplex == Some(0) {
+            return Err(ArgsError("--multiplex needs at least one path".to_string()));
+        }
+
+        // Path used by each message, skipping the standby path. Multiplexed
+        // messages take the first paths in turn.
+        let mut paths: Vec<usize> = (0..)
+            .filter(|i| Some(*i) != opts.standby_path)
+            .take(opts.multiplex.unwrap_or(messages.len()))
+            .collect();
+
+        if opts.multiplex.is_some() {
+            paths = (0..messages.len())
+                .map(|i| paths[i % paths.len()])
+                .collect();
+        }
+
+        if opts.empty_streams > 0 && (opts.multiplex.is_some() || opts.pad_to.is_some()) {
+            return Err(ArgsError(
+                "--empty-streams cannot be combined with --multiplex or --pad-to".to_string(),
+            ));
+        }
+
+        // Empty streams follow the messages, on their paths in turn.
+        let used = paths.clone();
+        let first_path = usize::from(opts.standby_path == Some(0));
+        paths.extend((0..opts.empty_streams).map(|i| match used.len() {
+            0 => first_path,
+            n => used[i % n],
+        }));
+        messages.resize(messages.len() + opts.empty_streams, Vec::new());
+
+        if opts.bufferbloat.is_some() && !messages.is_empty() {
+            return Err(ArgsError(
+                "--bufferbloat runs on a single path and takes no messages".to_string(),
+            ));
+        }
+
+        if opts.bufferbloat.is_some() && opts.interactive.is_some() {
+            return Err(ArgsError(
+                "--bufferbloat and --interactive cannot be combined".to_string(),
+            ));
+        }
+
+        if opts.file.is_some() && !messages.is_empty() {
+            return Err(ArgsError(
+                "--file runs on a single path and takes no messages".to_string(),
+            ));
+        }
+
+        if opts.file.is_some() && (opts.bufferbloat.is_some() || opts.interactive.is_some()) {
+            return Err(ArgsError(
+                "--file cannot be combined with --bufferbloat or --interactive".to_string(),
+            ));
+        }
+
+        if opts.file.is_some() && opts.reconnect > 0 {
+            return Err(ArgsError(
+                "--file cannot resume after a reconnection".to_string(),
+            ));
+        }
+
+        let file_transfer = match &opts.file {
+            Some(path) => {
+                let data = std::fs::read(path)
+                    .map_err(|e| ArgsError(format!("cannot read --file {}: {}", path, e)))?;
+
+                Some((Arc::from(data), opts.parallel_streams.unwrap_or(1)))
+            }
+
+            None => None,
+        };
+
+        if opts.handshakes.is_some()
+            && (!messages.is_empty()
+                || opts.bufferbloat.is_some()
+                || opts.file.is_some()
+                || opts.interactive.is_some())
+        {
+            return Err(ArgsError(
+                "--handshakes only establishes connections and takes no messages".to_string(),
+            ));
+        }
+
+        if opts.interactive == Some(0) {
+            return Err(ArgsError(
+                "--interactive needs at least one path".to_string(),
+            ));
+        }
+
+        if opts.bufferbloat.is_some() && opts.reconnect > 0 {
+            return Err(ArgsError(
+                "--bufferbloat cannot resume after a reconnection".to_string(),
+            ));
+        }
+
+        if opts.confirm == Confirm::Acks && opts.expect_transform.is_some() {
+            return Err(ArgsError(
+                "--expect-transform checks echoes, it cannot be combined with --confirm acks"
+                    .to_string(),
+            ));
+        }
+
+        if opts.confirm == Confirm::Acks && opts.multiplex.is_some() {
+            return Err(ArgsError(
+                "--confirm acks and --multiplex cannot be combined".to_string(),
+            ));
+        }
+
+        if opts.multiplex.is_some() && opts.pad_to.is_some() {
+            return Err(ArgsError(
+                "--multiplex and --pad-to cannot be combined".to_string(),
+            ));
+        }
+
+        let payloads = match opts.pad_to {
+            Some(size) => messages
+                .iter()
+                .map(|m| padding::pad(m, size))
+                .collect::<Result<_, _>>()
+                .map_err(|e| {
+                    ArgsError(format!("cannot pad message to --pad-to {}: {}", size, e))
+                })?,
+
+            None => messages.clone(),
+        };
+
+        let mut order: Vec<usize> = (0..messages.len()).collect();
+        if opts.shuffle {
+            SeededRng::new(seed).shuffle(&mut order);
+        }
+
+        let workload = Workload {
+            messages,
+            payloads,
+            pad_to: opts.pad_to,
+            paths,
+            order,
+            standby_path: opts.standby_path,
+            bufferbloat: opts
+                .bufferbloat
+                .map(|size| (size, Duration::from_millis(opts.probe_interval_ms))),
+            file_transfer,
+            stripe: opts
+                .stripe
+                .map(|paths| (paths, opts.chunk_size.unwrap_or(DEFAULT_STRIPE_CHUNK))),
+            path_schedule: opts.path_schedule.clone(),
+            stall_timeout: opts.stall_timeout_ms.map(Duration::from_millis),
+            handshake_timeout: Duration::from_millis(opts.handshake_timeout_ms),
+            quality_weights: opts.quality_weights,
+            owd: opts.owd,
+            sequence: opts.sequence,
+            flow_blocking: opts.flow_blocking,
+            run_id: opts.run_id.clone(),
+            reconnect: opts.reconnect,
+            max_ack_delay: opts.max_ack_delay_ms.unwrap_or(DEFAULT_MAX_ACK_DELAY),
+            path_keepalive: opts.path_keepalive_ms.map(Duration::from_millis),
+            path_idle_timeout: opts.path_idle_timeout_ms.map(Duration::from_millis),
+            keepalive: opts.keepalive_ms.map(Duration::from_millis),
+            dcid_reserve: opts.dcid_reserve,
+            cid_len: opts.cid_len,
+            tx_impair: opts.tx_impair.map(|spec| (spec, seed)),
+            interactive: opts.interactive,
+            keep_open: opts.keep_open,
+            log_sample_packets: opts.log_sample_packets,
+            burst_override: opts.burst_override,
+            stats_interval: (opts.json_events || opts.events_json.is_some())
+                .then(|| Duration::from_millis(opts.stats_interval_ms)),
+            send_watchdog: opts.send_watchdog,
+            strict: opts.strict,
+            require_multipath: opts.require_multipath,
+            chunk_size: opts.chunk_size,
+            max_concurrent_streams: opts.max_concurrent_streams,
+            read_budget: opts.read_budget,
+            hist_buckets: opts.hist_buckets,
+            confirm: opts.confirm,
+            expect_transform: opts.expect_transform,
+            multiplex: opts.multiplex.map(|_| opts.multiplex_streams),
+        };
+
+        if workload.path_count() > MAX_NUMBER_SOCKETS {
+            return Err(ArgsError(format!(
+                "at most {} paths are supported",
+                MAX_NUMBER_SOCKETS
+            )));
+        }
+
+        Ok(workload)
+    }
+
+    /// Restricts the workload to the first `available` local addresses,
+    /// e.g. because the others could not be set up. The messages of the
+    /// missing paths are spread over the remaining ones, skipping the
+    /// standby path, which is dropped if it is missing or the only path
+    /// left.
+    pub fn fit_paths(&mut self, available: usize) {
+        assert!(available > 0);
+
+        if self
+            .standby_path
+            .is_some_and(|i| i >= available || available == 1)
+        {
+            warn!("standby path {} unavailable", self.standby_path.unwrap());
+            self.standby_path = None;
+        }
+
+        let usable: Vec<usize> = (0..available)
+            .filter(|i| Some(*i) != self.standby_path)
+            .collect();
+
+        for path in &mut self.paths {
+            if *path >= available {
+                *path = usable[*path % usable.len()];
+            }
+        }
+
+        self.interactive = self.interactive.map(|n| n.min(available));
+
+        if self
+            .path_schedule
+            .as_ref()
+            .is_some_and(|s| s.max_path() >= available)
+        {
+            warn!("path schedule needs unavailable paths, ignored");
+            self.path_schedule = None;
+        }
+    }
+
+    /// The same workload with every message sent on the first path and no
+    /// standby path, for the single-path baseline.
+    pub fn single_path(&self) -> Workload {
+        let mut workload = self.clone();
+
+        workload.paths.fill(0);
+        workload.standby_path = None;
+        workload.interactive = None;
+        workload.stripe = None;
+        workload.path_schedule = None;
+
+        workload
+    }
+
+    /// Number of local addresses the client needs.
+    pub fn path_count(&self) -> usize {
+        self.paths
+            .iter()
+            .max()
+            .map_or(0, |i| i + 1)
+            .max(self.standby_path.map_or(1, |i| i + 1))
+            .max(self.interactive.unwrap_or(0))
+            .max(self.stripe.map_or(0, |(paths, _)| paths))
+            .max(self.path_schedule.as_ref().map_or(0, |s| s.max_path() + 1))
+    }
+}
+
+/// A message whose echo was fully received.
+#[derive(Clone, Debug)]
+pub struct Completed {
+    /// Echo of the message, without its padding.
+    pub echo: Vec<u8>,
+    pub record: MessageRecord,
+}
+
+/// What a call to `Client::step` produced.
+#[derive(Debug, Default)]
+pub struct StepOutput {
+    /// Datagrams to send from `SendInfo::from` to `SendInfo::to`.
+    pub datagrams: Vec<(Vec<u8>, quiche::SendInfo)>,
+
+    /// Time at which `step` must be called again if nothing is received.
+    pub timeout: Option<Instant>,
+
+    pub completed: Vec<Completed>,
+    pub events: Vec<Event>,
+}
+
+/// Reaction of a client to quiche's path events, see
+/// [`Client::set_path_policy`].
+pub type PathPolicy =
+    Box<dyn FnMut(&mut ClientState, quiche::PathEvent, &mut Vec<Event>) -> Vec<PathAction>>;
+
+/// Connection to one target, with the state of its messages.
+pub struct Client {
+    conn: quiche::Connection,
+    state: ClientState,
+    path_policy: PathPolicy,
+    dispatcher: Dispatcher,
+    upkeep: PathUpkeep,
+    /// Windows of `--path-schedule` and the bytes each carried.
+    schedule: Option<ScheduleTracker>,
+    tx_impair: Option<TxImpair>,
+    watchdog: Option<SendWatchdog>,
+    bufferbloat: Option<Bufferbloat>,
+    file_transfer: Option<FileTransfer>,
+    reads: ReadBudget,
+    received: Vec<bool>,
+    checks: EchoChecks,
+    /// Whether messages are pushed once connected, the connection then stays
+    /// open until closed explicitly.
+    interactive: bool,
+    /// Whether the connection stays open once every message is delivered,
+    /// until closed explicitly.
+    keep_open: bool,
+    /// Whether the automatic close was suppressed by `keep_open`.
+    kept_open: bool,
+    /// Samples the logs of the packets received.
+    packet_log: PacketLog,
+    /// Limits the packets sent per path and step.
+    burst: BurstLimit,
+    /// Outcomes of the migrations to another path.
+    migrations: Migrations,
+    /// Issues connection IDs for the paths of the server.
+    cids: CidIssuer,
+    /// Interval of the `PathStats` events, and when the next ones are due.
+    stats_interval: Option<Duration>,
+    next_stats: Option<Instant>,
+    /// Echoes received so far on each stream.
+    echoes: HashMap<u64, Vec<u8>>,
+    established: bool,
+    /// Loss detection timer expirations before the handshake completed.
+    handshake_ptos: usize,
+    /// Probe timeouts of each path, across reconnections.
+    ptos: PtoCounter,
+    /// Whether the handshake is considered confirmed, no path is probed
+    /// before while the server is limited by the anti-amplification limit.
+    confirmed: bool,
+    /// Whether the peer issued connection IDs for new paths.
+    peer_cids: bool,
+    /// Time after which the client falls back to a single path if the peer
+    /// did not issue connection IDs yet.
+    peer_cid_deadline: Option<Instant>,
+    /// Whether every message is sent on the initial path, because the peer
+    /// does not allow new paths.
+    single_path: bool,
+    /// Close the connection instead of falling back to the initial path.
+    require_multipath: bool,
+    /// Whether the connection was closed as the peer does not allow new
+    /// paths, with `require_multipath`.
+    multipath_refused: bool,
+    key_phase: KeyPhase,
+    /// Paths on which the echo of each stream was received so far.
+    stream_paths: HashMap<u64, PathList>,
+    /// Path on which the last packet was received.
+    last_recv_info: Option<quiche::RecvInfo>,
+    /// Paths probed and validated so far, across reconnections.
+    probed_paths: HashSet<(SocketAddr, SocketAddr)>,
+    validated_paths: HashSet<(SocketAddr, SocketAddr)>,
+    /// Stream bytes received on each path.
+    stream_bytes: HashMap<(SocketAddr, SocketAddr), u64>,
+    /// Last MTU of each path, across reconnections.
+    path_mtus: HashMap<(SocketAddr, SocketAddr), usize>,
+    start: Instant,
+    stall_timeout: Option<Duration>,
+    handshake_timeout: Duration,
+    /// Why the connection was closed before its handshake completed, once
+    /// `handshake_timeout` elapsed.
+    handshake_timed_out: Option<String>,
+    quality_weights: QualityWeights,
+    /// Control messages of the run, if it has an ID.
+    run: Option<RunControl>,
+    flow: FlowTracking,
+    /// Last time stream data was received or a path was validated.
+    last_progress: Instant,
+    stalled: bool,
+    /// Whether the connection was closed by the send watchdog.
+    send_stalled: bool,
+    /// Datagrams of the current connection rejected by quiche.
+    rejects: RejectTracker,
+    /// Whether the connection was closed because its datagrams looked
+    /// meant for another connection, with `--strict`.
+    misdelivered: bool,
+    strict: bool,
+    /// Time at which the client asked to be stepped again.
+    deadline: Option<Instant>,
+    /// Whether the peer closed the connection with a stateless reset, e.g.
+    /// because it restarted and lost the connection state.
+    stateless_reset: bool,
+    /// Whether the connection ended without a close from either side.
+    interrupted: bool,
+    reconnects_left: u32,
+    max_ack_delay: u64,
+    report: TargetReport,
+    /// Whether the connection is closed and its report complete.
+    finished: bool,
+    rng: SystemRandom,
+    buf: Vec<u8>,
+    events: Vec<Event>,
+    completed: Vec<Completed>,
+}
+
+/// Timers keeping the paths and the connection alive.
+struct PathUpkeep {
+    standby: Option<Standby>,
+    keepalive: Option<PathKeepalive>,
+    idle_paths: Option<IdlePaths>,
+    /// PINGs keeping the connection open while idle.
+    pings: Option<ConnKeepalive>,
+}
+
+/// Checks of the echoes against the messages sent.
+struct EchoChecks {
+    /// Size to which pushed messages are padded.
+    pad_to: Option<usize>,
+    /// Transformation of the messages expected as their echoes.
+    expect_transform: Option<Transform>,
+    /// One-way delays of the timestamped messages, across reconnections.
+    owd: Option<OwdStats>,
+    /// Order of the echoes, across reconnections, with `--sequence`.
+    sequence: Option<SequenceCheck>,
+}
+
+/// Flow control blocking of the connection.
+struct FlowTracking {
+    /// Whether the blocking is tracked, from the handshake.
+    tracked: bool,
+    /// Blocking so far, across reconnections.
+    blocking: Option<FlowBlocking>,
+}
+
+impl Client {
+    /// Wraps a connection created by `quiche::connect` from `local_addrs[0]`
+    /// to `peer_addrs[0]`. Path `i` goes from `local_addrs[i]` to
+    /// `peer_addrs[i]`.
+    pub fn new(
+        conn: quiche::Connection,
+        workload: &Workload,
+        local_addrs: Vec<SocketAddr>,
+        peer_addrs: Vec<SocketAddr>,
+        report: TargetReport,
+        now: Instant,
+    ) -> Self {
+        let messages = workload.messages.len();
+
+        let mut report = report;
+        if workload.max_concurrent_streams.is_some() {
+            report.queue_wait = Some(Histogram::default());
+        }
+        report.completion_times = workload.hist_buckets.map(CompletionTimes::new);
+        report.run_id = workload.run_id.clone();
+
+        let initial_path = (local_addrs[0], peer_addrs[0]);
+
+        Client {
+            conn,
+            dispatcher: Dispatcher {
+                messages: workload.messages.clone(),
+                payloads: workload.payloads.clone(),
+                paths: workload.paths.clone(),
+                order: workload.order.clone(),
+                chunk_size: workload.chunk_size,
+                multiplex: workload.multiplex,
+                probe: false,
+                dcid_reserve: workload.dcid_reserve,
+                reserve_hit: None,
+                probe_failed: None,
+                // The handshake uses the first connection ID of the server.
+                dcid_seqs: HashMap::from([(initial_path, 0)]),
+                closed_paths: HashSet::new(),
+                // The stuck time follows the PTO, set before each dispatch.
+                admission: workload
+                    .max_concurrent_streams
+                    .map(|max| Admission::new(max, Duration::ZERO)),
+                queue_wait: vec![Duration::ZERO; messages],
+                next: 0,
+                next_stream_id: 0,
+                uni: workload.confirm == Confirm::Acks,
+                next_uni_stream_id: 2,
+                unacked: vec![],
+                streams: HashMap::new(),
+                closing: HashSet::new(),
+                writers: vec![],
+                written: 0,
+                mux: HashMap::new(),
+                sent_at: vec![None; messages],
+                forced_path: None,
+                stamps: Stamps {
+                    clock: workload.owd.then(|| WallClock::new(now)),
+                    seqs: workload.sequence.then(|| vec![None; messages]),
+                },
+            },
+            schedule: workload.path_schedule.clone().map(ScheduleTracker::new),
+            upkeep: PathUpkeep {
+                standby: workload.standby_path.map(|i| Standby {
+                    local_addr: local_addrs[i],
+                    peer_addr: peer_addrs[i],
+                    probed: i == 0,
+                    last_keepalive: None,
+                    failover: None,
+                    liveness: Liveness::default(),
+                }),
+                keepalive: workload.path_keepalive.map(PathKeepalive::new),
+                idle_paths: workload.path_idle_timeout.map(IdlePaths::new),
+                pings: workload
+                    .keepalive
+                    .map(|interval| ConnKeepalive::new(interval, now)),
+            },
+            tx_impair: workload
+                .tx_impair
+                .map(|(spec, seed)| TxImpair::new(spec, seed)),
+            watchdog: workload
+                .send_watchdog
+                .map(|ptos| SendWatchdog::new(ptos, workload.strict, now)),
+            bufferbloat: workload
+                .bufferbloat
+                .map(|(size, interval)| Bufferbloat::new(size, interval)),
+            file_transfer: workload
+                .file_transfer
+                .clone()
+                .map(|(data, streams)| FileTransfer::new(data, streams, workload.stripe)),
+            reads: ReadBudget::new(workload.read_budget),
+            state: ClientState::new(local_addrs, peer_addrs, workload.standby_path),
+            path_policy: Box::new(handle_path_event),
+            received: vec![false; messages],
+            checks: EchoChecks {
+                pad_to: workload.pad_to,
+                expect_transform: workload.expect_transform,
+                owd: workload.owd.then(OwdStats::default),
+                sequence: workload.sequence.then(SequenceCheck::default),
+            },
+            interactive: workload.interactive.is_some(),
+            keep_open: workload.keep_open,
+            kept_open: false,
+            packet_log: PacketLog::new(workload.log_sample_packets),
+            burst: BurstLimit::new(workload.burst_override),
+            migrations: Migrations::default(),
+            cids: CidIssuer::new(workload.cid_len),
+            stats_interval: workload.stats_interval,
+            next_stats: workload.stats_interval.map(|i| now + i),
+            echoes: HashMap::new(),
+            established: false,
+            handshake_ptos: 0,
+            ptos: PtoCounter::default(),
+            confirmed: false,
+            peer_cids: false,
+            peer_cid_deadline: None,
+            single_path: false,
+            require_multipath: workload.require_multipath,
+            multipath_refused: false,
+            key_phase: KeyPhase::Handshake,
+            stream_paths: HashMap::new(),
+            last_recv_info: None,
+            probed_paths: HashSet::new(),
+            validated_paths: HashSet::new(),
+            stream_bytes: HashMap::new(),
+            path_mtus: HashMap::new(),
+            start: now,
+            stall_timeout: workload.stall_timeout,
+            handshake_timeout: workload.handshake_timeout,
+            handshake_timed_out: None,
+            quality_weights: workload.quality_weights,
+            run: workload.run_id.clone().map(RunControl::new),
+            flow: FlowTracking {
+                tracked: workload.flow_blocking,
+                blocking: None,
+            },
+            last_progress: now,
+            stalled: false,
+            send_stalled: false,
+            rejects: RejectTracker::default(),
+            misdelivered: false,
+            strict: workload.strict,
+            deadline: None,
+            stateless_reset: false,
+            interrupted: false,
+            reconnects_left: workload.reconnect,
+            max_ack_delay: workload.max_ack_delay,
+            report,
+            finished: false,
+            rng: SystemRandom::new(),
+            buf: vec![0; MAX_BUF_SIZE],
+            events: vec![],
+            completed: vec![],
+        }
+    }
+
+    pub fn conn(&self) -> &quiche::Connection {
+        &self.conn
+    }
+
+    /// Length of the source connection IDs, for the ones of new
+    /// connections.
+    pub fn cid_len(&self) -> usize {
+        self.cids.cid_len()
+    }
+
+    /// Probe timeouts of each path so far.
+    pub fn ptos(&self) -> &PtoCounter {
+        &self.ptos
+    }
+
+    /// Local address of each path.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.state.local_addrs
+    }
+
+    /// Peer address of each path.
+    pub fn peer_addrs(&self) -> &[SocketAddr] {
+        &self.state.peer_addrs
+    }
+
+    /// Paths validated so far, across reconnections, including the initial
+    /// path once established.
+    pub fn validated_paths(&self) -> &HashSet<(SocketAddr, SocketAddr)> {
+        &self.validated_paths
+    }
+
+    /// Validated paths of the current connection ranked by their quality,
+    /// best first.
+    pub fn path_quality(&self) -> Vec<PathQuality> {
+        let paths = self
+            .conn
+            .path_stats()
+            .filter(|s| self.validated_paths.contains(&(s.local_addr, s.peer_addr)))
+            .map(|s| PathSample::from(&s));
+
+        path_quality::rank(paths, &self.quality_weights)
+    }
+
+    /// Streams of the current connection the client keeps state for, by
+    /// stage.
+    pub fn stream_stages(&self) -> StreamStages {
+        self.dispatcher.stream_stages()
+    }
+
+    pub fn report(&self) -> &TargetReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> TargetReport {
+        self.report
+    }
+
+    /// Whether the handshake is considered confirmed, from which point new
+    /// paths are probed.
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed
+    }
+
+    /// Whether the connection is closed and its report complete.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Whether the connection was interrupted with messages outstanding and
+    /// may be replaced through `reconnect`.
+    pub fn can_reconnect(&self) -> bool {
+        self.finished
+            && self.interrupted
+            && self.reconnects_left > 0
+            && self.report.delivered < self.report.messages
+    }
+
+    /// Dispatches first the messages whose path has the lowest RTT in
+    /// `rtts`, e.g. as cached by an earlier run, so that the fastest paths
+    /// are probed first. The messages on uncached paths follow in their
+    /// order. Must be called before the first step.
+    pub fn prioritize_paths(&mut self, rtts: &HashMap<(SocketAddr, SocketAddr), Duration>) {
+        let state = &self.state;
+        let rtt = |path: usize| {
+            rtts.get(&(state.local_addrs[path], state.peer_addrs[path]))
+                .copied()
+        };
+
+        let Dispatcher { paths, order, .. } = &mut self.dispatcher;
+        order.sort_by_key(|i| {
+            let rtt = rtt(paths[*i]);
+            (rtt.is_none(), rtt)
+        });
+
+        let used: HashSet<usize> = paths.iter().copied().collect();
+        let hits = used.iter().filter(|p| rtt(**p).is_some()).count();
+
+        info!("{}/{} paths found in the path cache", hits, used.len());
+        self.report.path_cache_hits = Some((hits, used.len()));
+    }
+
+    /// Marks the messages `delivered` by an earlier run, e.g. before a
+    /// restart, as delivered so that only the others are sent. Must be
+    /// called before the first step.
+    pub fn skip_delivered(&mut self, delivered: &[usize]) {
+        for i in delivered {
+            if let Some(received) = self.received.get_mut(*i) {
+                *received = true;
+            }
+        }
+
+        self.dispatcher.order.retain(|i| !self.received[*i]);
+    }
+
+    /// Indices of the messages whose echo was received.
+    pub fn delivered(&self) -> Vec<usize> {
+        (0..self.received.len())
+            .filter(|i| self.received[*i])
+            .collect()
+    }
+
+    /// Replaces the reaction to quiche's path events, [`handle_path_event`]
+    /// by default, e.g. to choose which validated path to migrate to.
+    ///
+    /// `policy` is called during `step`, on the thread calling it, once per
+    /// path event and in the order quiche reports them. It gets the path
+    /// state of the client, may record connection events, which `step` then
+    /// returns, and returns the actions the client applies right after. It
+    /// has no access to the `Client`, so it cannot re-enter it; state shared
+    /// with the caller goes through e.g. an `Rc<RefCell<_>>`. The policy is
+    /// kept across reconnections.
+    pub fn set_path_policy<F>(&mut self, policy: F)
+    where
+        F: FnMut(&mut ClientState, quiche::PathEvent, &mut Vec<Event>) -> Vec<PathAction> + 'static,
+    {
+        self.path_policy = Box::new(policy);
+    }
+
+    /// Queues `message` to be sent on the next path in turn, skipping the
+    /// standby path, and returns its index.
+    pub fn push_message(&mut self, message: &[u8]) -> Result<usize, String> {
+        let payload = match self.checks.pad_to {
+            Some(size) => padding::pad(message, size)
+                .map_err(|e| format!("cannot pad message to {}: {}", size, e))?,
+
+            None => message.to_vec(),
+        };
+
+        let paths: Vec<usize> =
+            (0..self.state.local_addrs.len())
+                .filter(|i| {
+                    !self.upkeep.standby.as_ref().is_some_and(|s| {
+                        s.is(self.state.local_addrs[*i], self.state.peer_addrs[*i])
+                    })
+                })
+                .collect();
+
+        if paths.is_empty() {
+            return Err("no path besides the standby one".to_string());
+        }
+
+        let index = self.dispatcher.messages.len();
+        let path = if self.single_path {
+            0
+        } else {
+            paths[index % paths.len()]
+        };
+
+        let dispatcher = &mut self.dispatcher;
+        dispatcher.messages.push(message.to_vec());
+        dispatcher.payloads.push(payload);
+        dispatcher.paths.push(path);
+        dispatcher.order.push(index);
+        dispatcher.sent_at.push(None);
+        if let Some(seqs) = &mut dispatcher.stamps.seqs {
+            seqs.push(None);
+        }
+        dispatcher.queue_wait.push(Duration::ZERO);
+        self.received.push(false);
+        self.report.messages += 1;
+
+        Ok(index)
+    }
+
+    /// Makes path `path` the active one, probing it first if needed.
+    pub fn migrate(&mut self, path: usize) -> Result<(), String> {
+        let (Some(local_addr), Some(peer_addr)) = (
+            self.state.local_addrs.get(path),
+            self.state.peer_addrs.get(path),
+        ) else {
+            return Err(format!("no path {}", path));
+        };
+        let (local_addr, peer_addr) = (*local_addr, *peer_addr);
+
+        if self.dispatcher.closed_paths.contains(&path) {
+            return Err(format!("path {} was closed after being idle", path));
+        }
+
+        match self.conn.is_path_validated(local_addr, peer_addr) {
+            // Migrated to once validated.
+            Err(_) => {
+                let dcid_seq = self
+                    .conn
+                    .probe_path(local_addr, peer_addr)
+                    .map_err(|e| format!("cannot probe path {}: {:?}", path, e))?;
+                self.dispatcher
+                    .dcid_seqs
+                    .insert((local_addr, peer_addr), dcid_seq);
+                self.events.push(Event::PathProbed(local_addr, peer_addr));
+            }
+
+            Ok(false) => (),
+
+            Ok(true) => {
+                if let Err(e) = self.conn.migrate(local_addr, peer_addr) {
+                    self.events
+                        .push(Event::MigrationFailed(local_addr, peer_addr));
+                    return Err(format!("cannot migrate to path {}: {:?}", path, e));
+                }
+
+                self.events.push(Event::Migrated(local_addr, peer_addr));
+                self.state.active_path = (local_addr, peer_addr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the connection, once the messages in flight are echoed.
+    pub fn close(&mut self) {
+        self.interactive = false;
+        self.keep_open = false;
+    }
+
+    /// Whether every message was delivered and the connection is only kept
+    /// open by `--keep-open`.
+    pub fn is_kept_open(&self) -> bool {
+        self.keep_open && self.kept_open && !self.finished
+    }
+
+    /// Replaces the interrupted connection by `conn`, created like the first
+    /// one, and dispatches again the messages whose echo was not received.
+    pub fn reconnect(&mut self, conn: quiche::Connection, now: Instant) {
+        if let Some(error) = self.report.error.take() {
+            info!("reconnecting after {}", error);
+            self.report.interruptions.push(error);
+        }
+
+        self.report.outstanding.clear();
+        self.report.failure = None;
+        self.reconnects_left -= 1;
+
+        self.conn = conn;
+
+        let dispatcher = &mut self.dispatcher;
+        dispatcher.probe = false;
+        dispatcher.order.retain(|i| !self.received[*i]);
+        dispatcher.next = 0;
+        dispatcher.next_stream_id = 0;
+        dispatcher.next_uni_stream_id = 2;
+        dispatcher.unacked.clear();
+        dispatcher.streams.clear();
+        dispatcher.closing.clear();
+        dispatcher.forced_path = None;
+        dispatcher.dcid_seqs =
+            HashMap::from([((self.state.local_addrs[0], self.state.peer_addrs[0]), 0)]);
+        dispatcher.writers.clear();
+        dispatcher.written = 0;
+        dispatcher.mux.clear();
+        if let Some(admission) = &mut dispatcher.admission {
+            admission.clear();
+        }
+        for (i, sent_at) in dispatcher.sent_at.iter_mut().enumerate() {
+            if !self.received[i] {
+                *sent_at = None;
+            }
+        }
+
+        if let Some(sequence) = &mut self.checks.sequence {
+            sequence.restart();
+        }
+
+        if let Some(standby) = &mut self.upkeep.standby {
+            standby.probed = standby.local_addr == self.state.local_addrs[0];
+            standby.last_keepalive = None;
+            standby.failover = None;
+            standby.liveness.reset();
+        }
+
+        if let Some(keepalive) = &mut self.upkeep.keepalive {
+            keepalive.paths.clear();
+        }
+
+        if let Some(idle_paths) = &mut self.upkeep.idle_paths {
+            idle_paths.activity.clear();
+        }
+
+        if let Some(pings) = &mut self.upkeep.pings {
+            pings.restart(now);
+        }
+
+        if let Some(watchdog) = &mut self.watchdog {
+            *watchdog = SendWatchdog::new(watchdog.ptos, watchdog.strict, now);
+        }
+
+        if let Some(run) = &mut self.run {
+            *run = RunControl::new(run.run_id.clone());
+        }
+
+        self.echoes.clear();
+        self.stream_paths.clear();
+        self.state.active_path = (self.state.local_addrs[0], self.state.peer_addrs[0]);
+        self.state.validated.clear();
+        self.state.kept_alive.clear();
+        self.established = false;
+        self.handshake_ptos = 0;
+        self.ptos.restart();
+        self.confirmed = false;
+        self.peer_cids = false;
+        self.cids.restart();
+        self.peer_cid_deadline = None;
+        self.key_phase = KeyPhase::Handshake;
+        self.last_recv_info = None;
+        self.start = now;
+        self.last_progress = now;
+        self.stalled = false;
+        self.send_stalled = false;
+        self.rejects.restart();
+        self.handshake_timed_out = None;
+        self.multipath_refused = false;
+        self.stateless_reset = false;
+        self.interrupted = false;
+        self.finished = false;
+    }
+
+    /// Processes the datagrams received since the last call, runs the timers
+    /// due at `now` and returns the datagrams to send.
+    ///
+    /// quiche keeps its own clock, `now` only drives the timers of the
+    /// client. The datagrams are decrypted in place.
+    pub fn step(
+        &mut self,
+        now: Instant,
+        incoming: &mut [(Vec<u8>, quiche::RecvInfo)],
+    ) -> StepOutput {
+        if self.finished {
+            return StepOutput::default();
+        }
+
+        let backlog = self.reads.begin();
+        let was_established = self.established;
+
+        for (pkt, recv_info) in incoming.iter_mut() {
+            self.report.pre_validation.on_recv(pkt.len());
+
+            let path = (recv_info.to, recv_info.from);
+            let stray = !self.state.is_own_path(path);
+
+            // quiche creates the path, and validates it, without telling
+            // clients about it.
+            let new_path = stray
+                && !self
+                    .conn
+                    .path_stats()
+                    .any(|s| (s.local_addr, s.peer_addr) == path);
+
+            if stray {
+                self.report.stray_datagrams += 1;
+
+                if new_path {
+                    warn!(
+                        "{} datagram on unknown path ({}, {})",
+                        self.conn.trace_id(),
+                        path.0,
+                        path.1
+                    );
+                }
+            }
+
+            let dcid = quiche::Header::from_slice(pkt, self.cids.cid_len())
+                .ok()
+                .map(|hdr| hdr.dcid.to_vec());
+            let open = !self.conn.is_closed() && !self.conn.is_draining();
+            let received = self.conn.stats().recv;
+
+            let read = self.conn.recv(pkt, *recv_info);
+
+            // quiche silently drops the packets it cannot decrypt. A
+            // stateless reset adds none either, but closes the connection.
+            let rejected = self.conn.stats().recv == received && !self.conn.is_closed();
+            if open {
+                self.rejects
+                    .on_datagram(recv_info.to, dcid.as_deref(), rejected);
+                self.report.rejected_datagrams += u64::from(rejected);
+            }
+
+            let read = match read {
+                Ok(v) => v,
+
+                Err(e) => {
+                    error!("{} recv failed: {:?}", self.conn.trace_id(), e);
+                    continue;
+                }
+            };
+
+            if self.packet_log.sample(Level::Info, read) {
+                info!("{} processed {} bytes", self.conn.trace_id(), read);
+            }
+
+            if new_path && self.conn.is_path_validated(path.0, path.1).is_ok() {
+                self.events.push(Event::PathNew(path.0, path.1));
+            }
+
+            // quiche closes the connection without any error when a
+            // datagram is a stateless reset.
+            if self.conn.is_closed()
+                && self.conn.peer_error().is_none()
+                && self.conn.local_error().is_none()
+            {
+                warn!("{} stateless reset by peer", self.conn.trace_id());
+                self.stateless_reset = true;
+                break;
+            }
+
+            self.last_recv_info = Some(*recv_info);
+
+            // Streams are drained after each datagram, so that their data is
+            // attributed to the path it arrived on.
+            self.read_streams(now);
+        }
+
+        if let Some((read, skipped)) = self.packet_log.end_burst() {
+            info!(
+                "{} processed {} bytes ({} packets not logged before)",
+                self.conn.trace_id(),
+                read,
+                skipped
+            );
+        }
+
+        // The streams left over by the previous step, their data is
+        // attributed to the path of the last datagram.
+        if backlog {
+            self.read_streams(now);
+        }
+
+        self.complete_acked(now);
+        self.close_streams();
+
+        if let (Some(pings), false) = (&mut self.upkeep.pings, incoming.is_empty()) {
+            pings.on_recv(now);
+        }
+
+        if incoming.is_empty() {
+            trace!("timed out");
+
+            let expired = self.conn.timeout_instant().is_some_and(|t| t <= now);
+
+            // Before the handshake completes, the only timer besides the
+            // idle timeout is the loss detection one.
+            if expired && !self.established {
+                self.handshake_ptos += 1;
+            }
+
+            if expired {
+                self.ptos
+                    .before_timeout(self.conn.path_stats().map(|s| (&s).into()));
+            }
+
+            self.conn.on_timeout();
+
+            if expired {
+                self.ptos
+                    .after_timeout(self.conn.path_stats().map(|s| (&s).into()));
+            }
+        }
+
+        // quiche does not tell clients when the HANDSHAKE_DONE frame
+        // confirming the handshake arrives. The server sends it once it has
+        // the Finished of the client, so the first datagram received after
+        // the handshake completed is taken as the confirmation.
+        if was_established && !incoming.is_empty() && !self.confirmed {
+            self.confirm();
+        }
+
+        self.process(now);
+        self.track_transfer(now);
+        self.track_schedule(now);
+        self.track_flow(now);
+        self.close_idle_paths(now);
+
+        if incoming.is_empty() {
+            self.on_idle(now);
+        }
+
+        self.check_handshake_timeout(now);
+        self.check_misdelivery();
+        self.check_amplification();
+        self.check_send_stall(now);
+        self.sample_paths(now);
+
+        let mut datagrams = self.send();
+
+        if let Some(impair) = &mut self.tx_impair {
+            datagrams = impair.apply(datagrams);
+            self.report.tx_impaired = Some(impair.stats());
+        }
+
+        for (pkt, _) in &datagrams {
+            self.report.pre_validation.on_sent(pkt.len());
+        }
+
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.on_sent(&datagrams, now);
+        }
+
+        self.ptos
+            .on_sent(self.conn.path_stats().map(|s| (&s).into()));
+        self.track_paths();
+        self.track_migrations(now);
+
+        if self.conn.is_closed() {
+            self.finish(now);
+        }
+
+        self.deadline = self.timeout(now);
+
+        StepOutput {
+            datagrams,
+            timeout: self.deadline,
+            completed: std::mem::take(&mut self.completed),
+            events: std::mem::take(&mut self.events),
+        }
+    }
+
+    /// Lets the client probe new paths, now that the server is no longer
+    /// limited by the anti-amplification limit.
+    fn confirm(&mut self) {
+        self.confirmed = true;
+        self.dispatcher.probe = true;
+        self.report
+            .pre_validation
+            .validate(self.conn.stats().lost_bytes);
+        self.events.push(Event::HandshakeConfirmed);
+    }
+
+    fn timeout(&self, now: Instant) -> Option<Instant> {
+        if self.finished {
+            return None;
+        }
+
+        // Streams are left to read, the next step must not wait.
+        if self.reads.has_backlog() {
+            return Some(now);
+        }
+
+        let keepalive_timeout = self
+            .upkeep
+            .standby
+            .as_ref()
+            .and_then(|s| s.next_keepalive());
+
+        let suspect_timeout = self
+            .upkeep
+            .standby
+            .as_ref()
+            .and_then(|s| s.liveness.suspect_at());
+        let path_keepalive_timeout = self
+            .upkeep
+            .keepalive
+            .as_ref()
+            .and_then(|k| k.next_timeout());
+
+        let idle_path_timeout = self
+            .upkeep
+            .idle_paths
+            .as_ref()
+            .and_then(|i| i.next_timeout());
+
+        let ping_timeout = self
+            .upkeep
+            .pings
+            .as_ref()
+            .filter(|_| self.conn.is_established())
+            .map(|p| p.next_timeout());
+
+        let probe_timeout = self.bufferbloat.as_ref().and_then(|b| b.next_timeout());
+
+        let stall_timeout = self.stall_timeout.map(|t| self.last_progress + t);
+
+        let handshake_timeout = (!self.established && self.handshake_timed_out.is_none())
+            .then(|| self.start + self.handshake_timeout);
+
+        let admission_timeout = self
+            .dispatcher
+            .admission
+            .as_ref()
+            .and_then(|a| a.next_release());
+
+        let completion_timeout = self.run.as_ref().and_then(|r| r.next_timeout());
+
+        let watchdog_timeout = self
+            .watchdog
+            .as_ref()
+            .filter(|w| !w.reported && !self.blocked_streams().is_empty())
+            .map(|w| {
+                let deadline = w.last_sent + self.pto() * w.ptos;
+                w.next_check.map_or(deadline, |t| t.max(deadline))
+            });
+
+        [
+            self.conn.timeout_instant(),
+            keepalive_timeout,
+            suspect_timeout,
+            path_keepalive_timeout,
+            idle_path_timeout,
+            ping_timeout,
+            probe_timeout,
+            stall_timeout,
+            handshake_timeout,
+            admission_timeout,
+            completion_timeout,
+            watchdog_timeout,
+            self.peer_cid_deadline,
+            self.next_stats,
+            self.file_transfer.as_ref().and_then(|f| f.next_timeout()),
+            self.burst.next_release().map(|t| t.max(now)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Accounts the paths probed and validated by the events of this step.
+    fn track_paths(&mut self) {
+        for event in &self.events {
+            match *event {
+                Event::Established => {
+                    let initial = (self.state.local_addrs[0], self.state.peer_addrs[0]);
+                    self.probed_paths.insert(initial);
+                    self.validated_paths.insert(initial);
+                }
+
+                Event::PathProbed(local, peer) => {
+                    self.probed_paths.insert((local, peer));
+                }
+
+                Event::PathValidated(local, peer) => {
+                    self.validated_paths.insert((local, peer));
+                }
+
+                _ => (),
+            }
+        }
+    }
+
+    /// Accounts the migrations of this step and completes the pending one
+    /// once bytes arrive on its path.
+    fn track_migrations(&mut self, now: Instant) {
+        let recv_bytes = |conn: &quiche::Connection, path: (SocketAddr, SocketAddr)| {
+            conn.path_stats()
+                .find(|s| (s.local_addr, s.peer_addr) == path)
+                .map_or(0, |s| s.recv_bytes)
+        };
+
+        for event in &self.events {
+            match *event {
+                Event::Migrated(local, peer) => {
+                    let path = (local, peer);
+                    self.migrations
+                        .on_migrated(path, recv_bytes(&self.conn, path), now);
+                }
+
+                Event::MigrationFailed(local, peer) => self.migrations.on_refused((local, peer)),
+
+                Event::PathFailedValidation(local, peer) | Event::PathClosed(local, peer) => {
+                    self.migrations.on_path_lost((local, peer))
+                }
+
+                _ => (),
+            }
+        }
+
+        if let Some(path) = self.migrations.pending() {
+            self.migrations.on_recv(recv_bytes(&self.conn, path), now);
+        }
+
+        self.report.migrations = self.migrations.stats();
+    }
+
+    /// Emits the statistics of every path once they are due.
+    fn sample_paths(&mut self, now: Instant) {
+        let (Some(interval), Some(next)) = (self.stats_interval, self.next_stats) else {
+            return;
+        };
+
+        if now < next {
+            return;
+        }
+
+        for stats in self.conn.path_stats() {
+            self.events.push(Event::PathStats((&stats).into()));
+        }
+
+        self.events
+            .push(Event::StreamStages(self.dispatcher.stream_stages()));
+
+        self.next_stats = Some(now + interval);
+    }
+
+    /// Logs the new paths on which the server can no longer send before
+    /// the path is validated, its data then stalling whatever the congestion
+    /// window. quiche does not expose the limit, so it is derived from the
+    /// bytes exchanged on the path, as seen by the client.
+    fn check_amplification(&mut self) {
+        let initial = (self.state.local_addrs[0], self.state.peer_addrs[0]);
+
+        for stats in self.conn.path_stats() {
+            let path = (stats.local_addr, stats.peer_addr);
+
+            if path == initial
+                || self.conn.is_path_validated(path.0, path.1).unwrap_or(true)
+                || self.report.amplification_limited.contains(&path)
+                || !amplification::is_limited(stats.sent_bytes, stats.recv_bytes, stats.pmtu)
+            {
+                continue;
+            }
+
+            info!(
+                "{} path ({}, {}) not validated yet: the server received {} bytes and sent {}, \
+                 it waits for the validation to send more (anti-amplification limit)",
+                self.conn.trace_id(),
+                path.0,
+                path.1,
+                stats.sent_bytes,
+                stats.recv_bytes
+            );
+            self.report.amplification_limited.push(path);
+        }
+    }
+
+    /// Probe timeout of the active path, computed like quiche does (RFC 9002
+    /// section 6.2.1).
+    fn pto(&self) -> Duration {
+        let (rtt, rttvar) = self
+            .conn
+            .path_stats()
+            .find(|s| (s.local_addr, s.peer_addr) == self.state.active_path)
+            .map_or((Duration::ZERO, Duration::ZERO), |s| (s.rtt, s.rttvar));
+
+        let max_ack_delay = self
+            .conn
+            .peer_transport_params()
+            .map_or(0, |tp| tp.max_ack_delay);
+
+        rtt + (4 * rttvar).max(Duration::from_millis(1)) + Duration::from_millis(max_ack_delay)
+    }
+
+    /// Streams with data not accepted by quiche yet, with the path of each.
+    fn blocked_streams(&self) -> Vec<(u64, (SocketAddr, SocketAddr))> {
+        let mut blocked: Vec<_> = self
+            .dispatcher
+            .writers
+            .iter()
+            .filter(|w| !w.is_done())
+            .filter_map(|w| {
+                let idx_message = self.dispatcher.message_of_stream(w.stream_id())?;
+                let path = self.dispatcher.paths[idx_message];
+
+                Some((
+                    w.stream_id(),
+                    (self.state.local_addrs[path], self.state.peer_addrs[path]),
+                ))
+            })
+            .collect();
+
+        // Multiplexed messages are sent on the path of their stream.
+        for mux in self.dispatcher.mux.values().filter(|m| !m.drained) {
+            blocked.push((
+                mux.writer.stream_id(),
+                (
+                    self.state.local_addrs[mux.path],
+                    self.state.peer_addrs[mux.path],
+                ),
+            ));
+        }
+
+        // The bulk transfer runs on the initial path.
+        if let Some(bulk) = self
+            .bufferbloat
+            .as_ref()
+            .and_then(|b| b.bulk.as_ref())
+            .filter(|b| !b.is_done())
+        {
+            blocked.push((
+                bulk.stream_id(),
+                (self.state.local_addrs[0], self.state.peer_addrs[0]),
+            ));
+        }
+
+        // So do the ranges of the file transfer.
+        if let Some(transfer) = &self.file_transfer {
+            blocked.extend(
+                transfer
+                    .blocked_streams()
+                    .map(|id| (id, (self.state.local_addrs[0], self.state.peer_addrs[0]))),
+            );
+        }
+
+        blocked
+    }
+
+    /// Reports the progress of the file transfer, at most twice a second
+    /// and once it completes.
+    fn track_transfer(&mut self, now: Instant) {
+        let Some(transfer) = &mut self.file_transfer else {
+            return;
+        };
+
+        let paths = self
+            .conn
+            .path_stats()
+            .filter(|s| self.validated_paths.contains(&(s.local_addr, s.peer_addr)))
+            .count();
+
+        for progress in transfer.progress(paths, now) {
+            self.events.push(Event::TransferProgress(progress));
+        }
+    }
+
+    /// Accounts the bytes carried in the current window of the schedule.
+    fn track_schedule(&mut self, now: Instant) {
+        let Some(schedule) = &mut self.schedule else {
+            return;
+        };
+
+        let (counters, validated) = schedule_counters(
+            &self.conn,
+            (&self.state.local_addrs, &self.state.peer_addrs),
+            &self.stream_bytes,
+        );
+        let active = (0..self.state.local_addrs.len()).find(|i| {
+            (self.state.local_addrs[*i], self.state.peer_addrs[*i]) == self.state.active_path
+        });
+
+        schedule.on_step(
+            now.saturating_duration_since(self.start),
+            &counters,
+            active,
+            &validated,
+        );
+    }
+
+    /// Accounts the streams held back by the flow control limits of the
+    /// server.
+    fn track_flow(&mut self, now: Instant) {
+        let Some(flow) = &mut self.flow.blocking else {
+            return;
+        };
+
+        if !self.conn.is_established() || self.conn.is_closed() {
+            return;
+        }
+
+        let conn = &self.conn;
+        let dispatcher = &self.dispatcher;
+        let writers = dispatcher.writers.iter().filter(|w| !w.is_done()).chain(
+            dispatcher
+                .mux
+                .values()
+                .map(|m| &m.writer)
+                .filter(|w| !w.is_done()),
+        );
+
+        let streams: Vec<PendingStream> = writers
+            .map(|w| PendingStream {
+                stream_id: w.stream_id(),
+                written: w.written(),
+                capacity: conn.stream_capacity(w.stream_id()).unwrap_or(0),
+            })
+            .collect();
+
+        let rtt = conn
+            .path_stats()
+            .find(|s| (s.local_addr, s.peer_addr) == self.state.active_path)
+            .map_or(Duration::ZERO, |s| s.rtt);
+
+        let was_blocked = flow.is_blocked();
+        flow.update(dispatcher.stream_bytes(), &streams, rtt, now);
+
+        match (was_blocked, flow.is_blocked()) {
+            (false, true) => info!(
+                "{} blocked by the server's flow control after {} bytes",
+                conn.trace_id(),
+                dispatcher.stream_bytes()
+            ),
+
+            (true, false) => info!("{} flow control window raised", conn.trace_id()),
+
+            _ => (),
+        }
+    }
+
+    /// Closes the paths on which no stream data was received for the idle
+    /// timeout, other than the active and standby ones.
+    ///
+    /// quiche cannot abandon a path: the client stops keeping it alive and
+    /// sending on it, and retires its destination connection ID. quiche
+    /// links a spare connection ID to the path if any.
+    fn close_idle_paths(&mut self, now: Instant) {
+        let Some(idle_paths) = &mut self.upkeep.idle_paths else {
+            return;
+        };
+
+        if !self.conn.is_established() || self.conn.is_closed() {
+            return;
+        }
+
+        for i in 0..self.state.local_addrs.len() {
+            let path = (self.state.local_addrs[i], self.state.peer_addrs[i]);
+
+            if self.dispatcher.closed_paths.contains(&i) {
+                continue;
+            }
+
+            let validated = self.conn.is_path_validated(path.0, path.1).unwrap_or(false);
+
+            if path == self.state.active_path
+                || self
+                    .upkeep
+                    .standby
+                    .as_ref()
+                    .is_some_and(|s| s.is(path.0, path.1))
+                || !validated
+            {
+                idle_paths.activity.remove(&path);
+                continue;
+            }
+
+            let bytes = self.stream_bytes.get(&path).copied().unwrap_or(0);
+            if !idle_paths.is_expired(path, bytes, now) {
+                continue;
+            }
+
+            idle_paths.activity.remove(&path);
+            self.dispatcher.closed_paths.insert(i);
+            self.state.validated.remove(&path);
+            self.state.kept_alive.remove(&path);
+
+            if let Some(keepalive) = &mut self.upkeep.keepalive {
+                keepalive.close(path);
+            }
+
+            let dcid_seq = self.dispatcher.dcid_seqs.remove(&path);
+            if let Some(seq) = dcid_seq.filter(|_| !uses_zero_length_dcid(&self.conn)) {
+                if let Err(e) = self.conn.retire_dcid(seq) {
+                    warn!(
+                        "{} cannot retire DCID {} of path ({}, {}): {:?}",
+                        self.conn.trace_id(),
+                        seq,
+                        path.0,
+                        path.1,
+                        e
+                    );
+                }
+            }
+
+            self.events.push(Event::PathIdleClosed(path.0, path.1));
+        }
+    }
+
+    /// Reports the connection as stalled if it sent nothing for the PTOs of
+    /// the watchdog while it had stream data to send and room in the
+    /// congestion window of the active path.
+    fn check_send_stall(&mut self, now: Instant) {
+        let Some(watchdog) = &self.watchdog else {
+            return;
+        };
+
+        if watchdog.reported || !self.conn.is_established() || self.conn.is_closed() {
+            return;
+        }
+
+        let pto = self.pto();
+        let idle = now.saturating_duration_since(watchdog.last_sent);
+
+        if idle < pto * watchdog.ptos || watchdog.next_check.is_some_and(|t| now < t) {
+            return;
+        }
+
+        let blocked = self.blocked_streams();
+
+        // quiche does not expose the bytes in flight, acknowledgment-only
+        // packets make this estimate an upper bound.
+        let stats = self.conn.stats();
+        let in_flight = stats
+            .sent_bytes
+            .saturating_sub(stats.acked_bytes + stats.lost_bytes);
+
+        let paths: Vec<StalledPath> = self
+            .conn
+            .path_stats()
+            .map(|s| {
+                let path = (s.local_addr, s.peer_addr);
+
+                StalledPath {
+                    path,
+                    active: path == self.state.active_path,
+                    cwnd: s.cwnd,
+                    since_pacing_deadline: watchdog
+                        .pacing
+                        .get(&path)
+                        .map(|at| now.saturating_duration_since(*at)),
+                    blocked_streams: blocked
+                        .iter()
+                        .filter(|(_, p)| *p == path)
+                        .map(|(stream_id, _)| *stream_id)
+                        .collect(),
+                }
+            })
+            .collect();
+
+        if blocked.is_empty() || !paths.iter().any(|p| p.active && p.cwnd as u64 > in_flight) {
+            if let Some(watchdog) = &mut self.watchdog {
+                watchdog.next_check = Some(now + pto);
+            }
+
+            return;
+        }
+
+        let stall = SendStall {
+            at: now.saturating_duration_since(self.start),
+            idle,
+            pto,
+            late_by: self.deadline.map(|t| now.saturating_duration_since(t)),
+            quiche_timeout: self.conn.timeout(),
+            writable: self.conn.writable().collect(),
+            paths,
+        };
+
+        error!(
+            "{} sent nothing for {:?} with stream data and congestion window available: {:?}",
+            self.conn.trace_id(),
+            idle,
+            stall
+        );
+
+        self.report.send_stalls.push(stall);
+
+        let strict = watchdog.strict;
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.reported = true;
+        }
+
+        if strict {
+            self.conn.close(false, 0x1, b"send stall").ok();
+            self.send_stalled = true;
+        }
+    }
+
+    /// Logs where the datagrams rejected by quiche came from once there are
+    /// too many of them, the sign that the datagrams of the connections
+    /// sharing the sockets are demultiplexed wrong. With `--strict`, the
+    /// connection is also closed.
+    fn check_misdelivery(&mut self) {
+        let Some(diagnostic) = self.rejects.check() else {
+            return;
+        };
+
+        warn!("{} {}", self.conn.trace_id(), diagnostic);
+        self.report.misdelivery = Some(diagnostic);
+
+        if self.strict {
+            self.conn.close(false, 0x1, b"misdelivered datagrams").ok();
+            self.misdelivered = true;
+        }
+    }
+
+    /// Closes the connection if its handshake did not complete in time, so
+    /// that an unreachable server fails fast whatever the idle timeout.
+    fn check_handshake_timeout(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.start);
+
+        if self.established
+            || self.handshake_timed_out.is_some()
+            || self.conn.is_closed()
+            || elapsed < self.handshake_timeout
+        {
+            return;
+        }
+
+        let stats = self.conn.stats();
+        let progress = if stats.recv == 0 {
+            format!(
+                "nothing received for {} packets sent, {} PTOs",
+                stats.sent, self.handshake_ptos
+            )
+        } else {
+            format!(
+                "{} packets received for {} sent, {} PTOs",
+                stats.recv, stats.sent, self.handshake_ptos
+            )
+        };
+
+        warn!(
+            "{} handshake not completed after {:?}: {}, closing",
+            self.conn.trace_id(),
+            elapsed,
+            progress
+        );
+
+        self.conn.close(false, 0x1, b"handshake timeout").ok();
+        self.handshake_timed_out = Some(format!(
+            "handshake timed out after {:?} ({})",
+            self.handshake_timeout, progress
+        ));
+    }
+
+    /// How long to wait for the first connection ID of the peer.
+    fn peer_cid_timeout(&self) -> Duration {
+        let rtt = self
+            .conn
+            .path_stats()
+            .find(|s| (s.local_addr, s.peer_addr) == self.state.active_path)
+            .map_or(Duration::ZERO, |s| s.rtt);
+
+        PEER_CID_TIMEOUT.max(3 * rtt)
+    }
+
+    /// Falls back to sending every message on the initial path if the peer
+    /// does not allow new paths.
+    fn check_single_path(&mut self, now: Instant) {
+        if self.single_path
+            || (self.upkeep.standby.is_none()
+                && self.schedule.is_none()
+                && self.dispatcher.paths.iter().all(|p| *p == 0))
+        {
+            return;
+        }
+
+        self.peer_cids |= peer_allows_path(&self.conn);
+
+        if self.peer_cids {
+            self.peer_cid_deadline = None;
+        } else if self.conn.is_established() && self.peer_cid_deadline.is_none() {
+            self.peer_cid_deadline = Some(now + self.peer_cid_timeout());
+        }
+
+        // The connection ID of each path must be active at once.
+        let paths = self.state.local_addrs.len() as u64;
+        let tp = self.conn.peer_transport_params();
+
+        let reason = if tp.is_some_and(|tp| tp.disable_active_migration) {
+            "peer disabled active migration".to_string()
+        } else if let Some(limit) = tp
+            .map(|tp| tp.active_conn_id_limit)
+            .filter(|limit| *limit < paths)
+        {
+            format!("peer advertises active_connection_id_limit={}", limit)
+        } else if self.peer_cid_deadline.is_some_and(|t| now >= t) {
+            "peer issued no connection ID".to_string()
+        } else {
+            return;
+        };
+
+        if self.require_multipath {
+            error!(
+                "multipath disabled: {}, closing (--require-multipath)",
+                reason
+            );
+
+            self.conn.close(true, 0x0, b"multipath required").ok();
+            self.multipath_refused = true;
+            self.single_path = true;
+            self.report.single_path = Some(reason);
+            return;
+        }
+
+        warn!(
+            "multipath disabled: {}, falling back to the initial path",
+            reason
+        );
+
+        self.single_path = true;
+        self.peer_cid_deadline = None;
+        self.upkeep.standby = None;
+        self.state.standby = None;
+        self.dispatcher.paths.fill(0);
+        self.dispatcher.forced_path = None;
+        self.report.single_path = Some(reason);
+    }
+
+    /// Collects the packets to send on every path, a burst of at most the
+    /// send quantum of each.
+    fn send(&mut self) -> Vec<(Vec<u8>, quiche::SendInfo)> {
+        let conn = &mut self.conn;
+        let burst = &mut self.burst;
+        let mtu = conn.max_send_udp_payload_size();
+        let mut datagrams = vec![];
+
+        burst.begin();
+
+        for local_addr in self.state.local_addrs.iter().rev() {
+            // loop on different paths
+            for peer_addr in conn.paths_iter(*local_addr) {
+                let budget = burst.budget(conn.send_quantum_on_path(*local_addr, peer_addr), mtu);
+                let mut bytes = 0;
+                let mut packets = 0;
+                let mut last_at = None;
+                let mut capped = None;
+
+                loop {
+                    if bytes >= budget {
+                        capped = last_at;
+                        break;
+                    }
+
+                    let (write, send_info) = match conn.send_on_path(
+                        &mut self.buf,
+                        Some(*local_addr),
+                        Some(peer_addr),
+                    ) {
+                        Ok(v) => v,
+
+                        Err(quiche::Error::Done) => {
+                            trace!("{} done writing", conn.trace_id());
+                            break;
+                        }
+
+                        Err(e) => {
+                            error!("{} send failed: {:?}", conn.trace_id(), e);
+
+                            conn.close(false, 0x1, b"fail").ok();
+                            break;
+                        }
+                    };
+
+                    datagrams.push((self.buf[..write].to_vec(), send_info));
+                    bytes += write;
+                    packets += 1;
+                    last_at = Some(send_info.at);
+                }
+
+                burst.on_burst(packets, capped);
+            }
+        }
+
+        self.report.bursts = burst.stats();
+
+        datagrams
+    }
+
+    /// Reacts to the packets received since the last call.
+    fn process(&mut self, now: Instant) {
+        self.check_single_path(now);
+
+        let pto = self.pto();
+        let conn = &mut self.conn;
+        let events = &mut self.events;
+
+        if KeyPhase::of(conn) != self.key_phase {
+            self.key_phase = KeyPhase::of(conn);
+            events.push(Event::KeyPhase {
+                phase: self.key_phase,
+                resumed: conn.is_resumed(),
+            });
+        }
+
+        if let Some(keepalive) = &self.upkeep.keepalive {
+            self.state.kept_alive = keepalive.paths.keys().copied().collect();
+        }
+
+        while let Some(qe) = conn.path_event_next() {
+            if let quiche::PathEvent::Validated(..) = qe {
+                self.last_progress = now;
+            }
+
+            for action in (self.path_policy)(&mut self.state, qe, events) {
+                match action {
+                    PathAction::Migrate(local_addr, peer_addr) => {
+                        if let Err(e) = conn.migrate(local_addr, peer_addr) {
+                            error!(
+                                "Migration to path ({}, {}) failed: {:?}",
+                                local_addr, peer_addr, e
+                            );
+                            events.push(Event::MigrationFailed(local_addr, peer_addr));
+                        }
+                    }
+
+                    PathAction::FailOver => {
+                        if let Some(standby) = &mut self.upkeep.standby {
+                            if standby.fail_over(conn, events, now) {
+                                self.state.active_path = (standby.local_addr, standby.peer_addr);
+                            }
+                        }
+                    }
+
+                    PathAction::Reprobe(local_addr, peer_addr) => {
+                        if let Some(keepalive) = &mut self.upkeep.keepalive {
+                            keepalive.on_failed_validation(local_addr, peer_addr);
+                        }
+                    }
+                }
+            }
+        }
+
+        // quiche tells the MTU found by path MTU discovery only through the
+        // path stats.
+        for stats in conn.path_stats() {
+            let path = (stats.local_addr, stats.peer_addr);
+
+            if !self.report.initial_cwnds.iter().any(|(p, _)| *p == path) {
+                info!(
+                    "{} initial cwnd of path ({}, {}): {} bytes",
+                    conn.trace_id(),
+                    path.0,
+                    path.1,
+                    stats.cwnd
+                );
+                self.report.initial_cwnds.push((path, stats.cwnd));
+            }
+
+            if self
+                .path_mtus
+                .insert(path, stats.pmtu)
+                .is_some_and(|mtu| mtu != stats.pmtu)
+            {
+                events.push(Event::PathMtu(path.0, path.1, stats.pmtu));
+            }
+        }
+
+        // core of the client
+        if conn.is_established() {
+            if !self.established {
+                events.push(Event::Established);
+                let handshake_time = *self
+                    .report
+                    .handshake_time
+                    .get_or_insert(now.saturating_duration_since(self.start));
+                self.established = true;
+
+                let stats = conn.stats();
+                let recovery = HandshakeRecovery {
+                    ptos: self.handshake_ptos,
+                    retransmissions: stats.retrans,
+                    lost: stats.lost,
+                };
+                info!(
+                    "{} handshake took {} ms, {} PTOs, {} retransmissions",
+                    conn.trace_id(),
+                    handshake_time.as_millis(),
+                    recovery.ptos,
+                    recovery.retransmissions
+                );
+                self.report.handshake_recovery.get_or_insert(recovery);
+
+                if let Some(tp) = conn.peer_transport_params() {
+                    self.report.max_ack_delay = Some((self.max_ack_delay, tp.max_ack_delay));
+
+                    let max_data = tp.initial_max_data;
+                    let max_stream_data = tp.initial_max_stream_data_bidi_remote;
+                    match &mut self.flow.blocking {
+                        Some(flow) => flow.restart(max_data, max_stream_data),
+
+                        None if self.flow.tracked => {
+                            let total: u64 = self
+                                .dispatcher
+                                .payloads
+                                .iter()
+                                .map(|p| p.len() as u64)
+                                .sum();
+                            if total <= max_data {
+                                warn!(
+                                    "{} the {} bytes of the messages fit in the server's window \
+                                     of {} bytes, run the server with a smaller --max-data",
+                                    conn.trace_id(),
+                                    total,
+                                    max_data
+                                );
+                            }
+
+                            self.flow.blocking = Some(FlowBlocking::new(max_data, max_stream_data));
+                        }
+
+                        None => (),
+                    }
+                }
+            }
+
+            for scid in self.cids.issue(conn, &self.rng) {
+                events.push(Event::CidIssued(scid));
+            }
+            self.report.cids = self.cids.stats(conn);
+
+            if let Some(run) = &mut self.run {
+                run.start(conn, &mut self.dispatcher);
+            }
+
+            while let Some(cid) = conn.retired_scid_next() {
+                events.push(Event::CidRetired(cid));
+            }
+
+            if !self.interactive
+                && self.received.iter().all(|b| *b)
+                && self.bufferbloat.as_ref().is_none_or(|b| b.is_done())
+                && self.file_transfer.as_ref().is_none_or(|f| f.is_done())
+            {
+                let completed = match &mut self.run {
+                    Some(run) if !self.keep_open => run.complete(conn, &mut self.dispatcher, now),
+                    _ => true,
+                };
+
+                if !self.keep_open && completed {
+                    conn.close(true, 0x00, b"closing").ok();
+                } else if !self.kept_open {
+                    info!(
+                        "{} all messages delivered, not closing the connection (--keep-open)",
+                        conn.trace_id()
+                    );
+                    self.kept_open = true;
+                }
+            }
+
+            if let Some(standby) = self.upkeep.standby.as_mut().filter(|_| self.confirmed) {
+                standby.maintain(conn, self.state.active_path, events, now);
+            }
+
+            if let Some(keepalive) = self.upkeep.keepalive.as_mut().filter(|_| self.confirmed) {
+                keepalive.poll(
+                    conn,
+                    self.upkeep.standby.as_ref(),
+                    &mut self.report.keepalives,
+                    events,
+                    now,
+                );
+            }
+
+            if let Some(admission) = &mut self.dispatcher.admission {
+                admission.set_stuck_after(pto * STUCK_MESSAGE_PTOS);
+            }
+
+            if let (Some(schedule), false) = (&self.schedule, self.single_path) {
+                self.dispatcher.follow_schedule(
+                    conn,
+                    schedule.schedule(),
+                    (&self.state.local_addrs, &self.state.peer_addrs),
+                    &mut self.state.active_path,
+                    events,
+                    now.saturating_duration_since(self.start),
+                );
+            }
+
+            self.dispatcher.send_stream_new_path(
+                conn,
+                &self.state.local_addrs,
+                &self.state.peer_addrs,
+                &mut self.state.active_path,
+                events,
+                now,
+            );
+
+            if let Some(bufferbloat) = &mut self.bufferbloat {
+                bufferbloat.poll(conn, &mut self.dispatcher, events, now);
+            }
+
+            if let Some(transfer) = &mut self.file_transfer {
+                transfer.poll(
+                    conn,
+                    &mut self.dispatcher,
+                    (&self.state.local_addrs, &self.state.peer_addrs),
+                    &mut self.state.active_path,
+                    events,
+                    now,
+                );
+            }
+
+            self.dispatcher.poll_writers(conn);
+        }
+
+        if let Some(standby) = &mut self.upkeep.standby {
+            if self.confirmed && standby.is_suspect(conn, self.state.active_path, now) {
+                warn!(
+                    "No data received on active path ({}, {}) for {} RTTs",
+                    self.state.active_path.0, self.state.active_path.1, SUSPECT_RTTS
+                );
+                events.push(Event::PathSuspect(
+                    self.state.active_path.0,
+                    self.state.active_path.1,
+                ));
+
+                if standby.fail_over(conn, events, now) {
+                    self.state.active_path = (standby.local_addr, standby.peer_addr);
+                }
+            }
+
+            standby.check_failover(conn, &mut self.report, now);
+        }
+
+        let idle = now.saturating_duration_since(self.last_progress);
+
+        if self.stall_timeout.is_some_and(|t| idle >= t) && !self.stalled {
+            warn!("no progress for {:?}, closing", idle);
+            conn.close(true, 0x01, b"stalled").ok();
+            self.stalled = true;
+        }
+    }
+
+    /// Reads the data that became readable on the streams, received on the
+    /// path of `last_recv_info`.
+    fn read_streams(&mut self, now: Instant) {
+        let conn = &mut self.conn;
+        let buf = &mut self.buf;
+        let mut finished = vec![];
+
+        while let Some(stream_id) = self.reads.next(conn) {
+            loop {
+                let (read, fin) = match conn.stream_recv(stream_id, buf) {
+                    Ok(v) => v,
+
+                    Err(quiche::Error::StreamReset(code)) => {
+                        self.events.push(Event::StreamReset(stream_id, code));
+
+                        if self.dispatcher.streams.contains_key(&stream_id) {
+                            self.dispatcher.closing.insert(stream_id);
+                        }
+                        break;
+                    }
+
+                    Err(_) => break,
+                };
+
+                self.last_progress = now;
+
+                if let Some(info) = self.last_recv_info {
+                    *self.stream_bytes.entry((info.to, info.from)).or_default() += read as u64;
+                }
+
+                if let Some(bufferbloat) = &mut self.bufferbloat {
+                    if bufferbloat.on_stream_data(stream_id, read, fin, &mut self.report, now) {
+                        continue;
+                    }
+                }
+
+                if let Some(transfer) = &mut self.file_transfer {
+                    if transfer.on_stream_data(stream_id, &buf[..read], fin, &mut self.report, now)
+                    {
+                        continue;
+                    }
+                }
+
+                if let Some(info) = self.last_recv_info {
+                    self.stream_paths
+                        .entry(stream_id)
+                        .or_default()
+                        .push((info.to, info.from));
+                }
+
+                if let Some(mux) = self.dispatcher.mux.get_mut(&stream_id) {
+                    mux.decoder.push(&buf[..read]);
+
+                    while let Some(echo) = mux.decoder.next_frame() {
+                        let Some(idx_message) = mux.in_flight.pop_front() else {
+                            error!("Unexpected frame on stream {}", stream_id);
+                            continue;
+                        };
+
+                        // Frames completed by the same read share its path.
+                        let mut paths = self.stream_paths.remove(&stream_id).unwrap_or_default();
+                        if let (true, Some(info)) = (paths.paths().is_empty(), self.last_recv_info)
+                        {
+                            paths.push((info.to, info.from));
+                        }
+
+                        finished.push((idx_message, stream_id, Some(mux.echoed), echo, paths));
+                        mux.echoed += 1;
+                    }
+
+                    if fin {
+                        self.events.push(Event::StreamFin(stream_id));
+
+                        if mux.decoder.buffered() > 0 {
+                            error!("Truncated frame on stream {}", stream_id);
+                        }
+                    }
+
+                    continue;
+                }
+
+                self.echoes
+                    .entry(stream_id)
+                    .or_default()
+                    .extend_from_slice(&buf[..read]);
+
+                if !fin {
+                    continue;
+                }
+
+                self.events.push(Event::StreamFin(stream_id));
+
+                // Nothing more will be read, quiche collects the stream once
+                // what was sent on it is acknowledged too.
+                if self.dispatcher.streams.contains_key(&stream_id) {
+                    conn.stream_shutdown(stream_id, quiche::Shutdown::Read, 0)
+                        .ok();
+                    self.dispatcher.closing.insert(stream_id);
+                }
+
+                let mut echo = self.echoes.remove(&stream_id).unwrap_or_default();
+                let paths = self.stream_paths.remove(&stream_id).unwrap_or_default();
+
+                if self.checks.pad_to.is_some() {
+                    match padding::unpad(&echo) {
+                        Some(payload) => echo = payload.to_vec(),
+
+                        None => {
+                            error!("Invalid padded echo on stream {}", stream_id);
+                            continue;
+                        }
+                    }
+                }
+
+                let Some(idx_message) = self.dispatcher.message_of_stream(stream_id) else {
+                    continue;
+                };
+
+                // The server prepends the time it received the message to
+                // its echo, which starts with the time it was sent.
+                if let (Some(stats), Some(clock)) =
+                    (&mut self.checks.owd, &self.dispatcher.stamps.clock)
+                {
+                    let stamps = owd::unstamp(&echo)
+                        .and_then(|(echoed, rest)| Some((echoed, owd::unstamp(rest)?)));
+
+                    let Some((echoed, (sent, message))) = stamps else {
+                        error!("Echo on stream {} carries no timestamps", stream_id);
+                        continue;
+                    };
+
+                    let path = self.dispatcher.paths[idx_message];
+                    let path = (self.state.local_addrs[path], self.state.peer_addrs[path]);
+                    stats.record(path, sent, echoed, clock.micros(now));
+
+                    echo = message.to_vec();
+                }
+
+                finished.push((idx_message, stream_id, None, echo, paths));
+            }
+        }
+
+        for (idx_message, stream_id, frame, echo, paths) in finished {
+            self.complete_message(idx_message, stream_id, frame, echo, paths, now);
+        }
+    }
+
+    /// Completes the messages sent on unidirectional streams that quiche
+    /// collected, which it does once their data and fin are acknowledged.
+    /// Their paths are those they were sent on.
+    fn complete_acked(&mut self, now: Instant) {
+        let conn = &self.conn;
+        let mut acked = vec![];
+
+        self.dispatcher.unacked.retain(|stream_id| {
+            let collected = is_collected(conn, *stream_id);
+
+            if collected {
+                acked.push(*stream_id);
+            }
+
+            !collected
+        });
+
+        for stream_id in acked {
+            let Some(idx_message) = self.dispatcher.streams.remove(&stream_id) else {
+                continue;
+            };
+
+            let path = self.dispatcher.paths[idx_message];
+            let mut paths = PathList::default();
+            paths.push((self.state.local_addrs[path], self.state.peer_addrs[path]));
+
+            self.last_progress = now;
+            self.complete_message(idx_message, stream_id, None, vec![], paths, now);
+        }
+    }
+
+    /// Frees the state of the streams done with that quiche collected.
+    fn close_streams(&mut self) {
+        let conn = &self.conn;
+        let streams = &mut self.dispatcher.streams;
+        let echoes = &mut self.echoes;
+        let stream_paths = &mut self.stream_paths;
+
+        self.dispatcher.closing.retain(|stream_id| {
+            if !is_collected(conn, *stream_id) {
+                return true;
+            }
+
+            // Left over by a reset stream.
+            streams.remove(stream_id);
+            echoes.remove(stream_id);
+            stream_paths.remove(stream_id);
+            false
+        });
+    }
+
+    /// Records the completion of `idx_message`, whose echo was received on
+    /// `stream_id`, as frame `frame` of the stream if multiplexed.
+    fn complete_message(
+        &mut self,
+        idx_message: usize,
+        stream_id: u64,
+        frame: Option<usize>,
+        mut echo: Vec<u8>,
+        paths: PathList,
+        now: Instant,
+    ) {
+        self.received[idx_message] = true;
+
+        if let Some(admission) = &mut self.dispatcher.admission {
+            admission.complete(idx_message);
+        }
+
+        if let Some(seqs) = &self.dispatcher.stamps.seqs {
+            let sent = seqs[idx_message];
+            let checked = sequence::split(&echo)
+                .filter(|(seq, rest)| {
+                    Some(*seq) == sent && *rest == self.dispatcher.messages[idx_message]
+                })
+                .map(|(seq, _)| seq);
+
+            match (checked, &mut self.checks.sequence) {
+                (Some(seq), Some(order)) => {
+                    if let Some(after) = order.complete(seq) {
+                        self.events.push(Event::OutOfOrder {
+                            index: idx_message,
+                            stream_id,
+                            seq,
+                            after,
+                        });
+                    }
+
+                    echo.drain(..sequence::SEQ_LEN);
+                }
+
+                _ => {
+                    warn!(
+                        "echo of message {} on stream {} is not the message with its \
+                         sequence number",
+                        idx_message, stream_id
+                    );
+                    self.report.mismatched.push(idx_message);
+                }
+            }
+        }
+
+        // Padded echoes are already unpadded.
+        if let Some(transform) = self.checks.expect_transform {
+            if echo != transform.apply(&self.dispatcher.messages[idx_message]) {
+                warn!(
+                    "echo of message {} on stream {} is not its {} transform",
+                    idx_message, stream_id, transform
+                );
+                self.report.mismatched.push(idx_message);
+            }
+        }
+
+        if let Some(sent_at) = self.dispatcher.sent_at[idx_message] {
+            let record = MessageRecord {
+                index: idx_message,
+                stream_id,
+                frame,
+                paths,
+                latency: now.saturating_duration_since(sent_at),
+                queue_wait: self.dispatcher.queue_wait[idx_message],
+            };
+
+            self.events.push(Event::MessageCompleted {
+                index: idx_message,
+                stream_id,
+                bytes: echo.len(),
+                latency: record.latency,
+            });
+            self.report.record(record.clone());
+            self.completed.push(Completed { echo, record });
+        }
+    }
+
+    /// Runs the periodic tasks after a wake up without any packet.
+    fn on_idle(&mut self, now: Instant) {
+        if !self.conn.is_established() {
+            return;
+        }
+
+        if let Some(standby) = &mut self.upkeep.standby {
+            standby.maintain(
+                &mut self.conn,
+                self.state.active_path,
+                &mut self.events,
+                now,
+            );
+        }
+
+        if let Some(keepalive) = &mut self.upkeep.keepalive {
+            keepalive.poll(
+                &mut self.conn,
+                self.upkeep.standby.as_ref(),
+                &mut self.report.keepalives,
+                &mut self.events,
+                now,
+            );
+        }
+
+        if let Some(pings) = &mut self.upkeep.pings {
+            if let Some(idle) = pings.poll(&mut self.conn, now) {
+                info!(
+                    "{} keepalive PING after {:?} idle",
+                    self.conn.trace_id(),
+                    idle
+                );
+                self.report.keepalive_pings = pings.sent();
+            }
+        }
+    }
+
+    /// Completes the report once the connection is closed.
+    fn finish(&mut self, now: Instant) {
+        let conn = &self.conn;
+
+        self.report.path_usage = PathUsage {
+            probed: self.probed_paths.len(),
+            validated: self.validated_paths.len(),
+            used: self.stream_bytes.values().filter(|b| **b > 0).count(),
+        };
+
+        self.report.completion_acked = self.run.as_ref().is_some_and(|r| r.acked);
+        if let Some(flow) = &mut self.flow.blocking {
+            flow.close(now);
+            self.report.flow_blocking = Some(flow.stats(now));
+        }
+
+        // Paths without an RTT sample only carried the validation.
+        for stats in conn.path_stats() {
+            let path = (stats.local_addr, stats.peer_addr);
+
+            let delivery = &mut self.report.path_delivery;
+            match delivery.iter_mut().find(|d| d.path == path) {
+                Some(d) => {
+                    d.sent_bytes += stats.sent_bytes;
+                    d.lost_bytes += stats.lost_bytes;
+                }
+
+                None => delivery.push(PathDelivery {
+                    path,
+                    sent_bytes: stats.sent_bytes,
+                    lost_bytes: stats.lost_bytes,
+                }),
+            }
+
+            if let (true, Some(rtt)) = (self.validated_paths.contains(&path), stats.min_rtt) {
+                self.report.path_rtts.retain(|(p, _)| *p != path);
+                self.report.path_rtts.push((path, rtt));
+            }
+        }
+
+        self.report.stream_reads = self.reads.stats();
+        self.report.path_mtus = self.path_mtus.iter().map(|(p, m)| (*p, *m)).collect();
+        self.report.path_mtus.sort();
+        self.report.path_ptos = self.ptos.paths();
+        self.report.path_quality = self.path_quality();
+
+        if let Some(owd) = &self.checks.owd {
+            self.report.one_way_delays = owd.paths();
+        }
+
+        if let Some(sequence) = &self.checks.sequence {
+            self.report.ordering = Some(sequence.stats());
+        }
+
+        if let Some(schedule) = &mut self.schedule {
+            let (counters, _) = schedule_counters(
+                conn,
+                (&self.state.local_addrs, &self.state.peer_addrs),
+                &self.stream_bytes,
+            );
+            schedule.close(&counters);
+            self.report.path_windows = schedule.usage().to_vec();
+        }
+
+        // The wire bytes of every connection add up, while each message is
+        // delivered once whatever the reconnections. Messages delivered
+        // before a restart were not sent by this run.
+        let throughput = &mut self.report.throughput;
+        throughput.wire_bytes += conn.stats().sent_bytes;
+        throughput.app_bytes = (0..self.received.len())
+            .filter(|i| self.received[*i] && self.dispatcher.sent_at[*i].is_some())
+            .map(|i| self.dispatcher.messages[i].len() as u64)
+            .sum();
+        throughput.elapsed = now.duration_since(self.start);
+
+        self.events.push(Event::PathUsage(self.report.path_usage));
+        self.events.push(Event::Throughput(self.report.throughput));
+        self.events.push(Event::Closed);
+
+        info!(
+            "connection closed, {:?} {:?}",
+            conn.stats(),
+            conn.path_stats().collect::<Vec<quiche::PathStats>>()
+        );
+
+        // Echoes that are not the expected transform were not delivered.
+        self.report.delivered = self.received.iter().filter(|b| **b).count();
+        self.report.delivered -= self.report.mismatched.len();
+
+        if let Some(admission) = &self.dispatcher.admission {
+            self.report.stuck_messages = admission.stuck();
+        }
+        self.report.lost += conn.stats().lost;
+
+        // Messages dispatched again after a reconnection are counted once.
+        let dispatcher = &self.dispatcher;
+        self.report.logical_bytes = 0;
+        self.report.padded_bytes = 0;
+        for (i, _) in dispatcher
+            .sent_at
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.is_some())
+        {
+            self.report.logical_bytes += dispatcher.messages[i].len() as u64;
+            self.report.padded_bytes += dispatcher.payloads[i].len() as u64;
+        }
+
+        self.interrupted = self.stalled || self.stateless_reset || conn.is_timed_out();
+
+        if self.report.delivered < self.report.messages {
+            self.report.outstanding = self
+                .received
+                .iter()
+                .enumerate()
+                .filter(|(_, received)| !**received)
+                .map(|(i, _)| i)
+                .collect();
+
+            self.report.failure = Some(if !self.established {
+                Outcome::HandshakeFailed
+            } else if self.misdelivered {
+                Outcome::Misdelivered
+            } else if self.send_stalled {
+                Outcome::SendStalled
+            } else if self.multipath_refused {
+                Outcome::MultipathUnavailable
+            } else if self.stalled {
+                Outcome::Partial
+            } else if conn.peer_error().is_some() {
+                Outcome::PeerError
+            } else if conn.local_error().is_some_and(|e| !e.is_app) {
+                Outcome::LocalError
+            } else {
+                Outcome::Partial
+            });
+
+            self.report.error = Some(if let Some(timed_out) = &self.handshake_timed_out {
+                timed_out.clone()
+            } else if let (true, Some(diagnostic)) = (self.misdelivered, &self.report.misdelivery) {
+                diagnostic.clone()
+            } else if self.send_stalled {
+                "send stalled".to_string()
+            } else if let (true, Some(reason)) = (self.multipath_refused, &self.report.single_path)
+            {
+                format!("multipath required: {}", reason)
+            } else if self.stalled {
+                "stalled".to_string()
+            } else if let Some(alert) = conn
+                .peer_error()
+                .filter(|e| !self.established && !e.is_app)
+                .and_then(|e| tls_alert(e.error_code))
+            {
+                format!("handshake rejected by peer: TLS alert {}", alert)
+            } else if let (false, None) = (
+                self.report.mismatched.is_empty(),
+                self.checks.expect_transform,
+            ) {
+                format!(
+                    "{} echoes do not carry back their message",
+                    self.report.mismatched.len()
+                )
+            } else if !self.report.mismatched.is_empty() {
+                format!(
+                    "{} echoes are not the {} transform",
+                    self.report.mismatched.len(),
+                    self.checks
+                        .expect_transform
+                        .map_or("expected", |t| t.name())
+                )
+            } else if let Some(e) = conn.peer_error() {
+                format!("closed by peer with error {:#x}", e.error_code)
+            } else if let Some(e) = conn.local_error() {
+                format!("closed locally with error {:#x}", e.error_code)
+            } else if self.stateless_reset {
+                "stateless reset by peer".to_string()
+            } else if conn.is_timed_out() {
+                "idle timeout".to_string()
+            } else {
+                "closed".to_string()
+            });
+        }
+
+        // The echoes may all have arrived, the run still fails.
+        if self.misdelivered && self.report.failure.is_none() {
+            self.report.failure = Some(Outcome::Misdelivered);
+            self.report.error = self.report.misdelivery.clone();
+        }
+
+        self.finished = true;
+    }
+}
+
+/// Whether a new path can be probed without running out of connection IDs
+/// of the peer.
+///
+/// A peer using zero-length connection IDs issues none: its packets are told
+/// apart by 4-tuple, each path having its own socket, and every path uses the
+/// empty ID, so quiche probes new paths without consuming any.
+fn peer_allows_path(conn: &quiche::Connection) -> bool {
+    conn.available_dcids() > 0 || uses_zero_length_dcid(conn)
+}
+
+/// Whether the peer chose a zero-length connection ID, once its first packet
+/// was received.
+fn uses_zero_length_dcid(conn: &quiche::Connection) -> bool {
+    conn.destination_id().is_empty()
+}
+
+/// Counters of each path of the workload, and whether it is validated.
+fn schedule_counters(
+    conn: &quiche::Connection,
+    (local_addrs, peer_addrs): (&[SocketAddr], &[SocketAddr]),
+    stream_bytes: &HashMap<(SocketAddr, SocketAddr), u64>,
+) -> (Vec<PathCounters>, Vec<bool>) {
+    let stats: Vec<quiche::PathStats> = conn.path_stats().collect();
+
+    local_addrs
+        .iter()
+        .zip(peer_addrs)
+        .map(|(local, peer)| {
+            let path = stats
+                .iter()
+                .find(|s| (s.local_addr, s.peer_addr) == (*local, *peer));
+
+            let counters = PathCounters {
+                sent_bytes: path.map_or(0, |s| s.sent_bytes),
+                stream_bytes: stream_bytes.get(&(*local, *peer)).copied().unwrap_or(0),
+            };
+            let validated = conn.is_path_validated(*local, *peer).is_ok_and(|v| v);
+
+            (counters, validated)
+        })
+        .unzip()
+}
+
+/// Whether quiche collected `stream_id`, once everything sent and received on
+/// it is acknowledged and read.
+fn is_collected(conn: &quiche::Connection, stream_id: u64) -> bool {
+    matches!(
+        conn.stream_capacity(stream_id),
+        Err(quiche::Error::InvalidStreamState(_))
+    )
+}
+
+/// Describes the TLS alert carried by a QUIC `CRYPTO_ERROR` code.
+fn tls_alert(error_code: u64) -> Option<String> {
+    let alert = error_code.checked_sub(0x100).filter(|a| *a < 0x100)?;
+
+    let name = match alert {
+        40 => "handshake_failure",
+        42 => "bad_certificate",
+        43 => "unsupported_certificate",
+        44 => "certificate_revoked",
+        45 => "certificate_expired",
+        46 => "certificate_unknown",
+        48 => "unknown_ca",
+        116 => "certificate_required",
+        120 => "no_application_protocol",
+        _ => return Some(alert.to_string()),
+    };
+
+    Some(format!("{} ({})", alert, name))
+}
+
+/// Paths of a client and the state driven by their events, apart from the
+/// connection so that [`handle_path_event`] can be driven by a scripted
+/// sequence of events.
+#[derive(Clone, Debug)]
+pub struct ClientState {
+    /// Path `i` goes from `local_addrs[i]` to `peer_addrs[i]`.
+    pub local_addrs: Vec<SocketAddr>,
+    pub peer_addrs: Vec<SocketAddr>,
+
+    /// Path carrying the data.
+    pub active_path: (SocketAddr, SocketAddr),
+
+    /// Paths whose last validation succeeded and that were not closed since.
+    pub validated: HashSet<(SocketAddr, SocketAddr)>,
+
+    /// Path validated without carrying data, until the active one fails.
+    pub standby: Option<(SocketAddr, SocketAddr)>,
+
+    /// Paths watched by the keep-alives, validated again only after being
+    /// probed again.
+    pub kept_alive: HashSet<(SocketAddr, SocketAddr)>,
+}
+
+impl ClientState {
+    /// Starts on path 0, `standby` being the index of the standby path.
+    pub fn new(
+        local_addrs: Vec<SocketAddr>,
+        peer_addrs: Vec<SocketAddr>,
+        standby: Option<usize>,
+    ) -> Self {
+        ClientState {
+            active_path: (local_addrs[0], peer_addrs[0]),
+            standby: standby.map(|i| (local_addrs[i], peer_addrs[i])),
+            local_addrs,
+            peer_addrs,
+            validated: HashSet::new(),
+            kept_alive: HashSet::new(),
+        }
+    }
+
+    /// Whether `path` pairs a local address with the peer address of the
+    /// same index, as every path the client opens does.
+    pub fn is_own_path(&self, path: (SocketAddr, SocketAddr)) -> bool {
+        self.local_addrs
+            .iter()
+            .zip(&self.peer_addrs)
+            .any(|(local, peer)| (*local, *peer) == path)
+    }
+}
+
+/// What the connection must do after a path event, see
+/// [`handle_path_event`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathAction {
+    /// Migrate to the path, which became the active one.
+    Migrate(SocketAddr, SocketAddr),
+
+    /// The active path was lost, move to the standby path if it is
+    /// validated.
+    FailOver,
+
+    /// A path watched by the keep-alives failed validation, probe it again.
+    Reprobe(SocketAddr, SocketAddr),
+}
+
+/// Updates `state` on a path event, records the resulting connection events
+/// in `events` and returns what the connection must do.
+///
+/// A newly validated path of the client becomes the active one, except the
+/// standby path and the paths validated again after a keep-alive re-probe.
+/// Losing the active path asks for a failover if there is a standby path.
+pub fn handle_path_event(
+    state: &mut ClientState,
+    event: quiche::PathEvent,
+    events: &mut Vec<Event>,
+) -> Vec<PathAction> {
+    let mut actions = vec![];
+
+    match event {
+        quiche::PathEvent::New(local_addr, peer_addr) => {
+            events.push(Event::PathNew(local_addr, peer_addr));
+        }
+
+        quiche::PathEvent::Validated(local_addr, peer_addr) => {
+            let path = (local_addr, peer_addr);
+
+            events.push(Event::PathValidated(local_addr, peer_addr));
+            state.validated.insert(path);
+
+            // Only the configured paths carry messages.
+            if !state.is_own_path(path) {
+                return actions;
+            }
+
+            if state.standby == Some(path) {
+                info!("Standby path is ready");
+                return actions;
+            }
+
+            // Validated again after its keep-alives went unanswered.
+            if state.kept_alive.contains(&path) {
+                return actions;
+            }
+
+            events.push(Event::Migrated(local_addr, peer_addr));
+            state.active_path = path;
+            actions.push(PathAction::Migrate(local_addr, peer_addr));
+        }
+
+        quiche::PathEvent::FailedValidation(local_addr, peer_addr) => {
+            let path = (local_addr, peer_addr);
+
+            events.push(Event::PathFailedValidation(local_addr, peer_addr));
+            state.validated.remove(&path);
+
+            if state.kept_alive.contains(&path) {
+                actions.push(PathAction::Reprobe(local_addr, peer_addr));
+            }
+
+            if state.active_path == path && state.standby.is_some() {
+                actions.push(PathAction::FailOver);
+            }
+        }
+
+        quiche::PathEvent::Closed(local_addr, peer_addr) => {
+            let path = (local_addr, peer_addr);
+
+            events.push(Event::PathClosed(local_addr, peer_addr));
+            state.validated.remove(&path);
+
+            if state.active_path == path && state.standby.is_some() {
+                actions.push(PathAction::FailOver);
+            }
+        }
+
+        quiche::PathEvent::ReusedSourceConnectionId(cid_seq, old, new) => {
+            info!(
+                "Peer reused cid seq {} (initially {:?}) on {:?}",
+                cid_seq, old, new
+            );
+        }
+
+        // Only servers see their peer migrate.
+        quiche::PathEvent::PeerMigrated(..) => unreachable!(),
+    }
+
+    actions
+}
+
+/// Path kept validated and alive, without application data, so that the
+/// connection can move to it as soon as the active path is lost.
+struct Standby {
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    probed: bool,
+    last_keepalive: Option<Instant>,
+    /// Time the active path was detected as lost, and the number of bytes
+    /// received on the standby path at that time.
+    failover: Option<(Instant, u64)>,
+    /// Traffic of the active path, to fail over once it is suspected lost.
+    liveness: Liveness,
+}
+
+impl Standby {
+    fn is(&self, local_addr: SocketAddr, peer_addr: SocketAddr) -> bool {
+        self.local_addr == local_addr && self.peer_addr == peer_addr
+    }
+
+    fn next_keepalive(&self) -> Option<Instant> {
+        self.last_keepalive
+            .map(|last| last + STANDBY_KEEPALIVE_INTERVAL)
+    }
+
+    fn recv_bytes(&self, conn: &quiche::Connection) -> u64 {
+        conn.path_stats()
+            .find(|s| self.is(s.local_addr, s.peer_addr))
+            .map_or(0, |s| s.recv_bytes)
+    }
+
+    /// Probes the standby path once and keeps it alive while unused.
+    fn maintain(
+        &mut self,
+        conn: &mut quiche::Connection,
+        active: (SocketAddr, SocketAddr),
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) {
+        if !self.probed {
+            if peer_allows_path(conn) {
+                // Probed again on the next step if quiche refuses.
+                match conn.probe_path(self.local_addr, self.peer_addr) {
+                    Ok(_) => {
+                        events.push(Event::PathProbed(self.local_addr, self.peer_addr));
+                        self.probed = true;
+                    }
+
+                    Err(e) => {
+                        error!(
+                            "Probing standby path ({}, {}) failed: {:?}",
+                            self.local_addr, self.peer_addr, e
+                        );
+                        events.push(Event::PathProbeFailed(self.local_addr, self.peer_addr));
+                    }
+                }
+            }
+            return;
+        }
+
+        if active == (self.local_addr, self.peer_addr)
+            || !conn
+                .is_path_validated(self.local_addr, self.peer_addr)
+                .unwrap_or(false)
+        {
+            return;
+        }
+
+        if self.next_keepalive().is_none_or(|t| t <= now) {
+            trace!(
+                "keep-alive on standby path ({}, {})",
+                self.local_addr,
+                self.peer_addr
+            );
+            conn.send_ack_eliciting_on_path(self.local_addr, self.peer_addr)
+                .ok();
+            self.last_keepalive = Some(now);
+        }
+    }
+
+    /// Moves the connection to the standby path after the loss of the
+    /// active one. Returns whether the migration happened.
+    fn fail_over(
+        &mut self,
+        conn: &mut quiche::Connection,
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) -> bool {
+        if !conn
+            .is_path_validated(self.local_addr, self.peer_addr)
+            .unwrap_or(false)
+        {
+            info!("Standby path is not validated, cannot fail over");
+            return false;
+        }
+
+        if let Err(e) = conn.migrate(self.local_addr, self.peer_addr) {
+            error!("Failover to standby path failed: {:?}", e);
+            events.push(Event::MigrationFailed(self.local_addr, self.peer_addr));
+            return false;
+        }
+
+        info!(
+            "Failing over to standby path ({}, {})",
+            self.local_addr, self.peer_addr
+        );
+        events.push(Event::Migrated(self.local_addr, self.peer_addr));
+        self.failover = Some((now, self.recv_bytes(conn)));
+        true
+    }
+
+    /// Tells whether the active path is suspected lost: it kept sending and
+    /// received nothing for a few RTTs.
+    fn is_suspect(
+        &mut self,
+        conn: &quiche::Connection,
+        active: (SocketAddr, SocketAddr),
+        now: Instant,
+    ) -> bool {
+        if self.is(active.0, active.1) || self.failover.is_some() {
+            self.liveness.reset();
+            return false;
+        }
+
+        let Some(stats) = conn
+            .path_stats()
+            .find(|s| (s.local_addr, s.peer_addr) == active)
+        else {
+            return false;
+        };
+
+        self.liveness
+            .observe(active, stats.recv_bytes, stats.sent_bytes, stats.rtt, now)
+    }
+
+    /// Records the failover time once data arrives on the standby path.
+    fn check_failover(
+        &mut self,
+        conn: &quiche::Connection,
+        report: &mut TargetReport,
+        now: Instant,
+    ) {
+        if let Some((detected, recv_bytes)) = self.failover {
+            if self.recv_bytes(conn) > recv_bytes {
+                report.failover_time = Some(now.saturating_duration_since(detected));
+                self.failover = None;
+            }
+        }
+    }
+}
+
+/// Tells when the paths stopped carrying stream data for the idle timeout.
+struct IdlePaths {
+    timeout: Duration,
+    /// Stream bytes received on each path watched, and when they last grew.
+    activity: HashMap<(SocketAddr, SocketAddr), (u64, Instant)>,
+}
+
+impl IdlePaths {
+    fn new(timeout: Duration) -> Self {
+        IdlePaths {
+            timeout,
+            activity: HashMap::new(),
+        }
+    }
+
+    fn next_timeout(&self) -> Option<Instant> {
+        self.activity
+            .values()
+            .map(|(_, since)| *since + self.timeout)
+            .min()
+    }
+
+    /// Accounts the `bytes` received on `path` so far, and tells whether
+    /// they did not grow for the timeout. A path is watched from its first
+    /// call.
+    fn is_expired(&mut self, path: (SocketAddr, SocketAddr), bytes: u64, now: Instant) -> bool {
+        let (seen, since) = self.activity.entry(path).or_insert((bytes, now));
+
+        if bytes > *seen {
+            *seen = bytes;
+            *since = now;
+        }
+
+        now.saturating_duration_since(*since) >= self.timeout
+    }
+}
+
+/// Keeps the validated paths on which nothing is received alive with
+/// PATH_CHALLENGEs, so that middleboxes do not forget their state.
+///
+/// Challenges are probing frames, so the server does not migrate to the path
+/// they are sent on.
+struct PathKeepalive {
+    interval: Duration,
+    paths: HashMap<(SocketAddr, SocketAddr), IdlePath>,
+    /// Paths closed after being idle, never kept alive again.
+    closed: HashSet<(SocketAddr, SocketAddr)>,
+}
+
+/// Activity of a path kept alive.
+struct IdlePath {
+    /// Packets received on the path so far.
+    recv: usize,
+    last_recv: Instant,
+    last_keepalive: Option<Instant>,
+    /// Keep-alives sent since something was last received.
+    unanswered: u32,
+    /// Whether quiche gave up validating the path.
+    failed: bool,
+}
+
+impl IdlePath {
+    fn next_keepalive(&self, interval: Duration) -> Instant {
+        self.last_keepalive
+            .map_or(self.last_recv, |t| t.max(self.last_recv))
+            + interval
+    }
+}
+
+impl PathKeepalive {
+    fn new(interval: Duration) -> Self {
+        PathKeepalive {
+            interval,
+            paths: HashMap::new(),
+            closed: HashSet::new(),
+        }
+    }
+
+    fn close(&mut self, path: (SocketAddr, SocketAddr)) {
+        self.paths.remove(&path);
+        self.closed.insert(path);
+    }
+
+    fn next_timeout(&self) -> Option<Instant> {
+        self.paths
+            .values()
+            .map(|p| p.next_keepalive(self.interval))
+            .min()
+    }
+
+    fn on_failed_validation(&mut self, local_addr: SocketAddr, peer_addr: SocketAddr) {
+        if let Some(path) = self.paths.get_mut(&(local_addr, peer_addr)) {
+            path.failed = true;
+        }
+    }
+
+    /// Sends a keep-alive on every path on which nothing was received for an
+    /// interval, except the standby path which has its own, and counts them
+    /// in `stats`. Paths whose keep-alives go unanswered are probed again.
+    fn poll(
+        &mut self,
+        conn: &mut quiche::Connection,
+        standby: Option<&Standby>,
+        stats: &mut Vec<KeepaliveStats>,
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) {
+        let paths: Vec<_> = conn
+            .path_stats()
+            .map(|s| (s.local_addr, s.peer_addr, s.recv))
+            .collect();
+
+        for (local_addr, peer_addr, recv) in paths {
+            if standby.is_some_and(|s| s.is(local_addr, peer_addr))
+                || self.closed.contains(&(local_addr, peer_addr))
+            {
+                continue;
+            }
+
+            let path = match self.paths.get_mut(&(local_addr, peer_addr)) {
+                Some(v) => v,
+
+                None if conn
+                    .is_path_validated(local_addr, peer_addr)
+                    .unwrap_or(false) =>
+                {
+                    self.paths
+                        .entry((local_addr, peer_addr))
+                        .or_insert(IdlePath {
+                            recv,
+                            last_recv: now,
+                            last_keepalive: None,
+                            unanswered: 0,
+                            failed: false,
+                        })
+                }
+
+                None => continue,
+            };
+
+            if recv > path.recv {
+                path.recv = recv;
+                path.last_recv = now;
+                path.unanswered = 0;
+                path.failed = false;
+            }
+
+            if now < path.next_keepalive(self.interval) {
+                continue;
+            }
+
+            if path.last_keepalive.is_some_and(|t| t >= path.last_recv) {
+                path.unanswered += 1;
+            }
+
+            path.last_keepalive = Some(now);
+
+            if conn.probe_path(local_addr, peer_addr).is_err() {
+                continue;
+            }
+
+            let index = match stats.iter().position(|s| s.path == (local_addr, peer_addr)) {
+                Some(v) => v,
+
+                None => {
+                    stats.push(KeepaliveStats {
+                        path: (local_addr, peer_addr),
+                        keepalives: 0,
+                        reprobes: 0,
+                    });
+                    stats.len() - 1
+                }
+            };
+
+            if path.failed || path.unanswered >= KEEPALIVE_MAX_UNANSWERED {
+                info!(
+                    "Keep-alives unanswered on path ({}, {}), probing it again",
+                    local_addr, peer_addr
+                );
+                events.push(Event::PathProbed(local_addr, peer_addr));
+                path.unanswered = 0;
+                path.failed = false;
+                stats[index].reprobes += 1;
+            } else {
+                trace!("keep-alive on path ({}, {})", local_addr, peer_addr);
+                stats[index].keepalives += 1;
+            }
+        }
+    }
+}
+
+/// Control messages telling the server the run of the connection, and
+/// whether it completed.
+struct RunControl {
+    run_id: RunId,
+    /// Whether the start of the run was sent on this connection.
+    started: bool,
+    /// Stream of the completion message, and the time after which the
+    /// connection is closed even if it was not acknowledged.
+    completion: Option<(u64, Instant)>,
+    /// Whether the server acknowledged the completion message.
+    acked: bool,
+}
+
+impl RunControl {
+    fn new(run_id: RunId) -> Self {
+        RunControl {
+            run_id,
+            started: false,
+            completion: None,
+            acked: false,
+        }
+    }
+
+    /// Sends the start of the run once the connection is established.
+    fn start(&mut self, conn: &mut quiche::Connection, dispatcher: &mut Dispatcher) {
+        if self.started {
+            return;
+        }
+
+        self.started = true;
+        send_control(conn, dispatcher, &Control::Start(self.run_id.clone()));
+    }
+
+    /// Sends the completion of the run once every message was delivered,
+    /// and tells whether the connection may be closed: once the server
+    /// acknowledged it, or after `COMPLETION_WAIT`.
+    fn complete(
+        &mut self,
+        conn: &mut quiche::Connection,
+        dispatcher: &mut Dispatcher,
+        now: Instant,
+    ) -> bool {
+        let Some((stream_id, deadline)) = self.completion else {
+            let completion = Completion {
+                run_id: self.run_id.clone(),
+                messages: dispatcher.messages.len(),
+                bytes: dispatcher.messages.iter().map(|m| m.len() as u64).sum(),
+            };
+
+            let Some(stream_id) = send_control(conn, dispatcher, &Control::Complete(completion))
+            else {
+                return true;
+            };
+
+            self.completion = Some((stream_id, now + COMPLETION_WAIT));
+            return false;
+        };
+
+        if is_collected(conn, stream_id) {
+            self.acked = true;
+            return true;
+        }
+
+        if now >= deadline {
+            warn!(
+                "{} completion of the run not acknowledged after {:?}, closing anyway",
+                conn.trace_id(),
+                COMPLETION_WAIT
+            );
+            return true;
+        }
+
+        false
+    }
+
+    fn next_timeout(&self) -> Option<Instant> {
+        self.completion
+            .filter(|_| !self.acked)
+            .map(|(_, deadline)| deadline)
+    }
+}
+
+/// Sends `control` on a new unidirectional stream, and returns the stream if
+/// it could be sent.
+fn send_control(
+    conn: &mut quiche::Connection,
+    dispatcher: &mut Dispatcher,
+    control: &Control,
+) -> Option<u64> {
+    let stream_id = dispatcher.open_uni_stream();
+    let data = control.encode();
+
+    match conn.stream_send(stream_id, &data, true) {
+        Ok(written) if written == data.len() => {
+            dispatcher.written += written as u64;
+            info!("{} sent {}", conn.trace_id(), control);
+            Some(stream_id)
+        }
+
+        Ok(written) => {
+            dispatcher.written += written as u64;
+            warn!("{} no room to send {}", conn.trace_id(), control);
+            None
+        }
+
+        Err(e) => {
+            warn!("{} cannot send {}: {:?}", conn.trace_id(), control, e);
+            None
+        }
+    }
+}
+
+/// Watches for connections that send nothing for several PTOs while they
+/// have stream data to send and room in their congestion window, the sign of
+/// a timer or pacing bug in the driver.
+struct SendWatchdog {
+    ptos: u32,
+    /// Whether a stalled connection is closed.
+    strict: bool,
+    last_sent: Instant,
+    /// Pacing deadline given by quiche to the last datagram of each path.
+    pacing: HashMap<(SocketAddr, SocketAddr), Instant>,
+    /// Whether the current stall was reported already.
+    reported: bool,
+    /// Time before which the connection is not checked again, after it was
+    /// found idle but not stalled.
+    next_check: Option<Instant>,
+}
+
+impl SendWatchdog {
+    fn new(ptos: u32, strict: bool, now: Instant) -> Self {
+        SendWatchdog {
+            ptos,
+            strict,
+            last_sent: now,
+            pacing: HashMap::new(),
+            reported: false,
+            next_check: None,
+        }
+    }
+
+    fn on_sent(&mut self, datagrams: &[(Vec<u8>, quiche::SendInfo)], now: Instant) {
+        if datagrams.is_empty() {
+            return;
+        }
+
+        self.last_sent = now;
+        self.reported = false;
+        self.next_check = None;
+
+        for (_, send_info) in datagrams {
+            self.pacing
+                .insert((send_info.from, send_info.to), send_info.at);
+        }
+    }
+}
+
+/// Bulk transfer saturating the initial path, with small probes sent on the
+/// same path to measure how the latency grows as the queues fill.
+struct Bufferbloat {
+    bulk: Option<StreamWriter<io::Take<io::Repeat>>>,
+    bulk_size: u64,
+    bulk_echoed: u64,
+    interval: Duration,
+    started: Option<Instant>,
+    next_probe: Option<Instant>,
+    /// Probes waiting for their echo, with the time they were sent and the
+    /// number of bulk bytes queued at that time.
+    probes: HashMap<u64, (Instant, u64)>,
+}
+
+impl Bufferbloat {
+    fn new(bulk_size: u64, interval: Duration) -> Self {
+        Bufferbloat {
+            bulk: None,
+            bulk_size,
+            bulk_echoed: 0,
+            interval,
+            started: None,
+            next_probe: None,
+            probes: HashMap::new(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.bulk_echoed == self.bulk_size && self.probes.is_empty()
+    }
+
+    fn next_timeout(&self) -> Option<Instant> {
+        if self.bulk_echoed < self.bulk_size {
+            self.next_probe
+        } else {
+            None
+        }
+    }
+
+    /// Sends the bulk data and the probes that are due.
+    fn poll(
+        &mut self,
+        conn: &mut quiche::Connection,
+        dispatcher: &mut Dispatcher,
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) {
+        let started = *self.started.get_or_insert(now);
+
+        let bulk = self.bulk.get_or_insert_with(|| {
+            let stream_id = dispatcher.open_stream();
+            events.push(Event::StreamOpened(stream_id));
+            StreamWriter::new(stream_id, io::repeat(b'x').take(self.bulk_size))
+        });
+
+        if let Err(e) = bulk.poll_write(conn) {
+            error!("Writing bulk data failed: {}", e);
+        }
+
+        if self.bulk_echoed < self.bulk_size && self.next_probe.is_none_or(|t| t <= now) {
+            let stream_id = dispatcher.open_stream();
+            dispatcher
+                .writers
+                .push(StreamWriter::new(stream_id, Cursor::new(PROBE.to_vec())));
+            events.push(Event::StreamOpened(stream_id));
+
+            let queued = bulk.written() - self.bulk_echoed;
+            self.probes.insert(stream_id, (now, queued));
+            self.next_probe = Some(now.max(started) + self.interval);
+        }
+    }
+
+    /// Accounts data received on the streams of the measurement. Returns
+    /// false if `stream_id` is not one of them.
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        read: usize,
+        fin: bool,
+        report: &mut TargetReport,
+        now: Instant,
+    ) -> bool {
+        if self
+            .bulk
+            .as_ref()
+            .is_some_and(|b| b.stream_id() == stream_id)
+        {
+            self.bulk_echoed += read as u64;
+            return true;
+        }
+
+        if !self.probes.contains_key(&stream_id) {
+            return false;
+        }
+
+        if fin {
+            let (sent_at, queued_bytes) = self.probes.remove(&stream_id).unwrap();
+
+            report.load_samples.push(LoadSample {
+                at: sent_at - self.started.unwrap(),
+                queued_bytes,
+                latency: now.saturating_duration_since(sent_at),
+            });
+        }
+
+        true
+    }
+}
+
+/// Source of a stream of the file transfer: the header of its range, then
+/// the bytes of the range.
+type RangeSource = io::Chain<Cursor<[u8; HEADER_LEN]>, io::Take<Cursor<Arc<[u8]>>>>;
+
+/// File sent in byte ranges over one stream, then over several streams, to
+/// compare their goodput. The server reassembles each run and answers with
+/// the SHA-256 of the file.
+///
+/// With `--stripe`, the file is instead sent on each validated path alone,
+/// then in chunks striped across all of them, to compare the aggregate
+/// goodput with the best single path.
+struct FileTransfer {
+    data: Arc<[u8]>,
+    digest: Vec<u8>,
+    /// Layout of the runs not started yet.
+    runs: VecDeque<RunLayout>,
+    run: Option<TransferRun>,
+    next_id: u64,
+    /// Striping across the paths, until its runs are planned.
+    stripe: Option<Stripe>,
+    /// Snapshots of the runs not handed out yet.
+    progress: Vec<TransferProgress>,
+    /// Validated paths when last polled.
+    paths: usize,
+}
+
+/// How the ranges of a run are sent.
+#[derive(Clone, Debug)]
+enum RunLayout {
+    /// Over `n` streams at once, on whichever path is active.
+    Streams(usize),
+
+    /// In chunks of `chunk` bytes, each on its own stream opened once the
+    /// previous one was handed to quiche, on the next of `paths` in turn.
+    ///
+    /// quiche only sends stream data on the active path, so the client
+    /// migrates to the path of each chunk before writing it. Bytes of a
+    /// chunk still queued when the next one moves to another path leave on
+    /// that path.
+    Paths {
+        paths: Vec<(SocketAddr, SocketAddr)>,
+        chunk: u64,
+    },
+}
+
+/// Paths to stripe the file across, once validated.
+struct Stripe {
+    paths: usize,
+    chunk: u64,
+    /// Time after which the paths not validated yet are left out.
+    deadline: Option<Instant>,
+}
+
+/// Run of the file transfer in progress.
+struct TransferRun {
+    layout: RunLayout,
+    started: Instant,
+    writers: Vec<StreamWriter<RangeSource>>,
+    /// Ranges whose stream is not opened yet.
+    pending: VecDeque<(u64, u64)>,
+    /// Replies of the server received so far, per stream not finished yet.
+    replies: HashMap<u64, Vec<u8>>,
+    /// Digest computed by the server, once received.
+    digest: Option<Vec<u8>>,
+    /// Length of the range of each stream.
+    lengths: HashMap<u64, u64>,
+    /// Bytes of the ranges the server answered the fin of.
+    acked: u64,
+    meter: ProgressMeter,
+}
+
+impl FileTransfer {
+    fn new(data: Arc<[u8]>, streams: usize, stripe: Option<(usize, usize)>) -> Self {
+        let mut runs = VecDeque::new();
+        if stripe.is_none() {
+            runs.push_back(RunLayout::Streams(1));
+        }
+        if streams > 1 {
+            runs.push_back(RunLayout::Streams(streams));
+        }
+
+        FileTransfer {
+            digest: file_transfer::sha256(&data),
+            data,
+            runs,
+            run: None,
+            next_id: 0,
+            stripe: stripe.map(|(paths, chunk)| Stripe {
+                paths,
+                chunk: chunk as u64,
+                deadline: None,
+            }),
+            progress: Vec::new(),
+            paths: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.run.is_none() && self.runs.is_empty() && self.stripe.is_none()
+    }
+
+    /// When the paths not validated yet are left out of the striping.
+    fn next_timeout(&self) -> Option<Instant> {
+        self.stripe.as_ref().and_then(|s| s.deadline)
+    }
+
+    /// Probes the paths to stripe the file across, and plans a run on each
+    /// of them then a striped run once they are all validated, or the
+    /// others timed out. Returns whether the runs are planned.
+    fn plan_stripe(
+        &mut self,
+        conn: &mut quiche::Connection,
+        dispatcher: &Dispatcher,
+        (local_addrs, peer_addrs): (&[SocketAddr], &[SocketAddr]),
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) -> bool {
+        let Some(stripe) = &mut self.stripe else {
+            return true;
+        };
+
+        // Paths are probed once the handshake is confirmed, see `confirm`.
+        if !dispatcher.probe {
+            return false;
+        }
+
+        let deadline = *stripe
+            .deadline
+            .get_or_insert(now + STRIPE_VALIDATION_TIMEOUT);
+        let mut validated = vec![];
+
+        for (local_addr, peer_addr) in local_addrs.iter().zip(peer_addrs).take(stripe.paths) {
+            let (local_addr, peer_addr) = (*local_addr, *peer_addr);
+
+            match conn.is_path_validated(local_addr, peer_addr) {
+                Ok(true) => validated.push((local_addr, peer_addr)),
+
+                Ok(false) => (),
+
+                Err(_) => {
+                    if peer_allows_path(conn) && conn.probe_path(local_addr, peer_addr).is_ok() {
+                        events.push(Event::PathProbed(local_addr, peer_addr));
+                    }
+                }
+            }
+        }
+
+        if validated.len() < stripe.paths {
+            if now < deadline {
+                return false;
+            }
+
+            warn!(
+                "striping the file across {} of {} paths, the others were not validated in {:?}",
+                validated.len(),
+                stripe.paths,
+                STRIPE_VALIDATION_TIMEOUT
+            );
+        }
+
+        let size = self.data.len() as u64;
+        for path in &validated {
+            self.runs.push_back(RunLayout::Paths {
+                paths: vec![*path],
+                chunk: size,
+            });
+        }
+        if validated.len() > 1 {
+            self.runs.push_back(RunLayout::Paths {
+                paths: validated,
+                chunk: stripe.chunk,
+            });
+        }
+
+        self.stripe = None;
+        true
+    }
+
+    /// Starts the next run once the previous one completed, and writes the
+    /// ranges of the current one.
+    fn poll(
+        &mut self,
+        conn: &mut quiche::Connection,
+        dispatcher: &mut Dispatcher,
+        addrs: (&[SocketAddr], &[SocketAddr]),
+        active_path: &mut (SocketAddr, SocketAddr),
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) {
+        if self.run.is_none() {
+            if !self.plan_stripe(conn, dispatcher, addrs, events, now) {
+                return;
+            }
+
+            let Some(layout) = self.runs.pop_front() else {
+                return;
+            };
+
+            let size = self.data.len() as u64;
+            let pending = match &layout {
+                RunLayout::Streams(streams) => file_transfer::split(size, *streams),
+                RunLayout::Paths { chunk, .. } => file_transfer::chunks(size, *chunk),
+            };
+
+            match &layout {
+                RunLayout::Streams(streams) => info!(
+                    "Sending {} bytes on {} stream(s), transfer {}",
+                    size, streams, self.next_id
+                ),
+
+                RunLayout::Paths { paths, .. } => info!(
+                    "Sending {} bytes in {} range(s) over {} path(s), transfer {}",
+                    size,
+                    pending.len(),
+                    paths.len(),
+                    self.next_id
+                ),
+            }
+
+            self.run = Some(TransferRun {
+                layout,
+                started: now,
+                writers: Vec::new(),
+                pending: pending.into(),
+                replies: HashMap::new(),
+                digest: None,
+                lengths: HashMap::new(),
+                acked: 0,
+                meter: ProgressMeter::new(self.next_id, size, now),
+            });
+            self.next_id += 1;
+        }
+
+        let run = self.run.as_mut().unwrap();
+        let transfer = self.next_id - 1;
+
+        if let RunLayout::Streams(_) = run.layout {
+            while let Some(range) = run.pending.pop_front() {
+                run.open(&self.data, transfer, range, dispatcher, events);
+            }
+
+            for writer in &mut run.writers {
+                if let Err(e) = writer.poll_write(conn) {
+                    error!("Writing file range failed: {}", e);
+                }
+            }
+
+            return;
+        }
+
+        // The chunk being written, or the next one if the server accepts
+        // another stream.
+        if run.writers.last().is_none_or(|w| w.is_done())
+            && !run.pending.is_empty()
+            && conn.peer_streams_left_bidi() > 0
+        {
+            let range = run.pending.pop_front().unwrap();
+            run.open(&self.data, transfer, range, dispatcher, events);
+        }
+
+        let chunk = run.writers.len().saturating_sub(1);
+        let (Some(path), Some(writer)) = (
+            run.layout.path_of(chunk),
+            run.writers.last_mut().filter(|w| !w.is_done()),
+        ) else {
+            return;
+        };
+
+        if *active_path != path {
+            if conn.migrate(path.0, path.1).is_err() {
+                events.push(Event::MigrationFailed(path.0, path.1));
+                return;
+            }
+            events.push(Event::Migrated(path.0, path.1));
+            *active_path = path;
+        }
+
+        if let Err(e) = writer.poll_write(conn) {
+            error!("Writing file range failed: {}", e);
+        }
+    }
+
+    /// Accounts the replies received on the streams of the current run.
+    /// Returns false if `stream_id` is not one of them.
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        data: &[u8],
+        fin: bool,
+        report: &mut TargetReport,
+        now: Instant,
+    ) -> bool {
+        let Some(run) = &mut self.run else {
+            return false;
+        };
+
+        let Some(reply) = run.replies.get_mut(&stream_id) else {
+            return false;
+        };
+        reply.extend_from_slice(data);
+
+        if !fin {
+            return true;
+        }
+
+        let reply = run.replies.remove(&stream_id).unwrap();
+        if !reply.is_empty() {
+            run.digest = Some(reply);
+        }
+
+        // The server answers the fin once it has the whole range.
+        run.acked += run.lengths.get(&stream_id).copied().unwrap_or_default();
+
+        if run.replies.is_empty() && run.pending.is_empty() {
+            let mut run = self.run.take().unwrap();
+
+            if let Some(progress) = run.meter.update(run.sent(), run.acked, self.paths, now) {
+                self.progress.push(progress);
+            }
+
+            let digest_ok = run.digest.as_ref() == Some(&self.digest);
+
+            if !digest_ok {
+                warn!(
+                    "SHA-256 of the file on {} stream(s) differs: {}",
+                    run.writers.len(),
+                    run.digest
+                        .as_deref()
+                        .map_or("none".to_string(), file_transfer::hex)
+                );
+            }
+
+            report.transfer_runs.push(ReportedRun {
+                streams: run.writers.len(),
+                size: self.data.len() as u64,
+                paths: match run.layout {
+                    RunLayout::Streams(_) => vec![],
+                    RunLayout::Paths { paths, .. } => paths,
+                },
+                elapsed: now.saturating_duration_since(run.started),
+                digest_ok,
+            });
+        }
+
+        true
+    }
+
+    /// Snapshots of the progress of the runs due at `now`, the last one of
+    /// each run included.
+    fn progress(&mut self, paths: usize, now: Instant) -> Vec<TransferProgress> {
+        self.paths = paths;
+
+        if let Some(run) = &mut self.run {
+            if let Some(progress) = run.meter.update(run.sent(), run.acked, paths, now) {
+                self.progress.push(progress);
+            }
+        }
+
+        std::mem::take(&mut self.progress)
+    }
+
+    /// Streams of the current run with data left to write.
+    fn blocked_streams(&self) -> impl Iterator<Item = u64> + '_ {
+        self.run
+            .iter()
+            .flat_map(|r| &r.writers)
+            .filter(|w| !w.is_done())
+            .map(|w| w.stream_id())
+    }
+}
+
+impl RunLayout {
+    /// Path carrying the `i`-th range, if the layout sets one.
+    fn path_of(&self, i: usize) -> Option<(SocketAddr, SocketAddr)> {
+        match self {
+            RunLayout::Streams(_) => None,
+            RunLayout::Paths { paths, .. } => Some(paths[i % paths.len()]),
+        }
+    }
+}
+
+impl TransferRun {
+    /// Bytes of the file handed to quiche, headers left out.
+    fn sent(&self) -> u64 {
+        self.writers
+            .iter()
+            .map(|w| {
+                let len = self
+                    .lengths
+                    .get(&w.stream_id())
+                    .copied()
+                    .unwrap_or_default();
+                w.written().saturating_sub(HEADER_LEN as u64).min(len)
+            })
+            .sum()
+    }
+
+    /// Opens the stream of the range at `offset` of `len` bytes.
+    fn open(
+        &mut self,
+        data: &Arc<[u8]>,
+        transfer: u64,
+        (offset, len): (u64, u64),
+        dispatcher: &mut Dispatcher,
+        events: &mut Vec<Event>,
+    ) {
+        let stream_id = dispatcher.open_stream();
+        events.push(Event::StreamOpened(stream_id));
+
+        let header = RangeHeader {
+            transfer,
+            size: data.len() as u64,
+            offset,
+        };
+
+        let mut range = Cursor::new(data.clone());
+        range.set_position(offset);
+
+        self.writers.push(StreamWriter::new(
+            stream_id,
+            Cursor::new(header.encode()).chain(range.take(len)),
+        ));
+        self.replies.insert(stream_id, Vec::new());
+        self.lengths.insert(stream_id, len);
+    }
+}
+
+/// Stream carrying several messages of a path, each as a length-prefixed
+/// frame. The server echoes the frames in order, so the echoes complete the
+/// messages in the order they were queued.
+struct MuxStream {
+    path: usize,
+    writer: StreamWriter<Cursor<Vec<u8>>>,
+    /// Frames queued while the writer was busy with the previous ones.
+    queued: Vec<u8>,
+    /// Whether the writer handed all its frames to the stream.
+    drained: bool,
+    /// Messages queued on the stream whose echo was not received yet.
+    in_flight: VecDeque<usize>,
+    /// Number of frames echoed so far.
+    echoed: usize,
+    decoder: FrameDecoder,
+}
+
+impl MuxStream {
+    fn queue(&mut self, idx_message: usize, payload: &[u8]) {
+        self.queued.extend_from_slice(&frame::encode(payload));
+        self.in_flight.push_back(idx_message);
+    }
+
+    /// Writes the queued frames, handing them to the writer each time it
+    /// drained the previous ones.
+    fn poll_write(&mut self, conn: &mut quiche::Connection) -> Result<(), WriteError> {
+        if self.writer.is_done() {
+            return Ok(());
+        }
+
+        loop {
+            if self.drained {
+                if self.queued.is_empty() {
+                    return Ok(());
+                }
+
+                let frames = std::mem::take(&mut self.queued);
+                self.writer.resume(Cursor::new(frames));
+                self.drained = false;
+            }
+
+            match self.writer.poll_write(conn)? {
+                Progress::Drained => self.drained = true,
+
+                Progress::Stopped(code) => {
+                    info!(
+                        "Server stopped stream {} with code {}",
+                        self.writer.stream_id(),
+                        code
+                    );
+                    return Ok(());
+                }
+
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+/// What is prepended to each message before it is sent.
+struct Stamps {
+    /// Clock timestamping the messages, with `--owd`.
+    clock: Option<WallClock>,
+    /// Sequence number each message was last sent with, with `--sequence`.
+    seqs: Option<Vec<Option<u64>>>,
+}
+
+/// Messages to send and the state of their dispatch.
+struct Dispatcher {
+    messages: Vec<Vec<u8>>,
+    /// Bytes sent for each message, padded if requested.
+    payloads: Vec<Vec<u8>>,
+    /// Path used by each message.
+    paths: Vec<usize>,
+    /// Order in which the messages are dispatched.
+    order: Vec<usize>,
+    /// Maximum number of bytes of a message handed to its stream per write.
+    chunk_size: Option<usize>,
+    /// Number of streams per path carrying the messages as frames, if the
+    /// messages are multiplexed.
+    multiplex: Option<usize>,
+    /// Whether new paths may be probed, once the handshake is confirmed.
+    probe: bool,
+    /// Destination connection IDs left for migrations, new paths are not
+    /// probed below.
+    dcid_reserve: usize,
+    /// Message last held back by the reserve, logged once.
+    reserve_hit: Option<usize>,
+    /// Message whose path quiche last refused to probe, reported once.
+    probe_failed: Option<usize>,
+    /// Sequence number of the destination connection ID of each path the
+    /// client opened.
+    dcid_seqs: HashMap<(SocketAddr, SocketAddr), u64>,
+    /// Paths closed after being idle, by index, whose messages go to the
+    /// active path instead.
+    closed_paths: HashSet<usize>,
+    /// Limits the number of messages in flight, if set.
+    admission: Option<Admission>,
+    /// Time each message waited for a slot before being sent.
+    queue_wait: Vec<Duration>,
+    /// Number of messages dispatched so far.
+    next: usize,
+    /// Next client-initiated bidirectional stream to open.
+    next_stream_id: u64,
+    /// Whether the messages are sent on unidirectional streams, delivered
+    /// once acknowledged.
+    uni: bool,
+    next_uni_stream_id: u64,
+    /// Unidirectional streams fully handed to quiche, whose data is not
+    /// acknowledged yet.
+    unacked: Vec<u64>,
+    /// Message sent on each stream, until quiche collects the stream.
+    streams: HashMap<u64, usize>,
+    /// Streams whose echo is complete or that were reset, whose state is
+    /// freed once quiche collects them.
+    closing: HashSet<u64>,
+    /// Messages not yet fully handed to their stream.
+    writers: Vec<StreamWriter<Cursor<Vec<u8>>>>,
+    /// Bytes handed to the streams whose writer was dropped, and to the
+    /// control streams.
+    written: u64,
+    /// Streams carrying several messages, by stream ID.
+    mux: HashMap<u64, MuxStream>,
+    /// Time each message was dispatched.
+    sent_at: Vec<Option<Instant>>,
+    /// Path of every message dispatched during the current window of the
+    /// path schedule.
+    forced_path: Option<usize>,
+    stamps: Stamps,
+}
+
+impl Dispatcher {
+    /// Index of the message sent on `stream_id`.
+    fn message_of_stream(&self, stream_id: u64) -> Option<usize> {
+        self.streams.get(&stream_id).copied()
+    }
+
+    /// Allocates a new stream.
+    fn open_stream(&mut self) -> u64 {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 4;
+        stream_id
+    }
+
+    /// Allocates a new unidirectional stream.
+    fn open_uni_stream(&mut self) -> u64 {
+        let stream_id = self.next_uni_stream_id;
+        self.next_uni_stream_id += 4;
+        stream_id
+    }
+
+    /// Bytes handed to the streams of the connection so far, counted
+    /// against its flow control limit.
+    fn stream_bytes(&self) -> u64 {
+        let live: u64 = self.writers.iter().map(|w| w.written()).sum();
+        let mux: u64 = self.mux.values().map(|m| m.writer.written()).sum();
+
+        self.written + live + mux
+    }
+
+    fn send_stream_new_path(
+        &mut self,
+        conn: &mut quiche::Connection,
+        local_addrs: &[SocketAddr],
+        peer_addrs: &[SocketAddr],
+        active_path: &mut (SocketAddr, SocketAddr),
+        events: &mut Vec<Event>,
+        now: Instant,
+    ) {
+        if self.next >= self.order.len() {
+            return;
+        }
+        let idx_message = self.order[self.next];
+        if let Some(path) = self.forced_path {
+            self.paths[idx_message] = path;
+        }
+        if self.closed_paths.contains(&self.paths[idx_message]) {
+            if let Some(active) =
+                (0..local_addrs.len()).find(|i| (local_addrs[*i], peer_addrs[*i]) == *active_path)
+            {
+                self.paths[idx_message] = active;
+            }
+        }
+        let local_addr = local_addrs[self.paths[idx_message]];
+        let peer_addr = peer_addrs[self.paths[idx_message]];
+        let path_validated = conn.is_path_validated(local_addr, peer_addr);
+        if path_validated.is_err() && self.probe && peer_allows_path(conn) {
+            if !uses_zero_length_dcid(conn) && conn.available_dcids() <= self.dcid_reserve {
+                if self.reserve_hit != Some(idx_message) {
+                    info!(
+                        "not probing path ({}, {}) for message {}, {} DCIDs left are reserved",
+                        local_addr,
+                        peer_addr,
+                        idx_message,
+                        conn.available_dcids()
+                    );
+                    self.reserve_hit = Some(idx_message);
+                }
+                return;
+            }
+
+            // path doesn't exist, first probe it, the message waiting for
+            // another attempt if quiche refuses, e.g. for lack of a spare
+            // source connection ID
+            match conn.probe_path(local_addr, peer_addr) {
+                Ok(dcid_seq) => {
+                    self.dcid_seqs.insert((local_addr, peer_addr), dcid_seq);
+                    events.push(Event::PathProbed(local_addr, peer_addr));
+                }
+
+                Err(e) => {
+                    if self.probe_failed != Some(idx_message) {
+                        error!(
+                            "Probing path ({}, {}) for message {} failed: {:?}",
+                            local_addr, peer_addr, idx_message, e
+                        );
+                        events.push(Event::PathProbeFailed(local_addr, peer_addr));
+                        self.probe_failed = Some(idx_message);
+                    }
+                }
+            }
+        } else if path_validated.is_ok_and(|validated| validated) {
+            // wait for a slot before moving to the path
+            if let Some(admission) = &mut self.admission {
+                admission.offer(idx_message, now);
+
+                let Some((admitted, waited)) = admission.admit(now) else {
+                    return;
+                };
+
+                debug_assert_eq!(admitted, idx_message);
+                self.queue_wait[idx_message] = waited;
+            }
+
+            // path was validated for an earlier message, move back to it
+            if *active_path != (local_addr, peer_addr) {
+                if conn.migrate(local_addr, peer_addr).is_err() {
+                    events.push(Event::MigrationFailed(local_addr, peer_addr));
+                    return;
+                }
+                events.push(Event::Migrated(local_addr, peer_addr));
+                *active_path = (local_addr, peer_addr);
+            }
+
+            // path is validated, send on this new path
+            let mut payload = self.payloads[idx_message].clone();
+
+            // Numbered in the order the messages are sent on this
+            // connection.
+            if let Some(seqs) = &mut self.stamps.seqs {
+                let seq = self.next as u64;
+                seqs[idx_message] = Some(seq);
+                payload = sequence::prefix(seq, &payload);
+            }
+
+            if let Some(streams) = self.multiplex {
+                let path = self.paths[idx_message];
+                let stream_id = self.mux_stream(path, streams, events);
+                self.mux
+                    .get_mut(&stream_id)
+                    .unwrap()
+                    .queue(idx_message, &payload);
+            } else {
+                let payload = match &self.stamps.clock {
+                    Some(clock) => owd::stamp(clock.micros(now), &payload),
+                    None => payload,
+                };
+                let stream_id = match self.uni {
+                    true => self.open_uni_stream(),
+                    false => self.open_stream(),
+                };
+                self.streams.insert(stream_id, idx_message);
+                let writer = StreamWriter::new(stream_id, Cursor::new(payload));
+                self.writers.push(match self.chunk_size {
+                    Some(size) => writer.chunk_size(size),
+                    None => writer,
+                });
+                events.push(Event::StreamOpened(stream_id));
+            }
+            self.sent_at[idx_message] = Some(now);
+            self.next += 1;
+        }
+    }
+
+    /// Whether a new path may be probed now, leaving the destination
+    /// connection IDs of the reserve for migrations.
+    fn may_probe(&self, conn: &quiche::Connection) -> bool {
+        self.probe
+            && peer_allows_path(conn)
+            && (uses_zero_length_dcid(conn) || conn.available_dcids() > self.dcid_reserve)
+    }
+
+    /// Sends new stream data on the path of the window of `schedule` at
+    /// `elapsed`, and probes the path of the next window `SCHEDULE_LEAD`
+    /// before it starts so that the handover does not wait for a
+    /// validation.
+    fn follow_schedule(
+        &mut self,
+        conn: &mut quiche::Connection,
+        schedule: &PathSchedule,
+        (local_addrs, peer_addrs): (&[SocketAddr], &[SocketAddr]),
+        active_path: &mut (SocketAddr, SocketAddr),
+        events: &mut Vec<Event>,
+        elapsed: Duration,
+    ) {
+        if let Some(next) = schedule.upcoming(elapsed, SCHEDULE_LEAD) {
+            let path = schedule.windows()[next].path;
+            let (local_addr, peer_addr) = (local_addrs[path], peer_addrs[path]);
+
+            if conn.is_path_validated(local_addr, peer_addr).is_err()
+                && self.may_probe(conn)
+                && conn.probe_path(local_addr, peer_addr).is_ok()
+            {
+                events.push(Event::PathProbed(local_addr, peer_addr));
+            }
+        }
+
+        self.forced_path = schedule
+            .current(elapsed)
+            .map(|i| schedule.windows()[i].path);
+
+        // quiche sends stream data on the active path only, so the
+        // messages in flight move as well. Until the path is validated, the
+        // next message probes it.
+        let Some(path) = self.forced_path else {
+            return;
+        };
+        let (local_addr, peer_addr) = (local_addrs[path], peer_addrs[path]);
+
+        if *active_path == (local_addr, peer_addr)
+            || !conn
+                .is_path_validated(local_addr, peer_addr)
+                .is_ok_and(|v| v)
+        {
+            return;
+        }
+
+        if conn.migrate(local_addr, peer_addr).is_err() {
+            events.push(Event::MigrationFailed(local_addr, peer_addr));
+            return;
+        }
+
+        events.push(Event::Migrated(local_addr, peer_addr));
+        *active_path = (local_addr, peer_addr);
+    }
+
+    /// Stream of `path` on which the next message is multiplexed: a new one
+    /// until the path has `streams` of them, then the one with the fewest
+    /// messages awaiting their echo.
+    fn mux_stream(&mut self, path: usize, streams: usize, events: &mut Vec<Event>) -> u64 {
+        let on_path = self.mux.iter().filter(|(_, s)| s.path == path);
+
+        if on_path.clone().count() >= streams {
+            if let Some((stream_id, _)) = on_path.min_by_key(|(id, s)| (s.in_flight.len(), **id)) {
+                return *stream_id;
+            }
+        }
+
+        let stream_id = self.open_stream();
+        let writer = StreamWriter::new(stream_id, Cursor::new(Vec::new())).keep_open();
+        let writer = match self.chunk_size {
+            Some(size) => writer.chunk_size(size),
+            None => writer,
+        };
+
+        self.mux.insert(
+            stream_id,
+            MuxStream {
+                path,
+                writer,
+                queued: Vec::new(),
+                drained: true,
+                in_flight: VecDeque::new(),
+                echoed: 0,
+                decoder: FrameDecoder::new(),
+            },
+        );
+        events.push(Event::StreamOpened(stream_id));
+
+        stream_id
+    }
+
+    /// Streams carrying a single message by stage, those of the messages
+    /// not fully sent first.
+    fn stream_stages(&self) -> StreamStages {
+        let sending: HashSet<u64> = self.writers.iter().map(|w| w.stream_id()).collect();
+        let mut stages = StreamStages::default();
+
+        for stream_id in self.streams.keys() {
+            if self.closing.contains(stream_id) || self.unacked.contains(stream_id) {
+                stages.closing += 1;
+            } else if sending.contains(stream_id) {
+                stages.sending += 1;
+            } else {
+                stages.echoing += 1;
+            }
+        }
+
+        stages
+    }
+
+    /// Resumes the writes of the messages not fully sent yet.
+    fn poll_writers(&mut self, conn: &mut quiche::Connection) {
+        for mux in self.mux.values_mut() {
+            if let Err(e) = mux.poll_write(conn) {
+                error!("Writing on stream {} failed: {}", mux.writer.stream_id(), e);
+            }
+        }
+
+        let unacked = &mut self.unacked;
+        let closing = &mut self.closing;
+        let written = &mut self.written;
+
+        self.writers.retain_mut(|writer| {
+            match writer.poll_write(conn) {
+                Ok(Progress::Stopped(code)) => {
+                    info!(
+                        "Server stopped stream {} with code {}",
+                        writer.stream_id(),
+                        code
+                    );
+                    closing.insert(writer.stream_id());
+                    *written += writer.written();
+                    return false;
+                }
+
+                Ok(_) => (),
+
+                Err(e) => {
+                    error!("Writing on stream {} failed: {}", writer.stream_id(), e);
+                    closing.insert(writer.stream_id());
+                    *written += writer.written();
+                    return false;
+                }
+            }
+
+            // quiche collects the stream once its data is acknowledged.
+            if writer.is_done() && is_uni_stream(writer.stream_id()) {
+                unacked.push(writer.stream_id());
+            }
+
+            if writer.is_done() {
+                *written += writer.written();
+            }
+
+            !writer.is_done()
+        });
+    }
+}
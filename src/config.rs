@@ -0,0 +1,1784 @@
+use std::{fmt, net::SocketAddr, str::FromStr, time::Duration};
+
+use crate::shared::{
+    apps::{protos, App, Handler, DEFAULT_ALPN},
+    control::RunId,
+    encoding::Format,
+    fair_share::{DEFAULT_BYTE_BUDGET, DEFAULT_PACKET_BUDGET},
+    impair::ImpairSpec,
+    path_quality::QualityWeights,
+    path_schedule::PathSchedule,
+    payload,
+    read_budget::DEFAULT_READ_BUDGET,
+    shaper::EchoShaper,
+    transform::Transform,
+    transport::BindDev,
+    GREASE_VERSION, MAX_NUMBER_SOCKETS,
+};
+
+/// Certificate and key the server presents, generated by
+/// `certs/generate.sh`.
+pub const SERVER_CERT: &str = "certs/localhost.crt";
+pub const SERVER_KEY: &str = "certs/localhost.key";
+
+/// Certificate authority of the certificates generated by
+/// `certs/generate.sh`, which the server presents by default.
+pub const DEFAULT_CA: &str = "certs/ca.crt";
+
+/// Maximum number of streams of a file transfer.
+pub const MAX_PARALLEL_STREAMS: usize = 64;
+
+/// Default initial_max_data advertised to the peer, in bytes.
+pub const DEFAULT_MAX_DATA: u64 = 1_000_000;
+
+/// Default initial_max_stream_data advertised to the peer for each stream,
+/// in bytes.
+pub const DEFAULT_MAX_STREAM_DATA: u64 = 1_000_000;
+
+/// Streams of each direction both binaries let the peer open.
+pub const INITIAL_MAX_STREAMS: u64 = 100;
+
+/// RTT at which the warnings on the flow control limits give the rate they
+/// cap a transfer to.
+const REFERENCE_RTT: Duration = Duration::from_millis(50);
+
+/// Error returned when the command line cannot be turned into options.
+#[derive(Debug)]
+pub struct ArgsError(pub String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+/// Error returned when the options cannot be turned into a
+/// `quiche::Config`, e.g. a certificate that cannot be loaded.
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Options checked for the combinations of knobs that cannot work together.
+pub trait Validate {
+    /// Every constraint between fields the options break, as messages
+    /// naming the flags involved.
+    fn problems(&self) -> Vec<String>;
+
+    /// Combinations that work but likely not as intended, e.g. a workload
+    /// throttled by the flow control limits, as messages suggesting the
+    /// flag to change.
+    fn warnings(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Builds `ClientOptions` or `ServerOptions`, checking every constraint
+/// between their fields at once.
+///
+/// Both binaries parse their arguments into a builder, and library users
+/// start from the defaults and set the fields they need with `with`, so
+/// that invalid combinations are refused before anything runs.
+///
+/// The error of `build` lists every problem, one per line, instead of only
+/// the first one.
+#[derive(Debug, Default)]
+pub struct Builder<O> {
+    opts: O,
+}
+
+impl<O: Validate> Builder<O> {
+    /// Starts from `opts`, checked only by `build`.
+    pub fn new(opts: O) -> Self {
+        Builder { opts }
+    }
+
+    /// Sets fields of the options.
+    pub fn with<F: FnOnce(&mut O)>(mut self, set: F) -> Self {
+        set(&mut self.opts);
+        self
+    }
+
+    /// Returns the options if they break no constraint.
+    pub fn build(self) -> Result<O, ArgsError> {
+        let problems = self.opts.problems();
+
+        if !problems.is_empty() {
+            return Err(ArgsError(problems.join("\n")));
+        }
+
+        Ok(self.opts)
+    }
+}
+
+/// Server the client connects to.
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub name: String,
+
+    /// Address of the first server socket. Path `i` uses the port `i` above
+    /// this one, unless the addresses are in `peers`.
+    pub addr: SocketAddr,
+
+    /// ALPN overriding the default one for this target.
+    pub alpn: Option<String>,
+
+    /// Server name sent as SNI and checked against the certificate.
+    pub sni: Option<String>,
+
+    /// Addresses of the server sockets read from `--port-file`, path `i`
+    /// going to the `i`-th. Empty if they follow `addr`.
+    pub peers: Vec<SocketAddr>,
+}
+
+impl Target {
+    /// Server name sent as SNI and checked against the certificate of the
+    /// server: `sni` if set, else `localhost` for loopback addresses. IP
+    /// addresses are not allowed in SNI (RFC 6066), so other targets send
+    /// none and only the certificate chain is verified.
+    pub fn server_name(&self) -> Option<&str> {
+        self.sni
+            .as_deref()
+            .or_else(|| self.addr.ip().is_loopback().then_some("localhost"))
+    }
+
+    /// Address of the server socket of each path.
+    pub fn peer_addrs(&self) -> Vec<SocketAddr> {
+        if !self.peers.is_empty() {
+            return self.peers.clone();
+        }
+
+        (0..MAX_NUMBER_SOCKETS)
+            .map(|i| SocketAddr::new(self.addr.ip(), self.addr.port() + i as u16))
+            .collect()
+    }
+
+    /// The target whose server sockets are listed in a port file.
+    pub fn discovered(peers: Vec<SocketAddr>) -> Self {
+        Target {
+            addr: peers[0],
+            peers,
+            ..Default::default()
+        }
+    }
+}
+
+/// When a message counts as delivered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Confirm {
+    /// Once its full echo is received.
+    #[default]
+    Echo,
+
+    /// Once the peer acknowledged all its data, without waiting for an
+    /// echo. Each message is then sent on a unidirectional stream.
+    Acks,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target {
+            name: "default".to_string(),
+            addr: "127.0.0.1:8000".parse().unwrap(),
+            alpn: None,
+            sni: None,
+            peers: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = ArgsError;
+
+    /// Parses `<name>=<addr>[,alpn=<proto>][,sni=<server name>]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ArgsError(format!("invalid target '{}'", s));
+
+        let (name, rest) = s.split_once('=').ok_or_else(invalid)?;
+        let mut fields = rest.split(',');
+
+        let addr = fields
+            .next()
+            .and_then(|addr| addr.parse().ok())
+            .ok_or_else(invalid)?;
+
+        let mut target = Target {
+            name: name.to_string(),
+            addr,
+            alpn: None,
+            sni: None,
+            peers: Vec::new(),
+        };
+
+        for field in fields {
+            match field.split_once('=') {
+                Some(("alpn", alpn)) => target.alpn = Some(alpn.to_string()),
+
+                Some(("sni", sni)) => target.sni = Some(sni.to_string()),
+
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(target)
+    }
+}
+
+/// Options of the client binary.
+#[derive(Debug)]
+pub struct ClientOptions {
+    /// Messages to send, one per stream.
+    pub messages: Vec<Vec<u8>>,
+
+    /// Index of a path that is validated up front but only carries data
+    /// once the active path fails.
+    pub standby_path: Option<usize>,
+
+    /// Send the messages in a random order.
+    pub shuffle: bool,
+
+    /// Start the handshake with a reserved version, to check that the
+    /// servers negotiate a version they support.
+    pub grease: bool,
+
+    /// Seed of the generator used for randomized behaviors.
+    pub seed: Option<u64>,
+
+    /// ID of the run, sent to the servers and printed in the report, a
+    /// random one if not given.
+    pub run_id: Option<RunId>,
+
+    /// File to which connection events are appended as JSON lines.
+    pub events_json: Option<String>,
+
+    /// Write the connection events to the standard output as JSON lines,
+    /// everything else being printed to the standard error.
+    pub json_events: bool,
+
+    /// File to which the progress of the file transfer is appended as JSON
+    /// lines.
+    pub progress_json: Option<String>,
+
+    /// Do not print the progress of the file transfer on a terminal.
+    pub quiet: bool,
+
+    /// Interval between two samples of the path statistics written as
+    /// events, with `--events-json` or `--json-events`.
+    pub stats_interval_ms: u64,
+
+    /// File to which completed messages are appended as JSON lines.
+    pub records_json: Option<String>,
+
+    /// Format of the records of `--records-json`.
+    pub report_format: Format,
+
+    /// Format of the events of `--events-json` and `--progress-json`.
+    pub events_format: Format,
+
+    /// File to which the congestion window of every path is written as CSV.
+    pub cwnd_csv: Option<String>,
+
+    /// Interval between two samples of the congestion windows.
+    pub cwnd_interval_ms: u64,
+
+    /// Close a connection on which nothing was received for this long.
+    pub stall_timeout_ms: Option<u64>,
+
+    /// Number of times a connection interrupted by a stateless reset, an
+    /// idle timeout or a stall is re-established.
+    pub reconnect: u32,
+
+    /// Read further messages from stdin once connected, spread over this
+    /// many paths.
+    pub interactive: Option<usize>,
+
+    /// Keep the connection open once every message is delivered.
+    pub keep_open: bool,
+
+    /// Run the workload again over a single path once the run completed,
+    /// to compare with.
+    pub baseline: bool,
+
+    /// Interval of the keep-alives sent on idle validated paths.
+    pub path_keepalive_ms: Option<u64>,
+
+    /// Time after which a path other than the active and standby ones that
+    /// carried no stream data is closed.
+    pub path_idle_timeout_ms: Option<u64>,
+
+    /// Log one packet received out of this many.
+    pub log_sample_packets: u64,
+
+    /// Packets sent per path in one go, instead of quiche's send quantum.
+    pub burst_override: Option<usize>,
+
+    /// Number of destination connection IDs left unused by the probes of
+    /// new paths, for migrations.
+    pub dcid_reserve: usize,
+
+    /// Length of the connection IDs the client chooses, in bytes.
+    pub cid_len: usize,
+
+    /// Impairment of the outgoing datagrams, with the `danger` feature.
+    pub tx_impair: Option<ImpairSpec>,
+
+    /// Time without anything received after which a PING is sent, to keep
+    /// the connection open.
+    pub keepalive_ms: Option<u64>,
+
+    /// Idle timeout advertised to the server, none if `None`.
+    pub idle_timeout_ms: Option<u64>,
+
+    /// Number of PTOs without any packet sent, while stream data and
+    /// congestion window are available, after which a connection is
+    /// reported as stalled.
+    pub send_watchdog: Option<u32>,
+
+    /// Refuse to start if the options raise warnings, and close a connection
+    /// reported as stalled by the send watchdog.
+    pub strict: bool,
+
+    /// File caching the paths validated against each server and their RTT,
+    /// to probe the fastest ones first.
+    pub path_cache: Option<String>,
+
+    /// Time after which a cached path is forgotten.
+    pub path_cache_ttl_s: u64,
+
+    /// File saving the session and the messages delivered of each target,
+    /// to resume the run after a restart.
+    pub resume_state: Option<String>,
+
+    /// max_ack_delay transport parameter advertised to the servers, quiche's
+    /// default if `None`.
+    pub max_ack_delay_ms: Option<u64>,
+
+    /// Initial congestion window of each path, in packets, quiche's default
+    /// if `None`.
+    pub initial_cwnd: Option<usize>,
+
+    /// initial_max_data advertised to the peer, in bytes.
+    pub max_data: u64,
+
+    /// initial_max_stream_data advertised to the peer for each stream, in
+    /// bytes.
+    pub max_stream_data: u64,
+
+    /// Certificate chain and private key presented to servers that require
+    /// client authentication.
+    pub cert: Option<String>,
+    pub key: Option<String>,
+
+    /// Certificate authorities the certificates of the servers are verified
+    /// against.
+    pub ca: String,
+
+    /// Do not verify the certificates of the servers.
+    pub insecure: bool,
+
+    /// SOCKS5 proxy relaying the datagrams of every path.
+    pub socks5: Option<SocketAddr>,
+
+    /// Port of the socket of the first path, the others using the following
+    /// ports. The OS picks a free port for every socket if 0.
+    pub local_port: u16,
+
+    /// Print the address each client socket is bound to, one per path.
+    pub print_addrs: bool,
+
+    /// Interfaces some paths send through, their sockets being bound to the
+    /// unspecified address so that the interface picks the source address.
+    pub bind_devs: Vec<BindDev>,
+
+    /// File listing the addresses of the server sockets, written by the
+    /// server with `--port-file`.
+    pub port_file: Option<String>,
+
+    /// Servers to run the messages against, a single default one if empty.
+    pub targets: Vec<Target>,
+
+    /// Size to which every message is padded, with a length prefix that the
+    /// server strips before echoing.
+    pub pad_to: Option<usize>,
+
+    /// Maximum number of bytes of a message written to its stream at once,
+    /// the message being sent as several writes.
+    pub chunk_size: Option<usize>,
+
+    /// Maximum number of messages in flight at once.
+    pub max_concurrent_streams: Option<usize>,
+
+    /// Maximum number of streams read per wakeup.
+    pub read_budget: usize,
+
+    /// Send the messages over this many paths, as length-prefixed frames on
+    /// `multiplex_streams` streams per path instead of a stream per message.
+    pub multiplex: Option<usize>,
+
+    /// Number of streams per path carrying the multiplexed messages.
+    pub multiplex_streams: usize,
+
+    /// Number of streams opened with a fin and no data after the messages.
+    pub empty_streams: usize,
+
+    /// Size of the bulk transfer saturating the initial path, enables the
+    /// bufferbloat measurement.
+    pub bufferbloat: Option<u64>,
+
+    /// Interval between two latency probes of the bufferbloat measurement.
+    pub probe_interval_ms: u64,
+
+    /// File sent to a server reassembling it, instead of messages.
+    pub file: Option<String>,
+
+    /// Number of streams over which the file is sent after the single stream
+    /// baseline.
+    pub parallel_streams: Option<usize>,
+
+    /// Number of paths over which chunks of the file are sent round-robin,
+    /// after a run on each path alone.
+    pub stripe: Option<usize>,
+
+    /// Path new stream data goes on during each window of time, whatever
+    /// the path of the messages.
+    pub path_schedule: Option<PathSchedule>,
+
+    /// Close the connection instead of falling back to the initial path
+    /// when the peer does not allow the paths of the workload.
+    pub require_multipath: bool,
+
+    /// Weights of the RTT, loss rate and congestion window in the quality
+    /// score of each path reported at close.
+    pub quality_weights: QualityWeights,
+
+    /// Timestamp each message and measure the one-way delays of each path
+    /// from the timestamps echoed by the server.
+    pub owd: bool,
+
+    /// Prefix each message with its sequence number, and check that the
+    /// echoes carry it back and complete in sequence order.
+    pub sequence: bool,
+
+    /// Report how long the connection and its streams are blocked by the
+    /// flow control limits of the server, and how promptly they resume.
+    pub flow_blocking: bool,
+
+    /// Number of connections established one after the other to measure
+    /// the handshake time, instead of sending messages.
+    pub handshakes: Option<usize>,
+
+    /// Time after which a handshake that did not complete is failed.
+    pub handshake_timeout_ms: u64,
+
+    /// Probability that a datagram of the handshakes is dropped, in either
+    /// direction.
+    pub loss: f64,
+
+    /// Number of buckets of the histogram of completion times printed at
+    /// close, none if `None`.
+    pub hist_buckets: Option<usize>,
+
+    /// When a message counts as delivered.
+    pub confirm: Confirm,
+
+    /// Transformation the server applies to the messages, against which
+    /// their echoes are checked.
+    pub expect_transform: Option<Transform>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            messages: Vec::new(),
+            standby_path: None,
+            shuffle: false,
+            grease: false,
+            seed: None,
+            run_id: None,
+            events_json: None,
+            json_events: false,
+            progress_json: None,
+            quiet: false,
+            stats_interval_ms: 1000,
+            records_json: None,
+            report_format: Format::Json,
+            events_format: Format::Json,
+            cwnd_csv: None,
+            cwnd_interval_ms: 100,
+            stall_timeout_ms: None,
+            reconnect: 0,
+            max_ack_delay_ms: None,
+            initial_cwnd: None,
+            max_data: DEFAULT_MAX_DATA,
+            max_stream_data: DEFAULT_MAX_STREAM_DATA,
+            path_keepalive_ms: None,
+            path_idle_timeout_ms: None,
+            log_sample_packets: 1,
+            burst_override: None,
+            dcid_reserve: 0,
+            cid_len: quiche::MAX_CONN_ID_LEN,
+            tx_impair: None,
+            keepalive_ms: None,
+            idle_timeout_ms: None,
+            send_watchdog: None,
+            strict: false,
+            path_cache: None,
+            path_cache_ttl_s: 3600,
+            resume_state: None,
+            interactive: None,
+            keep_open: false,
+            baseline: false,
+            socks5: None,
+            cert: None,
+            key: None,
+            ca: DEFAULT_CA.to_string(),
+            insecure: false,
+            targets: Vec::new(),
+            local_port: 0,
+            print_addrs: false,
+            bind_devs: Vec::new(),
+            port_file: None,
+            pad_to: None,
+            chunk_size: None,
+            max_concurrent_streams: None,
+            read_budget: DEFAULT_READ_BUDGET,
+            multiplex: None,
+            multiplex_streams: 1,
+            empty_streams: 0,
+            bufferbloat: None,
+            probe_interval_ms: 100,
+            file: None,
+            parallel_streams: None,
+            stripe: None,
+            path_schedule: None,
+            require_multipath: false,
+            quality_weights: QualityWeights::default(),
+            owd: false,
+            sequence: false,
+            flow_blocking: false,
+            handshakes: None,
+            handshake_timeout_ms: 10000,
+            loss: 0.0,
+            hist_buckets: None,
+            confirm: Confirm::Echo,
+            expect_transform: None,
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Parses the client arguments (without the program name).
+    ///
+    /// Flags start with `--`, everything else is a message. `--` ends the
+    /// flags so that messages starting with dashes can still be sent.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgsError> {
+        let mut opts = ClientOptions::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    opts.messages.extend(args.by_ref().map(String::into_bytes));
+                }
+
+                "--standby-path" => {
+                    opts.standby_path = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--events-json" => {
+                    opts.events_json = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--json-events" => opts.json_events = true,
+
+                "--progress-json" => {
+                    opts.progress_json = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--quiet" => opts.quiet = true,
+
+                "--stats-interval-ms" => {
+                    let interval = parse_value(&arg, args.next())?;
+
+                    if interval == 0 {
+                        return Err(ArgsError(
+                            "--stats-interval-ms must be positive".to_string(),
+                        ));
+                    }
+
+                    opts.stats_interval_ms = interval;
+                }
+
+                "--records-json" => {
+                    opts.records_json = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--report-format" => {
+                    opts.report_format = parse_format(&arg, args.next(), "json", "cbor")?;
+                }
+
+                "--events-format" => {
+                    opts.events_format = parse_format(&arg, args.next(), "ndjson", "cbor-seq")?;
+                }
+
+                "--cwnd-csv" => {
+                    opts.cwnd_csv = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--cwnd-interval-ms" => {
+                    opts.cwnd_interval_ms = parse_value(&arg, args.next())?;
+                }
+
+                "--stall-timeout-ms" => {
+                    opts.stall_timeout_ms = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--reconnect" => {
+                    opts.reconnect = parse_value(&arg, args.next())?;
+                }
+
+                "--socks5" => {
+                    opts.socks5 = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--interactive" => {
+                    opts.interactive = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--keep-open" => opts.keep_open = true,
+
+                "--baseline" => opts.baseline = true,
+
+                "--path-keepalive" => {
+                    opts.path_keepalive_ms = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--path-idle-timeout" => {
+                    let timeout = parse_value(&arg, args.next())?;
+
+                    if timeout == 0 {
+                        return Err(ArgsError(
+                            "--path-idle-timeout must be positive".to_string(),
+                        ));
+                    }
+
+                    opts.path_idle_timeout_ms = Some(timeout);
+                }
+
+                "--log-sample-packets" => {
+                    opts.log_sample_packets = parse_log_sample(&arg, args.next())?;
+                }
+
+                "--burst-override" => {
+                    opts.burst_override = Some(parse_burst_override(&arg, args.next())?);
+                }
+
+                #[cfg(feature = "danger")]
+                "--tx-impair" => opts.tx_impair = Some(parse_impair(&arg, args.next())?),
+
+                "--dcid-reserve" => {
+                    opts.dcid_reserve = parse_value(&arg, args.next())?;
+                }
+
+                "--cid-len" => opts.cid_len = parse_cid_len(&arg, args.next())?,
+
+                "--keepalive-ms" => {
+                    let interval = parse_value(&arg, args.next())?;
+
+                    if interval == 0 {
+                        return Err(ArgsError("--keepalive-ms must be positive".to_string()));
+                    }
+
+                    opts.keepalive_ms = Some(interval);
+                }
+
+                "--idle-timeout-ms" => {
+                    opts.idle_timeout_ms = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--send-watchdog" => {
+                    let ptos = parse_value(&arg, args.next())?;
+
+                    if ptos == 0 {
+                        return Err(ArgsError("--send-watchdog must be positive".to_string()));
+                    }
+
+                    opts.send_watchdog = Some(ptos);
+                }
+
+                "--strict" => opts.strict = true,
+
+                "--path-cache" => {
+                    opts.path_cache = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--resume-state" => {
+                    opts.resume_state = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--path-cache-ttl" => {
+                    let ttl = parse_value(&arg, args.next())?;
+
+                    if ttl == 0 {
+                        return Err(ArgsError("--path-cache-ttl must be positive".to_string()));
+                    }
+
+                    opts.path_cache_ttl_s = ttl;
+                }
+
+                "--max-ack-delay-ms" => {
+                    let delay = parse_value(&arg, args.next())?;
+
+                    // Values of 2^14 or greater are invalid (RFC 9000).
+                    if delay >= 1 << 14 {
+                        return Err(ArgsError(format!(
+                            "--max-ack-delay-ms must be below {}",
+                            1 << 14
+                        )));
+                    }
+
+                    opts.max_ack_delay_ms = Some(delay);
+                }
+
+                "--initial-cwnd" => {
+                    opts.initial_cwnd = Some(parse_initial_cwnd(&arg, args.next())?);
+                }
+
+                "--max-data" => opts.max_data = parse_window(&arg, args.next())?,
+
+                "--max-stream-data" => opts.max_stream_data = parse_window(&arg, args.next())?,
+
+                "--cert" => {
+                    opts.cert = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--key" => {
+                    opts.key = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--ca" => opts.ca = parse_value(&arg, args.next())?,
+
+                "--insecure" => opts.insecure = true,
+
+                "--target" => {
+                    opts.targets.push(parse_value(&arg, args.next())?);
+                }
+
+                "--local-port" => opts.local_port = parse_value(&arg, args.next())?,
+
+                "--print-addrs" => opts.print_addrs = true,
+
+                "--bind-dev" => opts.bind_devs.push(parse_bind_dev(&arg, args.next())?),
+
+                "--port-file" => opts.port_file = Some(parse_value(&arg, args.next())?),
+
+                "--message-hex" => {
+                    let message = parse_message(&arg, args.next(), payload::decode_hex)?;
+                    opts.messages.push(message);
+                }
+
+                "--message-b64" => {
+                    let message = parse_message(&arg, args.next(), payload::decode_base64)?;
+                    opts.messages.push(message);
+                }
+
+                "--pad-to" => {
+                    opts.pad_to = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--chunk-size" => {
+                    let size = parse_value(&arg, args.next())?;
+
+                    if size == 0 {
+                        return Err(ArgsError("--chunk-size must be positive".to_string()));
+                    }
+
+                    opts.chunk_size = Some(size);
+                }
+
+                "--read-budget" => opts.read_budget = parse_read_budget(&arg, args.next())?,
+
+                "--max-concurrent-streams" => {
+                    let max = parse_value(&arg, args.next())?;
+
+                    if max == 0 {
+                        return Err(ArgsError(
+                            "--max-concurrent-streams must be positive".to_string(),
+                        ));
+                    }
+
+                    opts.max_concurrent_streams = Some(max);
+                }
+
+                "--multiplex" => {
+                    opts.multiplex = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--multiplex-streams" => {
+                    let streams = parse_value(&arg, args.next())?;
+
+                    if streams == 0 {
+                        return Err(ArgsError(
+                            "--multiplex-streams must be positive".to_string(),
+                        ));
+                    }
+
+                    opts.multiplex_streams = streams;
+                }
+
+                "--empty-streams" => {
+                    opts.empty_streams = parse_value(&arg, args.next())?;
+                }
+
+                "--bufferbloat" => {
+                    opts.bufferbloat = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--probe-interval-ms" => {
+                    opts.probe_interval_ms = parse_value(&arg, args.next())?;
+                }
+
+                "--file" => {
+                    opts.file = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--parallel-streams" => {
+                    let streams = parse_value(&arg, args.next())?;
+
+                    // Leaves room in the stream limit of the server.
+                    if !(1..=MAX_PARALLEL_STREAMS).contains(&streams) {
+                        return Err(ArgsError(format!(
+                            "--parallel-streams must be between 1 and {}",
+                            MAX_PARALLEL_STREAMS
+                        )));
+                    }
+
+                    opts.parallel_streams = Some(streams);
+                }
+
+                "--require-multipath" => opts.require_multipath = true,
+
+                "--quality-weights" => {
+                    opts.quality_weights = parse_weights(&arg, args.next())?;
+                }
+
+                "--path-schedule" => {
+                    opts.path_schedule = Some(parse_schedule(&arg, args.next())?);
+                }
+
+                "--stripe" => {
+                    let paths = parse_value(&arg, args.next())?;
+
+                    if !(2..=MAX_NUMBER_SOCKETS).contains(&paths) {
+                        return Err(ArgsError(format!(
+                            "--stripe must be between 2 and {}",
+                            MAX_NUMBER_SOCKETS
+                        )));
+                    }
+
+                    opts.stripe = Some(paths);
+                }
+
+                "--shuffle" => opts.shuffle = true,
+
+                "--grease" => opts.grease = true,
+
+                "--seed" => {
+                    opts.seed = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--run-id" => opts.run_id = Some(parse_run_id(&arg, args.next())?),
+
+                "--handshakes" => {
+                    let trials = parse_value(&arg, args.next())?;
+
+                    if trials == 0 {
+                        return Err(ArgsError("--handshakes must be positive".to_string()));
+                    }
+
+                    opts.handshakes = Some(trials);
+                }
+
+                "--owd" => opts.owd = true,
+
+                "--sequence" => opts.sequence = true,
+
+                "--flow-blocking" => opts.flow_blocking = true,
+
+                "--confirm" => {
+                    opts.confirm = match args.next().as_deref() {
+                        Some("echo") => Confirm::Echo,
+                        Some("acks") => Confirm::Acks,
+                        Some(v) => {
+                            return Err(ArgsError(format!("invalid value '{}' for --confirm", v)))
+                        }
+                        None => return Err(ArgsError("--confirm expects a value".to_string())),
+                    };
+                }
+
+                "--expect-transform" => {
+                    opts.expect_transform = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--hist-buckets" => {
+                    let buckets = parse_value(&arg, args.next())?;
+
+                    if buckets == 0 {
+                        return Err(ArgsError("--hist-buckets must be positive".to_string()));
+                    }
+
+                    opts.hist_buckets = Some(buckets);
+                }
+
+                "--handshake-timeout-ms" => {
+                    opts.handshake_timeout_ms = parse_value(&arg, args.next())?;
+                }
+
+                "--loss" => {
+                    let loss = parse_value(&arg, args.next())?;
+
+                    if !(0.0..1.0).contains(&loss) {
+                        return Err(ArgsError(
+                            "--loss must be at least 0 and below 1".to_string(),
+                        ));
+                    }
+
+                    opts.loss = loss;
+                }
+
+                _ if arg.starts_with("--") => {
+                    return Err(ArgsError(format!("unknown option {}", arg)));
+                }
+
+                _ => opts.messages.push(arg.into_bytes()),
+            }
+        }
+
+        Builder::new(opts).build()
+    }
+
+    /// Creates the configuration of the connections to `target`.
+    ///
+    /// `customize` runs last, once every flag is applied, so it can set
+    /// the quiche options no flag covers and override those the flags set.
+    /// The binary passes one that changes nothing.
+    pub fn quiche_config<F: FnOnce(&mut quiche::Config)>(
+        &self,
+        target: &Target,
+        log_keys: bool,
+        customize: F,
+    ) -> Result<quiche::Config, ConfigError> {
+        let version = match self.grease {
+            true => GREASE_VERSION,
+            false => quiche::PROTOCOL_VERSION,
+        };
+        let mut config = quiche::Config::new(version)
+            .map_err(|e| ConfigError(format!("cannot create the configuration: {:?}", e)))?;
+
+        let alpn = target.alpn.as_deref().unwrap_or(DEFAULT_ALPN);
+        config
+            .set_application_protos(&[alpn.as_bytes()])
+            .map_err(|e| ConfigError(format!("invalid application protocol {}: {:?}", alpn, e)))?;
+
+        if self.insecure {
+            config.verify_peer(false);
+        } else {
+            config.verify_peer(true);
+
+            config
+                .load_verify_locations_from_file(&self.ca)
+                .map_err(|e| {
+                    ConfigError(format!(
+                        "cannot load certificate authorities {}: {:?}",
+                        self.ca, e
+                    ))
+                })?;
+        }
+
+        set_transport_params(&mut config, self.max_data, self.max_stream_data);
+
+        if let Some(packets) = self.initial_cwnd {
+            config.set_initial_congestion_window_packets(packets);
+        }
+
+        if let Some(delay) = self.max_ack_delay_ms {
+            config.set_max_ack_delay(delay);
+        }
+
+        if let Some(timeout) = self.idle_timeout_ms {
+            config.set_max_idle_timeout(timeout);
+        }
+
+        if let (Some(cert), Some(key)) = (&self.cert, &self.key) {
+            load_identity(&mut config, cert, key)?;
+        }
+
+        if log_keys {
+            config.log_keys();
+        }
+
+        customize(&mut config);
+
+        Ok(config)
+    }
+}
+
+impl Validate for ClientOptions {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.interactive.is_some() && self.targets.len() > 1 {
+            problems.push("--interactive runs against a single target".to_string());
+        }
+
+        if self.resume_state.is_some() && (self.interactive.is_some() || self.handshakes.is_some())
+        {
+            problems.push(
+                "--resume-state cannot be combined with --interactive or --handshakes".to_string(),
+            );
+        }
+
+        if self.baseline
+            && (self.interactive.is_some()
+                || self.handshakes.is_some()
+                || self.resume_state.is_some()
+                || self.keep_open)
+        {
+            problems.push(
+                "--baseline cannot be combined with --interactive, --handshakes, \
+                 --resume-state or --keep-open"
+                    .to_string(),
+            );
+        }
+
+        if self.multiplex_streams != 1 && self.multiplex.is_none() {
+            problems.push("--multiplex-streams needs --multiplex".to_string());
+        }
+
+        if let (Some(keepalive), Some(idle)) = (self.keepalive_ms, self.idle_timeout_ms) {
+            if keepalive >= idle {
+                problems.push("--keepalive-ms must be below --idle-timeout-ms".to_string());
+            }
+        }
+
+        if self.loss > 0.0 && self.handshakes.is_none() {
+            problems.push("--loss needs --handshakes".to_string());
+        }
+
+        if self.port_file.is_some() && !self.targets.is_empty() {
+            problems.push("--port-file and --target cannot be combined".to_string());
+        }
+
+        if self.handshakes.is_some() && self.targets.len() > 1 {
+            problems.push("--handshakes runs against a single target".to_string());
+        }
+
+        if self.parallel_streams.is_some() && self.file.is_none() {
+            problems.push("--parallel-streams needs --file".to_string());
+        }
+
+        if self.progress_json.is_some() && self.file.is_none() {
+            problems.push("--progress-json needs --file".to_string());
+        }
+
+        if self.stripe.is_some() && self.file.is_none() {
+            problems.push("--stripe needs --file".to_string());
+        }
+
+        if self.stripe.is_some() && self.parallel_streams.is_some() {
+            problems.push("--stripe and --parallel-streams cannot be combined".to_string());
+        }
+
+        if self.cert.is_some() != self.key.is_some() {
+            problems.push("--cert and --key must be given together".to_string());
+        }
+
+        if self.path_schedule.is_some()
+            && (self.multiplex.is_some() || self.stripe.is_some() || self.standby_path.is_some())
+        {
+            problems.push(
+                "--path-schedule cannot be combined with --multiplex, --stripe or --standby-path"
+                    .to_string(),
+            );
+        }
+
+        if self.owd
+            && (self.multiplex.is_some()
+                || self.pad_to.is_some()
+                || self.expect_transform.is_some()
+                || self.confirm == Confirm::Acks)
+        {
+            problems.push(
+                "--owd cannot be combined with --multiplex, --pad-to, --expect-transform or \
+                 --confirm acks"
+                    .to_string(),
+            );
+        }
+
+        if self.sequence
+            && (self.pad_to.is_some()
+                || self.expect_transform.is_some()
+                || self.confirm == Confirm::Acks
+                || self.file.is_some())
+        {
+            problems.push(
+                "--sequence cannot be combined with --pad-to, --expect-transform, \
+                 --confirm acks or --file"
+                    .to_string(),
+            );
+        }
+
+        if self.flow_blocking
+            && (self.file.is_some() || self.bufferbloat.is_some() || self.handshakes.is_some())
+        {
+            problems.push(
+                "--flow-blocking cannot be combined with --file, --bufferbloat or --handshakes"
+                    .to_string(),
+            );
+        }
+
+        if self.path_idle_timeout_ms.is_some()
+            && (self.path_schedule.is_some()
+                || self.stripe.is_some()
+                || self.reconnect > 0
+                || self.baseline)
+        {
+            problems.push(
+                "--path-idle-timeout cannot be combined with --path-schedule, --stripe, --reconnect \
+                 or --baseline"
+                    .to_string(),
+            );
+        }
+
+        if !self.bind_devs.is_empty() && self.socks5.is_some() {
+            problems.push("--bind-dev cannot be combined with --socks5".to_string());
+        }
+
+        for (i, dev) in self.bind_devs.iter().enumerate() {
+            if self.bind_devs[..i].iter().any(|d| d.path == dev.path) {
+                problems.push(format!("--bind-dev {} is given more than once", dev.path));
+            }
+        }
+
+        if self.strict {
+            for warning in self.warnings() {
+                problems.push(format!("{} (refused with --strict)", warning));
+            }
+        }
+
+        problems
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        // Padded messages take the padded size on the wire.
+        let mut sizes: Vec<u64> = self
+            .messages
+            .iter()
+            .map(|m| self.pad_to.map_or(m.len(), |size| size.max(m.len())) as u64)
+            .collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+
+        let at_once = self.max_concurrent_streams.unwrap_or(sizes.len());
+
+        // Being held back by the windows is the point of --flow-blocking.
+        let windows = !self.flow_blocking;
+
+        let largest = sizes.first().copied().max(self.bufferbloat).unwrap_or(0);
+        if windows && largest > self.max_stream_data {
+            warnings.push(format!(
+                "a stream carries up to {} bytes but initial_max_stream_data ({} bytes) caps \
+                 each stream to ~{:.0} Mbit/s at {} ms RTT; consider --max-stream-data on the \
+                 client and the server",
+                largest,
+                self.max_stream_data,
+                window_rate(self.max_stream_data),
+                REFERENCE_RTT.as_millis()
+            ));
+        }
+
+        let in_flight = sizes.iter().take(at_once).sum::<u64>() + self.bufferbloat.unwrap_or(0);
+        if windows && in_flight > self.max_data {
+            warnings.push(format!(
+                "up to {} bytes are in flight at once but initial_max_data ({} bytes) caps the \
+                 connection to ~{:.0} Mbit/s at {} ms RTT; consider --max-data on the client \
+                 and the server",
+                in_flight,
+                self.max_data,
+                window_rate(self.max_data),
+                REFERENCE_RTT.as_millis()
+            ));
+        }
+
+        if windows && self.max_data < self.max_stream_data {
+            warnings.push(format!(
+                "initial_max_data ({} bytes) is below initial_max_stream_data ({} bytes), no \
+                 stream can fill its window; consider a larger --max-data",
+                self.max_data, self.max_stream_data
+            ));
+        }
+
+        // Multiplexed messages share a few streams.
+        if self.multiplex.is_none() && at_once as u64 > INITIAL_MAX_STREAMS {
+            warnings.push(format!(
+                "{} messages are sent at once but the server lets the client open {} streams, \
+                 the others wait for it to allow more; consider --max-concurrent-streams {}",
+                at_once, INITIAL_MAX_STREAMS, INITIAL_MAX_STREAMS
+            ));
+        }
+
+        warnings
+    }
+}
+
+/// Rate in megabits per second at which a flow control window of `bytes`
+/// lets data through at `REFERENCE_RTT`.
+fn window_rate(bytes: u64) -> f64 {
+    bytes as f64 * 8.0 / REFERENCE_RTT.as_secs_f64() / 1e6
+}
+
+/// Options of the server binary.
+#[derive(Debug)]
+pub struct ServerOptions {
+    /// Address of the first socket, the others use the following ports. The
+    /// OS picks a free port for every socket if its port is 0.
+    pub listen: SocketAddr,
+
+    /// File to which the addresses of the sockets are written once bound.
+    pub port_file: Option<String>,
+
+    /// Messages are padded by the client, strip the padding before printing
+    /// them and pad the echo to the same size.
+    pub padded: bool,
+
+    /// Delay before the echo of the data received on a stream is sent.
+    pub echo_delay_ms: Option<u64>,
+
+    /// Rate at which the echo of each stream is sent, in kilobits per
+    /// second.
+    pub echo_rate_kbps: Option<u64>,
+
+    /// Probe every known client address from every server socket.
+    pub probe_client_paths: bool,
+
+    /// Messages are sent as length-prefixed frames, several per stream, and
+    /// echoed frame by frame.
+    pub multiplex: bool,
+
+    /// Probe for a larger MTU on the active path (DPLPMTUD), up to this
+    /// many bytes.
+    pub pmtud: Option<usize>,
+
+    /// Reassemble the streams as file transfers instead of echoing them.
+    pub file_transfer: bool,
+
+    /// Handler of each application protocol offered, the default protocol
+    /// only if empty.
+    pub apps: Vec<App>,
+
+    /// Bytes of the files reassembled in memory beyond which the others are
+    /// written to disk.
+    pub spill_over: u64,
+
+    /// Maximum number of streams read per wakeup.
+    pub read_budget: usize,
+
+    /// Maximum number of packets each connection processes per wakeup.
+    pub packet_budget: usize,
+
+    /// Maximum number of stream bytes each connection serves per wakeup.
+    pub byte_budget: usize,
+
+    /// Read the messages without echoing them.
+    pub no_echo: bool,
+
+    /// Answer each message with its transformation instead of an echo.
+    pub transform: Option<Transform>,
+
+    /// Start the echo of each stream with the time it was received, for
+    /// clients run with `--owd`.
+    pub timestamps: bool,
+
+    /// Impairment of the outgoing datagrams, with the `danger` feature.
+    pub tx_impair: Option<ImpairSpec>,
+
+    /// Log one packet received or sent out of this many.
+    pub log_sample_packets: u64,
+
+    /// Packets sent per path in one go, instead of quiche's send quantum.
+    pub burst_override: Option<usize>,
+
+    /// Initial congestion window of each path, in packets, quiche's default
+    /// if `None`.
+    pub initial_cwnd: Option<usize>,
+
+    /// initial_max_data advertised to the peer, in bytes.
+    pub max_data: u64,
+
+    /// initial_max_stream_data advertised to the peer for each stream, in
+    /// bytes.
+    pub max_stream_data: u64,
+
+    /// Length of the connection IDs the server chooses, in bytes.
+    pub cid_len: usize,
+
+    /// Measure the time each wakeup of the event loop spends in each phase.
+    pub loop_timing: bool,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            port_file: None,
+            padded: false,
+            echo_delay_ms: None,
+            echo_rate_kbps: None,
+            probe_client_paths: false,
+            multiplex: false,
+            pmtud: None,
+            file_transfer: false,
+            apps: Vec::new(),
+            spill_over: 64 << 20,
+            read_budget: DEFAULT_READ_BUDGET,
+            packet_budget: DEFAULT_PACKET_BUDGET,
+            byte_budget: DEFAULT_BYTE_BUDGET,
+            no_echo: false,
+            transform: None,
+            timestamps: false,
+            tx_impair: None,
+            log_sample_packets: 1,
+            burst_override: None,
+            initial_cwnd: None,
+            max_data: DEFAULT_MAX_DATA,
+            max_stream_data: DEFAULT_MAX_STREAM_DATA,
+            cid_len: quiche::MAX_CONN_ID_LEN,
+            loop_timing: false,
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Parses the server arguments (without the program name).
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgsError> {
+        let mut opts = ServerOptions::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--listen" => {
+                    opts.listen = parse_value(&arg, args.next())?;
+                }
+
+                "--port-file" => opts.port_file = Some(parse_value(&arg, args.next())?),
+
+                "--padded" => opts.padded = true,
+
+                "--echo-delay" => {
+                    opts.echo_delay_ms = Some(parse_value(&arg, args.next())?);
+                }
+
+                "--echo-rate" => {
+                    let rate = parse_value(&arg, args.next())?;
+
+                    if rate == 0 {
+                        return Err(ArgsError("--echo-rate must be positive".to_string()));
+                    }
+
+                    opts.echo_rate_kbps = Some(rate);
+                }
+
+                "--probe-client-paths" => opts.probe_client_paths = true,
+
+                "--loop-timing" => opts.loop_timing = true,
+
+                "--read-budget" => opts.read_budget = parse_read_budget(&arg, args.next())?,
+
+                "--packet-budget" | "--byte-budget" => {
+                    let budget = parse_value(&arg, args.next())?;
+
+                    if budget == 0 {
+                        return Err(ArgsError(format!("{} must be positive", arg)));
+                    }
+
+                    if arg == "--packet-budget" {
+                        opts.packet_budget = budget;
+                    } else {
+                        opts.byte_budget = budget;
+                    }
+                }
+
+                "--multiplex" => opts.multiplex = true,
+
+                "--file-transfer" => opts.file_transfer = true,
+
+                "--app" => opts.apps.push(parse_app(&arg, args.next())?),
+
+                "--no-echo" => opts.no_echo = true,
+
+                "--transform" => opts.transform = Some(parse_value(&arg, args.next())?),
+
+                "--timestamps" => opts.timestamps = true,
+
+                #[cfg(feature = "danger")]
+                "--tx-impair" => opts.tx_impair = Some(parse_impair(&arg, args.next())?),
+
+                "--spill-over" => {
+                    opts.spill_over = parse_value(&arg, args.next())?;
+                }
+
+                "--log-sample-packets" => {
+                    opts.log_sample_packets = parse_log_sample(&arg, args.next())?;
+                }
+
+                "--burst-override" => {
+                    opts.burst_override = Some(parse_burst_override(&arg, args.next())?);
+                }
+
+                "--initial-cwnd" => {
+                    opts.initial_cwnd = Some(parse_initial_cwnd(&arg, args.next())?);
+                }
+
+                "--max-data" => opts.max_data = parse_window(&arg, args.next())?,
+
+                "--max-stream-data" => opts.max_stream_data = parse_window(&arg, args.next())?,
+
+                "--cid-len" => opts.cid_len = parse_cid_len(&arg, args.next())?,
+
+                "--pmtud" => {
+                    let max = parse_value(&arg, args.next())?;
+
+                    // quiche never sends less than 1200 bytes.
+                    if max < 1200 {
+                        return Err(ArgsError("--pmtud must be at least 1200".to_string()));
+                    }
+
+                    opts.pmtud = Some(max);
+                }
+
+                _ => return Err(ArgsError(format!("unknown option {}", arg))),
+            }
+        }
+
+        Builder::new(opts).build()
+    }
+
+    /// Application protocols offered and their handler: those of `--app`,
+    /// or the default protocol handled as `--file-transfer` says.
+    pub fn apps(&self) -> Vec<App> {
+        if !self.apps.is_empty() {
+            return self.apps.clone();
+        }
+
+        let handler = match self.file_transfer {
+            true => Handler::File,
+            false => Handler::Echo,
+        };
+
+        vec![App::new(DEFAULT_ALPN, handler)]
+    }
+
+    /// Shaper of the echoes, if they are delayed or rate limited.
+    pub fn echo_shaper(&self) -> Option<EchoShaper> {
+        if self.echo_delay_ms.is_none() && self.echo_rate_kbps.is_none() {
+            return None;
+        }
+
+        let shaper = EchoShaper::new(Duration::from_millis(self.echo_delay_ms.unwrap_or(0)));
+
+        Some(match self.echo_rate_kbps {
+            Some(kbps) => shaper.rate(kbps * 1000 / 8),
+            None => shaper,
+        })
+    }
+
+    /// Creates the configuration of the connections the server accepts.
+    ///
+    /// As for the client, `customize` runs once every flag is applied.
+    pub fn quiche_config<F: FnOnce(&mut quiche::Config)>(
+        &self,
+        log_keys: bool,
+        customize: F,
+    ) -> Result<quiche::Config, ConfigError> {
+        let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION)
+            .map_err(|e| ConfigError(format!("cannot create the configuration: {:?}", e)))?;
+
+        config
+            .set_application_protos(&protos(&self.apps()))
+            .map_err(|e| ConfigError(format!("invalid application protocols: {:?}", e)))?;
+
+        load_identity(&mut config, SERVER_CERT, SERVER_KEY)?;
+
+        set_transport_params(&mut config, self.max_data, self.max_stream_data);
+
+        if let Some(packets) = self.initial_cwnd {
+            config.set_initial_congestion_window_packets(packets);
+        }
+
+        if let Some(max) = self.pmtud {
+            config.discover_pmtu(true);
+            config.set_max_send_udp_payload_size(max);
+        }
+
+        if log_keys {
+            config.log_keys();
+        }
+
+        customize(&mut config);
+
+        Ok(config)
+    }
+}
+
+/// Sets the flow control limits and the streams both binaries let the peer
+/// open.
+fn set_transport_params(config: &mut quiche::Config, max_data: u64, max_stream_data: u64) {
+    config.set_initial_max_streams_bidi(INITIAL_MAX_STREAMS);
+    config.set_initial_max_streams_uni(INITIAL_MAX_STREAMS);
+
+    config.set_initial_max_data(max_data);
+    config.set_initial_max_stream_data_bidi_local(max_stream_data);
+    config.set_initial_max_stream_data_bidi_remote(max_stream_data);
+    config.set_initial_max_stream_data_uni(max_stream_data);
+
+    config.set_active_connection_id_limit(20);
+}
+
+/// Loads the certificate chain and the private key presented to the peer.
+fn load_identity(config: &mut quiche::Config, cert: &str, key: &str) -> Result<(), ConfigError> {
+    config
+        .load_cert_chain_from_pem_file(cert)
+        .map_err(|e| ConfigError(format!("cannot load certificate {}: {:?}", cert, e)))?;
+
+    config
+        .load_priv_key_from_pem_file(key)
+        .map_err(|e| ConfigError(format!("cannot load private key {}: {:?}", key, e)))
+}
+
+impl Validate for ServerOptions {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.padded && self.multiplex {
+            problems.push("--padded and --multiplex cannot be combined".to_string());
+        }
+
+        if self.file_transfer && (self.padded || self.multiplex) {
+            problems.push(
+                "--file-transfer cannot be combined with --padded or --multiplex".to_string(),
+            );
+        }
+
+        if self.no_echo && self.file_transfer {
+            problems.push("--no-echo and --file-transfer cannot be combined".to_string());
+        }
+
+        if self.transform.is_some() && (self.no_echo || self.file_transfer) {
+            problems.push(
+                "--transform cannot be combined with --no-echo or --file-transfer".to_string(),
+            );
+        }
+
+        if self.timestamps
+            && (self.padded || self.multiplex || self.transform.is_some() || self.no_echo)
+        {
+            problems.push(
+                "--timestamps cannot be combined with --padded, --multiplex, --transform or \
+                 --no-echo"
+                    .to_string(),
+            );
+        }
+
+        if self.file_transfer && !self.apps.is_empty() {
+            problems.push(
+                "--file-transfer cannot be combined with --app, use --app <alpn>=file".to_string(),
+            );
+        }
+
+        for (i, app) in self.apps.iter().enumerate() {
+            if self.apps[..i].iter().any(|a| a.alpn == app.alpn) {
+                problems.push(format!("--app {} is given more than once", app.alpn));
+            }
+        }
+
+        problems
+    }
+}
+
+fn parse_window(flag: &str, value: Option<String>) -> Result<u64, ArgsError> {
+    let bytes = parse_value(flag, value)?;
+
+    if bytes == 0 {
+        return Err(ArgsError(format!("{} must be positive", flag)));
+    }
+
+    Ok(bytes)
+}
+
+fn parse_read_budget(flag: &str, value: Option<String>) -> Result<usize, ArgsError> {
+    let budget = parse_value(flag, value)?;
+
+    if budget == 0 {
+        return Err(ArgsError("--read-budget must be positive".to_string()));
+    }
+
+    Ok(budget)
+}
+
+fn parse_log_sample(flag: &str, value: Option<String>) -> Result<u64, ArgsError> {
+    let every = parse_value(flag, value)?;
+
+    if every == 0 {
+        return Err(ArgsError(
+            "--log-sample-packets must be positive".to_string(),
+        ));
+    }
+
+    Ok(every)
+}
+
+fn parse_burst_override(flag: &str, value: Option<String>) -> Result<usize, ArgsError> {
+    let packets = parse_value(flag, value)?;
+
+    if packets == 0 {
+        return Err(ArgsError("--burst-override must be positive".to_string()));
+    }
+
+    Ok(packets)
+}
+
+fn parse_initial_cwnd(flag: &str, value: Option<String>) -> Result<usize, ArgsError> {
+    let packets = parse_value(flag, value)?;
+
+    if packets == 0 {
+        return Err(ArgsError("--initial-cwnd must be positive".to_string()));
+    }
+
+    Ok(packets)
+}
+
+/// Parses the format named `json` or `cbor` by `flag`.
+fn parse_format(
+    flag: &str,
+    value: Option<String>,
+    json: &str,
+    cbor: &str,
+) -> Result<Format, ArgsError> {
+    match value.as_deref() {
+        Some(v) if v == json => Ok(Format::Json),
+        Some(v) if v == cbor => Ok(Format::Cbor),
+        Some(v) => Err(ArgsError(format!("invalid value '{}' for {}", v, flag))),
+        None => Err(ArgsError(format!("{} expects a value", flag))),
+    }
+}
+
+fn parse_cid_len(flag: &str, value: Option<String>) -> Result<usize, ArgsError> {
+    let len = parse_value(flag, value)?;
+
+    // The servers route the datagrams by connection ID, which a zero-length
+    // one cannot do.
+    if !(1..=quiche::MAX_CONN_ID_LEN).contains(&len) {
+        return Err(ArgsError(format!(
+            "{} must be between 1 and {}",
+            flag,
+            quiche::MAX_CONN_ID_LEN
+        )));
+    }
+
+    Ok(len)
+}
+
+fn parse_schedule(flag: &str, value: Option<String>) -> Result<PathSchedule, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    let schedule: PathSchedule = value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))?;
+
+    if schedule.max_path() >= MAX_NUMBER_SOCKETS {
+        return Err(ArgsError(format!(
+            "{} paths must be below {}",
+            flag, MAX_NUMBER_SOCKETS
+        )));
+    }
+
+    Ok(schedule)
+}
+
+fn parse_bind_dev(flag: &str, value: Option<String>) -> Result<BindDev, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    let dev: BindDev = value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))?;
+
+    if dev.path >= MAX_NUMBER_SOCKETS {
+        return Err(ArgsError(format!(
+            "{} paths must be below {}",
+            flag, MAX_NUMBER_SOCKETS
+        )));
+    }
+
+    Ok(dev)
+}
+
+fn parse_run_id(flag: &str, value: Option<String>) -> Result<RunId, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))
+}
+
+fn parse_app(flag: &str, value: Option<String>) -> Result<App, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))
+}
+
+#[cfg(feature = "danger")]
+fn parse_impair(flag: &str, value: Option<String>) -> Result<ImpairSpec, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))
+}
+
+fn parse_weights(flag: &str, value: Option<String>) -> Result<QualityWeights, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    value
+        .parse()
+        .map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))
+}
+
+/// Decodes the message given to `flag` with `decode`.
+fn parse_message(
+    flag: &str,
+    value: Option<String>,
+    decode: fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    decode(&value).map_err(|e| ArgsError(format!("invalid value for {}: {}", flag, e)))
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, ArgsError> {
+    let value = value.ok_or_else(|| ArgsError(format!("{} expects a value", flag)))?;
+
+    value
+        .parse()
+        .map_err(|_| ArgsError(format!("invalid value '{}' for {}", value, flag)))
+}
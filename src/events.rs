@@ -0,0 +1,700 @@
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info};
+use quiche::ConnectionId;
+
+use crate::{
+    report::{PathUsage, Throughput},
+    shared::{
+        cbor::CborObject,
+        encoding::{Format, Object},
+        json::JsonObject,
+        progress::TransferProgress,
+        run_clock::RunClock,
+    },
+};
+
+/// Keys used by a connection to protect its application data.
+///
+/// quiche does not expose the key update phase, so only the progression
+/// from early data to 1-RTT keys is tracked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyPhase {
+    /// No application keys yet.
+    Handshake,
+
+    /// 0-RTT keys derived from a resumed session.
+    EarlyData,
+
+    /// 1-RTT keys, the handshake is complete.
+    OneRtt,
+}
+
+impl KeyPhase {
+    pub fn of(conn: &quiche::Connection) -> Self {
+        if conn.is_established() {
+            KeyPhase::OneRtt
+        } else if conn.is_in_early_data() {
+            KeyPhase::EarlyData
+        } else {
+            KeyPhase::Handshake
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyPhase::Handshake => "handshake",
+            KeyPhase::EarlyData => "0-rtt",
+            KeyPhase::OneRtt => "1-rtt",
+        }
+    }
+}
+
+/// Statistics of a path, sampled periodically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathSample {
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    pub rtt: Duration,
+    pub cwnd: usize,
+    /// Packets sent, received and lost on the path so far.
+    pub sent: usize,
+    pub recv: usize,
+    pub lost: usize,
+    pub sent_bytes: u64,
+    pub recv_bytes: u64,
+}
+
+impl From<&quiche::PathStats> for PathSample {
+    fn from(stats: &quiche::PathStats) -> Self {
+        PathSample {
+            local: stats.local_addr,
+            peer: stats.peer_addr,
+            rtt: stats.rtt,
+            cwnd: stats.cwnd,
+            sent: stats.sent,
+            recv: stats.recv,
+            lost: stats.lost,
+            sent_bytes: stats.sent_bytes,
+            recv_bytes: stats.recv_bytes,
+        }
+    }
+}
+
+/// Streams carrying a single message, by stage of their lifecycle. The
+/// streams multiplexing several messages live as long as the connection and
+/// are left out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamStages {
+    /// Message not fully handed to quiche yet.
+    pub sending: usize,
+    /// Message sent, its echo not complete yet.
+    pub echoing: usize,
+    /// Echo complete or stream reset, quiche not done with the stream until
+    /// everything sent on it is acknowledged.
+    pub closing: usize,
+}
+
+impl StreamStages {
+    /// Streams the client still keeps state for.
+    pub fn total(&self) -> usize {
+        self.sending + self.echoing + self.closing
+    }
+}
+
+/// Significant events of a connection.
+#[derive(Clone, Debug)]
+pub enum Event {
+    Established,
+    /// The client considers the handshake confirmed, see
+    /// `Client::is_confirmed`.
+    HandshakeConfirmed,
+    Closed,
+    /// The connection moved to new keys, `resumed` tells whether the
+    /// session was resumed.
+    KeyPhase {
+        phase: KeyPhase,
+        resumed: bool,
+    },
+    /// The peer used an address pair the client did not probe.
+    PathNew(SocketAddr, SocketAddr),
+    PathProbed(SocketAddr, SocketAddr),
+    /// quiche refused to probe the path, e.g. for lack of a spare
+    /// connection ID.
+    PathProbeFailed(SocketAddr, SocketAddr),
+    PathValidated(SocketAddr, SocketAddr),
+    PathFailedValidation(SocketAddr, SocketAddr),
+    PathClosed(SocketAddr, SocketAddr),
+    /// The active path received nothing for a few RTTs while it was
+    /// sending, the client fails over to the standby path.
+    PathSuspect(SocketAddr, SocketAddr),
+    /// The client closed the path after it carried no stream data for
+    /// `--path-idle-timeout`.
+    PathIdleClosed(SocketAddr, SocketAddr),
+    Migrated(SocketAddr, SocketAddr),
+    /// quiche refused to migrate to the path.
+    MigrationFailed(SocketAddr, SocketAddr),
+    PeerMigrated(SocketAddr, SocketAddr),
+    /// The MTU of the path changed, after a path MTU discovery probe.
+    PathMtu(SocketAddr, SocketAddr, usize),
+    CidIssued(ConnectionId<'static>),
+    CidRetired(ConnectionId<'static>),
+    StreamOpened(u64),
+    StreamFin(u64),
+    StreamReset(u64, u64),
+    /// The echo of message `index` was fully received, `latency` after the
+    /// message was sent.
+    MessageCompleted {
+        index: usize,
+        stream_id: u64,
+        bytes: usize,
+        latency: Duration,
+    },
+    /// The echo of message `index`, sent with sequence number `seq`,
+    /// completed after the echo of the message sent with `after`, with
+    /// `--sequence`.
+    OutOfOrder {
+        index: usize,
+        stream_id: u64,
+        seq: u64,
+        after: u64,
+    },
+    /// Statistics of a path, sampled every `--stats-interval-ms`.
+    PathStats(PathSample),
+    /// Streams in each stage of their lifecycle, sampled with the paths.
+    StreamStages(StreamStages),
+    /// Paths probed, validated and used, once the connection is closed.
+    PathUsage(PathUsage),
+    /// Bytes sent on the wire and application bytes delivered, once the
+    /// connection is closed.
+    Throughput(Throughput),
+    /// The options likely do not work as intended, before the connection
+    /// starts.
+    ConfigWarning(String),
+    /// Progress of a run of the file transfer, at most twice a second and
+    /// once it completes.
+    TransferProgress(TransferProgress),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Established => "established",
+            Event::HandshakeConfirmed => "handshake_confirmed",
+            Event::Closed => "closed",
+            Event::KeyPhase { .. } => "key_phase",
+            Event::PathNew(..) => "path_new",
+            Event::PathProbed(..) => "path_probed",
+            Event::PathProbeFailed(..) => "path_probe_failed",
+            Event::PathValidated(..) => "path_validated",
+            Event::PathFailedValidation(..) => "path_failed_validation",
+            Event::PathClosed(..) | Event::PathIdleClosed(..) => "path_closed",
+            Event::PathSuspect(..) => "path_suspect",
+            Event::Migrated(..) => "migrated",
+            Event::MigrationFailed(..) => "migration_failed",
+            Event::PeerMigrated(..) => "peer_migrated",
+            Event::PathMtu(..) => "path_mtu",
+            Event::CidIssued(..) => "cid_issued",
+            Event::CidRetired(..) => "cid_retired",
+            Event::StreamOpened(..) => "stream_opened",
+            Event::StreamFin(..) => "stream_fin",
+            Event::StreamReset(..) => "stream_reset",
+            Event::MessageCompleted { .. } => "message_completed",
+            Event::OutOfOrder { .. } => "out_of_order",
+            Event::PathStats(..) => "path_stats",
+            Event::StreamStages(..) => "stream_stages",
+            Event::PathUsage(..) => "path_usage",
+            Event::Throughput(..) => "throughput",
+            Event::ConfigWarning(..) => "config_warning",
+            Event::TransferProgress(..) => "transfer_progress",
+        }
+    }
+
+    /// The path the event is about, as (local address, peer address).
+    pub fn path(&self) -> Option<(SocketAddr, SocketAddr)> {
+        match *self {
+            Event::PathNew(local, peer)
+            | Event::PathProbed(local, peer)
+            | Event::PathProbeFailed(local, peer)
+            | Event::PathValidated(local, peer)
+            | Event::PathFailedValidation(local, peer)
+            | Event::PathClosed(local, peer)
+            | Event::PathSuspect(local, peer)
+            | Event::PathIdleClosed(local, peer)
+            | Event::Migrated(local, peer)
+            | Event::MigrationFailed(local, peer)
+            | Event::PeerMigrated(local, peer)
+            | Event::PathMtu(local, peer, _) => Some((local, peer)),
+
+            Event::PathStats(sample) => Some((sample.local, sample.peer)),
+
+            _ => None,
+        }
+    }
+}
+
+/// Hook notified of the events of a connection.
+pub trait ConnEvents {
+    fn on_event(&mut self, event: &Event);
+}
+
+/// Set of hooks, all notified of every event in registration order.
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Box<dyn ConnEvents>>,
+}
+
+impl Hooks {
+    pub fn push<H: ConnEvents + 'static>(&mut self, hook: H) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.on_event(&event);
+    }
+}
+
+impl ConnEvents for Hooks {
+    fn on_event(&mut self, event: &Event) {
+        for hook in &mut self.hooks {
+            hook.on_event(event);
+        }
+    }
+}
+
+/// Logs the events.
+#[derive(Default)]
+pub struct LogEvents {
+    prefix: String,
+}
+
+impl LogEvents {
+    /// Logs the events prefixed by `label`, to tell connections apart.
+    pub fn with_label(label: &str) -> Self {
+        LogEvents {
+            prefix: format!("{} ", label),
+        }
+    }
+}
+
+impl ConnEvents for LogEvents {
+    fn on_event(&mut self, event: &Event) {
+        let prefix = &self.prefix;
+
+        match event {
+            Event::Established => info!("{}Connection established", prefix),
+
+            Event::HandshakeConfirmed => info!("{}Handshake confirmed", prefix),
+
+            Event::Closed => info!("{}Connection closed", prefix),
+
+            Event::KeyPhase { phase, resumed } => {
+                info!(
+                    "{}Key phase is now {} (resumed: {})",
+                    prefix,
+                    phase.name(),
+                    resumed
+                )
+            }
+
+            Event::PathNew(local, peer) => info!("{}New path ({}, {})", prefix, local, peer),
+
+            Event::PathProbed(local, peer) => info!("{}Probing path ({}, {})", prefix, local, peer),
+
+            Event::PathProbeFailed(local, peer) => {
+                info!("{}Probing path ({}, {}) failed", prefix, local, peer)
+            }
+
+            Event::PathValidated(local, peer) => {
+                info!("{}Path ({}, {}) is now validated", prefix, local, peer)
+            }
+
+            Event::PathFailedValidation(local, peer) => {
+                info!("{}Path ({}, {}) failed validation", prefix, local, peer)
+            }
+
+            Event::PathClosed(local, peer) => {
+                info!(
+                    "{}Path ({}, {}) is now closed and unusable",
+                    prefix, local, peer
+                )
+            }
+
+            Event::PathSuspect(local, peer) => {
+                info!(
+                    "{}Path ({}, {}) receives nothing, suspected lost",
+                    prefix, local, peer
+                )
+            }
+
+            Event::PathIdleClosed(local, peer) => {
+                info!(
+                    "{}Path ({}, {}) closed, no stream data for the idle timeout",
+                    prefix, local, peer
+                )
+            }
+
+            Event::Migrated(local, peer) => {
+                info!("{}Migrated to path ({}, {})", prefix, local, peer)
+            }
+
+            Event::MigrationFailed(local, peer) => {
+                info!("{}Migration to path ({}, {}) failed", prefix, local, peer)
+            }
+
+            Event::PeerMigrated(local, peer) => {
+                info!("{}Connection migrated to ({}, {})", prefix, local, peer)
+            }
+
+            Event::PathMtu(local, peer, mtu) => {
+                info!("{}Path ({}, {}) MTU is now {}", prefix, local, peer, mtu)
+            }
+
+            Event::CidIssued(cid) => info!("{}Issued new source connection ID {:?}", prefix, cid),
+
+            Event::CidRetired(cid) => info!("{}Source connection ID {:?} retired", prefix, cid),
+
+            Event::StreamOpened(stream_id) => info!("{}Opened stream {}", prefix, stream_id),
+
+            Event::StreamFin(stream_id) => info!("{}Stream {} finished", prefix, stream_id),
+
+            Event::StreamReset(stream_id, code) => {
+                info!("{}Stream {} reset with code {}", prefix, stream_id, code)
+            }
+
+            Event::MessageCompleted {
+                index,
+                stream_id,
+                latency,
+                ..
+            } => debug!(
+                "{}Message {} completed on stream {} in {:?}",
+                prefix, index, stream_id, latency
+            ),
+
+            Event::OutOfOrder {
+                index,
+                stream_id,
+                seq,
+                after,
+            } => info!(
+                "{}Message {} (sequence {}) completed on stream {} after sequence {}",
+                prefix, index, seq, stream_id, after
+            ),
+
+            Event::PathStats(sample) => debug!(
+                "{}Path ({}, {}): rtt {:?}, cwnd {}, {} sent, {} lost",
+                prefix,
+                sample.local,
+                sample.peer,
+                sample.rtt,
+                sample.cwnd,
+                sample.sent,
+                sample.lost
+            ),
+
+            Event::StreamStages(stages) => debug!(
+                "{}Streams sending/echoing/closing: {}/{}/{}",
+                prefix, stages.sending, stages.echoing, stages.closing
+            ),
+
+            Event::PathUsage(usage) => {
+                info!(
+                    "{}Paths probed/validated/used: {}/{}/{}",
+                    prefix, usage.probed, usage.validated, usage.used
+                )
+            }
+
+            Event::Throughput(t) => {
+                info!(
+                    "{}Throughput {:.0} B/s ({} bytes), goodput {:.0} B/s ({} bytes)",
+                    prefix,
+                    t.throughput(),
+                    t.wire_bytes,
+                    t.goodput(),
+                    t.app_bytes
+                )
+            }
+
+            // Printed at startup already.
+            Event::ConfigWarning(message) => debug!("{}Warning: {}", prefix, message),
+
+            Event::TransferProgress(progress) => debug!("{}Progress of {}", prefix, progress),
+        }
+    }
+}
+
+/// Appends one JSON object per event to a file, or one CBOR map with
+/// `Format::Cbor`.
+///
+/// Timestamps are taken from the clock of the run, in microseconds since its
+/// start, and the first object written is its header with the wall-clock
+/// anchor.
+pub struct JsonEvents {
+    out: LineWriter<File>,
+    clock: RunClock,
+    header_written: bool,
+    label: Option<String>,
+    /// Names of the events written, all of them if empty.
+    only: Vec<&'static str>,
+    format: Format,
+}
+
+impl JsonEvents {
+    pub fn open<P: AsRef<Path>>(path: P, clock: RunClock) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(JsonEvents::new(file, clock))
+    }
+
+    /// Writes the events to `file`, already open, e.g. the standard output
+    /// with `--json-events`.
+    pub fn new(file: File, clock: RunClock) -> Self {
+        JsonEvents {
+            out: LineWriter::new(file),
+            clock,
+            header_written: false,
+            label: None,
+            only: Vec::new(),
+            format: Format::Json,
+        }
+    }
+
+    /// Writes the events in `format`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Writes only the events named `names`, e.g. the progress of the
+    /// transfers with `--progress-json`.
+    pub fn only(mut self, names: &[&'static str]) -> Self {
+        self.only = names.to_vec();
+        self
+    }
+
+    /// Tags every event with `label`, to tell connections apart.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn encode(event: &Event, time_us: u64, label: Option<&str>) -> String {
+        Self::build::<JsonObject>(event, time_us, label).finish()
+    }
+
+    /// Object of the fields of `event`, in any format.
+    pub fn build<O: Object>(event: &Event, time_us: u64, label: Option<&str>) -> O {
+        let mut obj = O::new().u64("time_us", time_us);
+
+        if let Some(label) = label {
+            obj = obj.str("target", label);
+        }
+
+        obj = obj.str("event", event.name());
+
+        if let Some((local, peer)) = event.path() {
+            obj = obj
+                .str("local", &local.to_string())
+                .str("peer", &peer.to_string());
+        }
+
+        match event {
+            Event::KeyPhase { phase, resumed } => {
+                obj = obj.str("phase", phase.name()).bool("resumed", *resumed);
+            }
+
+            Event::PathMtu(_, _, mtu) => {
+                obj = obj.u64("mtu", *mtu as u64);
+            }
+
+            Event::PathClosed(..) => {
+                obj = obj.str("reason", "unusable");
+            }
+
+            Event::PathIdleClosed(..) => {
+                obj = obj.str("reason", "idle");
+            }
+
+            Event::CidIssued(cid) | Event::CidRetired(cid) => {
+                obj = obj.str("cid", &format!("{:?}", cid));
+            }
+
+            Event::StreamOpened(stream_id) | Event::StreamFin(stream_id) => {
+                obj = obj.u64("stream_id", *stream_id);
+            }
+
+            Event::StreamReset(stream_id, code) => {
+                obj = obj.u64("stream_id", *stream_id).u64("code", *code);
+            }
+
+            Event::MessageCompleted {
+                index,
+                stream_id,
+                bytes,
+                latency,
+            } => {
+                obj = obj
+                    .u64("index", *index as u64)
+                    .u64("stream_id", *stream_id)
+                    .u64("bytes", *bytes as u64)
+                    .u64("latency_us", latency.as_micros() as u64);
+            }
+
+            Event::OutOfOrder {
+                index,
+                stream_id,
+                seq,
+                after,
+            } => {
+                obj = obj
+                    .u64("index", *index as u64)
+                    .u64("stream_id", *stream_id)
+                    .u64("seq", *seq)
+                    .u64("after", *after);
+            }
+
+            Event::PathStats(sample) => {
+                obj = obj
+                    .u64("rtt_us", sample.rtt.as_micros() as u64)
+                    .u64("cwnd", sample.cwnd as u64)
+                    .u64("sent", sample.sent as u64)
+                    .u64("recv", sample.recv as u64)
+                    .u64("lost", sample.lost as u64)
+                    .u64("sent_bytes", sample.sent_bytes)
+                    .u64("recv_bytes", sample.recv_bytes);
+            }
+
+            Event::StreamStages(stages) => {
+                obj = obj
+                    .u64("sending", stages.sending as u64)
+                    .u64("echoing", stages.echoing as u64)
+                    .u64("closing", stages.closing as u64);
+            }
+
+            Event::PathUsage(usage) => {
+                obj = obj
+                    .u64("probed", usage.probed as u64)
+                    .u64("validated", usage.validated as u64)
+                    .u64("used", usage.used as u64);
+            }
+
+            Event::Throughput(t) => {
+                obj = obj
+                    .u64("wire_bytes", t.wire_bytes)
+                    .u64("app_bytes", t.app_bytes)
+                    .u64("elapsed_us", t.elapsed.as_micros() as u64)
+                    .f64("throughput_bps", t.throughput() * 8.0)
+                    .f64("goodput_bps", t.goodput() * 8.0);
+            }
+
+            Event::ConfigWarning(message) => {
+                obj = obj.str("message", message);
+            }
+
+            Event::TransferProgress(p) => {
+                obj = obj
+                    .u64("transfer", p.transfer)
+                    .u64("size", p.size)
+                    .u64("sent", p.sent)
+                    .u64("acked", p.acked)
+                    .u64("elapsed_us", p.elapsed.as_micros() as u64)
+                    .f64("rate_bps", p.rate * 8.0)
+                    .f64("average_bps", p.average * 8.0)
+                    .u64("paths", p.paths as u64);
+
+                if let Some(eta) = p.eta() {
+                    obj = obj.u64("eta_us", eta.as_micros() as u64);
+                }
+            }
+
+            _ => (),
+        }
+
+        obj
+    }
+
+    /// Writes `event` as emitted at `now`, after the header if it is the
+    /// first.
+    pub fn write(&mut self, event: &Event, now: Instant) -> std::io::Result<()> {
+        if !self.only.is_empty() && !self.only.contains(&event.name()) {
+            return Ok(());
+        }
+
+        let time_us = self.clock.time_us(now);
+        let label = self.label.as_deref();
+
+        if !self.header_written {
+            self.header_written = true;
+
+            match self.format {
+                Format::Json => self
+                    .clock
+                    .header::<JsonObject>(label)
+                    .write_to(&mut self.out)?,
+                Format::Cbor => self
+                    .clock
+                    .header::<CborObject>(label)
+                    .write_to(&mut self.out)?,
+            }
+        }
+
+        match self.format {
+            Format::Json => {
+                Self::build::<JsonObject>(event, time_us, label).write_to(&mut self.out)
+            }
+            Format::Cbor => {
+                Self::build::<CborObject>(event, time_us, label).write_to(&mut self.out)
+            }
+        }
+    }
+}
+
+impl ConnEvents for JsonEvents {
+    fn on_event(&mut self, event: &Event) {
+        if let Err(e) = self.write(event, Instant::now()) {
+            error!("writing event failed: {}", e);
+        }
+    }
+}
+
+/// Keeps a line of the standard error updated with the progress of the
+/// transfers, for a terminal.
+#[derive(Default)]
+pub struct ProgressLine {
+    prefix: String,
+}
+
+impl ProgressLine {
+    /// Prefixes the line with `label`, to tell connections apart.
+    pub fn with_label(label: &str) -> Self {
+        ProgressLine {
+            prefix: format!("{} ", label),
+        }
+    }
+}
+
+impl ConnEvents for ProgressLine {
+    fn on_event(&mut self, event: &Event) {
+        let Event::TransferProgress(progress) = event else {
+            return;
+        };
+
+        let mut err = std::io::stderr().lock();
+
+        // Overwrites the previous line, which the last snapshot of a run
+        // ends.
+        let end = if progress.is_complete() { "\n" } else { "" };
+        write!(err, "\r\x1b[2K{}{}{}", self.prefix, progress, end).ok();
+        err.flush().ok();
+    }
+}
@@ -1 +1,7 @@
+pub mod client;
+pub mod config;
+pub mod events;
+pub mod path_cache;
+pub mod report;
+pub mod resume;
 pub mod shared;
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+/// Paths validated recently against each server, with their RTT, kept
+/// across runs so that the client probes the fastest paths first.
+///
+/// Validation is per connection, so cached paths are probed all the same.
+/// Entries are keyed by the server name sent as SNI and the server address,
+/// so that servers sharing a name or an address do not pollute each other,
+/// and expire `max_age` after their validation.
+///
+/// The file has one line per path,
+/// `<sni> <server addr> <local addr> <peer addr> <rtt_us> <validated_at>`,
+/// the validation time being in seconds since the Unix epoch. Lines starting
+/// with `#` are ignored.
+#[derive(Debug)]
+pub struct PathCache {
+    entries: Vec<CachedPath>,
+    max_age: Duration,
+}
+
+/// A path validated against a server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedPath {
+    pub sni: String,
+    pub server: SocketAddr,
+    pub path: (SocketAddr, SocketAddr),
+    pub rtt: Duration,
+
+    /// Seconds since the Unix epoch.
+    pub validated_at: u64,
+}
+
+impl PathCache {
+    pub fn new(max_age: Duration) -> Self {
+        PathCache {
+            entries: Vec::new(),
+            max_age,
+        }
+    }
+
+    /// Reads the entries of `path` that did not expire at `now`. A missing
+    /// file is an empty cache, invalid lines are skipped.
+    pub fn load<P: AsRef<Path>>(path: P, max_age: Duration, now: SystemTime) -> io::Result<Self> {
+        let mut cache = PathCache::new(max_age);
+
+        let file = match File::open(path) {
+            Ok(v) => v,
+
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+
+            Err(e) => return Err(e),
+        };
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some(entry) if !cache.is_expired(&entry, now) => cache.entries.push(entry),
+
+                Some(_) => (),
+
+                None => warn!("ignoring invalid path cache line '{}'", line),
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Writes every entry to `path`, replacing its content.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        writeln!(out, "# sni server local peer rtt_us validated_at")?;
+
+        for entry in &self.entries {
+            writeln!(
+                out,
+                "{} {} {} {} {} {}",
+                entry.sni,
+                entry.server,
+                entry.path.0,
+                entry.path.1,
+                entry.rtt.as_micros(),
+                entry.validated_at
+            )?;
+        }
+
+        out.flush()
+    }
+
+    pub fn entries(&self) -> &[CachedPath] {
+        &self.entries
+    }
+
+    /// RTT of the paths cached for the server `sni` at `server`.
+    pub fn rtts(
+        &self,
+        sni: &str,
+        server: SocketAddr,
+    ) -> HashMap<(SocketAddr, SocketAddr), Duration> {
+        self.entries
+            .iter()
+            .filter(|e| e.sni == sni && e.server == server)
+            .map(|e| (e.path, e.rtt))
+            .collect()
+    }
+
+    /// Records that `path` was validated against the server `sni` at
+    /// `server` with `rtt`, replacing its previous entry.
+    pub fn record(
+        &mut self,
+        sni: &str,
+        server: SocketAddr,
+        path: (SocketAddr, SocketAddr),
+        rtt: Duration,
+        now: SystemTime,
+    ) {
+        self.entries
+            .retain(|e| !(e.sni == sni && e.server == server && e.path == path));
+
+        self.entries.push(CachedPath {
+            sni: sni.to_string(),
+            server,
+            path,
+            rtt,
+            validated_at: unix_secs(now),
+        });
+    }
+
+    fn is_expired(&self, entry: &CachedPath, now: SystemTime) -> bool {
+        unix_secs(now).saturating_sub(entry.validated_at) > self.max_age.as_secs()
+    }
+}
+
+fn parse_line(line: &str) -> Option<CachedPath> {
+    let mut fields = line.split_whitespace();
+
+    let entry = CachedPath {
+        sni: fields.next()?.to_string(),
+        server: fields.next()?.parse().ok()?,
+        path: (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?),
+        rtt: Duration::from_micros(fields.next()?.parse().ok()?),
+        validated_at: fields.next()?.parse().ok()?,
+    };
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some(entry)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
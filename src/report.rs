@@ -0,0 +1,1611 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{LineWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+use crate::shared::{
+    amplification::PreValidation,
+    burst::BurstStats,
+    cbor::CborObject,
+    cids::CidStats,
+    control::RunId,
+    encoding::{Format, Object},
+    flow_block::FlowBlockStats,
+    impair::ImpairStats,
+    json::JsonObject,
+    migration::MigrationStats,
+    owd::{self, PathOwd},
+    path_quality::PathQuality,
+    path_schedule::WindowUsage,
+    pto::{PathPtos, PtoCounter},
+    read_budget::ReadStats,
+    run_clock::RunClock,
+    sequence::OrderStats,
+};
+
+/// Number of buckets of the latency histogram, the last one holds
+/// everything above 2^(HISTOGRAM_BUCKETS - 2) microseconds.
+const HISTOGRAM_BUCKETS: usize = 24;
+
+/// Number of completed messages kept for the final table.
+pub const RECENT_RECORDS: usize = 10;
+
+/// Latency distribution with power-of-two buckets, so that its size does not
+/// depend on the number of samples.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Bucket `i` counts the samples below 2^i microseconds that do not fit
+    /// in a lower bucket.
+    buckets: [u64; HISTOGRAM_BUCKETS],
+    count: u64,
+    sum: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl Histogram {
+    pub fn record(&mut self, sample: Duration) {
+        let us = sample.as_micros() as u64;
+        let bucket = (u64::BITS - us.leading_zeros()) as usize;
+
+        self.buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum += sample;
+        self.min = Some(self.min.map_or(sample, |min| min.min(sample)));
+        self.max = Some(self.max.map_or(sample, |max| max.max(sample)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.sum / self.count as u32)
+    }
+
+    /// Upper bound (exclusive, in microseconds) and count of each non-empty
+    /// bucket. The bound of the last bucket is `None`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| ((i < HISTOGRAM_BUCKETS - 1).then_some(1 << i), *count))
+    }
+}
+
+/// Width of the longest bar of the histogram of completion times.
+pub const HIST_BAR_WIDTH: u64 = 40;
+
+/// Completion times of the messages, drawn as a histogram with buckets of
+/// equal width between the fastest and the slowest message. Unlike
+/// [`Histogram`], every sample is kept, to place the buckets once all are
+/// known.
+#[derive(Debug)]
+pub struct CompletionTimes {
+    buckets: usize,
+    samples: Vec<Duration>,
+}
+
+impl CompletionTimes {
+    pub fn new(buckets: usize) -> Self {
+        CompletionTimes {
+            buckets: buckets.max(1),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, sample: Duration) {
+        self.samples.push(sample);
+    }
+
+    /// Lower bound and count of each bucket, empty ones included. A single
+    /// bucket holds every sample if they are all equal.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        let (Some(min), Some(max)) = (self.samples.iter().min(), self.samples.iter().max()) else {
+            return vec![];
+        };
+
+        let span = (*max - *min).as_nanos();
+        if span == 0 {
+            return vec![(*min, self.samples.len() as u64)];
+        }
+
+        let n = self.buckets as u128;
+        let mut counts = vec![0; self.buckets];
+        for sample in &self.samples {
+            let i = ((*sample - *min).as_nanos() * n / span).min(n - 1);
+            counts[i as usize] += 1;
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let offset = span * i as u128 / n;
+                (*min + Duration::from_nanos(offset as u64), count)
+            })
+            .collect()
+    }
+
+    /// One line per bucket: its lower bound, a bar of `#` proportional to
+    /// its count, and the count.
+    pub fn render(&self) -> Vec<String> {
+        let buckets = self.buckets();
+        let most = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+
+        buckets
+            .into_iter()
+            .map(|(bound, count)| {
+                let bar = "#".repeat((count * HIST_BAR_WIDTH).div_ceil(most.max(1)) as usize);
+                format!(
+                    "{:>12} |{:<width$} {}",
+                    format!("{:.1?}", bound),
+                    bar,
+                    count,
+                    width = HIST_BAR_WIDTH as usize
+                )
+            })
+            .collect()
+    }
+}
+
+/// Paths on which data was received, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathList(Vec<(SocketAddr, SocketAddr)>);
+
+impl PathList {
+    /// Adds `path` unless data was already last received on it.
+    pub fn push(&mut self, path: (SocketAddr, SocketAddr)) {
+        if self.0.last() != Some(&path) {
+            self.0.push(path);
+        }
+    }
+
+    pub fn paths(&self) -> &[(SocketAddr, SocketAddr)] {
+        &self.0
+    }
+}
+
+impl fmt::Display for PathList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (local, peer)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" -> ")?;
+            }
+
+            write!(f, "({}, {})", local, peer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A message whose echo was fully received.
+#[derive(Clone, Debug)]
+pub struct MessageRecord {
+    /// Index of the message on the command line.
+    pub index: usize,
+    pub stream_id: u64,
+
+    /// Position of the message among the frames of its stream, if the
+    /// messages are multiplexed.
+    pub frame: Option<usize>,
+
+    /// Paths on which the echo was received.
+    pub paths: PathList,
+
+    pub latency: Duration,
+
+    /// Time the message waited for a slot under `--max-concurrent-streams`
+    /// before being sent, not included in its latency.
+    pub queue_wait: Duration,
+}
+
+/// Writes each completed message as a JSON line, or a CBOR map with
+/// `Format::Cbor`, as soon as it completes, stamped with the clock of the
+/// run whose header comes first.
+#[derive(Debug)]
+pub struct RecordWriter {
+    out: LineWriter<File>,
+    clock: RunClock,
+    header_written: bool,
+    label: Option<String>,
+    format: Format,
+}
+
+impl RecordWriter {
+    pub fn open<P: AsRef<Path>>(path: P, clock: RunClock) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(RecordWriter {
+            out: LineWriter::new(file),
+            clock,
+            header_written: false,
+            label: None,
+            format: Format::Json,
+        })
+    }
+
+    /// Writes the records in `format`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Tags every record with `label`, to tell targets apart.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn encode(record: &MessageRecord, time_us: u64, label: Option<&str>) -> String {
+        Self::build::<JsonObject>(record, time_us, label).finish()
+    }
+
+    /// Object of the fields of `record`, completed `time_us` after the start
+    /// of the run, in any format.
+    pub fn build<O: Object>(record: &MessageRecord, time_us: u64, label: Option<&str>) -> O {
+        let mut obj = O::new().u64("time_us", time_us);
+
+        if let Some(label) = label {
+            obj = obj.str("target", label);
+        }
+
+        obj = obj
+            .u64("message", record.index as u64)
+            .u64("stream_id", record.stream_id);
+
+        if let Some(frame) = record.frame {
+            obj = obj.u64("frame", frame as u64);
+        }
+
+        obj = obj
+            .u64("latency_us", record.latency.as_micros() as u64)
+            .u64("queue_wait_us", record.queue_wait.as_micros() as u64);
+
+        let paths = record.paths.paths().iter().map(|(local, peer)| {
+            O::new()
+                .str("local", &local.to_string())
+                .str("peer", &peer.to_string())
+        });
+
+        obj.objects("paths", paths.collect())
+    }
+
+    /// Writes `record` as completed at `now`, after the header if it is
+    /// the first.
+    pub fn write(&mut self, record: &MessageRecord, now: Instant) -> std::io::Result<()> {
+        let time_us = self.clock.time_us(now);
+        let label = self.label.as_deref();
+
+        if !self.header_written {
+            self.header_written = true;
+
+            match self.format {
+                Format::Json => self
+                    .clock
+                    .header::<JsonObject>(label)
+                    .write_to(&mut self.out)?,
+                Format::Cbor => self
+                    .clock
+                    .header::<CborObject>(label)
+                    .write_to(&mut self.out)?,
+            }
+        }
+
+        match self.format {
+            Format::Json => {
+                Self::build::<JsonObject>(record, time_us, label).write_to(&mut self.out)
+            }
+            Format::Cbor => {
+                Self::build::<CborObject>(record, time_us, label).write_to(&mut self.out)
+            }
+        }
+    }
+}
+
+/// Latency of a probe sent while a bulk transfer fills the queues.
+#[derive(Debug)]
+pub struct LoadSample {
+    /// Time the probe was sent, since the start of the bulk transfer.
+    pub at: Duration,
+
+    /// Bulk bytes sent but not echoed back yet when the probe was sent.
+    pub queued_bytes: u64,
+
+    pub latency: Duration,
+}
+
+/// A run of the file transfer, on one or several streams.
+#[derive(Debug)]
+pub struct TransferRun {
+    /// Number of streams carrying the byte ranges of the file.
+    pub streams: usize,
+    pub size: u64,
+
+    /// Paths the ranges were sent on with `--stripe`, a single one for the
+    /// runs on each path alone. Empty for the other runs, sent on whichever
+    /// path is active.
+    pub paths: Vec<(SocketAddr, SocketAddr)>,
+
+    /// Time from the first byte sent to the last reply received.
+    pub elapsed: Duration,
+
+    /// Whether the SHA-256 computed by the server matches the file.
+    pub digest_ok: bool,
+}
+
+impl TransferRun {
+    /// Goodput of the run, in bytes per second.
+    pub fn goodput(&self) -> f64 {
+        self.size as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Bytes sent on the wire against the application bytes they delivered,
+/// over the lifetime of the connection. The gap between throughput and
+/// goodput is the overhead of headers, acknowledgments, padding, probes and
+/// retransmissions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Throughput {
+    /// UDP payload bytes sent, as counted by quiche.
+    pub wire_bytes: u64,
+
+    /// Bytes of the messages whose echo was received, before padding.
+    pub app_bytes: u64,
+
+    pub elapsed: Duration,
+}
+
+impl Throughput {
+    /// Wire bytes sent per second.
+    pub fn throughput(&self) -> f64 {
+        self.wire_bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Application bytes delivered per second.
+    pub fn goodput(&self) -> f64 {
+        self.app_bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Loss recovery during the handshake, as far as quiche exposes it.
+///
+/// quiche has no per packet number space statistics: the figures are read
+/// once the handshake completes, so they only cover the packets sent before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandshakeRecovery {
+    /// Expirations of the loss detection timer.
+    pub ptos: usize,
+    /// Frames retransmitted by quiche, mostly crypto data but also the
+    /// 0-RTT stream data if any.
+    pub retransmissions: usize,
+    /// Packets declared lost.
+    pub lost: usize,
+}
+
+impl fmt::Display for HandshakeRecovery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} PTOs, {} retransmissions, {} lost packets",
+            self.ptos, self.retransmissions, self.lost
+        )
+    }
+}
+
+/// Figures of a run compared against its single-path baseline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunSummary {
+    pub delivered: usize,
+    pub messages: usize,
+    pub handshake_time: Option<Duration>,
+    pub throughput: Throughput,
+    pub lost: usize,
+    /// Mean of the last smoothed RTT of the paths.
+    pub rtt: Option<Duration>,
+}
+
+impl From<&TargetReport> for RunSummary {
+    fn from(report: &TargetReport) -> Self {
+        let rtt = match report.path_rtts.len() {
+            0 => None,
+            n => Some(
+                report
+                    .path_rtts
+                    .iter()
+                    .map(|(_, rtt)| *rtt)
+                    .sum::<Duration>()
+                    / n as u32,
+            ),
+        };
+
+        RunSummary {
+            delivered: report.delivered,
+            messages: report.messages,
+            handshake_time: report.handshake_time,
+            throughput: report.throughput,
+            lost: report.lost,
+            rtt,
+        }
+    }
+}
+
+/// Multipath run against a target side by side with the same workload run
+/// over a single path, with `--baseline`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub multipath: RunSummary,
+    pub single_path: RunSummary,
+}
+
+impl Comparison {
+    pub fn new(multipath: &TargetReport, single_path: &TargetReport) -> Self {
+        Comparison {
+            name: multipath.name.clone(),
+            multipath: multipath.into(),
+            single_path: single_path.into(),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let runs = [self.multipath, self.single_path];
+        let optional = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{:?}", d));
+
+        writeln!(f, "== {} multipath vs single-path baseline", self.name)?;
+        writeln!(f, "  {:<12} {:>16} {:>16}", "", "multipath", "single-path")?;
+
+        let rows: [(&str, [String; 2]); 6] = [
+            (
+                "delivered",
+                runs.map(|r| format!("{}/{}", r.delivered, r.messages)),
+            ),
+            ("handshake", runs.map(|r| optional(r.handshake_time))),
+            (
+                "duration",
+                runs.map(|r| format!("{:?}", r.throughput.elapsed)),
+            ),
+            (
+                "goodput",
+                runs.map(|r| format!("{:.3} Mbit/s", r.throughput.goodput() * 8.0 / 1e6)),
+            ),
+            ("lost", runs.map(|r| r.lost.to_string())),
+            ("rtt", runs.map(|r| optional(r.rtt))),
+        ];
+
+        for (name, [multipath, single_path]) in rows {
+            writeln!(f, "  {:<12} {:>16} {:>16}", name, multipath, single_path)?;
+        }
+
+        write!(
+            f,
+            "  application bytes delivered: {} multipath, {} single-path",
+            self.multipath.throughput.app_bytes, self.single_path.throughput.app_bytes
+        )
+    }
+}
+
+/// Handshake times of connections established one after the other, to
+/// measure how robust the handshake is, e.g. under loss.
+#[derive(Debug, Default)]
+pub struct HandshakeTrials {
+    /// Handshake time of each trial that completed its handshake.
+    pub times: Vec<Duration>,
+
+    /// Why each of the other trials failed.
+    pub failures: Vec<String>,
+}
+
+impl HandshakeTrials {
+    pub fn attempts(&self) -> usize {
+        self.times.len() + self.failures.len()
+    }
+
+    /// Share of the trials that completed their handshake.
+    pub fn success_rate(&self) -> f64 {
+        self.times.len() as f64 / self.attempts().max(1) as f64
+    }
+
+    /// Handshake time within which `p` percent of the completed handshakes
+    /// completed (nearest rank).
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut times = self.times.clone();
+        times.sort();
+
+        let rank = (p / 100.0 * times.len() as f64).ceil() as usize;
+        times.get(rank.clamp(1, times.len().max(1)) - 1).copied()
+    }
+
+    /// Delivered if every handshake completed, partial if some did.
+    pub fn outcome(&self) -> Outcome {
+        if self.failures.is_empty() {
+            Outcome::Delivered
+        } else if self.times.is_empty() {
+            Outcome::HandshakeFailed
+        } else {
+            Outcome::Partial
+        }
+    }
+
+    pub fn print(&self, name: &str, loss: f64) {
+        println!("== {} (handshakes, loss {:.1}%)", name, loss * 100.0);
+        println!(
+            "  completed: {}/{} ({:.1}%)",
+            self.times.len(),
+            self.attempts(),
+            self.success_rate() * 100.0
+        );
+
+        if let (Some(p50), Some(p90), Some(p99), Some(max)) = (
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.times.iter().max(),
+        ) {
+            println!(
+                "  handshake p50/p90/p99/max: {:?}/{:?}/{:?}/{:?}",
+                p50, p90, p99, max
+            );
+        }
+
+        let mut failures: Vec<(&String, usize)> = vec![];
+        for reason in &self.failures {
+            match failures.iter_mut().find(|(r, _)| *r == reason) {
+                Some((_, count)) => *count += 1,
+                None => failures.push((reason, 1)),
+            }
+        }
+
+        for (reason, count) in failures {
+            println!("  failed: {} x {}", count, reason);
+        }
+
+        let outcome = self.outcome();
+        let reason = format!(
+            "{} of {} handshakes completed",
+            self.times.len(),
+            self.attempts()
+        );
+        println!("{}", outcome.banner(&reason));
+    }
+}
+
+/// Writes the congestion window and the PTO count of every path as CSV
+/// rows, at most once per interval, stamped with the clock of the run. The
+/// anchor of the clock is on a comment line before the header row.
+#[derive(Debug)]
+pub struct CwndSampler {
+    out: LineWriter<File>,
+    clock: RunClock,
+    interval: Duration,
+    next: Instant,
+}
+
+impl CwndSampler {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        clock: RunClock,
+        interval: Duration,
+    ) -> std::io::Result<Self> {
+        let mut out = LineWriter::new(File::create(path)?);
+        writeln!(out, "# run clock {}", clock)?;
+        writeln!(
+            out,
+            "time_us,target,local,peer,cwnd,rtt_us,pacing_rate,ptos"
+        )?;
+
+        Ok(CwndSampler {
+            out,
+            clock,
+            interval,
+            next: clock.start(),
+        })
+    }
+
+    /// Time of the next sample.
+    pub fn next_sample(&self) -> Instant {
+        self.next
+    }
+
+    /// Writes a row per path of each connection if the interval elapsed at
+    /// `now`.
+    pub fn sample<'a, I>(&mut self, now: Instant, conns: I) -> std::io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a str, &'a quiche::Connection, &'a PtoCounter)>,
+    {
+        if now < self.next {
+            return Ok(());
+        }
+
+        self.next = now + self.interval;
+
+        let time_us = self.clock.time_us(now);
+
+        for (target, conn, ptos) in conns {
+            for stats in conn.path_stats() {
+                writeln!(
+                    self.out,
+                    "{},{},{},{},{},{},{},{}",
+                    time_us,
+                    target,
+                    stats.local_addr,
+                    stats.peer_addr,
+                    stats.cwnd,
+                    stats.rtt.as_micros(),
+                    pacing_rate(&stats),
+                    ptos.ptos((stats.local_addr, stats.peer_addr))
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Pacing rate of a path in bytes per second.
+///
+/// quiche does not expose it, this is how its pacer derives it from the
+/// congestion window of the built-in congestion controllers other than BBR.
+pub fn pacing_rate(stats: &quiche::PathStats) -> u64 {
+    const PACING_MULTIPLIER: f64 = 1.25;
+
+    // Not paced until the first RTT sample.
+    if stats.min_rtt.is_none() || stats.rtt.is_zero() {
+        return 0;
+    }
+
+    (PACING_MULTIPLIER * stats.cwnd as f64 / stats.rtt.as_secs_f64()) as u64
+}
+
+/// Datagrams and bytes that went through one client socket, whatever the
+/// connection and path they belong to.
+#[derive(Clone, Debug)]
+pub struct SocketStats {
+    pub local_addr: SocketAddr,
+    pub sent: u64,
+    pub sent_bytes: u64,
+    pub recv: u64,
+    pub recv_bytes: u64,
+}
+
+impl SocketStats {
+    pub fn new(local_addr: SocketAddr) -> Self {
+        SocketStats {
+            local_addr,
+            sent: 0,
+            sent_bytes: 0,
+            recv: 0,
+            recv_bytes: 0,
+        }
+    }
+
+    pub fn on_send(&mut self, len: usize) {
+        self.sent += 1;
+        self.sent_bytes += len as u64;
+    }
+
+    pub fn on_recv(&mut self, len: usize) {
+        self.recv += 1;
+        self.recv_bytes += len as u64;
+    }
+
+    /// Whether nothing was sent nor received on the socket, e.g. because
+    /// its path never validated.
+    pub fn is_unused(&self) -> bool {
+        self.sent == 0 && self.recv == 0
+    }
+}
+
+/// How a run ended, each way with its own exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every message was delivered.
+    Delivered,
+
+    /// The connection ended, e.g. on a timeout, with messages outstanding.
+    Partial,
+
+    /// The connection ended before the handshake completed.
+    HandshakeFailed,
+
+    /// The peer closed the connection with a transport or application error.
+    PeerError,
+
+    /// A local error, such as a failing socket, stopped the run.
+    LocalError,
+
+    /// The connection stopped sending while it could, see
+    /// `--send-watchdog` and `--strict`.
+    SendStalled,
+
+    /// The peer does not allow the paths of the workload, with
+    /// `--require-multipath`.
+    MultipathUnavailable,
+
+    /// The datagrams of the connection looked meant for another one, with
+    /// `--strict`.
+    Misdelivered,
+
+    /// The run was interrupted by a signal.
+    Interrupted,
+}
+
+impl Outcome {
+    /// Exit code of the client binary.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Delivered => 0,
+            Outcome::Partial => 2,
+            Outcome::HandshakeFailed => 3,
+            Outcome::PeerError => 4,
+            Outcome::LocalError => 5,
+            Outcome::SendStalled => 6,
+            Outcome::MultipathUnavailable => 7,
+            Outcome::Misdelivered => 8,
+            Outcome::Interrupted => 130,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Delivered => "delivered",
+            Outcome::Partial => "partial",
+            Outcome::HandshakeFailed => "handshake failed",
+            Outcome::PeerError => "peer error",
+            Outcome::LocalError => "local error",
+            Outcome::SendStalled => "send stalled",
+            Outcome::MultipathUnavailable => "multipath unavailable",
+            Outcome::Misdelivered => "misdelivered",
+            Outcome::Interrupted => "interrupted",
+        }
+    }
+
+    /// Final line printed by the client binary.
+    pub fn banner(self, reason: &str) -> String {
+        format!(
+            "result: {} (exit {}): {}",
+            self.name(),
+            self.exit_code(),
+            reason
+        )
+    }
+}
+
+/// How many paths the connection probed, validated and actually used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathUsage {
+    /// Paths probed, including the initial path once the handshake
+    /// completed.
+    pub probed: usize,
+
+    /// Paths validated, including the initial path once the handshake
+    /// completed.
+    pub validated: usize,
+
+    /// Paths on which at least one byte of stream data was received.
+    pub used: usize,
+}
+
+/// Bytes sent on a path and how many of them got through, across
+/// reconnections.
+///
+/// quiche counts neither the bytes acknowledged nor those in flight per
+/// path, so the bytes not declared lost stand for those acknowledged: the
+/// bytes still in flight when the connection closed are counted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathDelivery {
+    pub path: (SocketAddr, SocketAddr),
+    pub sent_bytes: u64,
+    pub lost_bytes: u64,
+}
+
+impl PathDelivery {
+    /// Bytes sent and not declared lost.
+    pub fn acked_bytes(&self) -> u64 {
+        self.sent_bytes.saturating_sub(self.lost_bytes)
+    }
+
+    /// Share of the bytes sent that were not declared lost.
+    pub fn ratio(&self) -> f64 {
+        if self.sent_bytes == 0 {
+            return 1.0;
+        }
+
+        self.acked_bytes() as f64 / self.sent_bytes as f64
+    }
+}
+
+impl fmt::Display for PathDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}): {}/{} bytes acked ({:.1}%), {} lost",
+            self.path.0,
+            self.path.1,
+            self.acked_bytes(),
+            self.sent_bytes,
+            self.ratio() * 100.0,
+            self.lost_bytes
+        )
+    }
+}
+
+/// Keep-alives sent on a path on which nothing was received.
+#[derive(Clone, Debug)]
+pub struct KeepaliveStats {
+    pub path: (SocketAddr, SocketAddr),
+    pub keepalives: u64,
+
+    /// Validations requested after keep-alives went unanswered.
+    pub reprobes: u64,
+}
+
+/// State of a connection that sent nothing for several PTOs while it had
+/// stream data to send and room in its congestion window.
+#[derive(Clone, Debug)]
+pub struct SendStall {
+    /// Time since the start of the connection.
+    pub at: Duration,
+
+    /// Time since the last datagram was sent.
+    pub idle: Duration,
+
+    /// Probe timeout of the active path.
+    pub pto: Duration,
+
+    /// How late the client was stepped after the time it asked for.
+    pub late_by: Option<Duration>,
+
+    /// Time left until the timer of quiche expires.
+    pub quiche_timeout: Option<Duration>,
+
+    /// Streams quiche reports as writable.
+    pub writable: Vec<u64>,
+
+    pub paths: Vec<StalledPath>,
+}
+
+/// State of one path of a stalled connection.
+#[derive(Clone, Debug)]
+pub struct StalledPath {
+    pub path: (SocketAddr, SocketAddr),
+    pub active: bool,
+    pub cwnd: usize,
+
+    /// Time elapsed since the pacing deadline of the last datagram sent on
+    /// the path, if any was sent.
+    pub since_pacing_deadline: Option<Duration>,
+
+    /// Streams of the messages of the path with data not accepted by quiche
+    /// yet.
+    pub blocked_streams: Vec<u64>,
+}
+
+/// Outcome of the run against one target.
+#[derive(Debug)]
+pub struct TargetReport {
+    pub name: String,
+    pub addr: SocketAddr,
+
+    /// Number of messages to deliver.
+    pub messages: usize,
+
+    /// Number of messages whose echo was fully received.
+    pub delivered: usize,
+
+    /// ID of the run announced to the server, with `--run-id` or generated.
+    pub run_id: Option<RunId>,
+
+    /// Whether the server acknowledged the completion message sent before
+    /// closing.
+    pub completion_acked: bool,
+
+    /// Paths probed, validated and used, once the connection is closed.
+    pub path_usage: PathUsage,
+
+    /// Paths of the messages found in the path cache, and paths of the
+    /// messages, if a path cache was used.
+    pub path_cache_hits: Option<(usize, usize)>,
+
+    /// Minimum RTT of each validated path that carried acknowledged data,
+    /// across reconnections, to update the path cache.
+    pub path_rtts: Vec<((SocketAddr, SocketAddr), Duration)>,
+
+    /// Last MTU of each path, raised by path MTU discovery if enabled.
+    pub path_mtus: Vec<((SocketAddr, SocketAddr), usize)>,
+
+    /// Probe timeouts and losses of each path, across reconnections.
+    pub path_ptos: Vec<PathPtos>,
+
+    /// Bytes sent on each path and acknowledged, across reconnections.
+    pub path_delivery: Vec<PathDelivery>,
+
+    /// Quality of the validated paths of the last connection, best first.
+    pub path_quality: Vec<PathQuality>,
+
+    /// One-way delays of the paths, with `--owd`.
+    pub one_way_delays: Vec<PathOwd>,
+
+    /// Bytes carried during each window of `--path-schedule`.
+    pub path_windows: Vec<WindowUsage>,
+
+    /// Times the connection and its streams were blocked by the flow
+    /// control of the server, with `--flow-blocking`.
+    pub flow_blocking: Option<FlowBlockStats>,
+
+    /// Congestion window of each path when first seen, in bytes.
+    pub initial_cwnds: Vec<((SocketAddr, SocketAddr), usize)>,
+
+    /// New paths on which the server reached the anti-amplification limit
+    /// before they were validated.
+    pub amplification_limited: Vec<(SocketAddr, SocketAddr)>,
+
+    /// Streams read per step of the client.
+    pub stream_reads: ReadStats,
+
+    /// Bytes exchanged by the first connection before the handshake was
+    /// confirmed.
+    pub pre_validation: PreValidation,
+
+    /// Time between the start of the connection, including the creation of
+    /// its first packet, and the handshake completion.
+    pub handshake_time: Option<Duration>,
+
+    /// How much loss recovery the handshake needed.
+    pub handshake_recovery: Option<HandshakeRecovery>,
+
+    /// Reserved version the handshake started with, see `--grease`.
+    pub grease_version: Option<u32>,
+
+    /// Messages whose echo was not fully received.
+    pub outstanding: Vec<usize>,
+
+    /// max_ack_delay advertised by the client and by the peer, in
+    /// milliseconds.
+    pub max_ack_delay: Option<(u64, u64)>,
+
+    /// Time between sending each message and receiving its full echo.
+    pub latency: Histogram,
+
+    /// Time the messages waited for a slot before being sent, if their
+    /// concurrency is limited.
+    pub queue_wait: Option<Histogram>,
+
+    /// Completion time of every message, with `--hist-buckets`.
+    pub completion_times: Option<CompletionTimes>,
+
+    /// Messages that released their slot because their echo took too long,
+    /// and did not complete since.
+    pub stuck_messages: usize,
+
+    /// Messages whose echo is not the transform expected with
+    /// `--expect-transform`, or not the message with its sequence number
+    /// with `--sequence`, not counted as delivered.
+    pub mismatched: Vec<usize>,
+
+    /// Order in which the echoes completed, with `--sequence`.
+    pub ordering: Option<OrderStats>,
+
+    /// Last messages completed, at most `RECENT_RECORDS`.
+    pub recent: VecDeque<MessageRecord>,
+
+    /// Where every completed message is written.
+    pub records: Option<RecordWriter>,
+
+    /// Bytes of the messages sent, and the bytes actually written to their
+    /// streams once padded.
+    pub logical_bytes: u64,
+    pub padded_bytes: u64,
+
+    /// Number of packets declared lost.
+    pub lost: usize,
+
+    /// Bursts of packets sent, limited by the send quantum.
+    pub bursts: BurstStats,
+
+    /// Bytes sent on the wire and application bytes delivered, across
+    /// reconnections.
+    pub throughput: Throughput,
+
+    /// Datagrams quiche rejected, e.g. because they belong to another
+    /// connection, across reconnections.
+    pub rejected_datagrams: u64,
+
+    /// Destination connection IDs seen on each socket, once too many
+    /// datagrams were rejected.
+    pub misdelivery: Option<String>,
+
+    /// Datagrams received on a socket whose address pair is not one of the
+    /// configured paths, e.g. because of a misconfigured NAT.
+    pub stray_datagrams: u64,
+
+    /// Time between detecting the loss of the active path and the first
+    /// bytes received on the standby path.
+    pub failover_time: Option<Duration>,
+
+    /// Migrations to another path and their outcomes.
+    pub migrations: MigrationStats,
+
+    /// Connection IDs issued to the server, for its paths.
+    pub cids: CidStats,
+
+    /// Keep-alives sent on the idle paths.
+    pub keepalives: Vec<KeepaliveStats>,
+
+    /// PINGs sent to keep the idle connection open, with `--keepalive-ms`.
+    pub keepalive_pings: u64,
+
+    /// Outgoing datagrams impaired, with `--tx-impair`.
+    pub tx_impaired: Option<ImpairStats>,
+
+    /// Times the connection stopped sending while it could.
+    pub send_stalls: Vec<SendStall>,
+
+    /// Why every message was sent on the initial path.
+    pub single_path: Option<String>,
+
+    /// Latency under load, for the bufferbloat measurement.
+    pub load_samples: Vec<LoadSample>,
+
+    /// Runs of the file transfer, the single stream one first.
+    pub transfer_runs: Vec<TransferRun>,
+
+    /// Why the previous connections were interrupted, each followed by a
+    /// reconnection.
+    pub interruptions: Vec<String>,
+
+    /// Why the connection ended before delivering every message.
+    pub error: Option<String>,
+
+    /// How the connection ended if not with every message delivered, it
+    /// ended with messages outstanding if `None`.
+    pub failure: Option<Outcome>,
+}
+
+impl TargetReport {
+    pub fn new(name: &str, addr: SocketAddr, messages: usize) -> Self {
+        TargetReport {
+            name: name.to_string(),
+            addr,
+            messages,
+            delivered: 0,
+            run_id: None,
+            completion_acked: false,
+            path_usage: PathUsage::default(),
+            path_cache_hits: None,
+            path_rtts: Vec::new(),
+            path_mtus: Vec::new(),
+            path_ptos: Vec::new(),
+            path_delivery: Vec::new(),
+            path_quality: Vec::new(),
+            one_way_delays: Vec::new(),
+            path_windows: Vec::new(),
+            flow_blocking: None,
+            initial_cwnds: Vec::new(),
+            amplification_limited: Vec::new(),
+            stream_reads: ReadStats::default(),
+            pre_validation: PreValidation::new(false),
+            handshake_time: None,
+            handshake_recovery: None,
+            grease_version: None,
+            outstanding: Vec::new(),
+            max_ack_delay: None,
+            latency: Histogram::default(),
+            queue_wait: None,
+            completion_times: None,
+            stuck_messages: 0,
+            mismatched: Vec::new(),
+            ordering: None,
+            recent: VecDeque::with_capacity(RECENT_RECORDS),
+            records: None,
+            logical_bytes: 0,
+            padded_bytes: 0,
+            lost: 0,
+            throughput: Throughput::default(),
+            bursts: BurstStats::default(),
+            rejected_datagrams: 0,
+            misdelivery: None,
+            stray_datagrams: 0,
+            failover_time: None,
+            migrations: MigrationStats::default(),
+            cids: CidStats::default(),
+            keepalives: Vec::new(),
+            keepalive_pings: 0,
+            tx_impaired: None,
+            send_stalls: Vec::new(),
+            single_path: None,
+            load_samples: Vec::new(),
+            transfer_runs: Vec::new(),
+            interruptions: Vec::new(),
+            error: None,
+            failure: None,
+        }
+    }
+
+    /// Goodput of the file striped across the paths against the best one
+    /// of the runs on a single path, once both completed.
+    pub fn striping_gain(&self) -> Option<f64> {
+        let striped = self.transfer_runs.iter().find(|r| r.paths.len() > 1)?;
+        let best = self
+            .transfer_runs
+            .iter()
+            .filter(|r| r.paths.len() == 1)
+            .map(TransferRun::goodput)
+            .max_by(f64::total_cmp)?;
+
+        Some(striped.goodput() / best)
+    }
+
+    /// Accounts a completed message, only the last ones are kept in memory.
+    pub fn record(&mut self, record: MessageRecord) {
+        self.latency.record(record.latency);
+
+        if let Some(times) = &mut self.completion_times {
+            times.record(record.latency);
+        }
+
+        if let Some(queue_wait) = &mut self.queue_wait {
+            queue_wait.record(record.queue_wait);
+        }
+
+        if let Some(records) = &mut self.records {
+            if let Err(e) = records.write(&record, Instant::now()) {
+                error!("writing record failed: {}", e);
+            }
+        }
+
+        if self.recent.len() == RECENT_RECORDS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(record);
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        match self.failure {
+            Some(v) => v,
+
+            None if self.delivered < self.messages => Outcome::Partial,
+
+            None => Outcome::Delivered,
+        }
+    }
+
+    /// Why the run against the target ended.
+    pub fn reason(&self) -> String {
+        match self.outcome() {
+            Outcome::Delivered => format!("all {} messages delivered", self.messages),
+
+            outcome => format!(
+                "{}/{} messages delivered, {}",
+                self.delivered,
+                self.messages,
+                self.error.as_deref().unwrap_or(outcome.name())
+            ),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("== {} ({})", self.name, self.addr);
+        println!("  delivered: {}/{}", self.delivered, self.messages);
+
+        if let Some(run_id) = &self.run_id {
+            match self.completion_acked {
+                true => println!("  run id: {} (completion acknowledged)", run_id),
+                false => println!("  run id: {}", run_id),
+            }
+        }
+
+        println!(
+            "  paths probed/validated/used: {}/{}/{}",
+            self.path_usage.probed, self.path_usage.validated, self.path_usage.used
+        );
+
+        if let Some((hits, paths)) = self.path_cache_hits {
+            println!("  path cache hits: {}/{} paths", hits, paths);
+        }
+
+        if !self.outstanding.is_empty() {
+            let outstanding: Vec<String> = self.outstanding.iter().map(|i| i.to_string()).collect();
+            println!("  outstanding messages: {}", outstanding.join(", "));
+        }
+
+        match (self.handshake_time, self.handshake_recovery) {
+            (Some(time), Some(recovery)) => println!("  handshake: {:?}, {}", time, recovery),
+
+            (Some(time), None) => println!("  handshake: {:?}", time),
+
+            _ => (),
+        }
+
+        if let Some(version) = self.grease_version {
+            println!(
+                "  grease version {:#010x}: {}",
+                version,
+                match self.handshake_time {
+                    Some(_) => "handshake completed after version negotiation",
+                    None => "handshake did not complete",
+                }
+            );
+        }
+
+        if let Some((local, peer)) = self.max_ack_delay {
+            println!("  max ack delay client/peer: {}ms/{}ms", local, peer);
+        }
+
+        if let (Some(min), Some(avg), Some(max)) =
+            (self.latency.min(), self.latency.mean(), self.latency.max())
+        {
+            println!("  latency min/avg/max: {:?}/{:?}/{:?}", min, avg, max);
+
+            for (bound, count) in self.latency.buckets() {
+                match bound {
+                    Some(bound) => println!("    < {:?}: {}", Duration::from_micros(bound), count),
+                    None => println!("    more: {}", count),
+                }
+            }
+        }
+
+        if let Some(times) = &self.completion_times {
+            let lines = times.render();
+
+            if !lines.is_empty() {
+                println!("  completion times:");
+                for line in lines {
+                    println!("    {}", line);
+                }
+            }
+        }
+
+        if let Some(queue_wait) = &self.queue_wait {
+            if let (Some(min), Some(avg), Some(max)) =
+                (queue_wait.min(), queue_wait.mean(), queue_wait.max())
+            {
+                println!("  queue wait min/avg/max: {:?}/{:?}/{:?}", min, avg, max);
+            }
+        }
+
+        if !self.mismatched.is_empty() {
+            let mismatched: Vec<String> = self.mismatched.iter().map(|i| i.to_string()).collect();
+            println!("  mismatched echoes: {}", mismatched.join(", "));
+        }
+
+        if let Some(ordering) = &self.ordering {
+            println!("  ordering: {}", ordering);
+        }
+
+        if self.stuck_messages > 0 {
+            println!("  stuck messages: {}", self.stuck_messages);
+        }
+
+        if !self.recent.is_empty() {
+            println!("  last {} messages:", self.recent.len());
+            for record in &self.recent {
+                print!("    #{} stream {}", record.index, record.stream_id);
+                if let Some(frame) = record.frame {
+                    print!(" frame {}", frame);
+                }
+                print!(" latency {:?}", record.latency);
+                match record.paths.paths().len() {
+                    0 => println!(),
+                    1 => println!(" on path {}", record.paths),
+                    _ => println!(" on paths {}", record.paths),
+                }
+            }
+        }
+
+        if self.padded_bytes != self.logical_bytes {
+            println!(
+                "  bytes logical/padded: {}/{}",
+                self.logical_bytes, self.padded_bytes
+            );
+        }
+
+        println!("  lost packets: {}", self.lost);
+        println!("  bursts: {}", self.bursts);
+        println!(
+            "  throughput: {:.3} Mbit/s ({} bytes on the wire)",
+            self.throughput.throughput() * 8.0 / 1e6,
+            self.throughput.wire_bytes
+        );
+        println!(
+            "  goodput: {:.3} Mbit/s ({} application bytes delivered)",
+            self.throughput.goodput() * 8.0 / 1e6,
+            self.throughput.app_bytes
+        );
+
+        if self.stray_datagrams > 0 {
+            println!("  datagrams on unknown paths: {}", self.stray_datagrams);
+        }
+
+        if self.rejected_datagrams > 0 {
+            println!(
+                "  datagrams rejected by quiche: {}",
+                self.rejected_datagrams
+            );
+        }
+
+        if let Some(misdelivery) = &self.misdelivery {
+            println!("  misdelivery: {}", misdelivery);
+        }
+
+        if let Some(failover_time) = self.failover_time {
+            println!("  failover to standby path: {:?}", failover_time);
+        }
+
+        if self.migrations.attempted > 0 {
+            println!("  migrations: {}", self.migrations);
+        }
+
+        if self.cids.cids_issued > 0 {
+            println!("  connection IDs: {}", self.cids);
+        }
+
+        if !self.keepalives.is_empty() {
+            println!("  keep-alives:");
+            for stats in &self.keepalives {
+                println!(
+                    "    ({}, {}): {} sent, {} re-probes",
+                    stats.path.0, stats.path.1, stats.keepalives, stats.reprobes
+                );
+            }
+        }
+
+        if let Some(impaired) = self.tx_impaired {
+            println!("  tx impairment: {}", impaired);
+        }
+
+        if self.keepalive_pings > 0 {
+            println!("  keepalive PINGs: {}", self.keepalive_pings);
+        }
+
+        if !self.path_mtus.is_empty() {
+            println!("  path MTUs:");
+            for (path, mtu) in &self.path_mtus {
+                println!("    ({}, {}): {}", path.0, path.1, mtu);
+            }
+        }
+
+        if self.path_ptos.iter().any(|p| p.ptos > 0 || p.lost > 0) {
+            println!("  path PTOs:");
+            for path in &self.path_ptos {
+                println!("    {}", path);
+            }
+        }
+
+        if !self.path_delivery.is_empty() {
+            println!("  path delivery:");
+            for delivery in &self.path_delivery {
+                println!("    {}", delivery);
+            }
+        }
+
+        if self.path_quality.len() > 1 {
+            println!("  path quality:");
+            for quality in &self.path_quality {
+                println!("    {}", quality);
+            }
+        }
+
+        if !self.one_way_delays.is_empty() {
+            println!("  one-way delays:");
+            for delays in &self.one_way_delays {
+                println!("    {}", delays);
+            }
+
+            if let Some((forward, reverse)) = owd::spread(&self.one_way_delays) {
+                println!(
+                    "    spread across paths: forward {:.2}ms, reverse {:.2}ms",
+                    forward / 1000.0,
+                    reverse / 1000.0
+                );
+            }
+        }
+
+        if !self.path_windows.is_empty() {
+            println!("  path schedule:");
+            for window in &self.path_windows {
+                println!("    {}", window);
+            }
+        }
+
+        if let Some(flow) = &self.flow_blocking {
+            println!("  flow control: connection {}", flow.connection);
+
+            let longest = flow.longest_streams();
+            for (stream_id, stats) in &longest {
+                println!("    stream {}: {}", stream_id, stats);
+            }
+
+            if flow.streams.len() > longest.len() {
+                println!(
+                    "    ... {} more streams blocked",
+                    flow.streams.len() - longest.len()
+                );
+            }
+        }
+
+        if !self.initial_cwnds.is_empty() {
+            println!("  initial cwnds:");
+            for (path, cwnd) in &self.initial_cwnds {
+                println!("    ({}, {}): {} bytes", path.0, path.1, cwnd);
+            }
+        }
+
+        if !self.amplification_limited.is_empty() {
+            println!("  paths limited by anti-amplification before validation:");
+            for path in &self.amplification_limited {
+                println!("    ({}, {})", path.0, path.1);
+            }
+        }
+
+        if self.pre_validation.sent > 0 {
+            println!("  pre-validation: {}", self.pre_validation);
+        }
+
+        if self.stream_reads.wakeups > 0 {
+            println!("  streams read per step: {}", self.stream_reads);
+        }
+
+        for stall in &self.send_stalls {
+            println!(
+                "  send stall at {:?}: idle {:?} (pto {:?}), stepped {:?} late, quiche timeout in {:?}",
+                stall.at, stall.idle, stall.pto, stall.late_by, stall.quiche_timeout
+            );
+            println!("    writable streams: {:?}", stall.writable);
+
+            for path in &stall.paths {
+                println!(
+                    "    ({}, {}){}: cwnd {}, pacing deadline {:?} ago, blocked streams {:?}",
+                    path.path.0,
+                    path.path.1,
+                    if path.active { " active" } else { "" },
+                    path.cwnd,
+                    path.since_pacing_deadline,
+                    path.blocked_streams
+                );
+            }
+        }
+
+        if let Some(reason) = &self.single_path {
+            println!("  multipath disabled: {}", reason);
+        }
+
+        if !self.load_samples.is_empty() {
+            println!("  latency under load:");
+            for sample in &self.load_samples {
+                println!(
+                    "    t={:?} queued={} latency={:?}",
+                    sample.at, sample.queued_bytes, sample.latency
+                );
+            }
+        }
+
+        if let Some(baseline) = self.transfer_runs.first() {
+            println!("  file transfer of {} bytes:", baseline.size);
+            for run in &self.transfer_runs {
+                match run.paths.len() {
+                    0 => print!(
+                        "    {} stream{}: ",
+                        run.streams,
+                        if run.streams == 1 { "" } else { "s" }
+                    ),
+                    1 => print!("    path ({}, {}): ", run.paths[0].0, run.paths[0].1),
+                    n => print!("    striped over {} paths: ", n),
+                }
+                print!("{:?}, {:.2} Mbit/s", run.elapsed, run.goodput() * 8.0 / 1e6);
+                if run.paths.len() > 1 {
+                    if let Some(gain) = self.striping_gain() {
+                        print!(" ({:.2}x the best single path)", gain);
+                    }
+                } else if run.paths.is_empty() && run.streams != baseline.streams {
+                    print!(" ({:.2}x)", run.goodput() / baseline.goodput());
+                }
+                println!(", sha256 {}", if run.digest_ok { "ok" } else { "mismatch" });
+            }
+        }
+
+        if !self.interruptions.is_empty() {
+            println!(
+                "  interruptions: {} ({})",
+                self.interruptions.len(),
+                self.interruptions.join(", ")
+            );
+        }
+
+        if let Some(error) = &self.error {
+            println!("  error: {}", error);
+        }
+    }
+}
+
+/// Summary of a client run, printed once every connection is closed.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Clock of the run, whose anchor the other outputs carry as well.
+    pub clock: Option<RunClock>,
+
+    pub targets: Vec<TargetReport>,
+
+    /// Traffic of each client socket, shared by all the targets.
+    pub sockets: Vec<SocketStats>,
+
+    /// Multipath runs compared with their single-path baseline, with
+    /// `--baseline`. The outcome is the one of the multipath runs.
+    pub baselines: Vec<Comparison>,
+}
+
+impl Report {
+    pub fn print(&self) {
+        if let Some(clock) = &self.clock {
+            println!("== run clock {}", clock);
+        }
+
+        for target in &self.targets {
+            target.print();
+        }
+
+        for comparison in &self.baselines {
+            println!("{}", comparison);
+        }
+
+        if !self.sockets.is_empty() {
+            println!("== sockets");
+        }
+
+        for (i, socket) in self.sockets.iter().enumerate() {
+            if socket.is_unused() {
+                println!("  #{} {}: unused", i, socket.local_addr);
+                continue;
+            }
+
+            println!(
+                "  #{} {}: sent {} ({} bytes), received {} ({} bytes)",
+                i,
+                socket.local_addr,
+                socket.sent,
+                socket.sent_bytes,
+                socket.recv,
+                socket.recv_bytes
+            );
+        }
+
+        let (outcome, reason) = self.outcome();
+        println!("{}", outcome.banner(&reason));
+    }
+
+    /// Outcome of the run, the one of the worst target, with its reason.
+    pub fn outcome(&self) -> (Outcome, String) {
+        let Some(target) = self.targets.iter().max_by_key(|t| t.outcome().exit_code()) else {
+            return (Outcome::Delivered, "no target".to_string());
+        };
+
+        let reason = match self.targets.len() {
+            1 => target.reason(),
+            _ => format!("{}: {}", target.name, target.reason()),
+        };
+
+        (target.outcome(), reason)
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::Path,
+};
+
+use log::warn;
+
+use crate::shared::{file_transfer::hex, payload::decode_hex};
+
+/// Progress of a run against each target, saved as it goes so that a client
+/// restarted after a crash resumes it: the TLS session of each target is
+/// resumed and only the messages not delivered yet are sent.
+///
+/// quiche cannot serialize a connection, so a restarted client always opens
+/// a new connection, with new connection IDs and new paths. Only the session
+/// is resumed, which saves the certificate exchange. Messages in flight when
+/// the process stopped are sent again.
+///
+/// The file has one line per target,
+/// `<name> <addr> <messages> <session> <delivered>`, the session being the
+/// hex encoded ticket or `-` if the server sent none, and the messages
+/// delivered a comma separated list of indices or `-`. Lines starting with
+/// `#` are ignored.
+#[derive(Debug, Default)]
+pub struct ResumeState {
+    entries: Vec<TargetProgress>,
+}
+
+/// Progress of the run against a target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetProgress {
+    pub name: String,
+    pub addr: SocketAddr,
+
+    /// Number of messages of the run, a different run is not resumed.
+    pub messages: usize,
+
+    /// Session ticket of the last connection, for `set_session`.
+    pub session: Option<Vec<u8>>,
+
+    /// Indices of the messages whose echo was received.
+    pub delivered: Vec<usize>,
+}
+
+impl ResumeState {
+    pub fn new() -> Self {
+        ResumeState::default()
+    }
+
+    /// Reads the progress saved in `path`. A missing file is a new run,
+    /// invalid lines are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut state = ResumeState::new();
+
+        let file = match File::open(path) {
+            Ok(v) => v,
+
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(state),
+
+            Err(e) => return Err(e),
+        };
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some(entry) => state.entries.push(entry),
+
+                None => warn!("ignoring invalid resume state line '{}'", line),
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Writes the progress of every target to `path`, replacing its content.
+    ///
+    /// The state is written to a temporary file renamed over `path`, so that
+    /// a crash while saving keeps the previous state.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut out = BufWriter::new(File::create(&tmp)?);
+
+        writeln!(out, "# name addr messages session delivered")?;
+
+        for entry in &self.entries {
+            let session = entry.session.as_deref().map_or("-".to_string(), hex);
+            let delivered = match entry.delivered.is_empty() {
+                true => "-".to_string(),
+                false => entry
+                    .delivered
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            };
+
+            writeln!(
+                out,
+                "{} {} {} {} {}",
+                entry.name, entry.addr, entry.messages, session, delivered
+            )?;
+        }
+
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(tmp, path)
+    }
+
+    pub fn entries(&self) -> &[TargetProgress] {
+        &self.entries
+    }
+
+    /// Progress saved for the target `name` at `addr` by a run of
+    /// `messages` messages.
+    pub fn get(&self, name: &str, addr: SocketAddr, messages: usize) -> Option<&TargetProgress> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name && e.addr == addr && e.messages == messages)
+    }
+
+    /// Records the progress of a target, replacing its previous entry.
+    /// Returns whether it changed.
+    pub fn update(&mut self, progress: TargetProgress) -> bool {
+        let previous = self
+            .entries
+            .iter()
+            .position(|e| e.name == progress.name && e.addr == progress.addr);
+
+        match previous {
+            Some(i) if self.entries[i] == progress => false,
+
+            Some(i) => {
+                self.entries[i] = progress;
+                true
+            }
+
+            None => {
+                self.entries.push(progress);
+                true
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<TargetProgress> {
+    let mut fields = line.split_whitespace();
+
+    let name = fields.next()?.to_string();
+    let addr = fields.next()?.parse().ok()?;
+    let messages = fields.next()?.parse().ok()?;
+
+    let session = match fields.next()? {
+        "-" => None,
+        session => Some(decode_hex(session).ok()?),
+    };
+
+    let delivered = match fields.next()? {
+        "-" => Vec::new(),
+        list => list
+            .split(',')
+            .map(|i| i.parse().ok().filter(|i| *i < messages))
+            .collect::<Option<_>>()?,
+    };
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some(TargetProgress {
+        name,
+        addr,
+        messages,
+        session,
+        delivered,
+    })
+}
@@ -1,65 +1,124 @@
-use log::{error, info, trace};
+pub mod admission;
+pub mod amplification;
+pub mod apps;
+pub mod burst;
+pub mod cbor;
+pub mod cids;
+pub mod conn;
+pub mod control;
+pub mod demux;
+pub mod encoding;
+pub mod fair_share;
+pub mod file_transfer;
+pub mod flow_block;
+pub mod frame;
+pub mod impair;
+pub mod json;
+pub mod keepalive;
+pub mod liveness;
+pub mod log_sample;
+pub mod loop_timing;
+pub mod migration;
+#[cfg(feature = "netns-tests")]
+pub mod netns;
+pub mod owd;
+pub mod padding;
+pub mod path_quality;
+pub mod path_schedule;
+pub mod payload;
+pub mod ports;
+pub mod progress;
+pub mod pto;
+pub mod read_budget;
+pub mod rng;
+pub mod run_clock;
+pub mod sequence;
+pub mod server_paths;
+pub mod shaper;
+pub mod stream_writer;
+pub mod testing;
+pub mod transform;
+pub mod transport;
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, trace, Level};
 use ring::rand::SecureRandom;
 
-pub const MAX_NUMBER_SOCKETS: usize = 20;
-
-pub fn read_loop(
-    events: &mio::Events,
-    conn: &mut quiche::Connection,
-    socket: &mio::net::UdpSocket,
-    buf: &mut [u8],
-) {
-    'read: loop {
-        if events.is_empty() {
-            trace!("timed out");
-
-            conn.on_timeout();
+use burst::BurstLimit;
+use impair::{Impairment, TxImpair};
+use log_sample::PacketLog;
 
-            break 'read;
-        }
-
-        let (len, from) = match socket.recv_from(buf) {
-            Ok(v) => v,
-
-            Err(e) => {
-                // There are no more UDP packets to read, so end the read
-                // loop.
-                if e.kind() == std::io::ErrorKind::WouldBlock {
-                    trace!("recv() would block");
-                    break 'read;
-                }
+pub const MAX_NUMBER_SOCKETS: usize = 20;
 
-                panic!("recv() failed: {:?}", e);
+/// Reserved QUIC version (RFC 9000 section 15) offered by `--grease`, which
+/// servers must answer with a version negotiation.
+pub const GREASE_VERSION: u32 = 0x1a2a_3a4a;
+
+/// Waits for events like `mio::Poll::poll`, polling again for the time left
+/// when a signal interrupts the wait instead of failing with `Interrupted`.
+pub fn poll_events(
+    poll: &mut mio::Poll,
+    events: &mut mio::Events,
+    timeout: Option<Duration>,
+) -> io::Result<()> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+
+    loop {
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+
+        match poll.poll(events, timeout) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                trace!("poll interrupted by a signal");
             }
-        };
-
-        let pkt_buf = &mut buf[..len];
 
-        let recv_info = quiche::RecvInfo {
-            from,
-            to: socket.local_addr().unwrap(),
-        };
+            result => return result,
+        }
+    }
+}
 
-        let read = match conn.recv(pkt_buf, recv_info) {
-            Ok(v) => v,
+/// Whether `stream_id` is a unidirectional stream (RFC 9000 section 2.1).
+pub fn is_uni_stream(stream_id: u64) -> bool {
+    stream_id & 0x2 != 0
+}
 
-            Err(_) => {
-                // An error occurred, handle it.
-                break;
-            }
-        };
+/// Sends the pending packets of `conn` on every path, a burst of at most
+/// the send quantum of each as limited by `burst`, impaired by `impair` if
+/// set, and returns the number of bytes sent. Their logs are sampled by
+/// `packet_log`.
+pub fn write_loop(
+    conn: &mut quiche::Connection,
+    sockets: &[mio::net::UdpSocket],
+    out: &mut [u8],
+    mut impair: Option<&mut TxImpair>,
+    burst: &mut BurstLimit,
+    packet_log: &mut PacketLog,
+) -> usize {
+    let mut sent = 0;
+    let mtu = conn.max_send_udp_payload_size();
 
-        info!("{} processed {} bytes", conn.trace_id(), read);
-    }
-}
+    burst.begin();
 
-pub fn write_loop(conn: &mut quiche::Connection, sockets: &[mio::net::UdpSocket], out: &mut [u8]) {
     for i in (0..sockets.len()).rev() {
         let socket = &sockets[i];
         let local_addr = socket.local_addr().unwrap();
         // loop on different paths
         for peer_addr in conn.paths_iter(local_addr) {
+            let budget = burst.budget(conn.send_quantum_on_path(local_addr, peer_addr), mtu);
+            let mut bytes = 0;
+            let mut packets = 0;
+            let mut last_at = None;
+            let mut capped = None;
+
             loop {
+                if bytes >= budget {
+                    capped = last_at;
+                    break;
+                }
+
                 let (write, send_info) =
                     match conn.send_on_path(out, Some(local_addr), Some(peer_addr)) {
                         Ok(v) => v,
@@ -77,27 +136,65 @@ pub fn write_loop(conn: &mut quiche::Connection, sockets: &[mio::net::UdpSocket]
                         }
                     };
 
-                if let Err(e) = socket.send_to(&out[..write], send_info.to) {
-                    if e.kind() == std::io::ErrorKind::WouldBlock {
-                        trace!("send() would block");
-                        break;
+                bytes += write;
+                packets += 1;
+                last_at = Some(send_info.at);
+
+                let (len, copies) = match impair.as_mut().map(|i| i.next(write)) {
+                    Some(Impairment::Drop) => continue,
+                    Some(Impairment::Duplicate) => (write, 2),
+                    Some(Impairment::Truncate(len)) => (len, 1),
+                    Some(Impairment::Send) | None => (write, 1),
+                };
+
+                let mut blocked = false;
+                for _ in 0..copies {
+                    if let Err(e) = socket.send_to(&out[..len], send_info.to) {
+                        if e.kind() == std::io::ErrorKind::WouldBlock {
+                            trace!("send() would block");
+                            blocked = true;
+                            break;
+                        }
+
+                        panic!("send_to() failed: {:?}", e);
                     }
+                }
 
-                    panic!("send_to() failed: {:?}", e);
+                if blocked {
+                    break;
                 }
 
-                info!("{} written {} bytes", conn.trace_id(), write);
+                if packet_log.sample(Level::Info, write) {
+                    info!("{} written {} bytes", conn.trace_id(), write);
+                }
+                sent += write;
             }
+
+            burst.on_burst(packets, capped);
         }
     }
+
+    if let Some((write, skipped)) = packet_log.end_burst() {
+        info!(
+            "{} written {} bytes ({} packets not logged before)",
+            conn.trace_id(),
+            write,
+            skipped
+        );
+    }
+
+    sent
 }
 
+/// Draws a connection ID of `cid_len` bytes, at most
+/// `quiche::MAX_CONN_ID_LEN`, and a stateless reset token.
 pub fn generate_cid_and_reset_token<T: SecureRandom>(
     rng: &T,
+    cid_len: usize,
 ) -> (quiche::ConnectionId<'static>, u128) {
-    let mut scid = [0; quiche::MAX_CONN_ID_LEN];
+    let mut scid = vec![0; cid_len];
     rng.fill(&mut scid).unwrap();
-    let scid = scid.to_vec().into();
+    let scid = scid.into();
     let mut reset_token = [0; 16];
     rng.fill(&mut reset_token).unwrap();
     let reset_token = u128::from_be_bytes(reset_token);
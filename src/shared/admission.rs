@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Limits the number of messages in flight at once.
+///
+/// Messages offered wait in a FIFO until fewer than `max` messages are in
+/// flight, a message leaving the flight once completed. A message in flight
+/// for longer than `stuck_after`, e.g. sent on a path that went silent, is
+/// considered stuck and frees its slot, so that it cannot block the
+/// admission of the others forever.
+#[derive(Debug)]
+pub struct Admission {
+    max: usize,
+    stuck_after: Duration,
+    /// Messages waiting for a slot, with the time they were offered.
+    pending: VecDeque<(usize, Instant)>,
+    /// Messages holding a slot, with the time they were admitted.
+    in_flight: HashMap<usize, Instant>,
+    /// Messages that released their slot without completing.
+    stuck: HashSet<usize>,
+}
+
+impl Admission {
+    pub fn new(max: usize, stuck_after: Duration) -> Self {
+        Admission {
+            max,
+            stuck_after,
+            pending: VecDeque::new(),
+            in_flight: HashMap::new(),
+            stuck: HashSet::new(),
+        }
+    }
+
+    /// Sets the time after which a message in flight is stuck.
+    pub fn set_stuck_after(&mut self, stuck_after: Duration) {
+        self.stuck_after = stuck_after;
+    }
+
+    /// Queues `message` for admission, unless it is already queued or was
+    /// admitted.
+    pub fn offer(&mut self, message: usize, now: Instant) {
+        let known = self.in_flight.contains_key(&message)
+            || self.stuck.contains(&message)
+            || self.pending.iter().any(|(m, _)| *m == message);
+
+        if !known {
+            self.pending.push_back((message, now));
+        }
+    }
+
+    /// Next message to be admitted.
+    pub fn peek(&self) -> Option<usize> {
+        self.pending.front().map(|(m, _)| *m)
+    }
+
+    /// Admits the next message if a slot is free at `now`, and returns it
+    /// with the time it waited.
+    pub fn admit(&mut self, now: Instant) -> Option<(usize, Duration)> {
+        self.release_stuck(now);
+
+        if self.in_flight.len() >= self.max {
+            return None;
+        }
+
+        let (message, offered) = self.pending.pop_front()?;
+        self.in_flight.insert(message, now);
+
+        Some((message, now.saturating_duration_since(offered)))
+    }
+
+    /// Releases the slot of `message`, once its echo completed.
+    pub fn complete(&mut self, message: usize) {
+        self.in_flight.remove(&message);
+        self.stuck.remove(&message);
+    }
+
+    /// Number of messages holding a slot.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Number of messages stuck and not completed since.
+    pub fn stuck(&self) -> usize {
+        self.stuck.len()
+    }
+
+    /// Time at which a slot frees up because a message gets stuck, if a
+    /// message is waiting for one.
+    pub fn next_release(&self) -> Option<Instant> {
+        if self.pending.is_empty() || self.in_flight.len() < self.max {
+            return None;
+        }
+
+        self.in_flight
+            .values()
+            .map(|admitted| *admitted + self.stuck_after)
+            .min()
+    }
+
+    /// Forgets every message, e.g. when the connection is replaced.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.in_flight.clear();
+        self.stuck.clear();
+    }
+
+    fn release_stuck(&mut self, now: Instant) {
+        let stuck_after = self.stuck_after;
+        let stuck = &mut self.stuck;
+
+        self.in_flight.retain(|message, admitted| {
+            if now.saturating_duration_since(*admitted) < stuck_after {
+                return true;
+            }
+
+            stuck.insert(*message);
+            false
+        });
+    }
+}
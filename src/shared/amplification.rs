@@ -0,0 +1,86 @@
+use std::fmt;
+
+/// Bytes a server may send to an unvalidated address for each byte received
+/// from it (RFC 9000 section 8.1).
+pub const AMPLIFICATION_FACTOR: u64 = 3;
+
+/// Whether a server that received `from_client` bytes on a path it did not
+/// validate yet, and sent `to_client` bytes on it, cannot send a datagram of
+/// `mtu` bytes anymore: its data then waits for the validation of the path
+/// (RFC 9000 section 8.2.1).
+pub fn is_limited(from_client: u64, to_client: u64, mtu: usize) -> bool {
+    (from_client * AMPLIFICATION_FACTOR).saturating_sub(to_client) < mtu as u64
+}
+
+/// Bytes exchanged by one endpoint before the address of the client is
+/// validated, while the server is limited by the anti-amplification limit.
+///
+/// The server counts until the handshake completes, the client until it
+/// considers the handshake confirmed. Both sides count UDP payloads, so that
+/// the counters of the two ends mirror each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreValidation {
+    /// Whether the counters are those of the server.
+    pub server: bool,
+    pub sent: u64,
+    pub received: u64,
+    /// Bytes declared lost by quiche before the validation, sent for
+    /// nothing.
+    pub lost: u64,
+    pub validated: bool,
+}
+
+impl PreValidation {
+    pub fn new(server: bool) -> Self {
+        PreValidation {
+            server,
+            ..Default::default()
+        }
+    }
+
+    pub fn on_sent(&mut self, len: usize) {
+        if !self.validated {
+            self.sent += len as u64;
+        }
+    }
+
+    pub fn on_recv(&mut self, len: usize) {
+        if !self.validated {
+            self.received += len as u64;
+        }
+    }
+
+    /// Stops counting, `lost` being the bytes quiche declared lost so far.
+    pub fn validate(&mut self, lost: u64) {
+        if !self.validated {
+            self.lost = lost;
+            self.validated = true;
+        }
+    }
+
+    /// Bytes the server could still send before reaching the
+    /// anti-amplification limit.
+    pub fn headroom(&self) -> u64 {
+        let (from_client, to_client) = if self.server {
+            (self.received, self.sent)
+        } else {
+            (self.sent, self.received)
+        };
+
+        (from_client * AMPLIFICATION_FACTOR).saturating_sub(to_client)
+    }
+}
+
+impl fmt::Display for PreValidation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "sent {} received {} bytes, {} bytes wasted, {} bytes of {}x headroom left",
+            self.sent,
+            self.received,
+            self.lost,
+            self.headroom(),
+            AMPLIFICATION_FACTOR
+        )
+    }
+}
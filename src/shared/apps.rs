@@ -0,0 +1,260 @@
+use std::{collections::HashMap, fmt, str::FromStr, time::Instant};
+
+use log::info;
+use quiche::h3;
+
+use crate::shared::file_transfer::{self, FileSink, Reply};
+
+/// Application protocol offered by the server when none is configured.
+pub const DEFAULT_ALPN: &str = "http/0.9";
+
+/// What the server does with the streams of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handler {
+    /// Echoes each message, padded, multiplexed or transformed as the
+    /// options of the server say.
+    Echo,
+
+    /// Reassembles the streams as file transfers and answers them with the
+    /// digest of the file.
+    File,
+
+    /// Answers each HTTP/3 request with its body.
+    H3,
+}
+
+impl Handler {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Handler::Echo => "echo",
+            Handler::File => "file",
+            Handler::H3 => "h3",
+        }
+    }
+}
+
+impl FromStr for Handler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "echo" => Ok(Handler::Echo),
+            "file" => Ok(Handler::File),
+            "h3" => Ok(Handler::H3),
+            _ => Err(format!("unknown handler '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Application protocol served by a handler, as `<alpn>=<handler>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct App {
+    pub alpn: String,
+    pub handler: Handler,
+}
+
+impl App {
+    pub fn new(alpn: &str, handler: Handler) -> Self {
+        App {
+            alpn: alpn.to_string(),
+            handler,
+        }
+    }
+}
+
+impl FromStr for App {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (alpn, handler) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <alpn>=<handler>, got '{}'", s))?;
+
+        // Protocol IDs are sent with a one-byte length.
+        if alpn.is_empty() || alpn.len() > 255 {
+            return Err(format!("invalid application protocol '{}'", alpn));
+        }
+
+        Ok(App::new(alpn, handler.parse()?))
+    }
+}
+
+impl fmt::Display for App {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.alpn, self.handler)
+    }
+}
+
+/// Protocols to offer in the handshake, in order of preference.
+pub fn protos(apps: &[App]) -> Vec<&[u8]> {
+    apps.iter().map(|a| a.alpn.as_bytes()).collect()
+}
+
+/// Handler of the protocol `alpn` negotiated by a connection.
+pub fn handler(apps: &[App], alpn: &[u8]) -> Option<Handler> {
+    apps.iter()
+        .find(|a| a.alpn.as_bytes() == alpn)
+        .map(|a| a.handler)
+}
+
+/// What a server does with the data read from the streams of a connection,
+/// set up once its handshake completed, from the protocol it negotiated.
+pub trait StreamHandler {
+    /// Handles `data` read from `stream_id`, `fin` if it ends the stream,
+    /// and returns what to answer on the stream so far, `None` if nothing.
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        data: &[u8],
+        fin: bool,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, String>;
+}
+
+/// The file handler: reassembles the streams as file transfers and answers
+/// the last stream of each file with its digest.
+pub struct FileHandler {
+    sink: FileSink,
+}
+
+impl FileHandler {
+    /// Keeps the files in memory up to `spill_over` bytes.
+    pub fn new(spill_over: u64) -> Self {
+        FileHandler {
+            sink: FileSink::new(spill_over),
+        }
+    }
+}
+
+impl StreamHandler for FileHandler {
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        data: &[u8],
+        fin: bool,
+        _now: Instant,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let reply = self
+            .sink
+            .on_stream_data(stream_id, data, fin)
+            .map_err(|e| format!("invalid file range: {}", e))?;
+
+        if let Some(Reply::FileDone { size, digest }) = &reply {
+            info!(
+                "received file of {} bytes, sha256 {}",
+                size,
+                file_transfer::hex(digest)
+            );
+        }
+
+        Ok(reply.map(|r| r.bytes().to_vec()))
+    }
+}
+
+/// The HTTP/3 handler: answers each request with a 200 carrying the body of
+/// the request, once complete.
+///
+/// HTTP/3 reads the streams itself, so it is polled with the connection
+/// instead of being handed their data.
+pub struct H3Handler {
+    config: h3::Config,
+    /// Set up on the first poll, the handshake being complete.
+    conn: Option<h3::Connection>,
+    /// Bodies of the requests not complete yet.
+    bodies: HashMap<u64, Vec<u8>>,
+    /// Responses whose headers were sent, with the body the stream did not
+    /// accept yet.
+    pending: HashMap<u64, Vec<u8>>,
+}
+
+impl H3Handler {
+    pub fn new() -> h3::Result<Self> {
+        Ok(H3Handler {
+            config: h3::Config::new()?,
+            conn: None,
+            bodies: HashMap::new(),
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Reads the requests received on `conn` and answers those complete,
+    /// `buf` holding the body read at once.
+    pub fn poll(&mut self, conn: &mut quiche::Connection, buf: &mut [u8]) -> h3::Result<()> {
+        let h3 = match &mut self.conn {
+            Some(h3) => h3,
+            None => self
+                .conn
+                .insert(h3::Connection::with_transport(conn, &self.config)?),
+        };
+
+        self.pending.retain(|stream_id, body| {
+            let written = h3.send_body(conn, *stream_id, body, true).unwrap_or(0);
+            body.drain(..written);
+
+            !body.is_empty()
+        });
+
+        loop {
+            match h3.poll(conn) {
+                Ok((stream_id, h3::Event::Headers { .. })) => {
+                    info!("HTTP/3 request on stream {}", stream_id);
+                    self.bodies.insert(stream_id, vec![]);
+                }
+
+                Ok((stream_id, h3::Event::Data)) => {
+                    while let Ok(read) = h3.recv_body(conn, stream_id, buf) {
+                        let body = self.bodies.entry(stream_id).or_default();
+                        body.extend_from_slice(&buf[..read]);
+                    }
+                }
+
+                Ok((stream_id, h3::Event::Finished)) => {
+                    let body = self.bodies.remove(&stream_id).unwrap_or_default();
+                    let headers = [
+                        h3::Header::new(b":status", b"200"),
+                        h3::Header::new(b"content-length", body.len().to_string().as_bytes()),
+                    ];
+                    h3.send_response(conn, stream_id, &headers, body.is_empty())?;
+
+                    if !body.is_empty() {
+                        let written = match h3.send_body(conn, stream_id, &body, true) {
+                            Ok(v) => v,
+                            Err(h3::Error::Done) => 0,
+                            Err(e) => return Err(e),
+                        };
+
+                        if written < body.len() {
+                            self.pending.insert(stream_id, body[written..].to_vec());
+                        }
+                    }
+                }
+
+                Ok((stream_id, h3::Event::Reset(_))) => {
+                    self.bodies.remove(&stream_id);
+                    self.pending.remove(&stream_id);
+                }
+
+                Ok(_) => (),
+
+                Err(h3::Error::Done) => return Ok(()),
+
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Handler of a connection, set up from its [`Handler`].
+pub enum ConnHandler {
+    /// The server reads the streams and hands their data to the handler.
+    Streams(Box<dyn StreamHandler>),
+
+    /// HTTP/3 reads the streams itself.
+    H3(Box<H3Handler>),
+}
@@ -0,0 +1,78 @@
+use std::{fmt, time::Instant};
+
+/// Limits the packets pulled from quiche on a path in one go to its send
+/// quantum, the largest burst the congestion controller wants released at
+/// once, instead of draining everything the connection has to send.
+///
+/// The rest is sent on the next wakeup, at the release time quiche's pacer
+/// gave to the last packet of the burst, so that the poll loop handles what
+/// was received in between and the packets leave in paced bursts.
+#[derive(Debug, Default)]
+pub struct BurstLimit {
+    /// Burst size in packets replacing the send quantum, with
+    /// `--burst-override`.
+    override_packets: Option<usize>,
+    /// Release time of the last packet of a burst that reached the limit,
+    /// more packets are waiting then.
+    capped: Option<Instant>,
+    stats: BurstStats,
+}
+
+/// Bursts sent so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BurstStats {
+    /// Largest burst sent, in packets.
+    pub max_burst: usize,
+
+    /// Bursts that stopped at the limit while more packets were waiting.
+    pub capped: u64,
+}
+
+impl BurstLimit {
+    pub fn new(override_packets: Option<usize>) -> Self {
+        BurstLimit {
+            override_packets,
+            ..Default::default()
+        }
+    }
+
+    /// Bytes a burst may carry on a path whose send quantum is `quantum`,
+    /// at least one packet of `mtu` bytes.
+    pub fn budget(&self, quantum: usize, mtu: usize) -> usize {
+        match self.override_packets {
+            Some(packets) => packets.saturating_mul(mtu),
+            None => quantum.max(mtu),
+        }
+    }
+
+    /// Starts a new round of bursts, one per path.
+    pub fn begin(&mut self) {
+        self.capped = None;
+    }
+
+    /// Accounts a burst of `packets`, `capped` with the release time of its
+    /// last packet if it stopped at the limit.
+    pub fn on_burst(&mut self, packets: usize, capped: Option<Instant>) {
+        self.stats.max_burst = self.stats.max_burst.max(packets);
+
+        if let Some(at) = capped {
+            self.stats.capped += 1;
+            self.capped = Some(self.capped.map_or(at, |t| t.min(at)));
+        }
+    }
+
+    /// When the packets left by a capped burst are to be sent.
+    pub fn next_release(&self) -> Option<Instant> {
+        self.capped
+    }
+
+    pub fn stats(&self) -> BurstStats {
+        self.stats
+    }
+}
+
+impl fmt::Display for BurstStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "max {} packets, {} capped", self.max_burst, self.capped)
+    }
+}
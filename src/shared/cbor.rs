@@ -0,0 +1,313 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::shared::encoding::Object;
+
+const UINT: u8 = 0;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const SIMPLE: u8 = 7;
+
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const FLOAT32: u8 = 0xfa;
+const FLOAT64: u8 = 0xfb;
+const BREAK: u8 = 0xff;
+
+/// Additional information of an item whose length is not known upfront.
+const INDEFINITE: u8 = 31;
+
+/// Nesting depth of the maps beyond which `decode` gives up, far more than
+/// the records and events need, so that malformed data cannot exhaust the
+/// stack.
+pub const MAX_DEPTH: usize = 16;
+
+/// Builder of a CBOR map (RFC 8949), the binary counterpart of
+/// `JsonObject`.
+///
+/// Only what the outputs of this crate need is supported: text keys, text,
+/// unsigned, float and boolean values, and arrays of maps. The map has an
+/// indefinite length, so that fields can be added without counting them
+/// first.
+#[derive(Debug)]
+pub struct CborObject {
+    buf: Vec<u8>,
+}
+
+impl Default for CborObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CborObject {
+    pub fn new() -> Self {
+        CborObject {
+            buf: vec![MAP << 5 | INDEFINITE],
+        }
+    }
+
+    fn head(&mut self, major: u8, value: u64) {
+        let major = major << 5;
+
+        match value {
+            0..=23 => self.buf.push(major | value as u8),
+            24..=0xff => self.buf.extend_from_slice(&[major | 24, value as u8]),
+
+            0x100..=0xffff => {
+                self.buf.push(major | 25);
+                self.buf.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+
+            0x1_0000..=0xffff_ffff => {
+                self.buf.push(major | 26);
+                self.buf.extend_from_slice(&(value as u32).to_be_bytes());
+            }
+
+            _ => {
+                self.buf.push(major | 27);
+                self.buf.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+
+    fn text(&mut self, s: &str) {
+        self.head(TEXT, s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.push(BREAK);
+        self.buf
+    }
+}
+
+impl Object for CborObject {
+    fn new() -> Self {
+        CborObject::new()
+    }
+
+    fn str(mut self, key: &str, value: &str) -> Self {
+        self.text(key);
+        self.text(value);
+        self
+    }
+
+    fn u64(mut self, key: &str, value: u64) -> Self {
+        self.text(key);
+        self.head(UINT, value);
+        self
+    }
+
+    fn f64(mut self, key: &str, value: f64) -> Self {
+        self.text(key);
+        if value.is_finite() {
+            self.buf.push(FLOAT64);
+            self.buf.extend_from_slice(&value.to_be_bytes());
+        } else {
+            self.buf.push(NULL);
+        }
+        self
+    }
+
+    fn bool(mut self, key: &str, value: bool) -> Self {
+        self.text(key);
+        self.buf.push(if value { TRUE } else { FALSE });
+        self
+    }
+
+    fn objects(mut self, key: &str, values: Vec<Self>) -> Self {
+        self.text(key);
+        self.head(ARRAY, values.len() as u64);
+        for value in values {
+            self.buf.extend_from_slice(&value.finish());
+        }
+        self
+    }
+
+    /// Writes the map as the next item of a CBOR sequence.
+    fn write_to<W: Write>(self, out: &mut W) -> io::Result<()> {
+        out.write_all(&self.finish())
+    }
+}
+
+/// Error of data that is not a map written by `CborObject`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CborError {
+    /// The data ends within an item.
+    Truncated,
+
+    /// An item `CborObject` never writes, by its initial byte.
+    Unsupported(u8),
+
+    /// A text is not valid UTF-8.
+    InvalidText,
+
+    /// The maps are nested deeper than `MAX_DEPTH`.
+    TooDeep,
+}
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CborError::Truncated => f.write_str("data ends within an item"),
+            CborError::Unsupported(initial) => {
+                write!(f, "unsupported item (initial byte {:#04x})", initial)
+            }
+            CborError::InvalidText => f.write_str("text is not valid UTF-8"),
+            CborError::TooDeep => write!(f, "maps nested deeper than {}", MAX_DEPTH),
+        }
+    }
+}
+
+impl std::error::Error for CborError {}
+
+/// Decodes the map at the start of `data` into an object of another format,
+/// e.g. a `JsonObject` to print it, and returns it with the number of bytes
+/// it took.
+///
+/// A CBOR sequence is decoded by calling it again on the bytes left.
+pub fn decode<O: Object>(data: &[u8]) -> Result<(O, usize), CborError> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        depth: 0,
+    };
+    let obj = reader.map()?;
+
+    Ok((obj, reader.pos))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Maps being read.
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], CborError> {
+        let end = self.pos.checked_add(len).ok_or(CborError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(CborError::Truncated)?;
+        self.pos = end;
+
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, CborError> {
+        self.data.get(self.pos).copied().ok_or(CborError::Truncated)
+    }
+
+    /// Reads the head of an item of type `major`, and returns its argument,
+    /// `None` for an indefinite length.
+    fn head(&mut self, major: u8) -> Result<Option<u64>, CborError> {
+        let initial = self.peek()?;
+        if initial >> 5 != major {
+            return Err(CborError::Unsupported(initial));
+        }
+        self.pos += 1;
+
+        let value = match initial & 0x1f {
+            info @ 0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            INDEFINITE if major == MAP => return Ok(None),
+            _ => return Err(CborError::Unsupported(initial)),
+        };
+
+        Ok(Some(value))
+    }
+
+    fn text(&mut self) -> Result<&str, CborError> {
+        let initial = self.peek()?;
+        let len = self.head(TEXT)?.ok_or(CborError::Unsupported(initial))?;
+        let len = usize::try_from(len).map_err(|_| CborError::Truncated)?;
+
+        std::str::from_utf8(self.take(len)?).map_err(|_| CborError::InvalidText)
+    }
+
+    fn map<O: Object>(&mut self) -> Result<O, CborError> {
+        if self.depth == MAX_DEPTH {
+            return Err(CborError::TooDeep);
+        }
+
+        self.depth += 1;
+        let obj = self.entries();
+        self.depth -= 1;
+
+        obj
+    }
+
+    fn entries<O: Object>(&mut self) -> Result<O, CborError> {
+        let len = self.head(MAP)?;
+        let mut obj = O::new();
+        let mut read = 0;
+
+        loop {
+            match len {
+                None if self.peek()? == BREAK => {
+                    self.pos += 1;
+                    return Ok(obj);
+                }
+
+                Some(len) if read == len => return Ok(obj),
+
+                _ => (),
+            }
+
+            let key = self.text()?.to_string();
+            obj = self.field(obj, &key)?;
+            read += 1;
+        }
+    }
+
+    fn field<O: Object>(&mut self, obj: O, key: &str) -> Result<O, CborError> {
+        let initial = self.peek()?;
+
+        let obj = match (initial >> 5, initial) {
+            (UINT, _) => obj.u64(key, self.head(UINT)?.unwrap()),
+
+            (TEXT, _) => {
+                let value = self.text()?.to_string();
+                obj.str(key, &value)
+            }
+
+            (ARRAY, _) => {
+                let len = self.head(ARRAY)?.unwrap();
+                let values = (0..len).map(|_| self.map()).collect::<Result<_, _>>()?;
+                obj.objects(key, values)
+            }
+
+            (SIMPLE, FALSE | TRUE) => {
+                self.pos += 1;
+                obj.bool(key, initial == TRUE)
+            }
+
+            (SIMPLE, NULL) => {
+                self.pos += 1;
+                obj.f64(key, f64::NAN)
+            }
+
+            (SIMPLE, FLOAT32) => {
+                self.pos += 1;
+                let value = f32::from_be_bytes(self.take(4)?.try_into().unwrap());
+                obj.f64(key, value as f64)
+            }
+
+            (SIMPLE, FLOAT64) => {
+                self.pos += 1;
+                obj.f64(key, f64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+            }
+
+            _ => return Err(CborError::Unsupported(initial)),
+        };
+
+        Ok(obj)
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::HashSet, fmt};
+
+use log::warn;
+use ring::rand::SecureRandom;
+
+use super::{conn::ConnOps, generate_cid_and_reset_token};
+
+/// Draws of an ID and reset token before giving up on issuing one, when
+/// they keep colliding with those already issued.
+pub const MAX_DRAWS: usize = 16;
+
+/// Issues source connection IDs to the peer, for it to open new paths, up
+/// to the limit of active IDs the two ends negotiated.
+///
+/// Once the limit is reached, or quiche refuses an ID for another reason,
+/// nothing is attempted until the peer retires one of the active IDs or its
+/// transport parameters change the limit, instead of on every iteration of
+/// the event loop. Both are logged once
+/// per connection with the limit advertised by the peer, as a peer never
+/// retiring the IDs it was given stalls the paths that need them.
+///
+/// The IDs and reset tokens issued on a connection are kept, and a draw
+/// repeating one of them is redrawn: the peer would otherwise hold one
+/// token for two IDs, and a stateless reset could not tell them apart.
+#[derive(Debug)]
+pub struct CidIssuer {
+    /// Length of the IDs drawn by `issue`.
+    cid_len: usize,
+    /// Active IDs and peer limit when issuance stopped, it resumes once
+    /// there are fewer IDs or another limit.
+    stopped_at: Option<(usize, Option<u64>)>,
+    warned_limit: bool,
+    warned_error: bool,
+    issued: u64,
+    redrawn: u64,
+    cids: HashSet<quiche::ConnectionId<'static>>,
+    reset_tokens: HashSet<u128>,
+}
+
+/// Source connection IDs issued to the peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CidStats {
+    /// IDs issued so far, besides the one of the handshake.
+    pub cids_issued: u64,
+
+    /// IDs currently active, the one of the handshake included if not
+    /// retired.
+    pub cids_active: usize,
+
+    /// Draws thrown away because their ID or reset token was already
+    /// issued, or routed to another connection.
+    pub cids_redrawn: u64,
+}
+
+impl Default for CidIssuer {
+    fn default() -> Self {
+        CidIssuer::new(quiche::MAX_CONN_ID_LEN)
+    }
+}
+
+impl CidIssuer {
+    /// Issuer of IDs of `cid_len` bytes.
+    pub fn new(cid_len: usize) -> Self {
+        CidIssuer {
+            cid_len,
+            stopped_at: None,
+            warned_limit: false,
+            warned_error: false,
+            issued: 0,
+            redrawn: 0,
+            cids: HashSet::new(),
+            reset_tokens: HashSet::new(),
+        }
+    }
+
+    pub fn cid_len(&self) -> usize {
+        self.cid_len
+    }
+
+    /// Issues as many IDs as the peer accepts, and returns them.
+    pub fn issue<C: ConnOps, R: SecureRandom>(
+        &mut self,
+        conn: &mut C,
+        rng: &R,
+    ) -> Vec<quiche::ConnectionId<'static>> {
+        let cid_len = self.cid_len;
+        self.issue_with(
+            conn,
+            || generate_cid_and_reset_token(rng, cid_len),
+            |_| false,
+        )
+    }
+
+    /// Like `issue`, drawing the IDs and reset tokens from `generate` and
+    /// redrawing the IDs `taken` by another connection, e.g. in the routing
+    /// table of the server.
+    pub fn issue_with<C, G, T>(
+        &mut self,
+        conn: &mut C,
+        mut generate: G,
+        taken: T,
+    ) -> Vec<quiche::ConnectionId<'static>>
+    where
+        C: ConnOps,
+        G: FnMut() -> (quiche::ConnectionId<'static>, u128),
+        T: Fn(&quiche::ConnectionId) -> bool,
+    {
+        let mut issued = vec![];
+
+        if let Some((active, limit)) = self.stopped_at {
+            if conn.active_scids() >= active && conn.peer_active_cid_limit() == limit {
+                return issued;
+            }
+
+            self.stopped_at = None;
+        }
+
+        loop {
+            if conn.scids_left() == 0 {
+                if !self.warned_limit {
+                    warn!(
+                        "reached the limit of connection IDs issued to the peer: {} active, \
+                         peer limit {}",
+                        conn.active_scids(),
+                        limit(conn)
+                    );
+                    self.warned_limit = true;
+                }
+
+                break;
+            }
+
+            let Some((scid, reset_token)) = self.draw(&mut generate, &taken) else {
+                warn!(
+                    "no unique connection ID and reset token in {} draws, {} active",
+                    MAX_DRAWS,
+                    conn.active_scids()
+                );
+                break;
+            };
+
+            match conn.new_scid(&scid, reset_token, false) {
+                Ok(_) => {
+                    self.issued += 1;
+                    self.cids.insert(scid.clone());
+                    self.reset_tokens.insert(reset_token);
+                    issued.push(scid);
+                }
+
+                Err(e) => {
+                    if !self.warned_error {
+                        warn!(
+                            "issuing a connection ID failed: {:?}, {} active, peer limit {}",
+                            e,
+                            conn.active_scids(),
+                            limit(conn)
+                        );
+                        self.warned_error = true;
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        self.stopped_at = Some((conn.active_scids(), conn.peer_active_cid_limit()));
+        issued
+    }
+
+    /// Draws an ID and reset token neither issued nor `taken`, up to
+    /// `MAX_DRAWS` times.
+    fn draw<G, T>(
+        &mut self,
+        generate: &mut G,
+        taken: &T,
+    ) -> Option<(quiche::ConnectionId<'static>, u128)>
+    where
+        G: FnMut() -> (quiche::ConnectionId<'static>, u128),
+        T: Fn(&quiche::ConnectionId) -> bool,
+    {
+        for _ in 0..MAX_DRAWS {
+            let (scid, reset_token) = generate();
+
+            if !self.cids.contains(&scid)
+                && !self.reset_tokens.contains(&reset_token)
+                && !taken(&scid)
+            {
+                return Some((scid, reset_token));
+            }
+
+            self.redrawn += 1;
+        }
+
+        None
+    }
+
+    /// Forgets the previous connection, the IDs issued on it still being
+    /// counted.
+    pub fn restart(&mut self) {
+        *self = CidIssuer {
+            issued: self.issued,
+            redrawn: self.redrawn,
+            ..Default::default()
+        };
+    }
+
+    pub fn stats<C: ConnOps>(&self, conn: &C) -> CidStats {
+        CidStats {
+            cids_issued: self.issued,
+            cids_active: conn.active_scids(),
+            cids_redrawn: self.redrawn,
+        }
+    }
+}
+
+/// Limit of active IDs advertised by the peer, for the logs.
+fn limit<C: ConnOps>(conn: &C) -> String {
+    conn.peer_active_cid_limit()
+        .map_or("unknown".to_string(), |l| l.to_string())
+}
+
+impl fmt::Display for CidStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} issued, {} active",
+            self.cids_issued, self.cids_active
+        )?;
+
+        if self.cids_redrawn > 0 {
+            write!(f, ", {} redrawn", self.cids_redrawn)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+/// Subset of the `quiche::Connection` API used by the application logic.
+///
+/// Going through this trait rather than the concrete connection lets the
+/// logic be driven by a mock connection in tests.
+pub trait ConnOps {
+    fn stream_send(&mut self, stream_id: u64, buf: &[u8], fin: bool) -> quiche::Result<usize>;
+
+    fn stream_capacity(&self, stream_id: u64) -> quiche::Result<usize>;
+
+    fn stream_readable_next(&mut self) -> Option<u64>;
+
+    fn send_ack_eliciting(&mut self) -> quiche::Result<()>;
+
+    fn scids_left(&self) -> usize;
+
+    fn active_scids(&self) -> usize;
+
+    fn new_scid(
+        &mut self,
+        scid: &quiche::ConnectionId,
+        reset_token: u128,
+        retire_if_needed: bool,
+    ) -> quiche::Result<u64>;
+
+    /// Limit of active connection IDs advertised by the peer, once its
+    /// transport parameters are known.
+    fn peer_active_cid_limit(&self) -> Option<u64>;
+}
+
+impl ConnOps for quiche::Connection {
+    fn stream_send(&mut self, stream_id: u64, buf: &[u8], fin: bool) -> quiche::Result<usize> {
+        quiche::Connection::stream_send(self, stream_id, buf, fin)
+    }
+
+    fn stream_capacity(&self, stream_id: u64) -> quiche::Result<usize> {
+        quiche::Connection::stream_capacity(self, stream_id)
+    }
+
+    fn stream_readable_next(&mut self) -> Option<u64> {
+        quiche::Connection::stream_readable_next(self)
+    }
+
+    fn send_ack_eliciting(&mut self) -> quiche::Result<()> {
+        quiche::Connection::send_ack_eliciting(self)
+    }
+
+    fn scids_left(&self) -> usize {
+        quiche::Connection::scids_left(self)
+    }
+
+    fn active_scids(&self) -> usize {
+        quiche::Connection::active_scids(self)
+    }
+
+    fn new_scid(
+        &mut self,
+        scid: &quiche::ConnectionId,
+        reset_token: u128,
+        retire_if_needed: bool,
+    ) -> quiche::Result<u64> {
+        quiche::Connection::new_scid(self, scid, reset_token, retire_if_needed)
+    }
+
+    fn peer_active_cid_limit(&self) -> Option<u64> {
+        self.peer_transport_params()
+            .map(|tp| tp.active_conn_id_limit)
+    }
+}
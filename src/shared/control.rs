@@ -0,0 +1,214 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use super::is_uni_stream;
+
+/// Longest run ID, so that a control message fits a packet.
+pub const MAX_RUN_ID_LEN: usize = 64;
+
+/// Identifies the connections of one run of the client in the logs of both
+/// ends, e.g. to join their artifacts.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RunId(String);
+
+impl RunId {
+    /// Random run ID, from `seed`.
+    pub fn random(seed: u64) -> Self {
+        RunId(format!("{:016x}", seed))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for RunId {
+    type Err = String;
+
+    /// Accepts letters, digits, `-`, `_` and `.`, which keep the control
+    /// messages unambiguous.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+
+        if s.is_empty() || s.len() > MAX_RUN_ID_LEN || !s.chars().all(valid) {
+            return Err(format!(
+                "invalid run ID '{}', expected up to {} letters, digits, '-', '_' or '.'",
+                s, MAX_RUN_ID_LEN
+            ));
+        }
+
+        Ok(RunId(s.to_string()))
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What the client sends when all its messages were delivered, before
+/// closing the connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub run_id: RunId,
+    pub messages: usize,
+    /// Bytes of the messages, before any padding.
+    pub bytes: u64,
+}
+
+/// Message of the client to the server about the run itself, each on its
+/// own unidirectional stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Sent once the handshake completed, so that the server knows the run
+    /// of a connection that never completes.
+    Start(RunId),
+
+    Complete(Completion),
+}
+
+impl Control {
+    pub fn encode(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+
+    /// Parses the data of a whole stream, `None` if it is not a control
+    /// message.
+    pub fn parse(data: &[u8]) -> Option<Control> {
+        let text = std::str::from_utf8(data).ok()?;
+
+        if let Some(run_id) = text.strip_prefix("run start: run-id ") {
+            return Some(Control::Start(run_id.parse().ok()?));
+        }
+
+        let rest = text.strip_prefix("run complete: ")?;
+        let (messages, rest) = rest.split_once(" messages, ")?;
+        let (bytes, run_id) = rest.split_once(" bytes, run-id ")?;
+
+        Some(Control::Complete(Completion {
+            run_id: run_id.parse().ok()?,
+            messages: messages.parse().ok()?,
+            bytes: bytes.parse().ok()?,
+        }))
+    }
+
+    /// Parses what a read of `stream_id` returned, if it holds a whole
+    /// control message.
+    ///
+    /// Control messages are short enough to arrive in one read with the
+    /// fin. Messages of the workload sent on unidirectional streams, with
+    /// `--confirm acks`, are only mistaken for one if they are one.
+    pub fn from_read(stream_id: u64, data: &[u8], fin: bool) -> Option<Control> {
+        if !is_uni_stream(stream_id) || !fin {
+            return None;
+        }
+
+        Control::parse(data)
+    }
+}
+
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Control::Start(run_id) => write!(f, "run start: run-id {}", run_id),
+
+            Control::Complete(c) => write!(
+                f,
+                "run complete: {} messages, {} bytes, run-id {}",
+                c.messages, c.bytes, c.run_id
+            ),
+        }
+    }
+}
+
+/// Run of a connection on the server, from the control messages received.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnRun {
+    pub run_id: Option<RunId>,
+    pub completion: Option<Completion>,
+}
+
+impl ConnRun {
+    pub fn on_control(&mut self, control: Control) {
+        match control {
+            Control::Start(run_id) => self.run_id = Some(run_id),
+
+            Control::Complete(completion) => {
+                self.run_id = Some(completion.run_id.clone());
+                self.completion = Some(completion);
+            }
+        }
+    }
+
+    /// Whether the client said it completed its run before the connection
+    /// ended.
+    pub fn is_clean(&self) -> bool {
+        self.completion.is_some()
+    }
+}
+
+impl fmt::Display for ConnRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.run_id, &self.completion) {
+            (None, _) => write!(f, "unknown, no control message"),
+
+            (Some(run_id), Some(c)) => write!(
+                f,
+                "{}, clean: {} messages, {} bytes",
+                run_id, c.messages, c.bytes
+            ),
+
+            (Some(run_id), None) => write!(
+                f,
+                "{}, ended without completion (client crashed or aborted)",
+                run_id
+            ),
+        }
+    }
+}
+
+/// Connections that ended cleanly or not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunOutcomes {
+    pub clean: u64,
+    pub aborted: u64,
+}
+
+impl fmt::Display for RunOutcomes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} clean, {} aborted", self.clean, self.aborted)
+    }
+}
+
+/// Outcomes of the connections served, per run.
+#[derive(Debug, Default)]
+pub struct RunTally {
+    runs: BTreeMap<RunId, RunOutcomes>,
+    /// Connections whose run is not known, e.g. of clients predating the
+    /// control messages.
+    unknown: u64,
+}
+
+impl RunTally {
+    /// Accounts a connection that ended.
+    pub fn close(&mut self, run: &ConnRun) {
+        let Some(run_id) = &run.run_id else {
+            self.unknown += 1;
+            return;
+        };
+
+        let outcomes = self.runs.entry(run_id.clone()).or_default();
+        match run.is_clean() {
+            true => outcomes.clean += 1,
+            false => outcomes.aborted += 1,
+        }
+    }
+
+    pub fn get(&self, run_id: &RunId) -> Option<RunOutcomes> {
+        self.runs.get(run_id).copied()
+    }
+
+    pub fn unknown(&self) -> u64 {
+        self.unknown
+    }
+}
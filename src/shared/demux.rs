@@ -0,0 +1,157 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+};
+
+use quiche::ConnectionId;
+
+use super::file_transfer::hex;
+
+/// Datagrams a connection must have received before its rejection rate is
+/// judged.
+pub const MIN_DATAGRAMS: u64 = 10;
+
+/// Share of the datagrams of a connection rejected by quiche above which
+/// they are likely meant for another connection.
+pub const REJECT_RATE: f64 = 0.2;
+
+/// Connection each datagram received on the sockets shared by several
+/// connections belongs to, from its destination connection ID.
+#[derive(Debug)]
+pub struct Demux {
+    ids: HashMap<ConnectionId<'static>, usize>,
+    /// Length of the connection IDs, which short headers do not carry.
+    cid_len: usize,
+}
+
+impl Default for Demux {
+    fn default() -> Self {
+        Demux::new(quiche::MAX_CONN_ID_LEN)
+    }
+}
+
+impl Demux {
+    /// Demultiplexer of connections whose IDs are `cid_len` bytes long.
+    pub fn new(cid_len: usize) -> Self {
+        Demux {
+            ids: HashMap::new(),
+            cid_len,
+        }
+    }
+
+    pub fn insert(&mut self, cid: ConnectionId<'static>, id: usize) {
+        self.ids.insert(cid, id);
+    }
+
+    pub fn remove(&mut self, cid: &ConnectionId<'static>) {
+        self.ids.remove(cid);
+    }
+
+    pub fn cid_len(&self) -> usize {
+        self.cid_len
+    }
+
+    /// Connection of the destination connection ID `dcid`.
+    pub fn get(&self, dcid: &ConnectionId) -> Option<usize> {
+        self.ids.get(dcid).copied()
+    }
+
+    /// Connection of the datagram `pkt`, if its header parses and its
+    /// destination connection ID is known.
+    pub fn route(&self, pkt: &mut [u8]) -> Option<usize> {
+        let hdr = quiche::Header::from_slice(pkt, self.cid_len).ok()?;
+
+        self.get(&hdr.dcid)
+    }
+
+    /// Sends the datagrams of connection `from` to connection `to`, as a
+    /// demultiplexing bug would. Only meant for tests.
+    #[doc(hidden)]
+    pub fn misroute(&mut self, from: usize, to: usize) {
+        for id in self.ids.values_mut().filter(|id| **id == from) {
+            *id = to;
+        }
+    }
+}
+
+/// Datagrams of one destination connection ID received on one socket.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Seen {
+    datagrams: u64,
+    rejected: u64,
+}
+
+/// Counts the datagrams handed to a connection that quiche rejected.
+///
+/// quiche silently drops the packets it cannot decrypt, which is what
+/// happens to the datagrams of another connection: a datagram is rejected
+/// when it did not add a single packet to those quiche received. Once too
+/// many are, the destination connection IDs seen on each socket tell where
+/// the datagrams went astray.
+#[derive(Debug, Default)]
+pub struct RejectTracker {
+    datagrams: u64,
+    rejected: u64,
+    /// Datagrams per socket, then destination connection ID in hex.
+    seen: BTreeMap<(SocketAddr, String), Seen>,
+    reported: bool,
+}
+
+impl RejectTracker {
+    /// Accounts a datagram received on `socket` for `dcid`, if its header
+    /// parsed.
+    pub fn on_datagram(&mut self, socket: SocketAddr, dcid: Option<&[u8]>, rejected: bool) {
+        self.datagrams += 1;
+        self.rejected += u64::from(rejected);
+
+        let dcid = dcid.map_or("unparsable".to_string(), hex);
+        let seen = self.seen.entry((socket, dcid)).or_default();
+        seen.datagrams += 1;
+        seen.rejected += u64::from(rejected);
+    }
+
+    pub fn datagrams(&self) -> u64 {
+        self.datagrams
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Diagnostic of the misdelivery, the first time the share of rejected
+    /// datagrams exceeds `REJECT_RATE`.
+    pub fn check(&mut self) -> Option<String> {
+        if self.reported
+            || self.datagrams < MIN_DATAGRAMS
+            || (self.rejected as f64) <= self.datagrams as f64 * REJECT_RATE
+        {
+            return None;
+        }
+
+        self.reported = true;
+
+        let seen: Vec<String> = self
+            .seen
+            .iter()
+            .map(|((socket, dcid), seen)| {
+                format!(
+                    "{} on {}: {} rejected of {}",
+                    dcid, socket, seen.rejected, seen.datagrams
+                )
+            })
+            .collect();
+
+        Some(format!(
+            "{} of {} datagrams rejected by quiche, misdelivered? DCIDs seen: {}",
+            self.rejected,
+            self.datagrams,
+            seen.join(", ")
+        ))
+    }
+
+    /// A new connection starts, the datagrams of the previous one are left
+    /// out.
+    pub fn restart(&mut self) {
+        *self = RejectTracker::default();
+    }
+}
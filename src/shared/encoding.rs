@@ -0,0 +1,36 @@
+use std::io::{self, Write};
+
+/// Format of the records and events written to files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    #[default]
+    Json,
+
+    /// A sequence of CBOR maps (RFC 8742), read back with `report-dump`.
+    Cbor,
+}
+
+/// Object with the fields of a record or an event, whatever the format it
+/// is written in.
+///
+/// The outputs build their objects through this trait only, so that every
+/// format carries the same fields in the same order.
+pub trait Object: Sized {
+    fn new() -> Self;
+
+    fn str(self, key: &str, value: &str) -> Self;
+
+    fn u64(self, key: &str, value: u64) -> Self;
+
+    /// Adds `value`, null if not finite.
+    fn f64(self, key: &str, value: f64) -> Self;
+
+    fn bool(self, key: &str, value: bool) -> Self;
+
+    /// Adds an array of objects.
+    fn objects(self, key: &str, values: Vec<Self>) -> Self;
+
+    /// Writes the object as one item of a stream.
+    fn write_to<W: Write>(self, out: &mut W) -> io::Result<()>;
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Default number of packets a connection processes per wakeup.
+pub const DEFAULT_PACKET_BUDGET: usize = 32;
+
+/// Default number of stream bytes a connection serves per wakeup.
+pub const DEFAULT_BYTE_BUDGET: usize = 256 * 1024;
+
+/// Order in which a server services its connections, starting one further
+/// on each wakeup so that no connection always goes first.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    /// Connection to service first on the next wakeup, or the one following
+    /// it if it is gone.
+    next: u64,
+}
+
+impl RoundRobin {
+    /// Orders `ids` for this wakeup: from the next connection in turn, then
+    /// wrapping around.
+    pub fn order<I: IntoIterator<Item = u64>>(&mut self, ids: I) -> Vec<u64> {
+        let mut ids: Vec<u64> = ids.into_iter().collect();
+        ids.sort_unstable();
+
+        let start = ids.iter().position(|&id| id >= self.next).unwrap_or(0);
+        ids.rotate_left(start);
+
+        if let Some(&first) = ids.first() {
+            self.next = first + 1;
+        }
+
+        ids
+    }
+}
+
+/// Wakeups during which a connection left work for the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkStats {
+    /// Wakeups that stopped with packets left to process.
+    pub packets_limited: u64,
+    /// Wakeups that stopped with stream data possibly left to serve.
+    pub bytes_limited: u64,
+}
+
+impl fmt::Display for WorkStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "packet budget reached {} times, byte budget reached {} times",
+            self.packets_limited, self.bytes_limited
+        )
+    }
+}
+
+/// Work one connection may do per wakeup of the server, so that a chatty
+/// connection cannot hold the others back: the packets it received, queued
+/// here until processed, and the stream bytes it serves. The streams read
+/// are limited by its `ReadBudget`.
+///
+/// What is left over is carried to the next wakeup, which the server must
+/// not delay (see `has_backlog`).
+#[derive(Debug)]
+pub struct WorkBudget<P> {
+    packets: usize,
+    bytes: usize,
+    inbox: VecDeque<P>,
+    /// Packets taken and bytes served during the current wakeup.
+    taken: usize,
+    served: usize,
+    bytes_exhausted: bool,
+    stats: WorkStats,
+}
+
+impl<P> WorkBudget<P> {
+    pub fn new(packets: usize, bytes: usize) -> Self {
+        WorkBudget {
+            packets,
+            bytes,
+            inbox: VecDeque::new(),
+            taken: 0,
+            served: 0,
+            bytes_exhausted: false,
+            stats: WorkStats::default(),
+        }
+    }
+
+    /// Queues a packet received for the connection.
+    pub fn push(&mut self, pkt: P) {
+        self.inbox.push_back(pkt);
+    }
+
+    /// Starts a wakeup of the connection.
+    pub fn begin(&mut self) {
+        self.taken = 0;
+        self.served = 0;
+        self.bytes_exhausted = false;
+    }
+
+    /// Next packet to process during this wakeup, oldest first, if the
+    /// budget is not spent.
+    pub fn next_packet(&mut self) -> Option<P> {
+        if self.taken >= self.packets {
+            return None;
+        }
+
+        let pkt = self.inbox.pop_front()?;
+        self.taken += 1;
+
+        if self.taken == self.packets && !self.inbox.is_empty() {
+            self.stats.packets_limited += 1;
+        }
+
+        Some(pkt)
+    }
+
+    /// Accounts `bytes` of stream data served.
+    pub fn serve(&mut self, bytes: usize) {
+        self.served += bytes;
+    }
+
+    /// Whether more stream data may be served during this wakeup. Once it
+    /// returns false, the streams left must wait for the next one.
+    pub fn can_serve(&mut self) -> bool {
+        if self.served < self.bytes {
+            return true;
+        }
+
+        if !self.bytes_exhausted {
+            self.bytes_exhausted = true;
+            self.stats.bytes_limited += 1;
+        }
+
+        false
+    }
+
+    /// Whether packets or stream data are left for the next wakeup, which
+    /// must then come at once.
+    pub fn has_backlog(&self) -> bool {
+        !self.inbox.is_empty() || self.bytes_exhausted
+    }
+
+    pub fn stats(&self) -> WorkStats {
+        self.stats
+    }
+}
+
+/// Stream bytes served to each connection, to tell how evenly a server
+/// shares its time between them.
+#[derive(Debug, Default)]
+pub struct Fairness {
+    /// Time each connection was opened at and bytes served to it.
+    served: HashMap<u64, (Instant, u64)>,
+}
+
+impl Fairness {
+    pub fn open(&mut self, id: u64, now: Instant) {
+        self.served.insert(id, (now, 0));
+    }
+
+    pub fn on_served(&mut self, id: u64, bytes: usize) {
+        if let Some((_, served)) = self.served.get_mut(&id) {
+            *served += bytes as u64;
+        }
+    }
+
+    pub fn close(&mut self, id: u64) {
+        self.served.remove(&id);
+    }
+
+    /// Bytes served per second to connection `id` since it was opened.
+    pub fn rate(&self, id: u64, now: Instant) -> Option<f64> {
+        let (opened, served) = self.served.get(&id)?;
+
+        // A connection opened during this wakeup counts for a millisecond.
+        let elapsed = now
+            .saturating_duration_since(*opened)
+            .max(Duration::from_millis(1));
+
+        Some(*served as f64 / elapsed.as_secs_f64())
+    }
+
+    /// Ratio of the highest to the lowest rate of the open connections that
+    /// were served anything, 1 when they all get the same share. `None`
+    /// with fewer than two such connections.
+    pub fn ratio(&self, now: Instant) -> Option<f64> {
+        let rates: Vec<f64> = self
+            .served
+            .iter()
+            .filter(|(_, (_, served))| *served > 0)
+            .filter_map(|(&id, _)| self.rate(id, now))
+            .collect();
+
+        if rates.len() < 2 {
+            return None;
+        }
+
+        let max = rates.iter().cloned().fold(f64::MIN, f64::max);
+        let min = rates.iter().cloned().fold(f64::MAX, f64::min);
+
+        Some(max / min)
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, fmt, fs::File, io, os::unix::fs::FileExt, path::PathBuf};
+
+use log::info;
+use ring::digest;
+
+/// Size of the header starting each stream of a file transfer.
+pub const HEADER_LEN: usize = 24;
+
+/// Where the bytes of a stream go in the transferred file.
+///
+/// Each stream of a transfer starts with the transfer ID, the size of the
+/// whole file and the offset of the range it carries, as big-endian 64-bit
+/// integers, followed by the bytes of the range up to its fin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeHeader {
+    pub transfer: u64,
+    pub size: u64,
+    pub offset: u64,
+}
+
+impl RangeHeader {
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[..8].copy_from_slice(&self.transfer.to_be_bytes());
+        header[8..16].copy_from_slice(&self.size.to_be_bytes());
+        header[16..].copy_from_slice(&self.offset.to_be_bytes());
+        header
+    }
+
+    pub fn decode(header: &[u8; HEADER_LEN]) -> Self {
+        let field = |i: usize| u64::from_be_bytes(header[i..i + 8].try_into().unwrap());
+
+        RangeHeader {
+            transfer: field(0),
+            size: field(8),
+            offset: field(16),
+        }
+    }
+}
+
+/// Splits `size` bytes in at most `n` contiguous ranges of nearly equal
+/// length, as (offset, length). An empty file is a single empty range.
+pub fn split(size: u64, n: usize) -> Vec<(u64, u64)> {
+    let n = (n.max(1) as u64).min(size.max(1));
+    let (len, extra) = (size / n, size % n);
+
+    let mut offset = 0;
+    (0..n)
+        .map(|i| {
+            let range = (offset, len + u64::from(i < extra));
+            offset += range.1;
+            range
+        })
+        .collect()
+}
+
+/// Splits `size` bytes in contiguous ranges of `chunk` bytes, the last one
+/// possibly shorter, as (offset, length). An empty file is a single empty
+/// range.
+pub fn chunks(size: u64, chunk: u64) -> Vec<(u64, u64)> {
+    if size == 0 {
+        return vec![(0, 0)];
+    }
+
+    let chunk = chunk.max(1);
+    (0..size.div_ceil(chunk))
+        .map(|i| (i * chunk, chunk.min(size - i * chunk)))
+        .collect()
+}
+
+/// SHA-256 of `data`.
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    digest::digest(&digest::SHA256, data).as_ref().to_vec()
+}
+
+/// Lowercase hexadecimal form of `bytes`.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Error of a stream that does not follow the transfer format.
+#[derive(Debug)]
+pub enum SinkError {
+    Io(io::Error),
+
+    /// The stream ended before its header.
+    Truncated,
+
+    /// The stream carries bytes beyond the size of its file.
+    OutOfRange,
+
+    /// The stream announces a size differing from the other streams of the
+    /// transfer.
+    SizeMismatch,
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SinkError::Io(e) => write!(f, "spilling to disk failed: {}", e),
+            SinkError::Truncated => f.write_str("stream ended within its header"),
+            SinkError::OutOfRange => f.write_str("range goes beyond the file size"),
+            SinkError::SizeMismatch => f.write_str("file size differs between streams"),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+impl From<io::Error> for SinkError {
+    fn from(e: io::Error) -> Self {
+        SinkError::Io(e)
+    }
+}
+
+/// What to answer on a stream once its fin was received.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    /// The range was received, the file is not complete yet.
+    RangeDone,
+
+    /// The file is complete, the digest is sent on this stream.
+    FileDone { size: u64, digest: Vec<u8> },
+}
+
+impl Reply {
+    /// Bytes to send before the fin.
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Reply::RangeDone => &[],
+            Reply::FileDone { digest, .. } => digest,
+        }
+    }
+}
+
+/// Reassembles the files sent in byte ranges over several streams, as the
+/// receiving end of a file transfer.
+///
+/// Ranges are written at their offset as they arrive, whatever their order.
+/// Files are reassembled in memory as long as they take at most
+/// `spill_over` bytes together, the others are written to a temporary file
+/// with positional writes, removed once hashed. The fin of each stream is
+/// answered with an empty reply, but for the first fin received once the
+/// file is complete, which carries its SHA-256.
+#[derive(Debug)]
+pub struct FileSink {
+    spill_over: u64,
+    dir: PathBuf,
+    streams: HashMap<u64, SinkStream>,
+    files: HashMap<u64, SinkFile>,
+}
+
+#[derive(Debug, Default)]
+struct SinkStream {
+    header: Vec<u8>,
+    range: Option<RangeHeader>,
+    /// Bytes of the range received so far.
+    received: u64,
+}
+
+#[derive(Debug)]
+struct SinkFile {
+    size: u64,
+    buffer: Buffer,
+    received: u64,
+    /// Whether the digest was sent already.
+    answered: bool,
+}
+
+#[derive(Debug)]
+enum Buffer {
+    Memory(Vec<u8>),
+    Disk(File, PathBuf),
+}
+
+impl FileSink {
+    pub fn new(spill_over: u64) -> Self {
+        FileSink {
+            spill_over,
+            dir: std::env::temp_dir(),
+            streams: HashMap::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Spills the large files to `dir` instead of the temporary directory.
+    pub fn spill_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = dir;
+        self
+    }
+
+    /// Number of bytes of the files being reassembled in memory.
+    pub fn in_memory(&self) -> u64 {
+        self.files
+            .values()
+            .map(|f| match &f.buffer {
+                Buffer::Memory(buf) => buf.len() as u64,
+                Buffer::Disk(..) => 0,
+            })
+            .sum()
+    }
+
+    /// Forgets every transfer, e.g. when the connection is lost.
+    pub fn clear(&mut self) {
+        self.remove_spilled();
+        self.streams.clear();
+        self.files.clear();
+    }
+
+    /// Handles `data` read from `stream_id`, and returns the reply to send
+    /// with a fin once the stream is finished.
+    pub fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        mut data: &[u8],
+        fin: bool,
+    ) -> Result<Option<Reply>, SinkError> {
+        let stream = self.streams.entry(stream_id).or_default();
+
+        if stream.range.is_none() {
+            let missing = HEADER_LEN - stream.header.len();
+            let (header, rest) = data.split_at(missing.min(data.len()));
+            stream.header.extend_from_slice(header);
+            data = rest;
+
+            if let Ok(header) = <&[u8; HEADER_LEN]>::try_from(&stream.header[..]) {
+                stream.range = Some(RangeHeader::decode(header));
+            }
+        }
+
+        let Some(range) = stream.range else {
+            if fin {
+                self.streams.remove(&stream_id);
+                return Err(SinkError::Truncated);
+            }
+
+            return Ok(None);
+        };
+
+        let offset = range.offset + stream.received;
+        if offset.saturating_add(data.len() as u64) > range.size {
+            return Err(SinkError::OutOfRange);
+        }
+        stream.received += data.len() as u64;
+
+        if !self.files.contains_key(&range.transfer) {
+            let buffer = self.buffer(range, stream_id)?;
+
+            self.files.insert(
+                range.transfer,
+                SinkFile {
+                    size: range.size,
+                    buffer,
+                    received: 0,
+                    answered: false,
+                },
+            );
+        }
+
+        let file = self.files.get_mut(&range.transfer).unwrap();
+
+        if file.size != range.size {
+            return Err(SinkError::SizeMismatch);
+        }
+
+        match &mut file.buffer {
+            Buffer::Memory(buf) => {
+                let start = offset as usize;
+                buf[start..start + data.len()].copy_from_slice(data);
+            }
+
+            Buffer::Disk(f, _) => f.write_all_at(data, offset)?,
+        }
+        file.received += data.len() as u64;
+
+        if !fin {
+            return Ok(None);
+        }
+
+        self.streams.remove(&stream_id);
+
+        if file.received < file.size || file.answered {
+            return Ok(Some(Reply::RangeDone));
+        }
+
+        file.answered = true;
+        let size = file.size;
+        let digest = match &file.buffer {
+            Buffer::Memory(buf) => sha256(buf),
+
+            Buffer::Disk(f, _) => {
+                let mut ctx = digest::Context::new(&digest::SHA256);
+                let mut chunk = vec![0; 65536];
+                let mut offset = 0;
+
+                while offset < size {
+                    let read = f.read_at(&mut chunk, offset)?;
+                    if read == 0 {
+                        break;
+                    }
+                    ctx.update(&chunk[..read]);
+                    offset += read as u64;
+                }
+
+                ctx.finish().as_ref().to_vec()
+            }
+        };
+
+        // The file stays known, so that late fins of the transfer get an
+        // empty reply, but its bytes are released.
+        if let Buffer::Disk(_, path) = &file.buffer {
+            std::fs::remove_file(path)?;
+        }
+        file.buffer = Buffer::Memory(Vec::new());
+
+        Ok(Some(Reply::FileDone { size, digest }))
+    }
+
+    /// Buffer of the file of `range`, in memory unless it would take the
+    /// files kept in memory beyond `spill_over` bytes.
+    fn buffer(&self, range: RangeHeader, stream_id: u64) -> io::Result<Buffer> {
+        if self.in_memory().saturating_add(range.size) <= self.spill_over {
+            return Ok(Buffer::Memory(vec![0; range.size as usize]));
+        }
+
+        let path = self.dir.join(format!(
+            "quiche-test-{}-{}-{}",
+            std::process::id(),
+            range.transfer,
+            stream_id
+        ));
+        info!("spilling file of {} bytes to {:?}", range.size, path);
+
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(range.size)?;
+
+        Ok(Buffer::Disk(file, path))
+    }
+
+    /// Removes the spilled files of the transfers that did not complete.
+    fn remove_spilled(&self) {
+        for file in self.files.values() {
+            if let Buffer::Disk(_, path) = &file.buffer {
+                std::fs::remove_file(path).ok();
+            }
+        }
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        self.remove_spilled();
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Streams listed under the connection in the report, the longest blocked
+/// first.
+pub const REPORTED_STREAMS: usize = 5;
+
+/// Stream with data left to write, as seen after the writes of a step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingStream {
+    pub stream_id: u64,
+    /// Bytes handed to the stream so far.
+    pub written: u64,
+    /// Capacity quiche gives the stream.
+    pub capacity: usize,
+}
+
+/// Times the connection or a stream was held back by flow control.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BlockStats {
+    pub blocks: u64,
+    pub total: Duration,
+    pub longest: Duration,
+    /// Blocks that ended before the connection, and their sum in RTTs at
+    /// the time each ended.
+    resumed: u64,
+    rtts: f64,
+}
+
+impl BlockStats {
+    /// Accounts a block of `blocked`, which ended after `rtt` if it did.
+    fn record(&mut self, blocked: Duration, rtt: Option<Duration>) {
+        self.blocks += 1;
+        self.total += blocked;
+        self.longest = self.longest.max(blocked);
+
+        if let Some(rtt) = rtt.filter(|r| !r.is_zero()) {
+            self.resumed += 1;
+            self.rtts += blocked.as_secs_f64() / rtt.as_secs_f64();
+        }
+    }
+
+    /// Mean time the blocks lasted until the peer raised the limit, in RTTs.
+    pub fn mean_rtts(&self) -> Option<f64> {
+        (self.resumed > 0).then(|| self.rtts / self.resumed as f64)
+    }
+}
+
+impl fmt::Display for BlockStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.blocks == 0 {
+            return write!(f, "never blocked");
+        }
+
+        write!(
+            f,
+            "{} blocks, {:?} total, longest {:?}",
+            self.blocks, self.total, self.longest
+        )?;
+
+        match self.mean_rtts() {
+            Some(rtts) => write!(f, ", resumed after {:.1} RTTs on average", rtts),
+            None => write!(f, ", never resumed"),
+        }
+    }
+}
+
+/// Flow control blocking of the connection and of its streams.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlowBlockStats {
+    pub connection: BlockStats,
+    /// Streams blocked at least once, by ID.
+    pub streams: BTreeMap<u64, BlockStats>,
+}
+
+impl FlowBlockStats {
+    /// Streams blocked the longest, at most `REPORTED_STREAMS`.
+    pub fn longest_streams(&self) -> Vec<(u64, BlockStats)> {
+        let mut streams: Vec<_> = self.streams.iter().map(|(id, s)| (*id, *s)).collect();
+        streams.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(&b.0)));
+        streams.truncate(REPORTED_STREAMS);
+        streams
+    }
+}
+
+/// Tells when the connection and its streams are blocked by the flow
+/// control limits of the peer, and for how long.
+///
+/// quiche keeps the limits private, and the capacity it gives a stream is
+/// also capped by the congestion window. The limits are known from the
+/// transport parameters of the peer, and raised whenever quiche gives a
+/// capacity beyond them, i.e. once the peer sent MAX_DATA or
+/// MAX_STREAM_DATA. A stream without capacity is blocked by flow control
+/// once it wrote up to its limit, and the connection once every stream with
+/// data left has no capacity and the connection wrote up to its limit. An
+/// update the congestion window hides until it opens is only seen then, so
+/// the blocked times are upper bounds.
+#[derive(Debug)]
+pub struct FlowBlocking {
+    /// Limits the peer is known to have given, for the connection and for
+    /// each stream.
+    max_data: u64,
+    max_stream_data: u64,
+    stream_limits: HashMap<u64, u64>,
+    /// Start of the current blocks, with the bytes written then.
+    conn_block: Option<(Instant, u64)>,
+    stream_blocks: HashMap<u64, (Instant, u64)>,
+    stats: FlowBlockStats,
+}
+
+impl FlowBlocking {
+    /// Tracker of a connection whose peer advertised `max_data` and
+    /// `max_stream_data` for the streams the client opens.
+    pub fn new(max_data: u64, max_stream_data: u64) -> Self {
+        FlowBlocking {
+            max_data,
+            max_stream_data,
+            stream_limits: HashMap::new(),
+            conn_block: None,
+            stream_blocks: HashMap::new(),
+            stats: FlowBlockStats::default(),
+        }
+    }
+
+    /// Accounts the streams with data left after the writes of a step, the
+    /// connection having handed `written` bytes to its streams so far.
+    /// Blocks ending are counted in `rtt`.
+    ///
+    /// A block ends once bytes get through, even if the stream or the
+    /// connection wrote up to its new limit in the same step and is blocked
+    /// again.
+    pub fn update(&mut self, written: u64, streams: &[PendingStream], rtt: Duration, now: Instant) {
+        // What was written, or can be, fits in the limits.
+        self.max_data = self.max_data.max(written);
+
+        for s in streams {
+            let capacity = s.capacity as u64;
+
+            self.max_data = self.max_data.max(written + capacity);
+
+            let limit = self
+                .stream_limits
+                .entry(s.stream_id)
+                .or_insert(self.max_stream_data);
+            *limit = (*limit).max(s.written + capacity);
+        }
+
+        let conn_blocked = !streams.is_empty()
+            && written >= self.max_data
+            && streams.iter().all(|s| s.capacity == 0);
+
+        if let Some((since, at)) = self.conn_block {
+            if !conn_blocked || written > at {
+                self.stats.connection.record(now - since, Some(rtt));
+                self.conn_block = None;
+            }
+        }
+
+        if conn_blocked && self.conn_block.is_none() {
+            self.conn_block = Some((now, written));
+        }
+
+        let blocked: HashMap<u64, u64> = streams
+            .iter()
+            .filter(|s| s.capacity == 0 && s.written >= self.stream_limit(s.stream_id))
+            .map(|s| (s.stream_id, s.written))
+            .collect();
+
+        // Streams no longer blocked, including those with nothing left to
+        // write.
+        let stats = &mut self.stats;
+        self.stream_blocks.retain(|stream_id, (since, at)| {
+            if blocked.get(stream_id) == Some(at) {
+                return true;
+            }
+
+            stats
+                .streams
+                .entry(*stream_id)
+                .or_default()
+                .record(now - *since, Some(rtt));
+            false
+        });
+
+        for (stream_id, written) in blocked {
+            self.stream_blocks
+                .entry(stream_id)
+                .or_insert((now, written));
+        }
+    }
+
+    /// Whether the connection is blocked by flow control.
+    pub fn is_blocked(&self) -> bool {
+        self.conn_block.is_some()
+    }
+
+    /// Streams currently blocked by flow control.
+    pub fn blocked_streams(&self) -> impl Iterator<Item = u64> + '_ {
+        self.stream_blocks.keys().copied()
+    }
+
+    /// Blocks so far, those still going on included up to `now`.
+    pub fn stats(&self, now: Instant) -> FlowBlockStats {
+        let mut stats = self.stats.clone();
+
+        if let Some((since, _)) = self.conn_block {
+            stats.connection.record(now - since, None);
+        }
+
+        for (stream_id, (since, _)) in &self.stream_blocks {
+            stats
+                .streams
+                .entry(*stream_id)
+                .or_default()
+                .record(now - *since, None);
+        }
+
+        stats
+    }
+
+    /// The connection closed, its ongoing blocks end at `now`.
+    pub fn close(&mut self, now: Instant) {
+        self.stats = self.stats(now);
+        self.conn_block = None;
+        self.stream_blocks.clear();
+    }
+
+    /// A new connection starts, with the limits its peer advertised. The
+    /// blocks of the previous one are kept.
+    pub fn restart(&mut self, max_data: u64, max_stream_data: u64) {
+        self.max_data = max_data;
+        self.max_stream_data = max_stream_data;
+        self.stream_limits.clear();
+        self.conn_block = None;
+        self.stream_blocks.clear();
+    }
+
+    fn stream_limit(&self, stream_id: u64) -> u64 {
+        self.stream_limits
+            .get(&stream_id)
+            .copied()
+            .unwrap_or(self.max_stream_data)
+    }
+}
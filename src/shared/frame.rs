@@ -0,0 +1,58 @@
+/// Size of the big-endian length written before each frame.
+pub const LENGTH_PREFIX_LEN: usize = 4;
+
+/// Frames `payload` with its length, so that several payloads can share a
+/// stream.
+///
+/// Panics if `payload` is longer than `u32::MAX` bytes.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(payload.len()).expect("frame payload too long");
+
+    let mut frame = Vec::with_capacity(LENGTH_PREFIX_LEN + payload.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    frame
+}
+
+/// Splits the data of a stream back into the payloads framed by `encode`.
+///
+/// Data is pushed as it is read from the stream, whatever the boundaries of
+/// the reads: a frame split across several reads is returned once its last
+/// byte was pushed, and a read holding several frames returns each of them.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder::default()
+    }
+
+    /// Appends data read from the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the payload of the next complete frame, if any.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let (len, rest) = self.buf.split_first_chunk::<LENGTH_PREFIX_LEN>()?;
+        let len = u32::from_be_bytes(*len) as usize;
+
+        if rest.len() < len {
+            return None;
+        }
+
+        let payload = rest[..len].to_vec();
+        self.buf.drain(..LENGTH_PREFIX_LEN + len);
+
+        Some(payload)
+    }
+
+    /// Number of bytes of an incomplete frame received so far, which are
+    /// lost if the stream ends.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fmt, str::FromStr};
+
+use super::rng::SeededRng;
+
+/// Impairment applied to the outgoing datagrams, to exercise the loss
+/// recovery of the peer without a proxy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImpairSpec {
+    /// Drops each datagram with this probability.
+    Drop(f64),
+
+    /// Drops every nth datagram.
+    DropNth(u64),
+
+    /// Sends each datagram twice with this probability.
+    Dup(f64),
+
+    /// Truncates each datagram with this probability, to a random length.
+    Truncate(f64),
+}
+
+impl FromStr for ImpairSpec {
+    type Err = String;
+
+    /// Parses `drop:<p>`, `drop-nth:<n>`, `dup:<p>` or `truncate:<p>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid impairment '{}'", s))?;
+
+        let probability = || match value.parse::<f64>() {
+            Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+            _ => Err(format!("invalid probability '{}' in '{}'", value, s)),
+        };
+
+        match kind {
+            "drop" => Ok(ImpairSpec::Drop(probability()?)),
+
+            "drop-nth" => match value.parse() {
+                Ok(n) if n > 0 => Ok(ImpairSpec::DropNth(n)),
+                _ => Err(format!("invalid period '{}' in '{}'", value, s)),
+            },
+
+            "dup" => Ok(ImpairSpec::Dup(probability()?)),
+
+            "truncate" => Ok(ImpairSpec::Truncate(probability()?)),
+
+            _ => Err(format!("unknown impairment '{}'", kind)),
+        }
+    }
+}
+
+/// What happens to an outgoing datagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Impairment {
+    Send,
+    Drop,
+    Duplicate,
+    /// Only the first bytes are sent.
+    Truncate(usize),
+}
+
+/// Datagrams impaired so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImpairStats {
+    pub datagrams: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub truncated: u64,
+}
+
+impl fmt::Display for ImpairStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} dropped, {} duplicated, {} truncated of {} datagrams",
+            self.dropped, self.duplicated, self.truncated, self.datagrams
+        )
+    }
+}
+
+/// Applies an [`ImpairSpec`] to the outgoing datagrams, drawing from a
+/// seeded generator so that runs can be reproduced.
+#[derive(Clone, Debug)]
+pub struct TxImpair {
+    spec: ImpairSpec,
+    rng: SeededRng,
+    stats: ImpairStats,
+}
+
+impl TxImpair {
+    pub fn new(spec: ImpairSpec, seed: u64) -> Self {
+        TxImpair {
+            spec,
+            rng: SeededRng::new(seed),
+            stats: ImpairStats::default(),
+        }
+    }
+
+    /// What happens to the next datagram, of `len` bytes.
+    pub fn next(&mut self, len: usize) -> Impairment {
+        self.stats.datagrams += 1;
+
+        let impairment = match self.spec {
+            ImpairSpec::Drop(p) if self.rng.chance(p) => Impairment::Drop,
+
+            ImpairSpec::DropNth(n) if self.stats.datagrams.is_multiple_of(n) => Impairment::Drop,
+
+            ImpairSpec::Dup(p) if self.rng.chance(p) => Impairment::Duplicate,
+
+            ImpairSpec::Truncate(p) if len > 1 && self.rng.chance(p) => {
+                Impairment::Truncate(1 + self.rng.below(len as u64 - 1) as usize)
+            }
+
+            _ => Impairment::Send,
+        };
+
+        match impairment {
+            Impairment::Send => (),
+            Impairment::Drop => self.stats.dropped += 1,
+            Impairment::Duplicate => self.stats.duplicated += 1,
+            Impairment::Truncate(_) => self.stats.truncated += 1,
+        }
+
+        impairment
+    }
+
+    /// Applies the impairments to `datagrams`, in order.
+    pub fn apply<T: Clone>(&mut self, datagrams: Vec<(Vec<u8>, T)>) -> Vec<(Vec<u8>, T)> {
+        let mut out = Vec::with_capacity(datagrams.len());
+
+        for (mut pkt, info) in datagrams {
+            match self.next(pkt.len()) {
+                Impairment::Send => out.push((pkt, info)),
+
+                Impairment::Drop => (),
+
+                Impairment::Duplicate => {
+                    out.push((pkt.clone(), info.clone()));
+                    out.push((pkt, info));
+                }
+
+                Impairment::Truncate(len) => {
+                    pkt.truncate(len);
+                    out.push((pkt, info));
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn stats(&self) -> ImpairStats {
+        self.stats
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt::Write, io};
+
+use crate::shared::encoding::Object;
+
+/// Builder of a single-line JSON object.
+///
+/// Only what the outputs of this crate need is supported: string, number
+/// and boolean fields, plus raw values for nested objects and arrays.
+#[derive(Debug)]
+pub struct JsonObject {
+    buf: String,
+}
+
+impl Default for JsonObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonObject {
+    pub fn new() -> Self {
+        JsonObject {
+            buf: String::from("{"),
+        }
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.buf.len() > 1 {
+            self.buf.push(',');
+        }
+        write_str(&mut self.buf, key);
+        self.buf.push(':');
+    }
+
+    pub fn str(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        write_str(&mut self.buf, value);
+        self
+    }
+
+    pub fn u64(mut self, key: &str, value: u64) -> Self {
+        self.key(key);
+        write!(self.buf, "{}", value).unwrap();
+        self
+    }
+
+    pub fn f64(mut self, key: &str, value: f64) -> Self {
+        self.key(key);
+        if value.is_finite() {
+            write!(self.buf, "{}", value).unwrap();
+        } else {
+            self.buf.push_str("null");
+        }
+        self
+    }
+
+    pub fn bool(mut self, key: &str, value: bool) -> Self {
+        self.key(key);
+        self.buf.push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    /// Adds a value that is already JSON encoded.
+    pub fn raw(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        self.buf.push_str(value);
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.buf.push('}');
+        self.buf
+    }
+}
+
+impl Object for JsonObject {
+    fn new() -> Self {
+        JsonObject::new()
+    }
+
+    fn str(self, key: &str, value: &str) -> Self {
+        JsonObject::str(self, key, value)
+    }
+
+    fn u64(self, key: &str, value: u64) -> Self {
+        JsonObject::u64(self, key, value)
+    }
+
+    fn f64(self, key: &str, value: f64) -> Self {
+        JsonObject::f64(self, key, value)
+    }
+
+    fn bool(self, key: &str, value: bool) -> Self {
+        JsonObject::bool(self, key, value)
+    }
+
+    fn objects(self, key: &str, values: Vec<Self>) -> Self {
+        self.raw(key, &array(values.into_iter().map(JsonObject::finish)))
+    }
+
+    /// Writes the object as a line.
+    fn write_to<W: io::Write>(self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", self.finish())
+    }
+}
+
+/// Appends `s` to `out` as a JSON string literal.
+pub fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Encodes already JSON encoded values as an array.
+pub fn array<I: IntoIterator<Item = String>>(values: I) -> String {
+    let values: Vec<String> = values.into_iter().collect();
+    format!("[{}]", values.join(","))
+}
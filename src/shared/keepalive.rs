@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use super::conn::ConnOps;
+
+/// Keeps an idle connection open with PING frames.
+///
+/// Once nothing was received for `interval`, a PING elicits an
+/// acknowledgment from the peer: the PING restarts the idle timer of the
+/// peer, and its acknowledgment ours. The interval must then be below the
+/// idle timeout negotiated by the two ends.
+#[derive(Debug)]
+pub struct ConnKeepalive {
+    interval: Duration,
+    last_recv: Instant,
+    last_ping: Option<Instant>,
+    sent: u64,
+}
+
+impl ConnKeepalive {
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        ConnKeepalive {
+            interval,
+            last_recv: now,
+            last_ping: None,
+            sent: 0,
+        }
+    }
+
+    /// Accounts a datagram received at `now`.
+    pub fn on_recv(&mut self, now: Instant) {
+        self.last_recv = now;
+    }
+
+    /// Time at which the next PING is due, unless something is received.
+    pub fn next_timeout(&self) -> Instant {
+        self.last_ping
+            .map_or(self.last_recv, |t| t.max(self.last_recv))
+            + self.interval
+    }
+
+    /// Schedules a PING on `conn` if one is due at `now`, and returns the
+    /// time since something was last received if so.
+    pub fn poll<C: ConnOps>(&mut self, conn: &mut C, now: Instant) -> Option<Duration> {
+        if now < self.next_timeout() || conn.send_ack_eliciting().is_err() {
+            return None;
+        }
+
+        self.last_ping = Some(now);
+        self.sent += 1;
+
+        Some(now.saturating_duration_since(self.last_recv))
+    }
+
+    /// Forgets the previous connection, replaced at `now`.
+    pub fn restart(&mut self, now: Instant) {
+        self.last_recv = now;
+        self.last_ping = None;
+    }
+
+    /// PINGs sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+}
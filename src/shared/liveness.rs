@@ -0,0 +1,84 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// RTTs a path may receive nothing while it sends before it is suspected
+/// lost.
+pub const SUSPECT_RTTS: u32 = 4;
+
+/// Minimum time a path may receive nothing while it sends before it is
+/// suspected lost.
+pub const MIN_SUSPECT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Watches the traffic of the active path to suspect it lost.
+///
+/// A dead peer socket or a link dropping everything closes no path in
+/// quiche: the loss only shows as a path that keeps sending, its
+/// retransmissions and probes, and receives nothing. The path is suspected
+/// once it sent something and received nothing for `SUSPECT_RTTS` RTTs.
+#[derive(Debug, Default)]
+pub struct Liveness {
+    last: Option<Snapshot>,
+}
+
+/// Traffic of the path when it last received anything.
+#[derive(Debug)]
+struct Snapshot {
+    path: (SocketAddr, SocketAddr),
+    recv_bytes: u64,
+    sent_bytes: u64,
+    /// When the path is suspected lost, once it sent more since.
+    suspect_at: Option<Instant>,
+}
+
+impl Liveness {
+    /// Time at which the path is suspected lost, unless it receives
+    /// something meanwhile.
+    pub fn suspect_at(&self) -> Option<Instant> {
+        self.last.as_ref().and_then(|s| s.suspect_at)
+    }
+
+    /// Forgets the traffic seen so far.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Accounts the bytes `path` received and sent so far, and tells
+    /// whether it is suspected lost at `now`. A path is suspected once, then
+    /// again a timeout later if it still receives nothing.
+    pub fn observe(
+        &mut self,
+        path: (SocketAddr, SocketAddr),
+        recv_bytes: u64,
+        sent_bytes: u64,
+        rtt: Duration,
+        now: Instant,
+    ) -> bool {
+        match &mut self.last {
+            Some(last) if last.path == path && last.recv_bytes == recv_bytes => {
+                if last.suspect_at.is_none() && sent_bytes > last.sent_bytes {
+                    let timeout = (rtt * SUSPECT_RTTS).max(MIN_SUSPECT_TIMEOUT);
+                    last.suspect_at = Some(now + timeout);
+                }
+
+                let suspect = last.suspect_at.is_some_and(|t| t <= now);
+                if suspect {
+                    self.last = None;
+                }
+
+                suspect
+            }
+
+            _ => {
+                self.last = Some(Snapshot {
+                    path,
+                    recv_bytes,
+                    sent_bytes,
+                    suspect_at: None,
+                });
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use log::{log_enabled, Level};
+
+/// Sampling of the logs emitted for every packet received or sent, which
+/// make long runs slow and their logs huge.
+///
+/// Only one packet event out of `every` is logged, the first of each burst
+/// (the packets handled by one wakeup) always being logged. The last one is
+/// logged by the caller when `end_burst` tells it was skipped. Logs of other
+/// kinds, warnings and errors included, are not sampled.
+///
+/// Packet events are logged with their length, in bytes.
+#[derive(Debug)]
+pub struct PacketLog {
+    every: u64,
+    /// Events seen in the current burst.
+    seen: u64,
+    /// Events skipped since the last one logged.
+    skipped: u64,
+    /// Length of the last event.
+    last_len: usize,
+}
+
+impl PacketLog {
+    /// Logs one packet event out of `every`, every event if `every` is 0 or 1.
+    pub fn new(every: u64) -> Self {
+        PacketLog {
+            every: every.max(1),
+            seen: 0,
+            skipped: 0,
+            last_len: 0,
+        }
+    }
+
+    /// Whether the next packet event, of `len` bytes and logged at `level`,
+    /// is to be logged.
+    pub fn sample(&mut self, level: Level, len: usize) -> bool {
+        if !log_enabled!(level) {
+            return false;
+        }
+
+        self.last_len = len;
+
+        let log = self.seen.is_multiple_of(self.every);
+        self.seen += 1;
+
+        if log {
+            self.skipped = 0;
+        } else {
+            self.skipped += 1;
+        }
+
+        log
+    }
+
+    /// Ends the current burst. If its last event was skipped, returns its
+    /// length and the number of events skipped before it since the last one
+    /// logged, for the caller to log the last event.
+    pub fn end_burst(&mut self) -> Option<(usize, u64)> {
+        let skipped = std::mem::take(&mut self.skipped);
+        self.seen = 0;
+
+        skipped.checked_sub(1).map(|before| (self.last_len, before))
+    }
+}
+
+impl Default for PacketLog {
+    fn default() -> Self {
+        PacketLog::new(1)
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Interval at which the aggregates of the last wakeups are logged.
+pub const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Phase of a wakeup of the event loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading the sockets and handing the packets to the connections.
+    Read,
+    /// Reading the streams and scheduling the echoes.
+    Schedule,
+    /// Handling the path events of the connections.
+    PathEvents,
+    /// Sending the packets of the connections.
+    Write,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::Read,
+        Phase::Schedule,
+        Phase::PathEvents,
+        Phase::Write,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Read => "read",
+            Phase::Schedule => "schedule",
+            Phase::PathEvents => "path events",
+            Phase::Write => "write",
+        })
+    }
+}
+
+/// Time spent in a phase per wakeup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub wakeups: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl PhaseStats {
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / self.wakeups.max(1) as u128) as u64)
+    }
+
+    fn add(&mut self, elapsed: Duration) {
+        self.wakeups += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// Time spent in each phase per wakeup, over some wakeups.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoopStats {
+    pub phases: [PhaseStats; 4],
+}
+
+impl LoopStats {
+    pub fn phase(&self, phase: Phase) -> &PhaseStats {
+        &self.phases[phase.index()]
+    }
+
+    pub fn wakeups(&self) -> u64 {
+        self.phases[0].wakeups
+    }
+}
+
+impl fmt::Display for LoopStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} wakeups", self.wakeups())?;
+
+        for phase in Phase::ALL {
+            let stats = self.phase(phase);
+            write!(f, ", {} mean {:?} max {:?}", phase, stats.mean(), stats.max)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures the time each wakeup of the event loop spends in each phase,
+/// with `--loop-timing`.
+///
+/// A wakeup goes through the phases once per connection: each `lap` charges
+/// the time since the previous one to its phase, so the phases of all the
+/// connections add up. Only the phases are timed, not the wait for events.
+#[derive(Debug)]
+pub struct LoopTiming {
+    last: Instant,
+    /// Time spent in each phase during the current wakeup.
+    current: [Duration; 4],
+    /// Wakeups since the aggregates were last logged.
+    window: LoopStats,
+    window_start: Instant,
+    total: LoopStats,
+}
+
+impl LoopTiming {
+    pub fn new(now: Instant) -> Self {
+        LoopTiming {
+            last: now,
+            current: [Duration::ZERO; 4],
+            window: LoopStats::default(),
+            window_start: now,
+            total: LoopStats::default(),
+        }
+    }
+
+    /// Starts a wakeup, once the events are polled.
+    pub fn begin(&mut self, now: Instant) {
+        self.last = now;
+        self.current = [Duration::ZERO; 4];
+    }
+
+    /// Charges the time since the previous lap to `phase`.
+    pub fn lap(&mut self, phase: Phase, now: Instant) {
+        self.current[phase.index()] += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    /// Ends a wakeup, and returns the aggregates of the wakeups since the
+    /// last ones returned once `LOG_INTERVAL` elapsed.
+    pub fn end(&mut self, now: Instant) -> Option<LoopStats> {
+        for phase in Phase::ALL {
+            let elapsed = self.current[phase.index()];
+            self.window.phases[phase.index()].add(elapsed);
+            self.total.phases[phase.index()].add(elapsed);
+        }
+
+        if now.saturating_duration_since(self.window_start) < LOG_INTERVAL {
+            return None;
+        }
+
+        self.window_start = now;
+        Some(std::mem::take(&mut self.window))
+    }
+
+    /// Aggregates of every wakeup so far.
+    pub fn stats(&self) -> &LoopStats {
+        &self.total
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Outcome of the migrations of a connection to another path.
+///
+/// A migration succeeds once bytes are received on its path after it, and
+/// fails if quiche refused it or if its path failed validation or closed
+/// before that. A migration superseded by another one before either, or
+/// still pending when the connection closed, is neither.
+#[derive(Debug, Default)]
+pub struct Migrations {
+    /// Last migration, until its outcome is known.
+    pending: Option<Pending>,
+    stats: MigrationStats,
+}
+
+#[derive(Debug)]
+struct Pending {
+    path: (SocketAddr, SocketAddr),
+    started: Instant,
+    /// Bytes received on the path when migrating to it.
+    recv_bytes: u64,
+}
+
+/// Migrations attempted so far, and their outcomes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MigrationStats {
+    pub attempted: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Time between the successful migrations and the first bytes received
+    /// on their path, in total.
+    pub completion_time: Duration,
+}
+
+impl Migrations {
+    /// Accounts a migration to `path`, on which `recv_bytes` were received
+    /// so far.
+    pub fn on_migrated(&mut self, path: (SocketAddr, SocketAddr), recv_bytes: u64, now: Instant) {
+        self.stats.attempted += 1;
+        self.pending = Some(Pending {
+            path,
+            started: now,
+            recv_bytes,
+        });
+    }
+
+    /// Accounts a migration to `path` refused by quiche, either the pending
+    /// one or an attempt that never started.
+    pub fn on_refused(&mut self, path: (SocketAddr, SocketAddr)) {
+        if self.pending.as_ref().is_some_and(|p| p.path == path) {
+            self.pending = None;
+        } else {
+            self.stats.attempted += 1;
+        }
+
+        self.stats.failed += 1;
+    }
+
+    /// Fails the pending migration if it is to `path`, which failed
+    /// validation or closed.
+    pub fn on_path_lost(&mut self, path: (SocketAddr, SocketAddr)) {
+        if self.pending.as_ref().is_some_and(|p| p.path == path) {
+            self.pending = None;
+            self.stats.failed += 1;
+        }
+    }
+
+    /// Path of the pending migration.
+    pub fn pending(&self) -> Option<(SocketAddr, SocketAddr)> {
+        self.pending.as_ref().map(|p| p.path)
+    }
+
+    /// Completes the pending migration if more than the bytes received when
+    /// migrating were received on its path, `recv_bytes` now.
+    pub fn on_recv(&mut self, recv_bytes: u64, now: Instant) {
+        let Some(pending) = self.pending.take_if(|p| recv_bytes > p.recv_bytes) else {
+            return;
+        };
+
+        self.stats.succeeded += 1;
+        self.stats.completion_time += now.saturating_duration_since(pending.started);
+    }
+
+    pub fn stats(&self) -> MigrationStats {
+        self.stats
+    }
+}
+
+impl MigrationStats {
+    /// Mean time for a successful migration to complete.
+    pub fn mean_time(&self) -> Option<Duration> {
+        (self.succeeded > 0).then(|| self.completion_time / self.succeeded as u32)
+    }
+
+    /// Migrations neither succeeded nor failed.
+    pub fn unconfirmed(&self) -> u64 {
+        self.attempted - self.succeeded - self.failed
+    }
+}
+
+impl fmt::Display for MigrationStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} attempted, {} succeeded, {} failed, {} unconfirmed",
+            self.attempted,
+            self.succeeded,
+            self.failed,
+            self.unconfirmed()
+        )?;
+
+        if let Some(mean) = self.mean_time() {
+            write!(f, ", {:?} on average to complete", mean)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs::File,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    os::fd::AsRawFd,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use super::transport;
+
+/// Capabilities needed to create namespaces and enter them.
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_SYS_ADMIN: u32 = 21;
+
+/// Labs created by this process, to name their namespaces.
+static LABS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the process may create network namespaces and enter them, the
+/// tests needing them being skipped otherwise.
+pub fn privileged() -> bool {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return false;
+    };
+
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+        .is_some_and(|caps| caps & (1 << CAP_NET_ADMIN) != 0 && caps & (1 << CAP_SYS_ADMIN) != 0)
+}
+
+/// Delay and loss of a veth pair, added by `tc netem` in each direction.
+#[derive(Clone, Copy, Debug)]
+pub struct Netem {
+    pub delay: Duration,
+    /// Share of the packets dropped, between 0 and 1.
+    pub loss: f64,
+}
+
+impl Netem {
+    pub fn new(delay: Duration) -> Self {
+        Netem { delay, loss: 0.0 }
+    }
+
+    pub fn loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+}
+
+/// A client namespace and a server namespace joined by one veth pair per
+/// path, so that each path goes through its own interfaces.
+///
+/// Path `i` links `qt<i>c`, with address `10.99.<i>.1`, in the client
+/// namespace to `qt<i>s`, with address `10.99.<i>.2`, in the server one.
+/// The namespaces, and the interfaces with them, are deleted on drop.
+#[derive(Debug)]
+pub struct Lab {
+    client_ns: String,
+    server_ns: String,
+    paths: usize,
+}
+
+impl Lab {
+    /// Creates the namespaces, with one veth pair per entry of `links`.
+    pub fn new(links: &[Netem]) -> io::Result<Self> {
+        let id = LABS.fetch_add(1, Ordering::Relaxed);
+        let prefix = format!("qt-{}-{}", std::process::id(), id);
+
+        let mut lab = Lab {
+            client_ns: format!("{}-c", prefix),
+            server_ns: format!("{}-s", prefix),
+            paths: 0,
+        };
+
+        // Dropping the lab on an error deletes what was created.
+        run("ip", &["netns", "add", &lab.client_ns])?;
+        run("ip", &["netns", "add", &lab.server_ns])?;
+
+        for (path, netem) in links.iter().enumerate() {
+            lab.add_path(path, netem)?;
+            lab.paths += 1;
+        }
+
+        Ok(lab)
+    }
+
+    fn add_path(&self, path: usize, netem: &Netem) -> io::Result<()> {
+        let (client_if, server_if) = (Self::client_ifname(path), Self::server_ifname(path));
+
+        run(
+            "ip",
+            &[
+                "-n",
+                &self.client_ns,
+                "link",
+                "add",
+                &client_if,
+                "type",
+                "veth",
+                "peer",
+                "name",
+                &server_if,
+                "netns",
+                &self.server_ns,
+            ],
+        )?;
+
+        let ends = [
+            (&self.client_ns, &client_if, Self::client_ip(path)),
+            (&self.server_ns, &server_if, Self::server_ip(path)),
+        ];
+
+        for (ns, ifname, ip) in ends {
+            let addr = format!("{}/24", ip);
+            let delay = format!("{}us", netem.delay.as_micros());
+            let loss = format!("{}%", netem.loss * 100.0);
+
+            run("ip", &["-n", ns, "addr", "add", &addr, "dev", ifname])?;
+            run("ip", &["-n", ns, "link", "set", ifname, "up"])?;
+            run(
+                "tc",
+                &[
+                    "-n", ns, "qdisc", "add", "dev", ifname, "root", "netem", "delay", &delay,
+                    "loss", &loss,
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn paths(&self) -> usize {
+        self.paths
+    }
+
+    pub fn client_ifname(path: usize) -> String {
+        format!("qt{}c", path)
+    }
+
+    pub fn server_ifname(path: usize) -> String {
+        format!("qt{}s", path)
+    }
+
+    pub fn client_ip(path: usize) -> IpAddr {
+        Ipv4Addr::new(10, 99, path as u8, 1).into()
+    }
+
+    pub fn server_ip(path: usize) -> IpAddr {
+        Ipv4Addr::new(10, 99, path as u8, 2).into()
+    }
+
+    /// Binds a server socket on each path, in the server namespace, and
+    /// returns them with their addresses.
+    pub fn bind_server(&self) -> io::Result<(Vec<mio::net::UdpSocket>, Vec<SocketAddr>)> {
+        let sockets = in_netns(&self.server_ns, || {
+            (0..self.paths)
+                .map(|path| mio::net::UdpSocket::bind(SocketAddr::new(Self::server_ip(path), 0)))
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+
+        let addrs = sockets
+            .iter()
+            .map(|s| s.local_addr())
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok((sockets, addrs))
+    }
+
+    /// Binds a client socket on each path, in the client namespace. With
+    /// `by_device`, each is bound to the unspecified address and to the
+    /// interface of its path, as `--bind-dev` does, instead of to the
+    /// address of the interface.
+    pub fn bind_client(&self, by_device: bool) -> io::Result<Vec<mio::net::UdpSocket>> {
+        in_netns(&self.client_ns, || {
+            (0..self.paths)
+                .map(|path| {
+                    if !by_device {
+                        return mio::net::UdpSocket::bind(SocketAddr::new(
+                            Self::client_ip(path),
+                            0,
+                        ));
+                    }
+
+                    let socket = mio::net::UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+                    transport::bind_to_device(&socket, &Self::client_ifname(path))?;
+                    Ok(socket)
+                })
+                .collect()
+        })
+    }
+}
+
+impl Drop for Lab {
+    fn drop(&mut self) {
+        for ns in [&self.client_ns, &self.server_ns] {
+            let _ = run("ip", &["netns", "del", ns]);
+        }
+    }
+}
+
+/// Runs `f` with the current thread in the namespace `name`, then moves it
+/// back: the sockets created by `f` stay in `name`.
+fn in_netns<T, F: FnOnce() -> io::Result<T>>(name: &str, f: F) -> io::Result<T> {
+    let original = File::open("/proc/thread-self/ns/net")?;
+    let target = File::open(format!("/var/run/netns/{}", name))?;
+
+    setns(&target)?;
+    let result = f();
+
+    // A thread left in the namespace would bind every later socket there.
+    setns(&original).expect("cannot return to the original network namespace");
+
+    result
+}
+
+fn setns(ns: &File) -> io::Result<()> {
+    // SAFETY: the file descriptor is open for the duration of the call.
+    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+fn run(program: &str, args: &[&str]) -> io::Result<()> {
+    let status = Command::new(program).args(args).status()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            status
+        )));
+    }
+
+    Ok(())
+}
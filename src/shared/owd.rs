@@ -0,0 +1,153 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::SocketAddr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Bytes of a timestamp, microseconds since the Unix epoch in big endian.
+pub const STAMP_LEN: usize = 8;
+
+/// Wall clock read through monotonic instants, so that the timestamps follow
+/// the time the client and the simulator pass around.
+#[derive(Clone, Copy, Debug)]
+pub struct WallClock {
+    /// Microseconds since the Unix epoch at `at`.
+    base: u64,
+    at: Instant,
+}
+
+impl WallClock {
+    pub fn new(now: Instant) -> Self {
+        let base = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+
+        WallClock { base, at: now }
+    }
+
+    /// Shifts the clock by `micros`, as the clock of another host could be.
+    pub fn skewed(mut self, micros: i64) -> Self {
+        self.base = self.base.saturating_add_signed(micros);
+        self
+    }
+
+    /// Microseconds since the Unix epoch at `now`.
+    pub fn micros(&self, now: Instant) -> u64 {
+        self.base + now.saturating_duration_since(self.at).as_micros() as u64
+    }
+}
+
+/// Prepends the timestamp `micros` to `data`.
+pub fn stamp(micros: u64, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(STAMP_LEN + data.len());
+    out.extend_from_slice(&micros.to_be_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Splits the timestamp off the front of `data`.
+pub fn unstamp(data: &[u8]) -> Option<(u64, &[u8])> {
+    let (stamp, rest) = data.split_first_chunk::<STAMP_LEN>()?;
+
+    Some((u64::from_be_bytes(*stamp), rest))
+}
+
+/// Average one-way delays of the messages sent on a path, in microseconds.
+///
+/// They are differences between the clocks of the client and the server,
+/// so any offset between the clocks is added to the forward delay and taken
+/// from the reverse one. Their sum, the RTT seen by the message, does not
+/// depend on it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathOwd {
+    pub path: (SocketAddr, SocketAddr),
+    pub samples: u64,
+    /// From the client sending the message to the server receiving it.
+    pub forward: f64,
+    /// From the server echoing the message to the client receiving it.
+    pub reverse: f64,
+}
+
+impl PathOwd {
+    /// Forward delay above the reverse one, in microseconds.
+    pub fn asymmetry(&self) -> f64 {
+        self.forward - self.reverse
+    }
+}
+
+impl fmt::Display for PathOwd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}): forward {:.2}ms, reverse {:.2}ms, asymmetry {:+.2}ms over {} messages",
+            self.path.0,
+            self.path.1,
+            self.forward / 1000.0,
+            self.reverse / 1000.0,
+            self.asymmetry() / 1000.0,
+            self.samples
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Sums {
+    samples: u64,
+    forward: i64,
+    reverse: i64,
+}
+
+/// One-way delays of the timestamped messages, per path.
+#[derive(Debug, Default)]
+pub struct OwdStats {
+    paths: BTreeMap<(SocketAddr, SocketAddr), Sums>,
+}
+
+impl OwdStats {
+    /// Accounts a message sent on `path` at `sent`, received by the server
+    /// at `echoed` and whose echo arrived at `received`, all in
+    /// microseconds of the clock of their host.
+    pub fn record(
+        &mut self,
+        path: (SocketAddr, SocketAddr),
+        sent: u64,
+        echoed: u64,
+        received: u64,
+    ) {
+        let sums = self.paths.entry(path).or_default();
+        sums.samples += 1;
+        sums.forward += echoed as i64 - sent as i64;
+        sums.reverse += received as i64 - echoed as i64;
+    }
+
+    /// Average delays of each path, by path.
+    pub fn paths(&self) -> Vec<PathOwd> {
+        self.paths
+            .iter()
+            .map(|(path, sums)| PathOwd {
+                path: *path,
+                samples: sums.samples,
+                forward: sums.forward as f64 / sums.samples as f64,
+                reverse: sums.reverse as f64 / sums.samples as f64,
+            })
+            .collect()
+    }
+}
+
+/// Largest difference between the forward delays of two paths, and between
+/// their reverse delays, in microseconds. Unlike the delays themselves,
+/// these do not depend on the offset between the clocks.
+pub fn spread(paths: &[PathOwd]) -> Option<(f64, f64)> {
+    if paths.len() < 2 {
+        return None;
+    }
+
+    let range = |delay: fn(&PathOwd) -> f64| {
+        let min = paths.iter().map(delay).fold(f64::INFINITY, f64::min);
+        let max = paths.iter().map(delay).fold(f64::NEG_INFINITY, f64::max);
+        max - min
+    };
+
+    Some((range(|p| p.forward), range(|p| p.reverse)))
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+
+/// Size of the big-endian length written before the payload.
+pub const LENGTH_PREFIX_LEN: usize = 4;
+
+/// Error returned when a payload does not fit in the padded size.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PadError {
+    pub len: usize,
+    pub size: usize,
+}
+
+impl fmt::Display for PadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes do not fit in {} padded bytes ({} needed for the length)",
+            self.len, self.size, LENGTH_PREFIX_LEN
+        )
+    }
+}
+
+impl std::error::Error for PadError {}
+
+/// Frames `payload` with its length and pads it with zeros up to `size`.
+pub fn pad(payload: &[u8], size: usize) -> Result<Vec<u8>, PadError> {
+    let len = u32::try_from(payload.len()).ok();
+
+    let len = match len {
+        Some(len) if payload.len() + LENGTH_PREFIX_LEN <= size => len,
+
+        _ => {
+            return Err(PadError {
+                len: payload.len(),
+                size,
+            })
+        }
+    };
+
+    let mut frame = Vec::with_capacity(size);
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame.resize(size, 0);
+
+    Ok(frame)
+}
+
+/// Returns the payload of a frame built by `pad`, or `None` if the frame is
+/// shorter than its length says.
+pub fn unpad(frame: &[u8]) -> Option<&[u8]> {
+    let (len, rest) = frame.split_first_chunk::<LENGTH_PREFIX_LEN>()?;
+
+    rest.get(..u32::from_be_bytes(*len) as usize)
+}
@@ -0,0 +1,163 @@
+use std::{fmt, net::SocketAddr, str::FromStr, time::Duration};
+
+type Path = (SocketAddr, SocketAddr);
+
+/// Weights of the RTT, the loss rate and the congestion window in the
+/// quality of a path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityWeights {
+    pub rtt: f64,
+    pub loss: f64,
+    pub cwnd: f64,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        QualityWeights {
+            rtt: 0.4,
+            loss: 0.4,
+            cwnd: 0.2,
+        }
+    }
+}
+
+impl FromStr for QualityWeights {
+    type Err = String;
+
+    /// Parses comma-separated `rtt=<w>`, `loss=<w>` and `cwnd=<w>`, the
+    /// weights not given are 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = QualityWeights {
+            rtt: 0.0,
+            loss: 0.0,
+            cwnd: 0.0,
+        };
+
+        for item in s.split(',') {
+            let (name, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("invalid weight '{}'", item))?;
+
+            let value = match value.parse::<f64>() {
+                Ok(w) if w.is_finite() && w >= 0.0 => w,
+                _ => return Err(format!("invalid weight '{}' in '{}'", value, item)),
+            };
+
+            match name {
+                "rtt" => weights.rtt = value,
+                "loss" => weights.loss = value,
+                "cwnd" => weights.cwnd = value,
+                _ => return Err(format!("unknown weight '{}'", name)),
+            }
+        }
+
+        if weights.rtt + weights.loss + weights.cwnd == 0.0 {
+            return Err("at least one weight must be positive".to_string());
+        }
+
+        Ok(weights)
+    }
+}
+
+/// Figures of one path that its quality is computed from, from its
+/// `quiche::PathStats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathSample {
+    pub path: Path,
+    /// Smoothed RTT.
+    pub rtt: Duration,
+    pub sent: usize,
+    pub lost: usize,
+    pub cwnd: usize,
+}
+
+impl From<&quiche::PathStats> for PathSample {
+    fn from(stats: &quiche::PathStats) -> Self {
+        PathSample {
+            path: (stats.local_addr, stats.peer_addr),
+            rtt: stats.rtt,
+            sent: stats.sent,
+            lost: stats.lost,
+            cwnd: stats.cwnd,
+        }
+    }
+}
+
+/// Quality of one path, between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathQuality {
+    pub path: Path,
+    pub score: f64,
+    /// Components of the score, each between 0 and 1 before weighting.
+    pub rtt: f64,
+    pub loss: f64,
+    pub cwnd: f64,
+}
+
+impl fmt::Display for PathQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}): {:.2} (rtt {:.2}, loss {:.2}, cwnd {:.2})",
+            self.path.0, self.path.1, self.score, self.rtt, self.loss, self.cwnd
+        )
+    }
+}
+
+/// Scores `paths` relative to each other and ranks them, best first.
+///
+/// The RTT and the congestion window are normalized against the best path:
+/// the path with the lowest RTT and the one with the largest window get 1,
+/// a path twice as slow or with half the window gets 0.5. The loss
+/// component is the share of the packets sent that were not lost. The score
+/// is the weighted mean of the three, so 1 for a path that is the best on
+/// every count.
+pub fn rank<I: IntoIterator<Item = PathSample>>(
+    paths: I,
+    weights: &QualityWeights,
+) -> Vec<PathQuality> {
+    let paths: Vec<PathSample> = paths.into_iter().collect();
+
+    let min_rtt = paths.iter().map(|p| p.rtt).min().unwrap_or_default();
+    let max_cwnd = paths.iter().map(|p| p.cwnd).max().unwrap_or_default();
+    let total = weights.rtt + weights.loss + weights.cwnd;
+
+    let mut ranked: Vec<PathQuality> = paths
+        .iter()
+        .map(|p| {
+            let rtt = if p.rtt.is_zero() {
+                1.0
+            } else {
+                min_rtt.as_secs_f64() / p.rtt.as_secs_f64()
+            };
+
+            let loss = match p.sent {
+                0 => 1.0,
+                sent => 1.0 - (p.lost.min(sent) as f64 / sent as f64),
+            };
+
+            let cwnd = match max_cwnd {
+                0 => 1.0,
+                max => p.cwnd as f64 / max as f64,
+            };
+
+            let score = if total > 0.0 {
+                (weights.rtt * rtt + weights.loss * loss + weights.cwnd * cwnd) / total
+            } else {
+                0.0
+            };
+
+            PathQuality {
+                path: p.path,
+                score,
+                rtt,
+                loss,
+                cwnd,
+            }
+        })
+        .collect();
+
+    // Ties are broken by path, for a stable order.
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.path.cmp(&b.path)));
+    ranked
+}
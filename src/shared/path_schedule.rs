@@ -0,0 +1,292 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Time before a window starts from which its path is probed, so that it is
+/// validated by then and the handover costs no round trip.
+pub const SCHEDULE_LEAD: Duration = Duration::from_millis(500);
+
+/// Time during which new stream data goes on one path, since the client
+/// started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub path: usize,
+    pub start: Duration,
+    /// End of the window, the last one lasting until the run ends if
+    /// `None`.
+    pub end: Option<Duration>,
+}
+
+impl Window {
+    fn contains(&self, elapsed: Duration) -> bool {
+        elapsed >= self.start && self.end.is_none_or(|end| elapsed < end)
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "path {} from {:.3}s",
+            self.path,
+            self.start.as_secs_f64()
+        )?;
+
+        match self.end {
+            Some(end) => write!(f, " to {:.3}s", end.as_secs_f64()),
+            None => write!(f, " on"),
+        }
+    }
+}
+
+/// Windows of `--path-schedule`, in order and without overlap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathSchedule {
+    windows: Vec<Window>,
+}
+
+impl PathSchedule {
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    /// Highest path index of the schedule.
+    pub fn max_path(&self) -> usize {
+        self.windows.iter().map(|w| w.path).max().unwrap_or(0)
+    }
+
+    /// Index of the window in which `elapsed` falls, if any.
+    pub fn current(&self, elapsed: Duration) -> Option<usize> {
+        self.windows.iter().position(|w| w.contains(elapsed))
+    }
+
+    /// Index of the next window, if it starts within `lead` of `elapsed`.
+    pub fn upcoming(&self, elapsed: Duration, lead: Duration) -> Option<usize> {
+        self.windows
+            .iter()
+            .position(|w| w.start > elapsed && w.start - elapsed <= lead)
+    }
+}
+
+/// Parses `<secs>`, `<secs>s` or `<ms>ms`.
+fn parse_time(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid time '{}'", s);
+
+    if let Some(ms) = s.strip_suffix("ms") {
+        return ms.parse().map(Duration::from_millis).map_err(|_| invalid());
+    }
+
+    let secs: f64 = s
+        .strip_suffix('s')
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| invalid())?;
+
+    Duration::try_from_secs_f64(secs).map_err(|_| invalid())
+}
+
+impl FromStr for PathSchedule {
+    type Err = String;
+
+    /// Parses `<path>:<start>-[<end>],...`, e.g. `0:0-5s,1:5s-10s,0:10s-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut windows: Vec<Window> = vec![];
+
+        for window in s.split(',') {
+            let (path, span) = window
+                .split_once(':')
+                .ok_or_else(|| format!("expected <path>:<start>-<end>, got '{}'", window))?;
+            let (start, end) = span
+                .split_once('-')
+                .ok_or_else(|| format!("expected <start>-<end>, got '{}'", span))?;
+
+            let window = Window {
+                path: path
+                    .parse()
+                    .map_err(|_| format!("invalid path '{}'", path))?,
+                start: parse_time(start)?,
+                end: match end {
+                    "" => None,
+                    end => Some(parse_time(end)?),
+                },
+            };
+
+            if window.end.is_some_and(|end| end <= window.start) {
+                return Err(format!("window '{}' ends before it starts", span));
+            }
+
+            if let Some(last) = windows.last() {
+                if last.end.is_none_or(|end| end > window.start) {
+                    return Err(format!("window '{}' overlaps the previous one", span));
+                }
+            }
+
+            windows.push(window);
+        }
+
+        Ok(PathSchedule { windows })
+    }
+}
+
+/// Counters of a path since the connection started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathCounters {
+    /// Bytes sent on the path, whatever the frames.
+    pub sent_bytes: u64,
+    /// Stream bytes received on the path.
+    pub stream_bytes: u64,
+}
+
+/// How a window of the schedule was followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowUsage {
+    pub window: Window,
+    /// Bytes sent on the path of the window, and on the others.
+    pub sent_bytes: u64,
+    pub sent_elsewhere: u64,
+    /// Stream bytes received on the path of the window, and on the others:
+    /// the latter were sent before the handover or retransmitted.
+    pub stream_bytes: u64,
+    pub stream_elsewhere: u64,
+    /// Whether the path was validated when the window started.
+    pub ready: bool,
+    /// Time from the start of the window to the path becoming the active
+    /// one, if it did.
+    pub switch_delay: Option<Duration>,
+}
+
+impl fmt::Display for WindowUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: sent {} bytes on it and {} elsewhere, received {} stream bytes on it and {} \
+             elsewhere, {}",
+            self.window,
+            self.sent_bytes,
+            self.sent_elsewhere,
+            self.stream_bytes,
+            self.stream_elsewhere,
+            if self.ready {
+                "validated ahead"
+            } else {
+                "not validated ahead"
+            }
+        )?;
+
+        match self.switch_delay {
+            Some(delay) => write!(f, ", active after {} ms", delay.as_millis()),
+            None => write!(f, ", never active"),
+        }
+    }
+}
+
+/// Window in progress, with the counters of the paths when it started.
+#[derive(Debug)]
+struct Open {
+    index: usize,
+    start: Vec<PathCounters>,
+    ready: bool,
+    switch_delay: Option<Duration>,
+}
+
+/// Accounts the bytes each window of a schedule carried, on its path and
+/// on the others.
+#[derive(Debug)]
+pub struct ScheduleTracker {
+    schedule: PathSchedule,
+    open: Option<Open>,
+    usage: Vec<WindowUsage>,
+}
+
+impl ScheduleTracker {
+    pub fn new(schedule: PathSchedule) -> Self {
+        ScheduleTracker {
+            schedule,
+            open: None,
+            usage: vec![],
+        }
+    }
+
+    pub fn schedule(&self) -> &PathSchedule {
+        &self.schedule
+    }
+
+    /// Path of the window in progress, if any.
+    pub fn current_path(&self, elapsed: Duration) -> Option<usize> {
+        let index = self.schedule.current(elapsed)?;
+        Some(self.schedule.windows[index].path)
+    }
+
+    /// Accounts a step `elapsed` after the start, with the counters of each
+    /// path, the index of the active one and whether each is validated.
+    pub fn on_step(
+        &mut self,
+        elapsed: Duration,
+        counters: &[PathCounters],
+        active: Option<usize>,
+        validated: &[bool],
+    ) {
+        let index = self.schedule.current(elapsed);
+
+        if self.open.as_ref().map(|o| o.index) != index {
+            self.close(counters);
+
+            self.open = index.map(|index| Open {
+                index,
+                start: counters.to_vec(),
+                ready: validated
+                    .get(self.schedule.windows[index].path)
+                    .copied()
+                    .unwrap_or(false),
+                switch_delay: None,
+            });
+        }
+
+        let Some(open) = &mut self.open else {
+            return;
+        };
+
+        let window = self.schedule.windows[open.index];
+        if open.switch_delay.is_none() && active == Some(window.path) {
+            open.switch_delay = Some(elapsed.saturating_sub(window.start));
+        }
+    }
+
+    /// Closes the window in progress, once the connection is closed.
+    pub fn close(&mut self, counters: &[PathCounters]) {
+        let Some(open) = self.open.take() else {
+            return;
+        };
+
+        let window = self.schedule.windows[open.index];
+        let mut usage = WindowUsage {
+            window,
+            sent_bytes: 0,
+            sent_elsewhere: 0,
+            stream_bytes: 0,
+            stream_elsewhere: 0,
+            ready: open.ready,
+            switch_delay: open.switch_delay,
+        };
+
+        // The counters of quiche start over with a new connection.
+        for (path, now) in counters.iter().enumerate() {
+            let then = open.start.get(path).copied().unwrap_or_default();
+            let sent = now.sent_bytes.saturating_sub(then.sent_bytes);
+            let stream = now.stream_bytes.saturating_sub(then.stream_bytes);
+
+            if path == window.path {
+                usage.sent_bytes += sent;
+                usage.stream_bytes += stream;
+            } else {
+                usage.sent_elsewhere += sent;
+                usage.stream_elsewhere += stream;
+            }
+        }
+
+        self.usage.push(usage);
+    }
+
+    /// Windows completed so far.
+    pub fn usage(&self) -> &[WindowUsage] {
+        &self.usage
+    }
+}
@@ -0,0 +1,75 @@
+use super::file_transfer::hex;
+
+/// Number of bytes of a binary payload shown in its hex dump.
+pub const HEX_DUMP_LEN: usize = 32;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Decodes hexadecimal digits, in either case, two per byte.
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in '{}'", s));
+    }
+
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid hex digits in '{}'", s))
+        })
+        .collect()
+}
+
+/// Decodes standard base64 (RFC 4648 section 4), the trailing padding being
+/// optional.
+pub fn decode_base64(s: &str) -> Result<Vec<u8>, String> {
+    let digits = s.trim_end_matches('=');
+    if digits.len() % 4 == 1 || s.len() - digits.len() > 2 {
+        return Err(format!("invalid base64 length in '{}'", s));
+    }
+
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for c in digits.bytes() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|a| *a == c)
+            .ok_or_else(|| format!("invalid base64 character '{}' in '{}'", c as char, s))?;
+
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+/// The payload as text, if it is UTF-8 without control characters and can
+/// be printed as is on one line.
+pub fn printable(payload: &[u8]) -> Option<&str> {
+    std::str::from_utf8(payload)
+        .ok()
+        .filter(|text| !text.chars().any(char::is_control))
+}
+
+/// Length and hex dump of the first `HEX_DUMP_LEN` bytes of a binary
+/// payload.
+pub fn hex_dump(payload: &[u8]) -> String {
+    let shown = &payload[..payload.len().min(HEX_DUMP_LEN)];
+    let ellipsis = if shown.len() < payload.len() {
+        "..."
+    } else {
+        ""
+    };
+
+    format!("{} bytes [{}{}]", payload.len(), hex(shown), ellipsis)
+}
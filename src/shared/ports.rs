@@ -0,0 +1,72 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    net::SocketAddr,
+    path::Path,
+};
+
+/// Address of the `i`-th socket bound from `base`: the `i`-th port above
+/// the one of `base`, or `base` itself if its port is 0, for the OS to pick
+/// a free one, so that several runs on one machine do not collide.
+pub fn socket_addr(base: SocketAddr, i: usize) -> SocketAddr {
+    match base.port() {
+        0 => base,
+        port => SocketAddr::new(base.ip(), port + i as u16),
+    }
+}
+
+/// Binds `count` sockets from `base`, see `socket_addr`.
+pub fn bind_sockets(base: SocketAddr, count: usize) -> io::Result<Vec<mio::net::UdpSocket>> {
+    (0..count)
+        .map(|i| mio::net::UdpSocket::bind(socket_addr(base, i)))
+        .collect()
+}
+
+/// Writes the addresses of the server sockets to `path`, one per line, for
+/// clients and test harnesses to discover them.
+///
+/// The addresses are written to a temporary file renamed over `path`, so
+/// that readers polling for it never see a partial list.
+pub fn write_port_file<P: AsRef<Path>>(path: P, addrs: &[SocketAddr]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut out = File::create(&tmp)?;
+
+    for addr in addrs {
+        writeln!(out, "{}", addr)?;
+    }
+
+    out.sync_all()?;
+    std::fs::rename(tmp, path)
+}
+
+/// Reads the addresses written by `write_port_file`, the one of path `i` on
+/// line `i`.
+pub fn read_port_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<SocketAddr>> {
+    let mut addrs = vec![];
+
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let addr = line.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid address '{}'", line),
+            )
+        })?;
+        addrs.push(addr);
+    }
+
+    if addrs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no address"));
+    }
+
+    Ok(addrs)
+}
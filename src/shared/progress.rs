@@ -0,0 +1,143 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Minimum time between two progress snapshots of a transfer, besides its
+/// last one.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress of a run of a transfer at one point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferProgress {
+    /// Transfer the run sends, counted from 0.
+    pub transfer: u64,
+    /// Bytes of the transfer.
+    pub size: u64,
+    /// Bytes handed to quiche, each once whatever the retransmissions.
+    pub sent: u64,
+    /// Bytes the peer confirmed it received.
+    pub acked: u64,
+    /// Time since the run started.
+    pub elapsed: Duration,
+    /// Bytes acknowledged per second since the previous snapshot.
+    pub rate: f64,
+    /// Bytes acknowledged per second since the run started.
+    pub average: f64,
+    /// Validated paths of the connection.
+    pub paths: usize,
+}
+
+impl TransferProgress {
+    /// Whether every byte was acknowledged.
+    pub fn is_complete(&self) -> bool {
+        self.acked >= self.size
+    }
+
+    /// Time left at the average rate, if anything was acknowledged yet.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.is_complete() {
+            return Some(Duration::ZERO);
+        }
+
+        if self.average <= 0.0 {
+            return None;
+        }
+
+        Some(Duration::from_secs_f64(
+            (self.size - self.acked) as f64 / self.average,
+        ))
+    }
+}
+
+impl fmt::Display for TransferProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "transfer {}: {}/{} bytes sent, {} acked, {:.0} B/s (average {:.0} B/s), ETA ",
+            self.transfer, self.sent, self.size, self.acked, self.rate, self.average
+        )?;
+
+        match self.eta() {
+            Some(eta) => write!(f, "{:.1}s", eta.as_secs_f64())?,
+            None => write!(f, "unknown")?,
+        }
+
+        write!(f, ", {} path(s)", self.paths)
+    }
+}
+
+/// Turns the byte counters of a run into snapshots, at most one per
+/// `PROGRESS_INTERVAL` but the last one.
+///
+/// The acknowledged bytes of a snapshot never go below those of the
+/// previous one.
+#[derive(Debug)]
+pub struct ProgressMeter {
+    transfer: u64,
+    size: u64,
+    started: Instant,
+    /// Time and acknowledged bytes of the last snapshot.
+    last: Option<(Instant, u64)>,
+    acked: u64,
+    done: bool,
+}
+
+impl ProgressMeter {
+    pub fn new(transfer: u64, size: u64, now: Instant) -> Self {
+        ProgressMeter {
+            transfer,
+            size,
+            started: now,
+            last: None,
+            acked: 0,
+            done: false,
+        }
+    }
+
+    /// Snapshot of the run with `sent` bytes handed to quiche and `acked`
+    /// confirmed, if one is due. Once every byte is acknowledged, the final
+    /// snapshot is always returned, and nothing after it.
+    pub fn update(
+        &mut self,
+        sent: u64,
+        acked: u64,
+        paths: usize,
+        now: Instant,
+    ) -> Option<TransferProgress> {
+        if self.done {
+            return None;
+        }
+
+        self.acked = self.acked.max(acked).min(self.size);
+        self.done = self.acked >= self.size;
+
+        let (since, acked_then) = self.last.unwrap_or((self.started, 0));
+        if !self.done && self.last.is_some() && now < since + PROGRESS_INTERVAL {
+            return None;
+        }
+
+        self.last = Some((now, self.acked));
+
+        let per_second = |bytes: u64, since: Instant| {
+            let elapsed = now.saturating_duration_since(since);
+
+            if elapsed.is_zero() {
+                0.0
+            } else {
+                bytes as f64 / elapsed.as_secs_f64()
+            }
+        };
+
+        Some(TransferProgress {
+            transfer: self.transfer,
+            size: self.size,
+            sent: sent.max(self.acked),
+            acked: self.acked,
+            elapsed: now.saturating_duration_since(self.started),
+            rate: per_second(self.acked - acked_then, since),
+            average: per_second(self.acked, self.started),
+            paths,
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, fmt, net::SocketAddr};
+
+type Path = (SocketAddr, SocketAddr);
+
+/// PTOs after which a path that declared fewer packets lost looks
+/// black-holed rather than lossy.
+pub const BLACK_HOLE_PTOS: u64 = 3;
+
+/// Packet counters of one path, from its `quiche::PathStats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathCounters {
+    pub path: Path,
+    pub sent: usize,
+    pub lost: usize,
+}
+
+impl From<&quiche::PathStats> for PathCounters {
+    fn from(stats: &quiche::PathStats) -> Self {
+        PathCounters {
+            path: (stats.local_addr, stats.peer_addr),
+            sent: stats.sent,
+            lost: stats.lost,
+        }
+    }
+}
+
+/// Probe timeouts and losses of one path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathPtos {
+    pub path: Path,
+    pub ptos: u64,
+    /// Packets declared lost.
+    pub lost: u64,
+}
+
+impl PathPtos {
+    /// Whether the path had many PTOs but few losses: its packets went
+    /// unacknowledged without later ones being acknowledged to declare them
+    /// lost, as when a link silently drops everything.
+    pub fn black_holed(&self) -> bool {
+        self.ptos >= BLACK_HOLE_PTOS && self.lost < self.ptos
+    }
+}
+
+impl fmt::Display for PathPtos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}): {} PTOs, {} lost packets",
+            self.path.0, self.path.1, self.ptos, self.lost
+        )?;
+
+        if self.black_holed() {
+            write!(f, " (black hole?)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the probe timeouts of each path.
+///
+/// quiche keeps its PTO count private. When its loss detection timer
+/// expires on a path, `on_timeout` either declares packets lost by time
+/// threshold, or fires a PTO after which the path sends probes whatever its
+/// congestion window. A PTO is counted on the paths that declared nothing
+/// lost when the timer expired and then sent packets. A path that also had
+/// other packets to send in that step is counted too, the counts are upper
+/// bounds.
+#[derive(Debug, Default)]
+pub struct PtoCounter {
+    /// Packets lost on each path of the current connection when its timer
+    /// expired.
+    lost_before: HashMap<Path, usize>,
+    /// Paths that may have fired a PTO, with the packets they had sent.
+    armed: HashMap<Path, usize>,
+    /// Packets lost on each path of the current connection, last seen.
+    lost_seen: HashMap<Path, usize>,
+    paths: HashMap<Path, PathPtos>,
+}
+
+impl PtoCounter {
+    /// The timer of quiche expired, `on_timeout` is about to be called.
+    pub fn before_timeout<I: IntoIterator<Item = PathCounters>>(&mut self, paths: I) {
+        self.lost_before = paths.into_iter().map(|c| (c.path, c.lost)).collect();
+    }
+
+    /// `on_timeout` was called after `before_timeout`.
+    pub fn after_timeout<I: IntoIterator<Item = PathCounters>>(&mut self, paths: I) {
+        for counters in paths {
+            self.on_lost(&counters);
+
+            if self.lost_before.get(&counters.path) == Some(&counters.lost) {
+                self.armed.insert(counters.path, counters.sent);
+            }
+        }
+
+        self.lost_before.clear();
+    }
+
+    /// The packets of the step were sent.
+    pub fn on_sent<I: IntoIterator<Item = PathCounters>>(&mut self, paths: I) {
+        for counters in paths {
+            self.on_lost(&counters);
+
+            if self
+                .armed
+                .get(&counters.path)
+                .is_some_and(|sent| counters.sent > *sent)
+            {
+                self.entry(counters.path).ptos += 1;
+            }
+        }
+
+        self.armed.clear();
+    }
+
+    /// A new connection starts, its counters start from zero.
+    pub fn restart(&mut self) {
+        self.lost_before.clear();
+        self.armed.clear();
+        self.lost_seen.clear();
+    }
+
+    /// PTOs and losses of every path seen, across connections.
+    pub fn paths(&self) -> Vec<PathPtos> {
+        let mut paths: Vec<_> = self.paths.values().copied().collect();
+        paths.sort_by_key(|p| p.path);
+        paths
+    }
+
+    pub fn ptos(&self, path: Path) -> u64 {
+        self.paths.get(&path).map_or(0, |p| p.ptos)
+    }
+
+    fn on_lost(&mut self, counters: &PathCounters) {
+        let seen = self.lost_seen.entry(counters.path).or_default();
+        let new = counters.lost.saturating_sub(*seen) as u64;
+        *seen = counters.lost;
+
+        self.entry(counters.path).lost += new;
+    }
+
+    fn entry(&mut self, path: Path) -> &mut PathPtos {
+        self.paths.entry(path).or_insert(PathPtos {
+            path,
+            ptos: 0,
+            lost: 0,
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::VecDeque, fmt};
+
+use super::conn::ConnOps;
+
+/// Default number of streams read per wakeup.
+pub const DEFAULT_READ_BUDGET: usize = 64;
+
+/// Streams read per wakeup of a driver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    /// Wakeups during which at least one stream was read.
+    pub wakeups: u64,
+    pub streams: u64,
+    pub max_per_wakeup: usize,
+    /// Wakeups that ended with streams left to read.
+    pub budget_reached: u64,
+}
+
+impl ReadStats {
+    /// Average number of streams read per wakeup that read any.
+    pub fn mean(&self) -> f64 {
+        self.streams as f64 / self.wakeups.max(1) as f64
+    }
+}
+
+impl fmt::Display for ReadStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "avg {:.1}, max {} over {} wakeups (budget reached {} times)",
+            self.mean(),
+            self.max_per_wakeup,
+            self.wakeups,
+            self.budget_reached
+        )
+    }
+}
+
+/// Hands out the readable streams of a connection, at most `budget` per
+/// wakeup.
+///
+/// Streams are taken one at a time with `stream_readable_next`, which
+/// reports a stream once until new data arrives on it, instead of listing
+/// every readable stream on each wakeup. Each stream taken must be read
+/// until quiche returns `Done`, or handed back with `defer`. Once the budget
+/// is spent, the remaining streams wait for the next wakeup, which the
+/// driver must not delay (see `has_backlog`) so that its timers still run
+/// between two batches of streams.
+#[derive(Debug)]
+pub struct ReadBudget {
+    budget: usize,
+    /// Streams not read by an earlier wakeup, read first.
+    deferred: VecDeque<u64>,
+    /// Streams handed back during the current wakeup.
+    requeued: Vec<u64>,
+    /// Streams taken during the current wakeup.
+    taken: usize,
+    exhausted: bool,
+    stats: ReadStats,
+}
+
+impl ReadBudget {
+    pub fn new(budget: usize) -> Self {
+        ReadBudget {
+            budget,
+            deferred: VecDeque::new(),
+            requeued: Vec::new(),
+            taken: 0,
+            exhausted: false,
+            stats: ReadStats::default(),
+        }
+    }
+
+    /// Starts a wakeup. Returns whether the previous one left streams to
+    /// read.
+    pub fn begin(&mut self) -> bool {
+        let backlog = self.exhausted;
+
+        self.taken = 0;
+        self.exhausted = false;
+
+        for stream_id in self.requeued.drain(..) {
+            if !self.deferred.contains(&stream_id) {
+                self.deferred.push_back(stream_id);
+            }
+        }
+
+        backlog
+    }
+
+    /// Next stream to read during this wakeup, if any is readable and the
+    /// budget is not spent.
+    pub fn next<C: ConnOps>(&mut self, conn: &mut C) -> Option<u64> {
+        if self.taken >= self.budget {
+            if !self.exhausted {
+                self.exhausted = true;
+                self.stats.budget_reached += 1;
+            }
+
+            return None;
+        }
+
+        let stream_id = self
+            .deferred
+            .pop_front()
+            .or_else(|| conn.stream_readable_next())?;
+
+        if self.taken == 0 {
+            self.stats.wakeups += 1;
+        }
+
+        self.taken += 1;
+        self.stats.streams += 1;
+        self.stats.max_per_wakeup = self.stats.max_per_wakeup.max(self.taken);
+
+        Some(stream_id)
+    }
+
+    /// Hands back a stream taken but not read, to be taken again on the
+    /// next wakeup.
+    pub fn defer(&mut self, stream_id: u64) {
+        self.requeued.push(stream_id);
+    }
+
+    /// Whether the budget of the current wakeup was spent with streams
+    /// possibly left to read, the driver must then wake up again at once.
+    pub fn has_backlog(&self) -> bool {
+        self.exhausted
+    }
+
+    pub fn stats(&self) -> ReadStats {
+        self.stats
+    }
+}
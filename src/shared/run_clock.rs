@@ -0,0 +1,83 @@
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::shared::encoding::Object;
+
+/// Clock shared by every output of a run, so that their timestamps can be
+/// correlated.
+///
+/// It pairs the wall-clock time at the start of the run, the anchor written
+/// in the header of each output, with the monotonic instant of that start.
+/// Timestamps are monotonic offsets from the start (`time_us`), and the
+/// wall-clock time of a timestamp is the anchor plus its offset: the wall
+/// clock is read once, so that NTP steps or a clock changed by hand during
+/// the run neither reorder the timestamps nor skew the durations. Monotonic
+/// clocks may not count the time the host is suspended, the wall-clock times
+/// after a suspend then lag behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunClock {
+    start: Instant,
+    /// Microseconds since the Unix epoch at `start`.
+    anchor_us: u64,
+}
+
+impl RunClock {
+    /// Starts the clock at `now`, anchored to the current wall-clock time.
+    pub fn new(now: Instant) -> Self {
+        let anchor_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+
+        RunClock::with_anchor(now, anchor_us)
+    }
+
+    /// Starts the clock at `start`, the wall-clock time then being
+    /// `anchor_us` microseconds since the Unix epoch.
+    pub fn with_anchor(start: Instant, anchor_us: u64) -> Self {
+        RunClock { start, anchor_us }
+    }
+
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Microseconds since the Unix epoch at the start of the run.
+    pub fn anchor_us(&self) -> u64 {
+        self.anchor_us
+    }
+
+    /// Time elapsed between the start of the run and `now`, zero before it.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.start)
+    }
+
+    /// Microseconds elapsed between the start of the run and `now`.
+    pub fn time_us(&self, now: Instant) -> u64 {
+        self.elapsed(now).as_micros() as u64
+    }
+
+    /// Microseconds since the Unix epoch at `now`.
+    pub fn wall_us(&self, now: Instant) -> u64 {
+        self.anchor_us.saturating_add(self.time_us(now))
+    }
+
+    /// Header of the outputs written as objects, before their first one.
+    pub fn header<O: Object>(&self, label: Option<&str>) -> O {
+        let mut obj = O::new().u64("time_us", 0);
+
+        if let Some(label) = label {
+            obj = obj.str("target", label);
+        }
+
+        obj.str("event", "run_clock")
+            .u64("anchor_us", self.anchor_us)
+    }
+}
+
+impl fmt::Display for RunClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "anchor_us={} at time_us=0", self.anchor_us)
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+
+/// Bytes of a sequence number, big endian.
+pub const SEQ_LEN: usize = 8;
+
+/// Prepends the sequence number `seq` to `data`.
+pub fn prefix(seq: u64, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SEQ_LEN + data.len());
+    out.extend_from_slice(&seq.to_be_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Splits the sequence number off the front of `data`.
+pub fn split(data: &[u8]) -> Option<(u64, &[u8])> {
+    let (seq, rest) = data.split_first_chunk::<SEQ_LEN>()?;
+
+    Some((u64::from_be_bytes(*seq), rest))
+}
+
+/// Order in which the echoes completed, against the order their messages
+/// were sent in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OrderStats {
+    /// Echoes whose sequence number was checked.
+    pub checked: u64,
+
+    /// Echoes that completed after the echo of a message sent later.
+    pub out_of_order: u64,
+
+    /// Largest number of sequence numbers by which an echo was late.
+    pub max_displacement: u64,
+}
+
+impl fmt::Display for OrderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} echoes, {} out of order",
+            self.checked, self.out_of_order
+        )?;
+
+        if self.out_of_order > 0 {
+            write!(f, " (late by up to {})", self.max_displacement)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the echoes complete in the order of the sequence numbers of
+/// their messages, with `--sequence`.
+///
+/// QUIC orders the bytes of each stream, not the streams: the messages are
+/// sent on streams of their own, or in turn on the streams of several
+/// paths, so an echo may complete before the echo of a message sent
+/// earlier. Such an echo is in order, the earlier one is reported late once
+/// it completes.
+#[derive(Debug, Default)]
+pub struct SequenceCheck {
+    /// Highest sequence number completed on the current connection.
+    highest: Option<u64>,
+    stats: OrderStats,
+}
+
+impl SequenceCheck {
+    /// Accounts the echo of the message sent with `seq`, and returns the
+    /// highest sequence number completed before it if it is late.
+    pub fn complete(&mut self, seq: u64) -> Option<u64> {
+        self.stats.checked += 1;
+
+        match self.highest {
+            Some(highest) if seq < highest => {
+                self.stats.out_of_order += 1;
+                self.stats.max_displacement = self.stats.max_displacement.max(highest - seq);
+
+                Some(highest)
+            }
+
+            _ => {
+                self.highest = Some(seq);
+                None
+            }
+        }
+    }
+
+    /// Starts over with the sequence numbers of a new connection, keeping
+    /// the stats.
+    pub fn restart(&mut self) {
+        self.highest = None;
+    }
+
+    pub fn stats(&self) -> OrderStats {
+        self.stats
+    }
+}
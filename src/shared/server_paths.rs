@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+};
+
+use log::{debug, error, info};
+
+/// Counters of the path events of a server connection and of its probes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ServerPathStats {
+    pub new: u64,
+    pub validated: u64,
+    pub failed: u64,
+    pub closed: u64,
+    pub migrations: u64,
+
+    /// Paths probed by the server.
+    pub probes: u64,
+
+    /// Probes towards a known client address that quiche refused, because
+    /// the client never used the 4-tuple.
+    pub refused_probes: u64,
+}
+
+impl fmt::Display for ServerPathStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "new {}, validated {}, failed {}, closed {}, migrations {}, probes {} ({} refused)",
+            self.new,
+            self.validated,
+            self.failed,
+            self.closed,
+            self.migrations,
+            self.probes,
+            self.refused_probes
+        )
+    }
+}
+
+/// Paths of a server connection, tracked from its path events.
+///
+/// Every new path opened by the client is probed right away. With client
+/// path probes, the server also probes each known client address from each
+/// of its addresses. quiche only lets clients open paths, so the probes of
+/// 4-tuples the client never used are refused and only counted.
+#[derive(Debug)]
+pub struct ServerPaths {
+    local_addrs: Vec<SocketAddr>,
+    /// Client addresses, in the order they were seen.
+    client_addrs: Vec<SocketAddr>,
+    initial: (SocketAddr, SocketAddr),
+    probe_client_paths: bool,
+    validated: HashSet<(SocketAddr, SocketAddr)>,
+    /// 4-tuples the server probed or tried to.
+    probed: HashSet<(SocketAddr, SocketAddr)>,
+    /// Last MTU of each path, raised by path MTU discovery if enabled.
+    mtus: HashMap<(SocketAddr, SocketAddr), usize>,
+    pub stats: ServerPathStats,
+}
+
+impl ServerPaths {
+    /// Paths of a connection accepted on `initial`, the server listening on
+    /// `local_addrs`.
+    pub fn new(local_addrs: Vec<SocketAddr>, initial: (SocketAddr, SocketAddr)) -> Self {
+        ServerPaths {
+            local_addrs,
+            client_addrs: vec![initial.1],
+            initial,
+            probe_client_paths: false,
+            validated: HashSet::new(),
+            probed: HashSet::from([initial]),
+            mtus: HashMap::new(),
+            stats: ServerPathStats::default(),
+        }
+    }
+
+    /// Probes every known client address from every server address.
+    pub fn probe_client_paths(mut self) -> Self {
+        self.probe_client_paths = true;
+        self
+    }
+
+    /// Paths validated so far, including the initial path once the
+    /// connection is established.
+    pub fn validated(&self) -> &HashSet<(SocketAddr, SocketAddr)> {
+        &self.validated
+    }
+
+    pub fn client_addrs(&self) -> &[SocketAddr] {
+        &self.client_addrs
+    }
+
+    /// Last MTU of each path of the connection.
+    pub fn mtus(&self) -> &HashMap<(SocketAddr, SocketAddr), usize> {
+        &self.mtus
+    }
+
+    /// Handles the pending path events of `conn` and sends the probes they
+    /// call for.
+    pub fn handle_events(&mut self, conn: &mut quiche::Connection) {
+        if conn.is_established() {
+            self.validated.insert(self.initial);
+        }
+
+        while let Some(event) = conn.path_event_next() {
+            match event {
+                quiche::PathEvent::New(local_addr, peer_addr) => {
+                    info!(
+                        "{} Seen new path ({}, {})",
+                        conn.trace_id(),
+                        local_addr,
+                        peer_addr
+                    );
+
+                    self.stats.new += 1;
+                    self.on_client_addr(peer_addr);
+
+                    // Directly probe the new path.
+                    self.probe(conn, (local_addr, peer_addr));
+                }
+
+                quiche::PathEvent::Validated(local_addr, peer_addr) => {
+                    info!(
+                        "{} Path ({}, {}) is now validated",
+                        conn.trace_id(),
+                        local_addr,
+                        peer_addr
+                    );
+
+                    self.stats.validated += 1;
+                    self.validated.insert((local_addr, peer_addr));
+                }
+
+                quiche::PathEvent::FailedValidation(local_addr, peer_addr) => {
+                    info!(
+                        "{} Path ({}, {}) failed validation",
+                        conn.trace_id(),
+                        local_addr,
+                        peer_addr
+                    );
+
+                    self.stats.failed += 1;
+                    self.validated.remove(&(local_addr, peer_addr));
+                }
+
+                quiche::PathEvent::Closed(local_addr, peer_addr) => {
+                    info!(
+                        "{} Path ({}, {}) is now closed and unusable",
+                        conn.trace_id(),
+                        local_addr,
+                        peer_addr
+                    );
+
+                    self.stats.closed += 1;
+                    self.validated.remove(&(local_addr, peer_addr));
+                }
+
+                quiche::PathEvent::ReusedSourceConnectionId(cid_seq, old, new) => {
+                    info!(
+                        "{} Peer reused cid seq {} (initially {:?}) on {:?}",
+                        conn.trace_id(),
+                        cid_seq,
+                        old,
+                        new
+                    );
+                }
+
+                quiche::PathEvent::PeerMigrated(local_addr, peer_addr) => {
+                    info!(
+                        "{} Connection migrated to ({}, {})",
+                        conn.trace_id(),
+                        local_addr,
+                        peer_addr
+                    );
+
+                    self.stats.migrations += 1;
+                    self.on_client_addr(peer_addr);
+                }
+            }
+        }
+
+        if self.probe_client_paths {
+            self.probe_client_addrs(conn);
+        }
+
+        // The MTU found by path MTU discovery is only in the path stats.
+        for stats in conn.path_stats() {
+            let path = (stats.local_addr, stats.peer_addr);
+
+            if self
+                .mtus
+                .insert(path, stats.pmtu)
+                .is_some_and(|mtu| mtu != stats.pmtu)
+            {
+                info!(
+                    "{} Path ({}, {}) MTU is now {}",
+                    conn.trace_id(),
+                    path.0,
+                    path.1,
+                    stats.pmtu
+                );
+            }
+        }
+    }
+
+    fn on_client_addr(&mut self, addr: SocketAddr) {
+        if !self.client_addrs.contains(&addr) {
+            self.client_addrs.push(addr);
+        }
+    }
+
+    /// Probes the 4-tuples between the server addresses and the known
+    /// client addresses that were not probed yet.
+    fn probe_client_addrs(&mut self, conn: &mut quiche::Connection) {
+        let paths: Vec<(SocketAddr, SocketAddr)> = self
+            .local_addrs
+            .iter()
+            .flat_map(|local| self.client_addrs.iter().map(|peer| (*local, *peer)))
+            .filter(|path| !self.probed.contains(path))
+            .collect();
+
+        for path in paths {
+            self.probe(conn, path);
+        }
+    }
+
+    fn probe(
+        &mut self,
+        conn: &mut quiche::Connection,
+        (local_addr, peer_addr): (SocketAddr, SocketAddr),
+    ) {
+        self.probed.insert((local_addr, peer_addr));
+
+        match conn.probe_path(local_addr, peer_addr) {
+            Ok(_) => self.stats.probes += 1,
+
+            Err(quiche::Error::InvalidState) => {
+                debug!(
+                    "{} cannot open path ({}, {}) from the server",
+                    conn.trace_id(),
+                    local_addr,
+                    peer_addr
+                );
+
+                self.stats.refused_probes += 1;
+            }
+
+            Err(e) => error!(
+                "{} probing ({}, {}) failed: {:?}",
+                conn.trace_id(),
+                local_addr,
+                peer_addr,
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+use super::{
+    conn::ConnOps,
+    stream_writer::{Progress, StreamWriter, WriteError},
+};
+
+/// Smallest amount of data released at once by a rate limited stream, unless
+/// less remains, so that slow rates do not wake the server for every byte.
+const QUANTUM: usize = 1200;
+
+/// Holds back the echo of each stream, releasing it `delay` after it was
+/// received and, with a rate, through a token bucket per stream.
+///
+/// Released data is written with a [`StreamWriter`] kept open until the fin
+/// is released, so that what the stream cannot accept yet waits for its
+/// capacity. [`EchoShaper::next_release`] tells when data must be released
+/// next, to be folded into the timeout of the event loop.
+pub struct EchoShaper {
+    delay: Duration,
+    /// Bytes per second, unlimited if `None`.
+    rate: Option<u64>,
+    streams: HashMap<u64, ShapedStream>,
+}
+
+/// Data received at once on a stream, held until `at`.
+struct Chunk {
+    at: Instant,
+    data: Vec<u8>,
+    fin: bool,
+}
+
+struct ShapedStream {
+    held: VecDeque<Chunk>,
+    /// Data released but not handed to the writer yet.
+    released: Vec<u8>,
+    fin_released: bool,
+    writer: StreamWriter<Cursor<Vec<u8>>>,
+    /// Whether the writer is done with its source and can take the next one.
+    drained: bool,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl EchoShaper {
+    /// Releases the echo of each stream `delay` after it was received.
+    pub fn new(delay: Duration) -> Self {
+        EchoShaper {
+            delay,
+            rate: None,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Limits the echo of each stream to `rate` bytes per second.
+    pub fn rate(mut self, rate: u64) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// Number of bytes the token bucket of a stream holds at most: 10ms of
+    /// data, and at least a quantum.
+    fn burst(&self) -> f64 {
+        self.rate
+            .map_or(0.0, |r| (r as f64 / 100.0).max(QUANTUM as f64))
+    }
+
+    /// Queues `data` received at `now` on `stream_id` for its echo.
+    pub fn push(&mut self, stream_id: u64, data: &[u8], fin: bool, now: Instant) {
+        let burst = self.burst();
+
+        let stream = self
+            .streams
+            .entry(stream_id)
+            .or_insert_with(|| ShapedStream {
+                held: VecDeque::new(),
+                released: Vec::new(),
+                fin_released: false,
+                writer: StreamWriter::new(stream_id, Cursor::new(Vec::new())).keep_open(),
+                drained: true,
+                tokens: burst,
+                refilled: now,
+            });
+
+        stream.held.push_back(Chunk {
+            at: now + self.delay,
+            data: data.to_vec(),
+            fin,
+        });
+    }
+
+    /// Releases the data due at `now` and writes what was released as far
+    /// as the streams accept it.
+    pub fn poll<C: ConnOps>(&mut self, conn: &mut C, now: Instant) {
+        let rate = self.rate;
+        let burst = self.burst();
+
+        self.streams.retain(|stream_id, stream| {
+            stream.release(rate, burst, now);
+
+            match stream.write(conn) {
+                Ok(done) => !done,
+
+                Err(e) => {
+                    error!("echo on stream {} failed: {}", stream_id, e);
+                    false
+                }
+            }
+        });
+    }
+
+    /// Time at which data must be released next, if any is held.
+    pub fn next_release(&self) -> Option<Instant> {
+        self.streams
+            .values()
+            .filter_map(|s| s.next_release(self.rate, self.burst()))
+            .min()
+    }
+
+    /// Drops the data held for every stream.
+    pub fn clear(&mut self) {
+        self.streams.clear();
+    }
+}
+
+impl ShapedStream {
+    fn refill(&mut self, rate: Option<u64>, burst: f64, now: Instant) {
+        let Some(rate) = rate else {
+            return;
+        };
+
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(burst);
+        self.refilled = now;
+    }
+
+    /// Moves the held data that is due at `now`, and allowed by the bucket,
+    /// to the released data.
+    fn release(&mut self, rate: Option<u64>, burst: f64, now: Instant) {
+        self.refill(rate, burst, now);
+
+        while let Some(chunk) = self.held.front_mut() {
+            if chunk.at > now {
+                break;
+            }
+
+            let allowed = match rate {
+                Some(_) => chunk.data.len().min(self.tokens as usize),
+                None => chunk.data.len(),
+            };
+
+            if allowed < chunk.data.len() && allowed < QUANTUM {
+                break;
+            }
+
+            self.released.extend(chunk.data.drain(..allowed));
+
+            if rate.is_some() {
+                self.tokens -= allowed as f64;
+            }
+
+            if !chunk.data.is_empty() {
+                break;
+            }
+
+            self.fin_released |= chunk.fin;
+            self.held.pop_front();
+        }
+    }
+
+    /// Hands the released data to the writer. Returns true once the stream
+    /// is done.
+    fn write<C: ConnOps>(&mut self, conn: &mut C) -> Result<bool, WriteError> {
+        loop {
+            if self.drained {
+                if !self.released.is_empty() {
+                    let data = std::mem::take(&mut self.released);
+                    self.writer.resume(Cursor::new(data));
+                } else if self.fin_released && self.held.is_empty() {
+                    self.writer.finish();
+                } else {
+                    return Ok(false);
+                }
+
+                self.drained = false;
+            }
+
+            match self.writer.poll_write(conn)? {
+                Progress::Drained => self.drained = true,
+
+                Progress::Finished | Progress::Stopped(_) => return Ok(true),
+
+                Progress::Written(_) | Progress::Blocked => return Ok(false),
+            }
+        }
+    }
+
+    fn next_release(&self, rate: Option<u64>, burst: f64) -> Option<Instant> {
+        let chunk = self.held.front()?;
+
+        let Some(rate) = rate else {
+            return Some(chunk.at);
+        };
+
+        // Wait for enough tokens to release a quantum, or the whole chunk.
+        let needed = chunk.data.len().min(QUANTUM) as f64;
+        let missing = (needed.min(burst) - self.tokens).max(0.0);
+        let refilled = self.refilled + Duration::from_secs_f64(missing / rate as f64);
+
+        Some(chunk.at.max(refilled))
+    }
+}
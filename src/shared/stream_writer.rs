@@ -0,0 +1,211 @@
+use std::{fmt, io::Read};
+
+use super::conn::ConnOps;
+
+/// Maximum number of bytes pulled from the source at once, unless a smaller
+/// chunk size is set.
+const MAX_CHUNK: usize = 16384;
+
+/// Outcome of a call to [`StreamWriter::poll_write`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// Bytes were handed to the stream but more data remains.
+    Written(usize),
+
+    /// Nothing could be written, wait for the stream to become writable.
+    Blocked,
+
+    /// All the data and the fin have been handed to the stream.
+    Finished,
+
+    /// All the data of the source has been handed to a stream kept open,
+    /// see [`StreamWriter::keep_open`].
+    Drained,
+
+    /// The peer asked to stop sending with the given error code.
+    Stopped(u64),
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    Io(std::io::Error),
+    Quiche(quiche::Error),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::Io(e) => write!(f, "reading source failed: {}", e),
+            WriteError::Quiche(e) => write!(f, "stream_send failed: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<std::io::Error> for WriteError {
+    fn from(e: std::io::Error) -> Self {
+        WriteError::Io(e)
+    }
+}
+
+/// Writes the content of a source on a stream, as fast as the stream
+/// capacity allows.
+///
+/// Data is pulled from the source only when the stream can take it, so
+/// large sources are never buffered entirely. Whatever the stream did not
+/// accept is kept and retried on the next call, which should happen when the
+/// stream becomes writable again. The fin is sent exactly once, with the
+/// last byte of the source, unless the stream is kept open.
+pub struct StreamWriter<R> {
+    stream_id: u64,
+    source: R,
+    pending: Vec<u8>,
+    pending_off: usize,
+    eof: bool,
+    /// Whether the stream stays open once the source is exhausted.
+    keep_open: bool,
+    /// Maximum number of bytes handed to the stream per write.
+    chunk_size: usize,
+    fin_sent: bool,
+    stopped: Option<u64>,
+    written: u64,
+}
+
+impl<R: Read> StreamWriter<R> {
+    pub fn new(stream_id: u64, source: R) -> Self {
+        StreamWriter {
+            stream_id,
+            source,
+            pending: Vec::new(),
+            pending_off: 0,
+            eof: false,
+            keep_open: false,
+            chunk_size: MAX_CHUNK,
+            fin_sent: false,
+            stopped: None,
+            written: 0,
+        }
+    }
+
+    /// Leaves the stream open once the source is exhausted, so that it can
+    /// carry the content of further sources, until `finish` is called.
+    pub fn keep_open(mut self) -> Self {
+        self.keep_open = true;
+        self
+    }
+
+    /// Hands at most `size` bytes to the stream per write, so that the source
+    /// is sent as several writes. The fin follows the last one.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.clamp(1, MAX_CHUNK);
+        self
+    }
+
+    /// Writes the content of `source` next, on the same stream. The previous
+    /// source must have been drained.
+    pub fn resume(&mut self, source: R) {
+        debug_assert!(!self.fin_sent);
+
+        self.source = source;
+        self.eof = false;
+    }
+
+    /// Sends the fin once the source is exhausted, on its own if all the
+    /// data was already handed to the stream.
+    pub fn finish(&mut self) {
+        self.keep_open = false;
+    }
+
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
+    /// Number of bytes handed to the stream so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Whether the fin was sent or the peer stopped the stream.
+    pub fn is_done(&self) -> bool {
+        self.fin_sent || self.stopped.is_some()
+    }
+
+    /// Writes as much as the stream accepts.
+    pub fn poll_write<C: ConnOps>(&mut self, conn: &mut C) -> Result<Progress, WriteError> {
+        if self.fin_sent {
+            return Ok(Progress::Finished);
+        }
+
+        if let Some(code) = self.stopped {
+            return Ok(Progress::Stopped(code));
+        }
+
+        let mut written = 0;
+
+        loop {
+            if self.pending_off == self.pending.len() && !self.eof {
+                let cap = match conn.stream_capacity(self.stream_id) {
+                    Ok(v) => v,
+
+                    // The stream is not opened yet.
+                    Err(quiche::Error::InvalidStreamState(_)) => MAX_CHUNK,
+
+                    Err(quiche::Error::StreamStopped(code)) => {
+                        self.stopped = Some(code);
+                        return Ok(Progress::Stopped(code));
+                    }
+
+                    Err(e) => return Err(WriteError::Quiche(e)),
+                };
+
+                // Always read at least one byte, so that the end of the
+                // source is noticed even when the stream has no capacity.
+                self.pending.resize(cap.clamp(1, self.chunk_size), 0);
+                let read = self.source.read(&mut self.pending)?;
+                self.pending.truncate(read);
+                self.pending_off = 0;
+                self.eof = read == 0;
+            }
+
+            let buf = &self.pending[self.pending_off..];
+            let fin = self.eof && !self.keep_open;
+
+            if buf.is_empty() && self.eof && !fin {
+                return Ok(Progress::Drained);
+            }
+
+            match conn.stream_send(self.stream_id, buf, fin) {
+                Ok(sent) => {
+                    self.pending_off += sent;
+                    self.written += sent as u64;
+                    written += sent;
+
+                    if self.pending_off == self.pending.len() && fin {
+                        self.fin_sent = true;
+                        return Ok(Progress::Finished);
+                    }
+
+                    if sent < buf.len() {
+                        break;
+                    }
+                }
+
+                Err(quiche::Error::Done) => break,
+
+                Err(quiche::Error::StreamStopped(code)) => {
+                    self.stopped = Some(code);
+                    return Ok(Progress::Stopped(code));
+                }
+
+                Err(e) => return Err(WriteError::Quiche(e)),
+            }
+        }
+
+        if written > 0 {
+            Ok(Progress::Written(written))
+        } else {
+            Ok(Progress::Blocked)
+        }
+    }
+}
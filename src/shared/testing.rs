@@ -0,0 +1,1613 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::trace;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use super::{
+    apps::{self, App, ConnHandler, FileHandler, H3Handler, Handler, StreamHandler},
+    cids::CidIssuer,
+    conn::ConnOps,
+    control::{ConnRun, Control},
+    demux::Demux,
+    generate_cid_and_reset_token, is_uni_stream,
+    owd::{self, WallClock},
+    ports,
+    rng::SeededRng,
+    server_paths::ServerPaths,
+    shaper::EchoShaper,
+    transform::{StreamTransform, Transform},
+    transport,
+};
+use crate::{
+    client::{Client, Completed, Workload},
+    events::Event,
+    report::TargetReport,
+};
+
+/// Data written on a stream of a [`MockConn`].
+#[derive(Debug, Default)]
+pub struct MockStream {
+    pub data: Vec<u8>,
+    /// Size of each write that carried data.
+    pub writes: Vec<usize>,
+    pub fin: bool,
+    /// Remaining send capacity, unlimited if `None`.
+    pub capacity: Option<usize>,
+    /// Error code of a STOP_SENDING received from the peer.
+    pub stopped: Option<u64>,
+}
+
+/// In-memory connection recording what is written on its streams.
+#[derive(Debug, Default)]
+pub struct MockConn {
+    pub streams: HashMap<u64, MockStream>,
+    /// Streams reported readable, in order.
+    pub readable: VecDeque<u64>,
+    /// Ack-eliciting packets scheduled, e.g. PINGs.
+    pub pings: usize,
+    /// Source connection IDs issued and still active.
+    pub scids: Vec<quiche::ConnectionId<'static>>,
+    /// Limit of active connection IDs advertised by the peer, none if its
+    /// transport parameters are not known yet.
+    pub peer_cid_limit: Option<u64>,
+    /// Error returned by `new_scid` instead of issuing an ID.
+    pub new_scid_error: Option<quiche::Error>,
+}
+
+impl MockConn {
+    pub fn stream(&mut self, stream_id: u64) -> &mut MockStream {
+        self.streams.entry(stream_id).or_default()
+    }
+}
+
+impl ConnOps for MockConn {
+    fn stream_send(&mut self, stream_id: u64, buf: &[u8], fin: bool) -> quiche::Result<usize> {
+        let stream = self.stream(stream_id);
+
+        if let Some(code) = stream.stopped {
+            return Err(quiche::Error::StreamStopped(code));
+        }
+
+        if stream.fin {
+            return Err(quiche::Error::FinalSize);
+        }
+
+        let cap = stream.capacity.unwrap_or(usize::MAX);
+        if cap == 0 && !buf.is_empty() {
+            return Err(quiche::Error::Done);
+        }
+
+        let sent = buf.len().min(cap);
+        stream.data.extend_from_slice(&buf[..sent]);
+        if sent > 0 {
+            stream.writes.push(sent);
+        }
+        stream.fin = fin && sent == buf.len();
+        if let Some(capacity) = &mut stream.capacity {
+            *capacity -= sent;
+        }
+
+        Ok(sent)
+    }
+
+    fn stream_capacity(&self, stream_id: u64) -> quiche::Result<usize> {
+        match self.streams.get(&stream_id) {
+            Some(MockStream {
+                stopped: Some(code),
+                ..
+            }) => Err(quiche::Error::StreamStopped(*code)),
+
+            Some(stream) => Ok(stream.capacity.unwrap_or(usize::MAX)),
+
+            None => Err(quiche::Error::InvalidStreamState(stream_id)),
+        }
+    }
+
+    fn stream_readable_next(&mut self) -> Option<u64> {
+        self.readable.pop_front()
+    }
+
+    fn send_ack_eliciting(&mut self) -> quiche::Result<()> {
+        self.pings += 1;
+        Ok(())
+    }
+
+    fn scids_left(&self) -> usize {
+        let limit = self.peer_cid_limit.unwrap_or(0) as usize;
+        limit.saturating_sub(self.scids.len())
+    }
+
+    fn active_scids(&self) -> usize {
+        self.scids.len()
+    }
+
+    fn new_scid(
+        &mut self,
+        scid: &quiche::ConnectionId,
+        _reset_token: u128,
+        _retire_if_needed: bool,
+    ) -> quiche::Result<u64> {
+        if let Some(e) = self.new_scid_error {
+            return Err(e);
+        }
+
+        if self.scids_left() == 0 {
+            return Err(quiche::Error::IdLimit);
+        }
+
+        self.scids.push(scid.clone().into_owned());
+        Ok(self.scids.len() as u64 - 1)
+    }
+
+    fn peer_active_cid_limit(&self) -> Option<u64> {
+        self.peer_cid_limit
+    }
+}
+
+/// Size of the stateless resets sent by [`EchoServer`].
+const STATELESS_RESET_LEN: usize = 43;
+
+/// Bare echo server, the in-memory counterpart of the server binary.
+pub struct EchoServer {
+    conn: Option<quiche::Connection>,
+    config: quiche::Config,
+    addrs: Vec<SocketAddr>,
+    rng: SystemRandom,
+    buf: Vec<u8>,
+    /// Token advertised to the clients, kept across restarts.
+    reset_token: Option<u128>,
+    /// Stateless resets and version negotiations to send, in reply to
+    /// packets of unknown connections.
+    resets: Vec<(Vec<u8>, quiche::RecvInfo)>,
+    /// Whether connection IDs are issued for new paths.
+    issue_cids: bool,
+    cids: CidIssuer,
+    /// Whether the connection uses a zero-length connection ID, its packets
+    /// being told apart by 4-tuple.
+    zero_length_cids: bool,
+    /// Delays and rate limits the echoes, if set.
+    shaper: Option<EchoShaper>,
+    /// Paths of the connection, once accepted.
+    paths: Option<ServerPaths>,
+    /// Whether every known client address is probed from every server
+    /// address.
+    probe_client_paths: bool,
+    /// Handler of each application protocol, chosen per connection once the
+    /// handshake completed, if any.
+    apps: Vec<App>,
+    /// Handler of the connections without `apps`, or negotiating none of
+    /// them.
+    default_handler: Handler,
+    /// Size up to which the files of the file handler stay in memory.
+    spill_over: u64,
+    /// Handler of the current connection, once set up.
+    handler: Option<(Handler, ConnHandler)>,
+    echo: bool,
+    /// Answers each stream with its transformation instead of an echo.
+    transform: Option<Transform>,
+    /// Starts the echo of each stream with the time it was received, if
+    /// set.
+    clock: Option<WallClock>,
+    /// Echo data not yet accepted by each stream, and whether it ends with
+    /// a fin. Streams with pending data are not read until it is flushed.
+    pending: HashMap<u64, (Vec<u8>, bool)>,
+    /// Run of the client, from its control messages.
+    run: ConnRun,
+}
+
+impl EchoServer {
+    /// Creates a server accepting the first connection reaching one of
+    /// `addrs`.
+    pub fn new(config: quiche::Config, addrs: Vec<SocketAddr>) -> Self {
+        EchoServer {
+            conn: None,
+            config,
+            addrs,
+            rng: SystemRandom::new(),
+            buf: vec![0; 65535],
+            reset_token: None,
+            resets: vec![],
+            issue_cids: true,
+            cids: CidIssuer::default(),
+            zero_length_cids: false,
+            shaper: None,
+            paths: None,
+            probe_client_paths: false,
+            apps: vec![],
+            default_handler: Handler::Echo,
+            spill_over: 0,
+            handler: None,
+            echo: true,
+            transform: None,
+            clock: None,
+            pending: HashMap::new(),
+            run: ConnRun::default(),
+        }
+    }
+
+    /// Never issues connection IDs, so that the client cannot open new
+    /// paths.
+    pub fn without_cids(mut self) -> Self {
+        self.issue_cids = false;
+        self
+    }
+
+    /// Uses a zero-length connection ID, as deployments routing by 4-tuple
+    /// do. The client then needs no new connection ID to open a path.
+    pub fn with_zero_length_cids(mut self) -> Self {
+        self.zero_length_cids = true;
+        self.issue_cids = false;
+        self
+    }
+
+    /// Advertises `limit` as active_connection_id_limit, as third-party
+    /// servers keeping the default of 2 do.
+    pub fn with_cid_limit(mut self, limit: u64) -> Self {
+        self.config.set_active_connection_id_limit(limit);
+        self
+    }
+
+    /// Asks the client not to migrate.
+    pub fn without_migration(mut self) -> Self {
+        self.config.set_disable_active_migration(true);
+        self
+    }
+
+    /// Advertises `token` and answers the packets of connections lost by a
+    /// restart with stateless resets, as a server keeping its reset keys
+    /// would.
+    pub fn with_reset_token(mut self, token: u128) -> Self {
+        self.config.set_stateless_reset_token(Some(token));
+        self.reset_token = Some(token);
+        self
+    }
+
+    /// Echoes through `shaper` instead of as soon as data is received.
+    pub fn with_echo_shaper(mut self, shaper: EchoShaper) -> Self {
+        self.shaper = Some(shaper);
+        self
+    }
+
+    /// Probes for a larger MTU on the active path, up to `max` bytes, like
+    /// the server binary with `--pmtud`.
+    pub fn with_pmtud(mut self, max: usize) -> Self {
+        self.config.discover_pmtu(true);
+        self.config.set_max_send_udp_payload_size(max);
+        self
+    }
+
+    /// Probes every known client address from every server address, like
+    /// the server binary with `--probe-client-paths`.
+    pub fn with_client_path_probes(mut self) -> Self {
+        self.probe_client_paths = true;
+        self
+    }
+
+    /// Reassembles the streams as file transfers and answers them with the
+    /// digest of the file, like the server binary with `--file-transfer`.
+    pub fn with_file_sink(mut self, spill_over: u64) -> Self {
+        self.default_handler = Handler::File;
+        self.spill_over = spill_over;
+        self
+    }
+
+    /// Offers the protocols of `apps` and handles each connection as the
+    /// protocol it negotiated says, like the server binary with `--app`.
+    /// The files are reassembled in memory up to `spill_over` bytes.
+    pub fn with_apps(mut self, apps: &[App], spill_over: u64) -> Self {
+        self.config
+            .set_application_protos(&apps::protos(apps))
+            .unwrap();
+        self.apps = apps.to_vec();
+        self.spill_over = spill_over;
+        self
+    }
+
+    /// Reads the streams without echoing them, like the server binary with
+    /// `--no-echo`.
+    pub fn without_echo(mut self) -> Self {
+        self.echo = false;
+        self
+    }
+
+    /// Answers each stream with its transformation, like the server binary
+    /// with `--transform`. Multiplexed messages are not supported.
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Starts the echo of each stream with the time it was received, like
+    /// the server binary with `--timestamps`, from a clock `skew_us` ahead
+    /// of the one of the client.
+    pub fn with_timestamps(mut self, skew_us: i64) -> Self {
+        self.clock = Some(WallClock::new(Instant::now()).skewed(skew_us));
+        self
+    }
+
+    /// Loses the connection state, as if the process was restarted.
+    pub fn restart(&mut self) {
+        trace!("echo server restarted");
+        self.clear();
+    }
+
+    /// Drops the state of the current connection.
+    fn clear(&mut self) {
+        self.conn = None;
+        self.paths = None;
+        self.handler = None;
+        self.pending.clear();
+        self.cids = CidIssuer::default();
+        self.run = ConnRun::default();
+
+        if let Some(shaper) = &mut self.shaper {
+            shaper.clear();
+        }
+    }
+
+    pub fn conn(&self) -> Option<&quiche::Connection> {
+        self.conn.as_ref()
+    }
+
+    /// Handler of the current connection, once its handshake completed or
+    /// its early data arrived.
+    pub fn handler(&self) -> Option<Handler> {
+        self.handler.as_ref().map(|(handler, _)| *handler)
+    }
+
+    /// Paths of the connection, once accepted.
+    pub fn paths(&self) -> Option<&ServerPaths> {
+        self.paths.as_ref()
+    }
+
+    /// Run of the current connection, from the control messages of the
+    /// client.
+    pub fn run(&self) -> &ConnRun {
+        &self.run
+    }
+
+    pub fn recv(&mut self, pkt: &[u8], recv_info: quiche::RecvInfo) {
+        // Once the connection closed, a new client may connect.
+        if self.conn.as_ref().is_some_and(|conn| conn.is_closed()) && is_initial(pkt) {
+            trace!("echo server accepting a new connection");
+            self.clear();
+        }
+
+        if self.conn.is_none() {
+            let Ok(hdr) = quiche::Header::from_slice(&mut pkt.to_vec(), quiche::MAX_CONN_ID_LEN)
+            else {
+                return;
+            };
+
+            if hdr.ty != quiche::Type::Initial {
+                self.stateless_reset(recv_info);
+                return;
+            }
+
+            if !quiche::version_is_supported(hdr.version) {
+                let mut out = vec![0; self.buf.len()];
+                let len = quiche::negotiate_version(&hdr.scid, &hdr.dcid, &mut out).unwrap();
+                out.truncate(len);
+
+                let recv_info = quiche::RecvInfo {
+                    from: recv_info.to,
+                    to: recv_info.from,
+                };
+                self.resets.push((out, recv_info));
+                return;
+            }
+        }
+
+        let conn = self.conn.get_or_insert_with(|| {
+            let scid = match self.zero_length_cids {
+                true => vec![],
+                false => {
+                    let mut scid = vec![0; quiche::MAX_CONN_ID_LEN];
+                    self.rng.fill(&mut scid).unwrap();
+                    scid
+                }
+            };
+            let scid = quiche::ConnectionId::from_vec(scid);
+
+            let paths = ServerPaths::new(self.addrs.clone(), (recv_info.to, recv_info.from));
+            self.paths = Some(match self.probe_client_paths {
+                true => paths.probe_client_paths(),
+                false => paths,
+            });
+
+            quiche::accept(&scid, None, recv_info.to, recv_info.from, &mut self.config).unwrap()
+        });
+
+        conn.recv(&mut pkt.to_vec(), recv_info).ok();
+    }
+
+    /// Queues a stateless reset in reply to a packet received with
+    /// `recv_info`, if the server has a reset token.
+    fn stateless_reset(&mut self, recv_info: quiche::RecvInfo) {
+        let Some(token) = self.reset_token else {
+            return;
+        };
+
+        // Looks like a short header packet, ends with the token.
+        let mut reset = vec![0; STATELESS_RESET_LEN];
+        self.rng.fill(&mut reset).unwrap();
+        reset[0] = 0x40 | (reset[0] & 0x3f);
+        reset[STATELESS_RESET_LEN - 16..].copy_from_slice(&token.to_be_bytes());
+
+        let recv_info = quiche::RecvInfo {
+            from: recv_info.to,
+            to: recv_info.from,
+        };
+        self.resets.push((reset, recv_info));
+    }
+
+    pub fn timeout(&self) -> Option<Instant> {
+        self.conn.as_ref().and_then(|c| c.timeout_instant())
+    }
+
+    pub fn on_timeout(&mut self) {
+        if let Some(conn) = &mut self.conn {
+            conn.on_timeout();
+        }
+    }
+
+    /// Time at which a shaped echo must be released next.
+    pub fn echo_timeout(&self) -> Option<Instant> {
+        self.shaper.as_ref().and_then(|s| s.next_release())
+    }
+
+    /// Echoes what was received and returns the datagrams to send.
+    pub fn send(&mut self) -> Vec<(Vec<u8>, quiche::RecvInfo)> {
+        self.send_at(Instant::now())
+    }
+
+    /// Like `send`, with `now` as the time of the shaped echoes.
+    pub fn send_at(&mut self, now: Instant) -> Vec<(Vec<u8>, quiche::RecvInfo)> {
+        let mut datagrams = std::mem::take(&mut self.resets);
+
+        let Some(conn) = &mut self.conn else {
+            return datagrams;
+        };
+
+        let buf = &mut self.buf;
+
+        if let Some(paths) = &mut self.paths {
+            paths.handle_events(conn);
+        }
+
+        // Streams are only readable once the protocol they speak is known.
+        if self.handler.is_none() && (conn.is_established() || conn.is_in_early_data()) {
+            let handler =
+                apps::handler(&self.apps, conn.application_proto()).unwrap_or(self.default_handler);
+
+            let conn_handler = match handler {
+                Handler::Echo => ConnHandler::Streams(Box::new(Echo {
+                    transform: self.transform,
+                    transforms: HashMap::new(),
+                    clock: self.clock,
+                    stamped: HashSet::new(),
+                })),
+                Handler::File => ConnHandler::Streams(Box::new(FileHandler::new(self.spill_over))),
+                Handler::H3 => ConnHandler::H3(Box::new(H3Handler::new().unwrap())),
+            };
+            self.handler = Some((handler, conn_handler));
+        }
+
+        self.pending.retain(|stream_id, (data, fin)| {
+            let written = conn.stream_send(*stream_id, data, *fin).unwrap_or(0);
+            data.drain(..written);
+
+            !data.is_empty()
+        });
+
+        match self.handler.as_mut().map(|(_, h)| h) {
+            Some(ConnHandler::H3(h3)) => {
+                if let Err(e) = h3.poll(conn, buf) {
+                    trace!("HTTP/3 failed: {:?}", e);
+                }
+            }
+
+            Some(ConnHandler::Streams(handler)) => {
+                for stream_id in conn.readable() {
+                    // Read again once the echo is flushed.
+                    if self.pending.contains_key(&stream_id) {
+                        continue;
+                    }
+
+                    while let Ok((read, fin)) = conn.stream_recv(stream_id, buf) {
+                        if let Some(control) = Control::from_read(stream_id, &buf[..read], fin) {
+                            self.run.on_control(control);
+                            continue;
+                        }
+
+                        let Some(answer) = handler
+                            .on_stream_data(stream_id, &buf[..read], fin, now)
+                            .unwrap()
+                        else {
+                            continue;
+                        };
+
+                        // Unidirectional streams cannot be answered.
+                        if !self.echo || is_uni_stream(stream_id) {
+                            continue;
+                        }
+
+                        match &mut self.shaper {
+                            Some(shaper) => shaper.push(stream_id, &answer, fin, now),
+
+                            None => {
+                                let written =
+                                    conn.stream_send(stream_id, &answer, fin).unwrap_or(0);
+
+                                if written < answer.len() {
+                                    self.pending
+                                        .insert(stream_id, (answer[written..].to_vec(), fin));
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            None => (),
+        }
+
+        if let Some(shaper) = &mut self.shaper {
+            shaper.poll(conn, now);
+        }
+
+        if self.issue_cids {
+            self.cids.issue(conn, &self.rng);
+        }
+
+        for local in &self.addrs {
+            for peer in conn.paths_iter(*local) {
+                while let Ok((write, send_info)) = conn.send_on_path(buf, Some(*local), Some(peer))
+                {
+                    let recv_info = quiche::RecvInfo {
+                        from: send_info.from,
+                        to: send_info.to,
+                    };
+                    datagrams.push((buf[..write].to_vec(), recv_info));
+                }
+            }
+        }
+
+        datagrams
+    }
+}
+
+/// Echo handler of [`EchoServer`], answering each stream with its data,
+/// transformed or timestamped as set up.
+struct Echo {
+    transform: Option<Transform>,
+    transforms: HashMap<u64, StreamTransform>,
+    clock: Option<WallClock>,
+    stamped: HashSet<u64>,
+}
+
+impl StreamHandler for Echo {
+    fn on_stream_data(
+        &mut self,
+        stream_id: u64,
+        data: &[u8],
+        fin: bool,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let echo = match self.transform {
+            Some(transform) => {
+                let stream = self
+                    .transforms
+                    .entry(stream_id)
+                    .or_insert_with(|| StreamTransform::new(transform));
+                let mut out = stream.update(data);
+
+                if fin {
+                    let stream = self.transforms.remove(&stream_id).unwrap();
+                    out.extend_from_slice(&stream.finish());
+                }
+
+                out
+            }
+
+            None => data.to_vec(),
+        };
+
+        let echo = match &self.clock {
+            Some(clock) if self.stamped.insert(stream_id) => owd::stamp(clock.micros(now), &echo),
+            _ => echo,
+        };
+
+        if fin {
+            self.stamped.remove(&stream_id);
+        }
+
+        if echo.is_empty() && !fin {
+            return Ok(None);
+        }
+
+        Ok(Some(echo))
+    }
+}
+
+/// Characteristics of the link carrying one path, the same in both
+/// directions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Link {
+    /// One-way propagation delay.
+    pub delay: Duration,
+
+    /// Probability that a datagram is dropped.
+    pub loss: f64,
+
+    /// Probability that a datagram starting with a long header packet, i.e.
+    /// carrying the handshake, is dropped, if higher than `loss`.
+    pub handshake_loss: f64,
+
+    /// Bytes per second, unlimited if `None`.
+    pub bandwidth: Option<u64>,
+
+    /// Time after which a NAT on the path forgets its mapping if the client
+    /// sends nothing, blackholing the path from then on.
+    pub nat_timeout: Option<Duration>,
+
+    /// Time after the start of the simulation from which the link drops
+    /// everything, as when the server socket of the path dies.
+    pub down_after: Option<Duration>,
+}
+
+impl Link {
+    pub fn new(delay: Duration) -> Self {
+        Link {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    pub fn loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn handshake_loss(mut self, loss: f64) -> Self {
+        self.handshake_loss = loss;
+        self
+    }
+
+    pub fn bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    pub fn nat_timeout(mut self, timeout: Duration) -> Self {
+        self.nat_timeout = Some(timeout);
+        self
+    }
+
+    pub fn down_after(mut self, after: Duration) -> Self {
+        self.down_after = Some(after);
+        self
+    }
+
+    /// Time needed to put `len` bytes on the link.
+    fn transmission_time(&self, len: usize) -> Duration {
+        self.bandwidth.map_or(Duration::ZERO, |bw| {
+            Duration::from_secs_f64(len as f64 / bw as f64)
+        })
+    }
+}
+
+/// Datagram travelling on a link.
+struct InFlight {
+    to_client: bool,
+    pkt: Vec<u8>,
+    recv_info: quiche::RecvInfo,
+}
+
+/// Connects a [`Client`] and an [`EchoServer`] through an in-memory network
+/// with a [`Link`] per path, in virtual time.
+///
+/// Instead of sleeping, the clock jumps to the next datagram delivery or
+/// timeout, so latencies measured by the client are those of the links.
+/// Losses are drawn from a generator seeded by the scenario.
+///
+/// quiche 0.22 reads the system clock internally, so its own timers (loss
+/// recovery, path validation retries) only fire once they elapsed in real
+/// time: the simulator waits for them when nothing else is pending. Runs
+/// without loss never wait, and are reproducible from their seed. Runs with
+/// loss are not: when those timers fire depends on how long the run took in
+/// real time, and so do the datagrams sent and dropped after them.
+pub struct Simulator {
+    client: Client,
+    server: EchoServer,
+    /// Link of path `i`, the one of the `i`-th client address.
+    links: Vec<Link>,
+    rng: SeededRng,
+    start: Instant,
+    now: Instant,
+    /// Datagrams in flight by delivery time, then by sending order.
+    in_flight: BTreeMap<(Instant, u64), InFlight>,
+    sent: u64,
+    /// Time until which each path is busy sending, per direction.
+    busy_until: HashMap<(usize, bool), Instant>,
+    /// Time the client last sent something on each path.
+    last_outbound: HashMap<usize, Instant>,
+    /// Paths whose NAT mapping expired.
+    unmapped: HashSet<usize>,
+    client_timeout: Option<Instant>,
+    dropped: u64,
+    /// Time at which the server loses its connection state.
+    restart_at: Option<Instant>,
+    /// Period during which the client is not stepped.
+    freeze: Option<(Instant, Instant)>,
+    /// Datagrams delivered to the client while it was frozen.
+    held: Vec<(Vec<u8>, quiche::RecvInfo)>,
+    /// Configuration of the connections replacing interrupted ones.
+    reconnect_config: Option<quiche::Config>,
+    cid_rng: SystemRandom,
+    /// Events of the client so far.
+    events: Vec<Event>,
+    /// Longest wall-clock time a step of the client took.
+    longest_step: Duration,
+}
+
+impl Simulator {
+    /// Runs `client` against `server`, `now` must be the time the client was
+    /// created with. Paths without a link in `links` are perfect.
+    pub fn new(
+        client: Client,
+        server: EchoServer,
+        links: Vec<Link>,
+        seed: u64,
+        now: Instant,
+    ) -> Self {
+        Simulator {
+            client,
+            server,
+            links,
+            rng: SeededRng::new(seed),
+            start: now,
+            now,
+            in_flight: BTreeMap::new(),
+            sent: 0,
+            busy_until: HashMap::new(),
+            last_outbound: HashMap::new(),
+            unmapped: HashSet::new(),
+            client_timeout: None,
+            dropped: 0,
+            restart_at: None,
+            freeze: None,
+            held: vec![],
+            reconnect_config: None,
+            cid_rng: SystemRandom::new(),
+            events: vec![],
+            longest_step: Duration::ZERO,
+        }
+    }
+
+    /// Restarts the server `after` the start of the simulation.
+    pub fn restart_server_after(mut self, after: Duration) -> Self {
+        self.restart_at = Some(self.now + after);
+        self
+    }
+
+    /// Stops stepping the client `after` the start of the simulation, for
+    /// `duration`, as a driver missing its deadlines would. The datagrams
+    /// delivered meanwhile are handed to the client once it is stepped
+    /// again.
+    pub fn freeze_client(mut self, after: Duration, duration: Duration) -> Self {
+        self.freeze = Some((self.now + after, self.now + after + duration));
+        self
+    }
+
+    /// Re-establishes interrupted connections with `config`, as many times
+    /// as the workload of the client allows.
+    pub fn reconnect_with(mut self, config: quiche::Config) -> Self {
+        self.reconnect_config = Some(config);
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The client, e.g. to set it up before the simulation runs.
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    pub fn server(&self) -> &EchoServer {
+        &self.server
+    }
+
+    pub fn into_client(self) -> Client {
+        self.client
+    }
+
+    /// The server, e.g. to serve another client with it.
+    pub fn into_server(self) -> EchoServer {
+        self.server
+    }
+
+    /// Current virtual time.
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Whether the NAT of path `path` still has its mapping.
+    pub fn is_mapped(&self, path: usize) -> bool {
+        let timeout = self.links.get(path).and_then(|l| l.nat_timeout);
+        let expired = self
+            .last_outbound
+            .get(&path)
+            .zip(timeout)
+            .is_some_and(|(last, timeout)| self.now.duration_since(*last) > timeout);
+
+        !expired && !self.unmapped.contains(&path)
+    }
+
+    /// Connection events of the client so far, in order.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Number of datagrams dropped by the links so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Longest wall-clock time a step of the client took so far, for which
+    /// its timers were late at worst.
+    pub fn longest_step(&self) -> Duration {
+        self.longest_step
+    }
+
+    /// Runs until `messages` messages completed, the connection is closed or
+    /// `limit` of virtual time elapsed, and returns the completed messages.
+    pub fn run(&mut self, messages: usize, limit: Duration) -> Vec<Completed> {
+        let deadline = self.now + limit;
+        let mut completed = vec![];
+        let mut incoming = vec![];
+
+        loop {
+            let started = Instant::now();
+            let output = self.client.step(self.now, &mut incoming);
+            self.longest_step = self.longest_step.max(started.elapsed());
+
+            completed.extend(output.completed);
+            self.events.extend(output.events);
+            self.client_timeout = output.timeout;
+
+            for (pkt, send_info) in output.datagrams {
+                let recv_info = quiche::RecvInfo {
+                    from: send_info.from,
+                    to: send_info.to,
+                };
+                self.transmit(pkt, recv_info, false);
+            }
+
+            if completed.len() >= messages {
+                break;
+            }
+
+            if self.client.is_finished() {
+                if !self.client.can_reconnect() || !self.reconnect() {
+                    break;
+                }
+
+                incoming = vec![];
+                continue;
+            }
+
+            match self.advance(deadline) {
+                Some(v) => incoming = v,
+                None => break,
+            }
+        }
+
+        completed
+    }
+
+    /// Runs until the connection is closed, e.g. for the parts of the
+    /// report completed at close, and returns the messages completed
+    /// meanwhile.
+    pub fn run_until_closed(&mut self, limit: Duration) -> Vec<Completed> {
+        self.run(usize::MAX, limit)
+    }
+
+    /// Replaces the interrupted connection of the client. Returns false if
+    /// no connection could be created.
+    fn reconnect(&mut self) -> bool {
+        let Some(config) = &mut self.reconnect_config else {
+            return false;
+        };
+
+        let (scid, _) = generate_cid_and_reset_token(&self.cid_rng, self.client.cid_len());
+
+        let local_addr = self.client.local_addrs()[0];
+        let peer_addr = self.client.peer_addrs()[0];
+
+        match quiche::connect(None, &scid, local_addr, peer_addr, config) {
+            Ok(conn) => {
+                self.client.reconnect(conn, self.now);
+                true
+            }
+
+            Err(e) => {
+                trace!("reconnection failed: {:?}", e);
+                false
+            }
+        }
+    }
+
+    /// Puts a datagram on the link of its path, unless it is lost.
+    fn transmit(&mut self, pkt: Vec<u8>, recv_info: quiche::RecvInfo, to_client: bool) {
+        let client_addr = if to_client {
+            recv_info.to
+        } else {
+            recv_info.from
+        };
+
+        let path = self
+            .client
+            .local_addrs()
+            .iter()
+            .position(|a| *a == client_addr)
+            .unwrap_or(usize::MAX);
+        let link = self.links.get(path).copied().unwrap_or_default();
+
+        if link
+            .down_after
+            .is_some_and(|after| self.now >= self.start + after)
+        {
+            trace!("path {} is down", path);
+            self.dropped += 1;
+            return;
+        }
+
+        if let Some(timeout) = link.nat_timeout {
+            let last = *self.last_outbound.entry(path).or_insert(self.now);
+
+            if self.now.duration_since(last) > timeout && self.unmapped.insert(path) {
+                trace!("NAT mapping of path {} expired", path);
+            }
+
+            if self.unmapped.contains(&path) {
+                self.dropped += 1;
+                return;
+            }
+
+            if !to_client {
+                self.last_outbound.insert(path, self.now);
+            }
+        }
+
+        let loss = match pkt.first() {
+            Some(first) if first & 0x80 != 0 => link.loss.max(link.handshake_loss),
+            _ => link.loss,
+        };
+
+        if loss > 0.0 && self.rng.chance(loss) {
+            trace!("dropped {} bytes on path {}", pkt.len(), path);
+            self.dropped += 1;
+            return;
+        }
+
+        let busy_until = self.busy_until.entry((path, to_client)).or_insert(self.now);
+        let sent_at = (*busy_until).max(self.now) + link.transmission_time(pkt.len());
+        *busy_until = sent_at;
+
+        self.in_flight.insert(
+            (sent_at + link.delay, self.sent),
+            InFlight {
+                to_client,
+                pkt,
+                recv_info,
+            },
+        );
+        self.sent += 1;
+    }
+
+    /// Moves the clock forward, letting the server react, until the client
+    /// has datagrams to receive or a timeout to handle. Returns `None` if
+    /// nothing can happen before `deadline`.
+    fn advance(&mut self, deadline: Instant) -> Option<Vec<(Vec<u8>, quiche::RecvInfo)>> {
+        loop {
+            let delivery = self.in_flight.keys().next().map(|(at, _)| *at);
+            let quiche_timeout = [self.client.conn().timeout_instant(), self.server.timeout()]
+                .into_iter()
+                .flatten()
+                .min();
+
+            // A frozen client only wakes up at the end of the freeze.
+            let client_timeout = match self.freeze {
+                Some((from, until)) if from <= self.now && self.now < until => Some(until),
+                _ => self.client_timeout,
+            };
+
+            let wake = [
+                delivery,
+                client_timeout,
+                self.server.timeout(),
+                self.server.echo_timeout(),
+                self.restart_at,
+            ]
+            .into_iter()
+            .flatten()
+            .min()?;
+
+            if wake > deadline {
+                return None;
+            }
+
+            // quiche only fires its timers once they elapsed on the system
+            // clock.
+            if delivery.is_none_or(|at| at > wake) && quiche_timeout == Some(wake) {
+                std::thread::sleep(wake.saturating_duration_since(Instant::now()));
+            }
+
+            self.now = self.now.max(wake);
+
+            if self.restart_at.is_some_and(|t| t <= self.now) {
+                self.server.restart();
+                self.restart_at = None;
+            }
+
+            let mut to_client = vec![];
+            let mut to_server = false;
+
+            while let Some(entry) = self.in_flight.first_entry() {
+                if entry.key().0 > self.now {
+                    break;
+                }
+
+                let datagram = entry.remove();
+
+                if datagram.to_client {
+                    to_client.push((datagram.pkt, datagram.recv_info));
+                } else {
+                    self.server.recv(&datagram.pkt, datagram.recv_info);
+                    to_server = true;
+                }
+            }
+
+            if !to_server && self.server.timeout().is_some_and(|t| t <= self.now) {
+                self.server.on_timeout();
+            }
+
+            for (pkt, recv_info) in self.server.send_at(self.now) {
+                self.transmit(pkt, recv_info, true);
+            }
+
+            if let Some((from, until)) = self.freeze {
+                if from <= self.now && self.now < until {
+                    self.held.append(&mut to_client);
+                    continue;
+                }
+
+                if self.now >= until {
+                    self.freeze = None;
+                    to_client.splice(0..0, std::mem::take(&mut self.held));
+                    return Some(to_client);
+                }
+            }
+
+            if !to_client.is_empty() || self.client_timeout.is_some_and(|t| t <= self.now) {
+                return Some(to_client);
+            }
+        }
+    }
+}
+
+/// Client and echo server exchanging real datagrams on the loopback, like
+/// the binaries: every socket is bound on a port picked by the OS, and the
+/// client learns the addresses of the server from its port file. Runs in
+/// parallel, even in one process, do not collide.
+pub struct UdpRun {
+    client: Client,
+    server: EchoServer,
+    client_sockets: Vec<mio::net::UdpSocket>,
+    server_sockets: Vec<mio::net::UdpSocket>,
+    /// Time each datagram of the client was sent.
+    departures: Vec<Instant>,
+    /// Size of each datagram of the client.
+    datagram_sizes: Vec<usize>,
+}
+
+impl UdpRun {
+    /// Starts a server with `server_config` writing its addresses to
+    /// `port_file`, and a client of `workload` connecting to them with
+    /// `client_config`.
+    pub fn start(
+        server_config: quiche::Config,
+        client_config: &mut quiche::Config,
+        workload: &Workload,
+        port_file: &Path,
+    ) -> io::Result<Self> {
+        let any_port = SocketAddr::from(([127, 0, 0, 1], 0));
+        let paths = workload.path_count();
+
+        let server_sockets = ports::bind_sockets(any_port, paths)?;
+        let server_addrs = server_sockets
+            .iter()
+            .map(|s| s.local_addr())
+            .collect::<io::Result<Vec<_>>>()?;
+        ports::write_port_file(port_file, &server_addrs)?;
+
+        let peer_addrs = ports::read_port_file(port_file)?;
+        let client_sockets = ports::bind_sockets(any_port, paths)?;
+
+        Self::with_sockets(
+            server_config,
+            client_config,
+            workload,
+            (server_sockets, peer_addrs),
+            client_sockets,
+        )
+    }
+
+    /// Like `start`, over sockets bound by the caller, e.g. in other
+    /// network namespaces. The client reaches the server socket of each
+    /// path at the address given with it.
+    pub fn with_sockets(
+        server_config: quiche::Config,
+        client_config: &mut quiche::Config,
+        workload: &Workload,
+        (server_sockets, peer_addrs): (Vec<mio::net::UdpSocket>, Vec<SocketAddr>),
+        client_sockets: Vec<mio::net::UdpSocket>,
+    ) -> io::Result<Self> {
+        let server_addrs = server_sockets
+            .iter()
+            .map(|s| s.local_addr())
+            .collect::<io::Result<Vec<_>>>()?;
+        let local_addrs = client_sockets
+            .iter()
+            .map(|s| s.local_addr())
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let (scid, _) = generate_cid_and_reset_token(&SystemRandom::new(), workload.cid_len);
+        let conn = quiche::connect(
+            Some("localhost"),
+            &scid,
+            local_addrs[0],
+            peer_addrs[0],
+            client_config,
+        )
+        .map_err(|e| io::Error::other(format!("connect failed: {:?}", e)))?;
+
+        let report = TargetReport::new("udp", peer_addrs[0], workload.messages.len());
+        let client = Client::new(
+            conn,
+            workload,
+            local_addrs,
+            peer_addrs,
+            report,
+            Instant::now(),
+        );
+
+        Ok(UdpRun {
+            client,
+            server: EchoServer::new(server_config, server_addrs),
+            client_sockets,
+            server_sockets,
+            departures: vec![],
+            datagram_sizes: vec![],
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Time each datagram of the client was sent, in order, as a capture
+    /// would timestamp them.
+    pub fn departures(&self) -> &[Instant] {
+        &self.departures
+    }
+
+    /// Size of each datagram of the client, in the order they were sent.
+    pub fn datagram_sizes(&self) -> &[usize] {
+        &self.datagram_sizes
+    }
+
+    /// Runs until `messages` messages completed, the connection is closed or
+    /// `limit` elapsed, and returns the completed messages.
+    pub fn run(&mut self, messages: usize, limit: Duration) -> io::Result<Vec<Completed>> {
+        let deadline = Instant::now() + limit;
+        let mut buf = [0; 65535];
+        let mut completed = vec![];
+        let mut incoming = vec![];
+        let mut timeout = Some(Instant::now());
+
+        loop {
+            let now = Instant::now();
+
+            if !incoming.is_empty() || timeout.is_some_and(|t| t <= now) {
+                let output = self.client.step(now, &mut incoming);
+                completed.extend(output.completed);
+                timeout = output.timeout;
+                incoming.clear();
+
+                for (pkt, send_info) in output.datagrams {
+                    send(&self.client_sockets, &pkt, send_info.from, send_info.to)?;
+                    self.departures.push(Instant::now());
+                    self.datagram_sizes.push(pkt.len());
+                }
+            }
+
+            if completed.len() >= messages || self.client.is_finished() || now >= deadline {
+                return Ok(completed);
+            }
+
+            let mut received = false;
+
+            for socket in &self.server_sockets {
+                let to = socket.local_addr()?;
+
+                while let Some((len, from)) = recv(socket, &mut buf)? {
+                    self.server.recv(&buf[..len], quiche::RecvInfo { from, to });
+                    received = true;
+                }
+            }
+
+            if !received && self.server.timeout().is_some_and(|t| t <= now) {
+                self.server.on_timeout();
+            }
+
+            for (pkt, recv_info) in self.server.send() {
+                send(&self.server_sockets, &pkt, recv_info.from, recv_info.to)?;
+            }
+
+            for socket in &self.client_sockets {
+                let to = socket.local_addr()?;
+
+                while let Some((len, from)) = recv(socket, &mut buf)? {
+                    incoming.push((buf[..len].to_vec(), quiche::RecvInfo { from, to }));
+                }
+            }
+
+            if incoming.is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+/// Clients sharing the same local addresses, each against its own echo
+/// server, the datagrams of the servers going through a [`Demux`] as those
+/// of several targets do in the client binary. Runs in real time, the
+/// datagrams being delivered at once.
+pub struct SharedSockets {
+    clients: Vec<Client>,
+    servers: Vec<EchoServer>,
+    demux: Demux,
+    timeouts: Vec<Option<Instant>>,
+    incoming: Vec<Vec<(Vec<u8>, quiche::RecvInfo)>>,
+}
+
+impl SharedSockets {
+    /// Starts `connections` clients of `workload`, connecting with
+    /// `client_config` to servers created with `server_config`.
+    pub fn new<F: Fn() -> quiche::Config>(
+        workload: &Workload,
+        connections: usize,
+        client_config: &mut quiche::Config,
+        server_config: F,
+    ) -> Self {
+        let local_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+            .map(|i| SocketAddr::from(([127, 0, 0, 1], 9000 + i)))
+            .collect();
+
+        let rng = SystemRandom::new();
+        let now = Instant::now();
+        let mut demux = Demux::new(workload.cid_len);
+        let (mut clients, mut servers) = (vec![], vec![]);
+
+        for id in 0..connections {
+            let peer_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+                .map(|i| SocketAddr::from(([127, 0, 0, 1], 8000 + 100 * id as u16 + i)))
+                .collect();
+
+            let (scid, _) = generate_cid_and_reset_token(&rng, workload.cid_len);
+            let conn = quiche::connect(
+                Some("localhost"),
+                &scid,
+                local_addrs[0],
+                peer_addrs[0],
+                client_config,
+            )
+            .unwrap();
+            demux.insert(scid, id);
+
+            let report = TargetReport::new(
+                &format!("conn{}", id),
+                peer_addrs[0],
+                workload.messages.len(),
+            );
+            clients.push(Client::new(
+                conn,
+                workload,
+                local_addrs.clone(),
+                peer_addrs.clone(),
+                report,
+                now,
+            ));
+            servers.push(EchoServer::new(server_config(), peer_addrs));
+        }
+
+        SharedSockets {
+            clients,
+            servers,
+            demux,
+            timeouts: vec![Some(now); connections],
+            incoming: vec![vec![]; connections],
+        }
+    }
+
+    pub fn clients(&self) -> &[Client] {
+        &self.clients
+    }
+
+    /// The demultiplexer, e.g. to break it on purpose.
+    pub fn demux_mut(&mut self) -> &mut Demux {
+        &mut self.demux
+    }
+
+    /// Runs until `done` holds for the clients, they are all finished or
+    /// `limit` elapsed. Returns whether `done` held.
+    pub fn run_until<F: FnMut(&[Client]) -> bool>(&mut self, limit: Duration, mut done: F) -> bool {
+        let deadline = Instant::now() + limit;
+
+        loop {
+            let now = Instant::now();
+            let mut busy = false;
+
+            for (id, client) in self.clients.iter_mut().enumerate() {
+                let mut incoming = std::mem::take(&mut self.incoming[id]);
+
+                if client.is_finished()
+                    || (incoming.is_empty() && self.timeouts[id].is_none_or(|t| t > now))
+                {
+                    continue;
+                }
+
+                let output = client.step(now, &mut incoming);
+                self.timeouts[id] = output.timeout;
+
+                for event in &output.events {
+                    match event {
+                        Event::CidIssued(cid) => self.demux.insert(cid.clone(), id),
+                        Event::CidRetired(cid) => self.demux.remove(cid),
+                        _ => (),
+                    }
+                }
+
+                for (pkt, send_info) in output.datagrams {
+                    let recv_info = quiche::RecvInfo {
+                        from: send_info.from,
+                        to: send_info.to,
+                    };
+                    self.servers[id].recv(&pkt, recv_info);
+                    busy = true;
+                }
+            }
+
+            if done(&self.clients) {
+                return true;
+            }
+
+            if self.clients.iter().all(|c| c.is_finished()) || now >= deadline {
+                return false;
+            }
+
+            for server in &mut self.servers {
+                if server.timeout().is_some_and(|t| t <= now) {
+                    server.on_timeout();
+                }
+
+                for (mut pkt, recv_info) in server.send() {
+                    match self.demux.route(&mut pkt) {
+                        Some(id) => {
+                            self.incoming[id].push((pkt, recv_info));
+                            busy = true;
+                        }
+
+                        None => trace!("datagram for an unknown connection"),
+                    }
+                }
+            }
+
+            if !busy {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+/// Whether `pkt` is an Initial packet, opening a connection.
+fn is_initial(pkt: &[u8]) -> bool {
+    quiche::Header::from_slice(&mut pkt.to_vec(), quiche::MAX_CONN_ID_LEN)
+        .is_ok_and(|hdr| hdr.ty == quiche::Type::Initial)
+}
+
+/// Sends `pkt` to `to` from the socket of `sockets` bound to `from`.
+fn send(
+    sockets: &[mio::net::UdpSocket],
+    pkt: &[u8],
+    from: SocketAddr,
+    to: SocketAddr,
+) -> io::Result<()> {
+    for socket in sockets {
+        if socket.local_addr()? == from {
+            match socket.send_to(pkt, to) {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => return Err(e),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a datagram from the non-blocking `socket`, if one is pending.
+fn recv(socket: &mio::net::UdpSocket, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+    match socket.recv_from(buf) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// SOCKS5 proxy only supporting UDP associations without authentication,
+/// each one relayed from its own socket by a thread.
+pub struct Socks5Responder {
+    addr: SocketAddr,
+    associations: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Socks5Responder {
+    /// Listens on a free port of the loopback interface.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+
+        let responder = Socks5Responder {
+            addr: listener.local_addr()?,
+            associations: Arc::new(AtomicUsize::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+
+        let associations = responder.associations.clone();
+        let stop = responder.stop.clone();
+
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let associations = associations.clone();
+                        let stop = stop.clone();
+
+                        thread::spawn(move || {
+                            if let Err(e) = Self::associate(stream, &associations, &stop) {
+                                trace!("SOCKS5 association failed: {}", e);
+                            }
+                        });
+                    }
+
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(responder)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Number of UDP associations established so far.
+    pub fn associations(&self) -> usize {
+        self.associations.load(Ordering::Relaxed)
+    }
+
+    /// Answers the handshake on `control` then relays the datagrams of the
+    /// client until the responder is dropped.
+    fn associate(
+        mut control: TcpStream,
+        associations: &AtomicUsize,
+        stop: &AtomicBool,
+    ) -> io::Result<()> {
+        control.set_nonblocking(false)?;
+
+        let mut greeting = [0; 2];
+        control.read_exact(&mut greeting)?;
+        let mut methods = vec![0; greeting[1] as usize];
+        control.read_exact(&mut methods)?;
+        control.write_all(&[5, 0])?;
+
+        let mut request = [0; 3];
+        control.read_exact(&mut request)?;
+        let client = transport::read_addr(&mut control)?;
+
+        if request[1] != 3 {
+            // Command not supported.
+            control.write_all(&[5, 7, 0])?;
+            return Ok(());
+        }
+
+        let relay = UdpSocket::bind("127.0.0.1:0")?;
+        relay.set_read_timeout(Some(Duration::from_millis(20)))?;
+
+        associations.fetch_add(1, Ordering::Relaxed);
+
+        let mut reply = vec![5, 0, 0];
+        transport::encode_addr(&mut reply, relay.local_addr()?);
+        control.write_all(&reply)?;
+
+        let mut buf = [0; 65535];
+        let mut wrapped = vec![];
+
+        while !stop.load(Ordering::Relaxed) {
+            let (len, from) = match relay.recv_from(&mut buf) {
+                Ok(v) => v,
+
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue;
+                }
+
+                Err(e) => return Err(e),
+            };
+
+            if from == client {
+                if len < 3 {
+                    continue;
+                }
+
+                let mut datagram = &buf[3..len];
+                let Ok(to) = transport::read_addr(&mut datagram) else {
+                    continue;
+                };
+
+                relay.send_to(datagram, to)?;
+            } else {
+                wrapped.clear();
+                wrapped.extend_from_slice(&[0, 0, 0]);
+                transport::encode_addr(&mut wrapped, from);
+                wrapped.extend_from_slice(&buf[..len]);
+
+                relay.send_to(&wrapped, client)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Socks5Responder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{fmt, str::FromStr};
+
+use ring::digest;
+
+/// Transformation the server applies to the messages instead of echoing
+/// them, so that a client reading its own send buffer by mistake cannot pass
+/// for a correct echo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// ASCII letters in uppercase, the other bytes unchanged.
+    Upper,
+
+    /// Bytes in reverse order.
+    Reverse,
+
+    /// SHA-256 of the message.
+    Sha256,
+}
+
+impl Transform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transform::Upper => "upper",
+            Transform::Reverse => "reverse",
+            Transform::Sha256 => "sha256",
+        }
+    }
+
+    /// Transforms a whole message.
+    pub fn apply(&self, data: &[u8]) -> Vec<u8> {
+        let mut stream = StreamTransform::new(*self);
+        let mut out = stream.update(data);
+        out.extend_from_slice(&stream.finish());
+        out
+    }
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upper" => Ok(Transform::Upper),
+            "reverse" => Ok(Transform::Reverse),
+            "sha256" => Ok(Transform::Sha256),
+            _ => Err(format!("unknown transform '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A [`Transform`] applied to a message as its chunks are read from a
+/// stream, whatever their boundaries.
+///
+/// Uppercase chunks are returned as they arrive, while a reversed message or
+/// a digest is only known once the message ends.
+pub struct StreamTransform {
+    state: State,
+}
+
+enum State {
+    Upper,
+    Reverse(Vec<u8>),
+    Sha256(digest::Context),
+}
+
+impl StreamTransform {
+    pub fn new(transform: Transform) -> Self {
+        let state = match transform {
+            Transform::Upper => State::Upper,
+            Transform::Reverse => State::Reverse(Vec::new()),
+            Transform::Sha256 => State::Sha256(digest::Context::new(&digest::SHA256)),
+        };
+
+        StreamTransform { state }
+    }
+
+    /// Transforms the next chunk of the message, returning the output
+    /// available so far.
+    pub fn update(&mut self, chunk: &[u8]) -> Vec<u8> {
+        match &mut self.state {
+            State::Upper => chunk.to_ascii_uppercase(),
+
+            State::Reverse(buf) => {
+                buf.extend_from_slice(chunk);
+                Vec::new()
+            }
+
+            State::Sha256(ctx) => {
+                ctx.update(chunk);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the rest of the output, once the message ended.
+    pub fn finish(self) -> Vec<u8> {
+        match self.state {
+            State::Upper => Vec::new(),
+
+            State::Reverse(mut buf) => {
+                buf.reverse();
+                buf
+            }
+
+            State::Sha256(ctx) => ctx.finish().as_ref().to_vec(),
+        }
+    }
+}
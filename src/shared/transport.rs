@@ -0,0 +1,354 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    str::FromStr,
+};
+
+use log::{info, trace};
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const UDP_ASSOCIATE: u8 = 3;
+const SUCCEEDED: u8 = 0;
+const ATYP_IPV4: u8 = 1;
+const ATYP_IPV6: u8 = 4;
+
+/// Longest interface name, without the terminating NUL.
+const MAX_IFNAME_LEN: usize = 15;
+
+/// Sends and receives the datagrams of one client path.
+pub trait DatagramTransport {
+    /// Address identifying the transport in the paths of the connection.
+    fn local_addr(&self) -> SocketAddr;
+
+    fn send_to(&self, buf: &[u8], to: SocketAddr) -> io::Result<usize>;
+
+    /// Receives a datagram, with the address of the peer that sent it.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    /// Registers the transport to be polled for readability.
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token) -> io::Result<()>;
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()>;
+}
+
+/// Datagrams sent straight from a UDP socket.
+pub struct Udp {
+    socket: mio::net::UdpSocket,
+}
+
+impl Udp {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(Udp {
+            socket: mio::net::UdpSocket::bind(addr)?,
+        })
+    }
+
+    /// Binds `addr` and sends through the interface `ifname` only, whatever
+    /// the routes say (`SO_BINDTODEVICE`).
+    pub fn bind_device(addr: SocketAddr, ifname: &str) -> io::Result<Self> {
+        let socket = mio::net::UdpSocket::bind(addr)?;
+        bind_to_device(&socket, ifname)?;
+
+        Ok(Udp { socket })
+    }
+}
+
+/// Restricts `socket` to the interface `ifname`, which needs
+/// `CAP_NET_RAW` on kernels before 5.7.
+#[cfg(target_os = "linux")]
+pub fn bind_to_device(socket: &mio::net::UdpSocket, ifname: &str) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the name is valid for its length, which the kernel does not
+    // read past.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            ifname.as_ptr() as *const libc::c_void,
+            ifname.len() as libc::socklen_t,
+        )
+    };
+
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_to_device(_: &mio::net::UdpSocket, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface needs Linux",
+    ))
+}
+
+/// Interface the socket of a client path is bound to, as
+/// `<path>:<ifname>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BindDev {
+    pub path: usize,
+    pub ifname: String,
+}
+
+impl FromStr for BindDev {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, ifname) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <path>:<ifname>, got '{}'", s))?;
+
+        let path = path
+            .parse()
+            .map_err(|_| format!("invalid path '{}'", path))?;
+
+        if ifname.is_empty() || ifname.len() > MAX_IFNAME_LEN || ifname.contains('\0') {
+            return Err(format!("invalid interface name '{}'", ifname));
+        }
+
+        Ok(BindDev {
+            path,
+            ifname: ifname.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for BindDev {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.path, self.ifname)
+    }
+}
+
+impl DatagramTransport for Udp {
+    fn local_addr(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    fn send_to(&self, buf: &[u8], to: SocketAddr) -> io::Result<usize> {
+        self.socket.send_to(buf, to)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token) -> io::Result<()> {
+        registry.register(&mut self.socket, token, mio::Interest::READABLE)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        registry.deregister(&mut self.socket)
+    }
+}
+
+/// Transport of a path the client closed, which sends and receives nothing.
+///
+/// It takes the place of the socket of the path, so that the other paths
+/// keep their index.
+pub struct Closed {
+    local_addr: SocketAddr,
+}
+
+impl Closed {
+    pub fn new(local_addr: SocketAddr) -> Self {
+        Closed { local_addr }
+    }
+}
+
+impl DatagramTransport for Closed {
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn send_to(&self, _: &[u8], _: SocketAddr) -> io::Result<usize> {
+        Err(io::ErrorKind::NotConnected.into())
+    }
+
+    fn recv_from(&self, _: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+
+    fn register(&mut self, _: &mio::Registry, _: mio::Token) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn deregister(&mut self, _: &mio::Registry) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Datagrams relayed by a SOCKS5 proxy through a UDP association
+/// (RFC 1928), each one carrying the address of its peer in a header.
+pub struct Socks5 {
+    socket: mio::net::UdpSocket,
+    /// Address to which the proxy relays the datagrams from.
+    relay: SocketAddr,
+    /// The association lasts as long as this connection.
+    _control: TcpStream,
+    /// Buffer in which the datagrams sent are wrapped.
+    buf: RefCell<Vec<u8>>,
+}
+
+impl Socks5 {
+    /// Binds a socket to `addr` and asks the proxy at `proxy` to relay its
+    /// datagrams.
+    pub fn associate(addr: SocketAddr, proxy: SocketAddr) -> io::Result<Self> {
+        let socket = mio::net::UdpSocket::bind(addr)?;
+        let local_addr = socket.local_addr()?;
+
+        let mut control = TcpStream::connect(proxy)?;
+
+        control.write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])?;
+
+        let mut reply = [0; 2];
+        control.read_exact(&mut reply)?;
+
+        if reply != [SOCKS_VERSION, NO_AUTHENTICATION] {
+            return Err(socks_error("proxy requires authentication"));
+        }
+
+        let mut request = vec![SOCKS_VERSION, UDP_ASSOCIATE, 0];
+        encode_addr(&mut request, local_addr);
+        control.write_all(&request)?;
+
+        let mut reply = [0; 3];
+        control.read_exact(&mut reply)?;
+
+        if reply[0] != SOCKS_VERSION || reply[1] != SUCCEEDED {
+            return Err(socks_error(&format!(
+                "UDP ASSOCIATE refused with code {}",
+                reply[1]
+            )));
+        }
+
+        let mut relay = read_addr(&mut control)?;
+
+        // The proxy may leave it to the client to use its own address.
+        if relay.ip().is_unspecified() {
+            relay.set_ip(proxy.ip());
+        }
+
+        info!("{} relayed by {} through {}", local_addr, proxy, relay);
+
+        Ok(Socks5 {
+            socket,
+            relay,
+            _control: control,
+            buf: RefCell::new(Vec::new()),
+        })
+    }
+}
+
+impl DatagramTransport for Socks5 {
+    fn local_addr(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
+    fn send_to(&self, buf: &[u8], to: SocketAddr) -> io::Result<usize> {
+        let mut wrapped = self.buf.borrow_mut();
+        wrapped.clear();
+
+        // Reserved bytes, then a fragment number of 0 as datagrams are never
+        // fragmented.
+        wrapped.extend_from_slice(&[0, 0, 0]);
+        encode_addr(&mut wrapped, to);
+        wrapped.extend_from_slice(buf);
+
+        self.socket.send_to(&wrapped, self.relay)?;
+
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        loop {
+            let (len, from) = self.socket.recv_from(buf)?;
+
+            if from != self.relay {
+                trace!("dropped datagram from {}, not the relay", from);
+                continue;
+            }
+
+            let mut header = &buf[..len];
+
+            if header.len() < 3 || header[2] != 0 {
+                trace!("dropped fragmented or truncated datagram");
+                continue;
+            }
+
+            header = &header[3..];
+
+            let Ok(peer) = read_addr(&mut header) else {
+                trace!("dropped datagram with an invalid address");
+                continue;
+            };
+
+            let start = len - header.len();
+            buf.copy_within(start..len, 0);
+
+            return Ok((len - start, peer));
+        }
+    }
+
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token) -> io::Result<()> {
+        registry.register(&mut self.socket, token, mio::Interest::READABLE)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        registry.deregister(&mut self.socket)
+    }
+}
+
+fn socks_error(msg: &str) -> io::Error {
+    io::Error::other(format!("SOCKS5: {}", msg))
+}
+
+/// Appends `addr` as an address type, address and port.
+pub fn encode_addr(out: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            out.push(ATYP_IPV4);
+            out.extend_from_slice(&ip.octets());
+        }
+
+        IpAddr::V6(ip) => {
+            out.push(ATYP_IPV6);
+            out.extend_from_slice(&ip.octets());
+        }
+    }
+
+    out.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Reads an address type, address and port. Domain names are not supported.
+pub fn read_addr<R: Read>(input: &mut R) -> io::Result<SocketAddr> {
+    let mut atyp = [0; 1];
+    input.read_exact(&mut atyp)?;
+
+    let ip = match atyp[0] {
+        ATYP_IPV4 => {
+            let mut octets = [0; 4];
+            input.read_exact(&mut octets)?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+
+        ATYP_IPV6 => {
+            let mut octets = [0; 16];
+            input.read_exact(&mut octets)?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+
+        atyp => return Err(socks_error(&format!("unsupported address type {}", atyp))),
+    };
+
+    let mut port = [0; 2];
+    input.read_exact(&mut port)?;
+
+    Ok(SocketAddr::new(ip, u16::from_be_bytes(port)))
+}
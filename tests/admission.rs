@@ -0,0 +1,113 @@
+use std::{
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use quiche_test::shared::{
+    admission::Admission,
+    stream_writer::{Progress, StreamWriter},
+    testing::MockConn,
+};
+
+use common::ms;
+
+mod common;
+
+/// Admits as many messages as possible at `now` and writes each of them on
+/// stream `4 * message`. Returns the messages admitted, with their wait.
+fn dispatch(
+    admission: &mut Admission,
+    conn: &mut MockConn,
+    now: Instant,
+) -> Vec<(usize, Duration)> {
+    let mut admitted = vec![];
+
+    while let Some((message, waited)) = admission.admit(now) {
+        let payload = format!("message {}", message).into_bytes();
+        let mut writer = StreamWriter::new(message as u64 * 4, Cursor::new(payload));
+        assert_eq!(writer.poll_write(conn).unwrap(), Progress::Finished);
+
+        admitted.push((message, waited));
+    }
+
+    admitted
+}
+
+#[test]
+fn limits_messages_in_flight() {
+    let start = Instant::now();
+    let mut conn = MockConn::default();
+    let mut admission = Admission::new(2, Duration::from_secs(10));
+
+    for message in 0..4 {
+        admission.offer(message, start);
+    }
+
+    assert_eq!(
+        dispatch(&mut admission, &mut conn, start),
+        [(0, Duration::ZERO), (1, Duration::ZERO)]
+    );
+    assert_eq!(conn.streams.len(), 2);
+    assert_eq!(admission.peek(), Some(2));
+
+    // Messages are admitted in order as the others complete.
+    admission.complete(1);
+    assert_eq!(
+        dispatch(&mut admission, &mut conn, start + ms(30)),
+        [(2, ms(30))]
+    );
+
+    admission.complete(0);
+    admission.complete(2);
+    assert_eq!(
+        dispatch(&mut admission, &mut conn, start + ms(50)),
+        [(3, ms(50))]
+    );
+    assert_eq!(conn.stream(12).data, b"message 3");
+    assert!(conn.stream(12).fin);
+}
+
+#[test]
+fn offered_once() {
+    let start = Instant::now();
+    let mut conn = MockConn::default();
+    let mut admission = Admission::new(1, Duration::from_secs(10));
+
+    admission.offer(0, start);
+    admission.offer(0, start + ms(10));
+    assert_eq!(
+        dispatch(&mut admission, &mut conn, start),
+        [(0, Duration::ZERO)]
+    );
+
+    // Already in flight.
+    admission.offer(0, start + ms(20));
+    assert_eq!(admission.peek(), None);
+}
+
+#[test]
+fn stuck_message_frees_its_slot() {
+    let start = Instant::now();
+    let mut conn = MockConn::default();
+    let mut admission = Admission::new(1, ms(100));
+
+    admission.offer(0, start);
+    admission.offer(1, start);
+    assert_eq!(dispatch(&mut admission, &mut conn, start).len(), 1);
+
+    assert!(dispatch(&mut admission, &mut conn, start + ms(50)).is_empty());
+    assert_eq!(admission.next_release(), Some(start + ms(100)));
+
+    // Message 0 never completes, message 1 gets its slot.
+    assert_eq!(
+        dispatch(&mut admission, &mut conn, start + ms(100)),
+        [(1, ms(100))]
+    );
+    assert_eq!(admission.stuck(), 1);
+    assert_eq!(admission.next_release(), None);
+
+    // A late completion is still accounted.
+    admission.complete(0);
+    assert_eq!(admission.stuck(), 0);
+    assert_eq!(admission.in_flight(), 1);
+}
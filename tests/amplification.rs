@@ -0,0 +1,37 @@
+use quiche_test::shared::amplification::{self, PreValidation};
+
+#[test]
+fn mirrored_headroom() {
+    let mut client = PreValidation::new(false);
+    let mut server = PreValidation::new(true);
+
+    // The client Initial, then the server flight, then the client Finished.
+    for (from_client, len) in [(true, 1200), (false, 1200), (false, 1200), (true, 1200)] {
+        if from_client {
+            client.on_sent(len);
+            server.on_recv(len);
+        } else {
+            server.on_sent(len);
+            client.on_recv(len);
+        }
+    }
+
+    assert_eq!(client.headroom(), 3 * 2400 - 2400);
+    assert_eq!(server.headroom(), client.headroom());
+
+    // Nothing is counted once validated.
+    server.validate(0);
+    server.on_sent(100_000);
+    assert_eq!(server.sent, 2400);
+    assert_eq!(server.headroom(), 4800);
+}
+
+#[test]
+fn limited_below_one_datagram() {
+    // A 100 bytes probe lets the server send 300 bytes, less than a datagram.
+    assert!(amplification::is_limited(100, 0, 1200));
+
+    assert!(!amplification::is_limited(1200, 2400, 1200));
+    assert!(amplification::is_limited(1200, 2401, 1200));
+    assert!(amplification::is_limited(1200, 4000, 1200));
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::burst::{BurstLimit, BurstStats};
+
+#[test]
+fn budget_is_send_quantum_unless_overridden() {
+    assert_eq!(BurstLimit::new(None).budget(12_000, 1200), 12_000);
+    assert_eq!(BurstLimit::new(None).budget(0, 1200), 1200);
+    assert_eq!(BurstLimit::new(Some(4)).budget(12_000, 1200), 4800);
+    assert_eq!(
+        BurstLimit::new(Some(usize::MAX)).budget(0, 1200),
+        usize::MAX
+    );
+}
+
+#[test]
+fn stats_track_largest_and_capped_bursts() {
+    let now = Instant::now();
+    let mut burst = BurstLimit::new(None);
+
+    burst.on_burst(3, None);
+    burst.on_burst(10, Some(now));
+    burst.on_burst(7, None);
+
+    assert_eq!(
+        burst.stats(),
+        BurstStats {
+            max_burst: 10,
+            capped: 1,
+        }
+    );
+}
+
+#[test]
+fn release_is_earliest_capped_burst_of_round() {
+    let now = Instant::now();
+    let mut burst = BurstLimit::new(None);
+
+    assert_eq!(burst.next_release(), None);
+
+    burst.begin();
+    burst.on_burst(10, Some(now + Duration::from_millis(5)));
+    burst.on_burst(10, Some(now + Duration::from_millis(2)));
+    burst.on_burst(4, None);
+    assert_eq!(burst.next_release(), Some(now + Duration::from_millis(2)));
+
+    burst.begin();
+    burst.on_burst(4, None);
+    assert_eq!(burst.next_release(), None);
+}
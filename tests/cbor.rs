@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use quiche_test::{
+    events::{Event, JsonEvents},
+    report::{MessageRecord, PathList, RecordWriter, Throughput},
+    shared::{
+        cbor::{self, CborError, CborObject},
+        encoding::Object,
+        json::JsonObject,
+        rng::SeededRng,
+    },
+};
+
+use common::path;
+
+mod common;
+
+/// Decodes `data`, a single map, back to JSON.
+fn to_json(data: &[u8]) -> String {
+    let (obj, len) = cbor::decode::<JsonObject>(data).unwrap();
+    assert_eq!(len, data.len());
+
+    obj.finish()
+}
+
+#[test]
+fn record_round_trip() {
+    let mut paths = PathList::default();
+    paths.push(path(0));
+    paths.push(path(1));
+
+    let record = MessageRecord {
+        index: 300,
+        stream_id: 70_000,
+        frame: Some(2),
+        paths,
+        latency: Duration::from_secs(5000),
+        queue_wait: Duration::ZERO,
+    };
+
+    let data = RecordWriter::build::<CborObject>(&record, 9, Some("a")).finish();
+    assert_eq!(to_json(&data), RecordWriter::encode(&record, 9, Some("a")));
+}
+
+#[test]
+fn event_round_trip() {
+    let throughput = Event::Throughput(Throughput {
+        wire_bytes: 3000,
+        app_bytes: 1000,
+        elapsed: Duration::from_millis(300),
+    });
+    let warning = Event::ConfigWarning("window \"too\" small\n".to_string());
+
+    for event in [throughput, warning] {
+        let data = JsonEvents::build::<CborObject>(&event, 7, None).finish();
+        assert_eq!(to_json(&data), JsonEvents::encode(&event, 7, None));
+    }
+
+    // Not finite numbers are null in both formats.
+    let data = CborObject::new()
+        .f64("rate", f64::NAN)
+        .bool("done", true)
+        .finish();
+    assert_eq!(to_json(&data), "{\"rate\":null,\"done\":true}");
+}
+
+#[test]
+fn sequence_decoded_item_by_item() {
+    let mut data = CborObject::new().u64("a", 1).finish();
+    data.extend(CborObject::new().u64("b", u64::MAX).finish());
+
+    let (first, len) = cbor::decode::<JsonObject>(&data).unwrap();
+    assert_eq!(first.finish(), "{\"a\":1}");
+
+    let (second, _) = cbor::decode::<JsonObject>(&data[len..]).unwrap();
+    assert_eq!(second.finish(), format!("{{\"b\":{}}}", u64::MAX));
+
+    let err = cbor::decode::<JsonObject>(&data[..len - 1]).unwrap_err();
+    assert_eq!(err, CborError::Truncated);
+
+    // A bare number is not a record.
+    let err = cbor::decode::<JsonObject>(&[0x01]).unwrap_err();
+    assert_eq!(err, CborError::Unsupported(0x01));
+}
+
+/// A map with a single key holding an array of `depth - 1` such maps, the
+/// innermost one empty.
+fn nested(depth: usize) -> Vec<u8> {
+    let mut data = vec![];
+
+    for _ in 1..depth {
+        data.extend([0xa1, 0x61, b'a', 0x81]);
+    }
+    data.push(0xa0);
+
+    data
+}
+
+#[test]
+fn nesting_depth_capped() {
+    let data = nested(cbor::MAX_DEPTH);
+    let (_, len) = cbor::decode::<JsonObject>(&data).unwrap();
+    assert_eq!(len, data.len());
+
+    let err = cbor::decode::<JsonObject>(&nested(cbor::MAX_DEPTH + 1)).unwrap_err();
+    assert_eq!(err, CborError::TooDeep);
+
+    // Deep enough to overflow the stack without the cap.
+    let err = cbor::decode::<JsonObject>(&nested(1_000_000)).unwrap_err();
+    assert_eq!(err, CborError::TooDeep);
+}
+
+#[test]
+fn malformed_input_rejected() {
+    let mut paths = PathList::default();
+    paths.push(path(0));
+
+    let record = MessageRecord {
+        index: 1,
+        stream_id: 4,
+        frame: None,
+        paths,
+        latency: Duration::from_millis(20),
+        queue_wait: Duration::ZERO,
+    };
+    let data = RecordWriter::build::<CborObject>(&record, 9, Some("a")).finish();
+
+    // Every truncation ends within an item.
+    for len in 0..data.len() {
+        let err = cbor::decode::<JsonObject>(&data[..len]).unwrap_err();
+        assert_eq!(err, CborError::Truncated);
+    }
+
+    // Corrupted bytes and random data decode or fail, without panicking.
+    let mut rng = SeededRng::new(61);
+
+    for _ in 0..10_000 {
+        let mut corrupted = data.clone();
+        for _ in 0..=rng.below(4) {
+            let i = rng.below(corrupted.len() as u64) as usize;
+            corrupted[i] = rng.next_u64() as u8;
+        }
+        cbor::decode::<JsonObject>(&corrupted).ok();
+
+        let len = rng.below(64) as usize;
+        let random: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+        cbor::decode::<JsonObject>(&random).ok();
+    }
+
+    // Lengths beyond the data are truncations, not allocations.
+    for head in [
+        &[
+            0xa1, 0x61, b'a', 0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ][..],
+        &[0xa1, 0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..],
+        &[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..],
+    ] {
+        let err = cbor::decode::<JsonObject>(head).unwrap_err();
+        assert_eq!(err, CborError::Truncated);
+    }
+}
+
+#[test]
+fn invalid_text_rejected() {
+    // Keys and values alike, a lone continuation byte and a truncated
+    // two-byte sequence.
+    for data in [
+        &[0xa1, 0x61, 0x80, 0x01][..],
+        &[0xa1, 0x61, b'a', 0x62, b'x', 0xc3][..],
+        &[0xbf, 0x61, b'a', 0x61, 0xff, 0xff][..],
+    ] {
+        let err = cbor::decode::<JsonObject>(data).unwrap_err();
+        assert_eq!(err, CborError::InvalidText);
+    }
+
+    // A text value longer than the data left is a truncation.
+    let err = cbor::decode::<JsonObject>(&[0xa1, 0x61, b'a', 0x7a, 0xff, 0xff, 0xff, 0xff, b'x'])
+        .unwrap_err();
+    assert_eq!(err, CborError::Truncated);
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use quiche_test::shared::{
+    cids::{CidIssuer, CidStats, MAX_DRAWS},
+    generate_cid_and_reset_token,
+    rng::SeededRng,
+    testing::MockConn,
+};
+use ring::rand::SystemRandom;
+
+#[test]
+fn cids_and_reset_tokens_are_unique() {
+    let rng = SystemRandom::new();
+
+    let mut cids = HashSet::new();
+    let mut reset_tokens = HashSet::new();
+
+    for _ in 0..100_000 {
+        let (cid, reset_token) = generate_cid_and_reset_token(&rng, quiche::MAX_CONN_ID_LEN);
+
+        assert_eq!(cid.len(), quiche::MAX_CONN_ID_LEN);
+        assert!(cids.insert(cid), "duplicate connection ID");
+        assert!(reset_tokens.insert(reset_token), "duplicate reset token");
+    }
+}
+
+#[test]
+fn issued_cids_have_configured_length() {
+    let rng = SystemRandom::new();
+    let mut conn = conn();
+    let mut issuer = CidIssuer::new(4);
+
+    let issued = issuer.issue(&mut conn, &rng);
+    assert_eq!(issued.len(), 2);
+    assert!(issued.iter().all(|cid| cid.len() == 4));
+}
+
+/// Mock connection whose peer accepts 3 active connection IDs, the one of
+/// the handshake included.
+fn conn() -> MockConn {
+    let (scid, _) = generate_cid_and_reset_token(&SystemRandom::new(), quiche::MAX_CONN_ID_LEN);
+
+    MockConn {
+        scids: vec![scid],
+        peer_cid_limit: Some(3),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn issuance_stops_at_peer_limit() {
+    let rng = SystemRandom::new();
+    let mut conn = conn();
+    let mut issuer = CidIssuer::default();
+
+    assert_eq!(issuer.issue(&mut conn, &rng).len(), 2);
+    assert_eq!(
+        issuer.stats(&conn),
+        CidStats {
+            cids_issued: 2,
+            cids_active: 3,
+            cids_redrawn: 0,
+        }
+    );
+
+    assert!(issuer.issue(&mut conn, &rng).is_empty());
+
+    // The peer retires one, its replacement is issued.
+    conn.scids.remove(0);
+    assert_eq!(issuer.issue(&mut conn, &rng).len(), 1);
+    assert_eq!(issuer.stats(&conn).cids_issued, 3);
+    assert_eq!(issuer.stats(&conn).cids_active, 3);
+}
+
+#[test]
+fn issuance_stops_on_error() {
+    let rng = SystemRandom::new();
+    let mut conn = conn();
+    conn.new_scid_error = Some(quiche::Error::InvalidState);
+    let mut issuer = CidIssuer::default();
+
+    assert!(issuer.issue(&mut conn, &rng).is_empty());
+
+    // Not retried until the active IDs or the peer limit change.
+    conn.new_scid_error = None;
+    assert!(issuer.issue(&mut conn, &rng).is_empty());
+
+    conn.peer_cid_limit = Some(4);
+    assert_eq!(issuer.issue(&mut conn, &rng).len(), 3);
+    assert_eq!(issuer.stats(&conn).cids_issued, 3);
+}
+
+/// Draws IDs and reset tokens among 4 values only, so that they collide.
+fn colliding(seed: u64) -> impl FnMut() -> (quiche::ConnectionId<'static>, u128) {
+    let mut rng = SeededRng::new(seed);
+
+    move || {
+        let n = rng.below(4) as u8;
+        (vec![n; quiche::MAX_CONN_ID_LEN].into(), n as u128)
+    }
+}
+
+#[test]
+fn colliding_draws_are_redrawn() {
+    let mut conn = MockConn {
+        peer_cid_limit: Some(5),
+        ..conn()
+    };
+    let mut issuer = CidIssuer::default();
+
+    let issued = issuer.issue_with(&mut conn, colliding(9), |_| false);
+    assert_eq!(issued.len(), 4);
+    assert_eq!(issued.iter().collect::<HashSet<_>>().len(), 4);
+    assert_eq!(issuer.stats(&conn).cids_redrawn, 6);
+
+    // Once every value is issued, the issuer gives up instead of reusing
+    // one.
+    conn.scids.remove(0);
+    assert!(issuer
+        .issue_with(&mut conn, colliding(9), |_| false)
+        .is_empty());
+    assert_eq!(issuer.stats(&conn).cids_redrawn, 6 + MAX_DRAWS as u64);
+
+    // A new connection starts with no ID issued.
+    issuer.restart();
+    assert_eq!(
+        issuer.issue_with(&mut conn, colliding(9), |_| false).len(),
+        1
+    );
+}
+
+#[test]
+fn duplicate_reset_token_is_redrawn() {
+    let mut conn = conn();
+    let mut issuer = CidIssuer::default();
+
+    let mut draws = vec![
+        (vec![1; quiche::MAX_CONN_ID_LEN].into(), 7),
+        (vec![2; quiche::MAX_CONN_ID_LEN].into(), 7),
+        (vec![3; quiche::MAX_CONN_ID_LEN].into(), 8),
+    ]
+    .into_iter();
+
+    let issued = issuer.issue_with(&mut conn, || draws.next().unwrap(), |_| false);
+    assert_eq!(
+        issued,
+        [
+            vec![1; quiche::MAX_CONN_ID_LEN].into(),
+            vec![3; quiche::MAX_CONN_ID_LEN].into()
+        ]
+    );
+    assert_eq!(issuer.stats(&conn).cids_redrawn, 1);
+}
+
+#[test]
+fn ids_taken_by_another_connection_are_redrawn() {
+    let mut conn = conn();
+    let mut issuer = CidIssuer::default();
+    let taken: quiche::ConnectionId = vec![1; quiche::MAX_CONN_ID_LEN].into();
+
+    let issued = issuer.issue_with(&mut conn, colliding(3), |scid| *scid == taken);
+    assert_eq!(issued.len(), 2);
+    assert!(!issued.contains(&taken));
+    assert_eq!(issuer.stats(&conn).cids_redrawn, 3);
+}
@@ -0,0 +1,364 @@
+use std::{net::SocketAddr, time::Instant};
+
+use quiche_test::{
+    client::{Client, Completed, Workload},
+    config::ClientOptions,
+    report::{Outcome, TargetReport},
+    shared::testing::EchoServer,
+};
+
+use common::{addr, config, options};
+
+mod common;
+
+/// Runs the messages of `opts` against an echo server until all of them
+/// complete.
+fn run(opts: &ClientOptions) -> Vec<Completed> {
+    run_with(opts, config(false), config(true)).0
+}
+
+/// Runs the messages of `opts` against an echo server until all of them
+/// complete or the connection is closed.
+fn run_with(
+    opts: &ClientOptions,
+    client_config: quiche::Config,
+    server_config: quiche::Config,
+) -> (Vec<Completed>, Client) {
+    run_redirected(opts, client_config, server_config, |_| {})
+}
+
+/// Like `run_with`, `redirect` choosing the client address each datagram of
+/// the server is delivered to.
+fn run_redirected<F>(
+    opts: &ClientOptions,
+    client_config: quiche::Config,
+    server_config: quiche::Config,
+    redirect: F,
+) -> (Vec<Completed>, Client)
+where
+    F: FnMut(&mut quiche::RecvInfo),
+{
+    let (mut client, mut server) = connect(opts, client_config, server_config);
+    let completed = drive(&mut client, &mut server, opts.messages.len(), redirect);
+
+    (completed, client)
+}
+
+/// Creates a client sending the messages of `opts` and the echo server it
+/// connects to.
+fn connect(
+    opts: &ClientOptions,
+    mut client_config: quiche::Config,
+    server_config: quiche::Config,
+) -> (Client, EchoServer) {
+    let workload = Workload::from_options(opts, 7).unwrap();
+
+    let local_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+        .map(|i| addr(9000 + i))
+        .collect();
+    let peer_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+        .map(|i| addr(8000 + i))
+        .collect();
+
+    let scid = quiche::ConnectionId::from_ref(&[0xba; quiche::MAX_CONN_ID_LEN]);
+    let conn = quiche::connect(
+        Some("localhost"),
+        &scid,
+        local_addrs[0],
+        peer_addrs[0],
+        &mut client_config,
+    )
+    .unwrap();
+
+    let report = TargetReport::new("test", peer_addrs[0], opts.messages.len());
+    let client = Client::new(
+        conn,
+        &workload,
+        local_addrs,
+        peer_addrs.clone(),
+        report,
+        Instant::now(),
+    );
+
+    (client, EchoServer::new(server_config, peer_addrs))
+}
+
+/// Exchanges datagrams between `client` and `server` until `messages`
+/// messages complete or the connection is closed, `redirect` choosing the
+/// client address each datagram of the server is delivered to.
+fn drive<F>(
+    client: &mut Client,
+    server: &mut EchoServer,
+    messages: usize,
+    mut redirect: F,
+) -> Vec<Completed>
+where
+    F: FnMut(&mut quiche::RecvInfo),
+{
+    let mut completed = vec![];
+    let mut incoming = vec![];
+
+    let deadline = Instant::now() + std::time::Duration::from_secs(2);
+    while Instant::now() < deadline {
+        let output = client.step(Instant::now(), &mut incoming);
+        completed.extend(output.completed);
+
+        for (pkt, send_info) in &output.datagrams {
+            let recv_info = quiche::RecvInfo {
+                from: send_info.from,
+                to: send_info.to,
+            };
+            server.recv(pkt, recv_info);
+        }
+
+        if completed.len() == messages || client.is_finished() {
+            break;
+        }
+
+        incoming = server.send();
+
+        for (_, recv_info) in &mut incoming {
+            redirect(recv_info);
+        }
+    }
+
+    completed
+}
+
+#[test]
+fn one_message_per_path() {
+    let completed = run(&options(&["a", "b", "c"]));
+
+    assert_eq!(completed.len(), 3);
+
+    for (i, message) in completed.iter().enumerate() {
+        assert_eq!(message.record.index, i);
+        assert_eq!(message.echo, ["a", "b", "c"][i].as_bytes());
+        assert_eq!(
+            message.record.paths.paths(),
+            [(addr(9000 + i as u16), addr(8000 + i as u16))]
+        );
+    }
+}
+
+#[test]
+fn shuffled_dispatch() {
+    let opts = ClientOptions {
+        shuffle: true,
+        ..options(&["a", "b", "c", "d", "e"])
+    };
+    let order = Workload::from_options(&opts, 7).unwrap().order;
+
+    let completed = run(&opts);
+    assert_eq!(completed.len(), 5);
+
+    // Streams are opened in dispatch order.
+    for message in &completed {
+        let index = message.record.index;
+        let position = order.iter().position(|i| *i == index).unwrap();
+
+        assert_eq!(message.record.stream_id, position as u64 * 4);
+        assert_eq!(message.echo, opts.messages[index]);
+    }
+}
+
+#[test]
+fn paths_fit_available_sockets() {
+    let opts = ClientOptions {
+        standby_path: Some(1),
+        ..options(&["a", "b", "c", "d"])
+    };
+    let mut workload = Workload::from_options(&opts, 7).unwrap();
+    assert_eq!(workload.paths, [0, 2, 3, 4]);
+    assert_eq!(workload.path_count(), 5);
+
+    workload.fit_paths(3);
+    assert_eq!(workload.paths, [0, 2, 2, 0]);
+    assert_eq!(workload.standby_path, Some(1));
+    assert_eq!(workload.path_count(), 3);
+
+    // The standby path cannot be the only one.
+    workload.fit_paths(1);
+    assert_eq!(workload.paths, [0, 0, 0, 0]);
+    assert_eq!(workload.standby_path, None);
+    assert_eq!(workload.path_count(), 1);
+}
+
+#[test]
+fn client_certificate_accepted() {
+    let mut client_config = config(false);
+    client_config
+        .load_cert_chain_from_pem_file("certs/localhost.crt")
+        .unwrap();
+    client_config
+        .load_priv_key_from_pem_file("certs/localhost.key")
+        .unwrap();
+
+    let mut server_config = config(true);
+    server_config.verify_peer(true);
+    server_config
+        .load_verify_locations_from_file("certs/ca.crt")
+        .unwrap();
+
+    let (completed, _) = run_with(&options(&["a"]), client_config, server_config);
+    assert_eq!(completed.len(), 1);
+}
+
+#[test]
+fn client_certificate_rejected() {
+    let mut client_config = config(false);
+    client_config
+        .load_cert_chain_from_pem_file("certs/localhost.crt")
+        .unwrap();
+    client_config
+        .load_priv_key_from_pem_file("certs/localhost.key")
+        .unwrap();
+
+    // The server trusts no certificate authority.
+    let mut server_config = config(true);
+    server_config.verify_peer(true);
+
+    let (completed, client) = run_with(&options(&["a"]), client_config, server_config);
+    assert!(completed.is_empty());
+    assert!(client.is_finished());
+
+    let error = client.report().error.as_deref().unwrap();
+    assert!(error.starts_with("handshake rejected by peer"), "{}", error);
+    assert_eq!(client.report().outcome(), Outcome::HandshakeFailed);
+}
+
+#[test]
+fn server_certificate_untrusted() {
+    // The client trusts no certificate authority.
+    let mut client_config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+    client_config
+        .set_application_protos(&[b"http/0.9"])
+        .unwrap();
+    client_config.verify_peer(true);
+
+    let (completed, client) = run_with(&options(&["a"]), client_config, config(true));
+    assert!(completed.is_empty());
+    assert!(client.is_finished());
+    assert_eq!(client.report().outcome(), Outcome::HandshakeFailed);
+}
+
+#[test]
+fn max_ack_delay_reported() {
+    let mut opts = options(&["a"]);
+    opts.max_ack_delay_ms = Some(50);
+
+    let mut client_config = config(false);
+    client_config.set_max_ack_delay(50);
+
+    let mut server_config = config(true);
+    server_config.set_max_ack_delay(100);
+
+    let (completed, client) = run_with(&opts, client_config, server_config);
+    assert_eq!(completed.len(), 1);
+    assert_eq!(client.report().max_ack_delay, Some((50, 100)));
+}
+
+#[test]
+fn datagram_on_wrong_socket() {
+    // The first datagram the server sends on the second path reaches the
+    // socket of the first one.
+    let mut redirected = false;
+    let (completed, client) = run_redirected(
+        &options(&["a", "b"]),
+        config(false),
+        config(true),
+        |recv_info| {
+            if recv_info.to == addr(9001) && !redirected {
+                recv_info.to = addr(9000);
+                redirected = true;
+            }
+        },
+    );
+
+    assert!(redirected);
+    assert_eq!(completed.len(), 2);
+    assert!(client.report().stray_datagrams >= 1);
+
+    // The unknown path is never used for messages.
+    for message in &completed {
+        let i = message.record.index as u16;
+        assert_eq!(
+            message.record.paths.paths(),
+            [(addr(9000 + i), addr(8000 + i))]
+        );
+    }
+}
+
+#[test]
+fn delivered_messages_skipped_on_resume() {
+    let opts = options(&["a", "b", "c"]);
+    let (mut client, mut server) = connect(&opts, config(false), config(true));
+
+    // Delivered before a restart.
+    client.skip_delivered(&[0, 2]);
+
+    let completed = drive(&mut client, &mut server, usize::MAX, |_| {});
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].record.index, 1);
+    assert_eq!(completed[0].echo, b"b");
+
+    assert!(client.is_finished());
+    assert_eq!(client.delivered(), [0, 1, 2]);
+    assert_eq!(client.report().delivered, 3);
+    assert_eq!(client.report().throughput.app_bytes, 1);
+}
+
+#[test]
+fn keep_open_after_delivery() {
+    let mut opts = options(&["a", "b"]);
+    opts.keep_open = true;
+
+    let (mut client, mut server) = connect(&opts, config(false), config(true));
+
+    // Runs until the deadline of drive, the connection staying open.
+    let completed = drive(&mut client, &mut server, usize::MAX, |_| {});
+    assert_eq!(completed.len(), 2);
+    assert!(!client.is_finished());
+    assert!(client.is_kept_open());
+
+    client.close();
+    drive(&mut client, &mut server, usize::MAX, |_| {});
+    assert!(client.is_finished());
+    assert!(!client.is_kept_open());
+    assert_eq!(client.report().delivered, 2);
+    assert_eq!(client.report().outcome(), Outcome::Delivered);
+}
+
+#[test]
+fn interactive_messages() {
+    let mut opts = options(&["a"]);
+    opts.interactive = Some(2);
+
+    let (mut client, mut server) = connect(&opts, config(false), config(true));
+
+    // The connection stays open once the initial message completed.
+    let completed = drive(&mut client, &mut server, 1, |_| {});
+    assert_eq!(completed.len(), 1);
+    assert!(!client.is_finished());
+
+    assert_eq!(client.push_message(b"b"), Ok(1));
+    assert_eq!(client.push_message(b"c"), Ok(2));
+
+    let mut completed = drive(&mut client, &mut server, 2, |_| {});
+    assert_eq!(completed.len(), 2);
+    completed.sort_by_key(|c| c.record.index);
+
+    // Pushed messages take the paths in turn.
+    for (message, (echo, path)) in completed.iter().zip([("b", 1), ("c", 0)]) {
+        assert_eq!(message.echo, echo.as_bytes());
+        assert_eq!(
+            message.record.paths.paths(),
+            [(addr(9000 + path), addr(8000 + path))]
+        );
+    }
+
+    client.close();
+    drive(&mut client, &mut server, usize::MAX, |_| {});
+    assert!(client.is_finished());
+    assert_eq!(client.report().delivered, 3);
+}
@@ -0,0 +1,66 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use quiche_test::config::ClientOptions;
+
+pub fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+/// Path `i` of the client, from port 9000 + i to port 8000 + i.
+pub fn path(i: u16) -> (SocketAddr, SocketAddr) {
+    (addr(9000 + i), addr(8000 + i))
+}
+
+pub fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+/// File `name` of this test process in the temporary directory.
+pub fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("quiche-test-{}-{}", std::process::id(), name))
+}
+
+pub fn options(messages: &[&str]) -> ClientOptions {
+    ClientOptions {
+        messages: messages.iter().map(|m| m.as_bytes().to_vec()).collect(),
+        ..Default::default()
+    }
+}
+
+pub fn config(server: bool) -> quiche::Config {
+    config_with_version(server, quiche::PROTOCOL_VERSION)
+}
+
+pub fn config_with_version(server: bool, version: u32) -> quiche::Config {
+    let mut config = quiche::Config::new(version).unwrap();
+
+    config.set_application_protos(&[b"http/0.9"]).unwrap();
+
+    if server {
+        config
+            .load_cert_chain_from_pem_file("certs/localhost.crt")
+            .unwrap();
+        config
+            .load_priv_key_from_pem_file("certs/localhost.key")
+            .unwrap();
+    } else {
+        config.verify_peer(true);
+        config
+            .load_verify_locations_from_file("certs/ca.crt")
+            .unwrap();
+    }
+
+    config.set_initial_max_streams_bidi(100);
+    config.set_initial_max_data(1000000);
+    config.set_initial_max_stream_data_bidi_local(1000000);
+    config.set_initial_max_stream_data_bidi_remote(1000000);
+    config.set_initial_max_streams_uni(100);
+    config.set_initial_max_stream_data_uni(1000000);
+    config.set_active_connection_id_limit(20);
+
+    config
+}
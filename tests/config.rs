@@ -0,0 +1,556 @@
+use std::str::FromStr;
+
+use quiche_test::{
+    config::{Builder, ClientOptions, Confirm, ServerOptions, Target, Validate},
+    shared::{
+        apps::{App, Handler},
+        control::RunId,
+        encoding::Format,
+        transform::Transform,
+        transport::BindDev,
+    },
+};
+
+/// Constraint broken by the options it sets, with the message refusing them.
+type Rule<O> = (&'static str, Box<dyn Fn(&mut O)>);
+
+/// Every constraint between the client options, with options breaking it.
+fn client_rules() -> Vec<Rule<ClientOptions>> {
+    vec![
+        (
+            "--interactive runs against a single target",
+            Box::new(|o| {
+                o.interactive = Some(0);
+                o.targets = vec![Target::default(), Target::default()];
+            }),
+        ),
+        (
+            "--resume-state cannot be combined with --interactive or --handshakes",
+            Box::new(|o| {
+                o.resume_state = Some("state".to_string());
+                o.handshakes = Some(1);
+            }),
+        ),
+        (
+            "--baseline cannot be combined with --interactive, --handshakes, \
+             --resume-state or --keep-open",
+            Box::new(|o| {
+                o.baseline = true;
+                o.keep_open = true;
+            }),
+        ),
+        (
+            "--multiplex-streams needs --multiplex",
+            Box::new(|o| o.multiplex_streams = 4),
+        ),
+        (
+            "--keepalive-ms must be below --idle-timeout-ms",
+            Box::new(|o| {
+                o.keepalive_ms = Some(1000);
+                o.idle_timeout_ms = Some(1000);
+            }),
+        ),
+        ("--loss needs --handshakes", Box::new(|o| o.loss = 0.1)),
+        (
+            "--port-file and --target cannot be combined",
+            Box::new(|o| {
+                o.port_file = Some("ports".to_string());
+                o.targets = vec![Target::default()];
+            }),
+        ),
+        (
+            "--handshakes runs against a single target",
+            Box::new(|o| {
+                o.handshakes = Some(1);
+                o.targets = vec![Target::default(), Target::default()];
+            }),
+        ),
+        (
+            "--parallel-streams needs --file",
+            Box::new(|o| o.parallel_streams = Some(4)),
+        ),
+        (
+            "--progress-json needs --file",
+            Box::new(|o| o.progress_json = Some("progress".to_string())),
+        ),
+        (
+            "--cert and --key must be given together",
+            Box::new(|o| o.cert = Some("cert.pem".to_string())),
+        ),
+        (
+            "--owd cannot be combined with --multiplex, --pad-to, --expect-transform or \
+             --confirm acks",
+            Box::new(|o| {
+                o.owd = true;
+                o.pad_to = Some(64);
+            }),
+        ),
+        (
+            "--bind-dev cannot be combined with --socks5",
+            Box::new(|o| {
+                o.bind_devs = vec!["0:eth0".parse().unwrap()];
+                o.socks5 = Some("127.0.0.1:1080".parse().unwrap());
+            }),
+        ),
+        (
+            "--bind-dev 1 is given more than once",
+            Box::new(|o| {
+                o.bind_devs = vec!["1:eth0".parse().unwrap(), "1:eth1".parse().unwrap()];
+            }),
+        ),
+        (
+            "--path-schedule cannot be combined with --multiplex, --stripe or --standby-path",
+            Box::new(|o| {
+                o.path_schedule = Some("0:0-1s,1:1s-".parse().unwrap());
+                o.standby_path = Some(1);
+            }),
+        ),
+        (
+            "--path-idle-timeout cannot be combined with --path-schedule, --stripe, --reconnect \
+             or --baseline",
+            Box::new(|o| {
+                o.path_idle_timeout_ms = Some(1000);
+                o.reconnect = 1;
+            }),
+        ),
+        (
+            "--sequence cannot be combined with --pad-to, --expect-transform, --confirm acks \
+             or --file",
+            Box::new(|o| {
+                o.sequence = true;
+                o.confirm = Confirm::Acks;
+            }),
+        ),
+        (
+            "--flow-blocking cannot be combined with --file, --bufferbloat or --handshakes",
+            Box::new(|o| {
+                o.flow_blocking = true;
+                o.handshakes = Some(1);
+            }),
+        ),
+    ]
+}
+
+/// Every constraint between the server options, with options breaking it.
+fn server_rules() -> Vec<Rule<ServerOptions>> {
+    vec![
+        (
+            "--padded and --multiplex cannot be combined",
+            Box::new(|o| {
+                o.padded = true;
+                o.multiplex = true;
+            }),
+        ),
+        (
+            "--file-transfer cannot be combined with --padded or --multiplex",
+            Box::new(|o| {
+                o.file_transfer = true;
+                o.multiplex = true;
+            }),
+        ),
+        (
+            "--no-echo and --file-transfer cannot be combined",
+            Box::new(|o| {
+                o.no_echo = true;
+                o.file_transfer = true;
+            }),
+        ),
+        (
+            "--transform cannot be combined with --no-echo or --file-transfer",
+            Box::new(|o| {
+                o.transform = Some(Transform::Upper);
+                o.no_echo = true;
+            }),
+        ),
+        (
+            "--timestamps cannot be combined with --padded, --multiplex, --transform or \
+             --no-echo",
+            Box::new(|o| {
+                o.timestamps = true;
+                o.transform = Some(Transform::Reverse);
+            }),
+        ),
+        (
+            "--file-transfer cannot be combined with --app, use --app <alpn>=file",
+            Box::new(|o| {
+                o.file_transfer = true;
+                o.apps = vec![App::new("echo/1", Handler::Echo)];
+            }),
+        ),
+        (
+            "--app echo/1 is given more than once",
+            Box::new(|o| {
+                o.apps = vec![
+                    App::new("echo/1", Handler::Echo),
+                    App::new("echo/1", Handler::File),
+                ];
+            }),
+        ),
+    ]
+}
+
+#[test]
+fn defaults_are_valid() {
+    assert!(ClientOptions::default().problems().is_empty());
+    assert!(ServerOptions::default().problems().is_empty());
+}
+
+#[test]
+fn client_rules_each_refused() {
+    for (problem, set) in client_rules() {
+        let err = Builder::<ClientOptions>::default()
+            .with(set)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.0, problem);
+    }
+}
+
+#[test]
+fn server_rules_each_refused() {
+    for (problem, set) in server_rules() {
+        let err = Builder::<ServerOptions>::default()
+            .with(set)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.0, problem);
+    }
+}
+
+#[test]
+fn every_problem_listed() {
+    let err = Builder::<ClientOptions>::default()
+        .with(|o| {
+            o.parallel_streams = Some(2);
+            o.loss = 0.1;
+            o.key = Some("key.pem".to_string());
+        })
+        .build()
+        .unwrap_err();
+
+    assert_eq!(
+        err.0.lines().collect::<Vec<_>>(),
+        [
+            "--loss needs --handshakes",
+            "--parallel-streams needs --file",
+            "--cert and --key must be given together",
+        ]
+    );
+}
+
+#[test]
+fn command_line_goes_through_builder() {
+    let args = [
+        "--parallel-streams",
+        "2",
+        "--multiplex-streams",
+        "2",
+        "hello",
+    ]
+    .map(String::from);
+    let err = ClientOptions::from_args(args).unwrap_err();
+
+    assert_eq!(
+        err.0,
+        "--multiplex-streams needs --multiplex\n--parallel-streams needs --file"
+    );
+}
+
+#[test]
+fn server_apps_by_alpn() {
+    let args = ["--app", "echo/1=echo", "--app", "http/0.9=file"].map(String::from);
+    let opts = ServerOptions::from_args(args).unwrap();
+
+    assert_eq!(
+        opts.apps(),
+        [
+            App::new("echo/1", Handler::Echo),
+            App::new("http/0.9", Handler::File)
+        ]
+    );
+
+    // Without --app, the default protocol is served as before.
+    let opts = ServerOptions::from_args(["--file-transfer".to_string()]).unwrap();
+    assert_eq!(opts.apps(), [App::new("http/0.9", Handler::File)]);
+
+    for app in ["h3=http3", "echo", "=echo"] {
+        let err = ServerOptions::from_args(["--app", app].map(String::from)).unwrap_err();
+        assert!(err.0.starts_with("invalid value for --app"), "{}", err.0);
+    }
+
+    let opts = ServerOptions::from_args(["--app", "h3=h3"].map(String::from)).unwrap();
+    assert_eq!(opts.apps(), [App::new("h3", Handler::H3)]);
+}
+
+#[test]
+fn client_path_schedule() {
+    let args = ["--path-schedule", "0:0-5s,1:5s-10s,0:10s-", "hello"].map(String::from);
+    let opts = ClientOptions::from_args(args).unwrap();
+
+    assert_eq!(opts.path_schedule.unwrap().windows().len(), 3);
+
+    for schedule in ["0:5s-1s", "0:0-5s,1:4s-", "x:0-1s", "0:0-1h"] {
+        let args = ["--path-schedule", schedule, "hello"].map(String::from);
+        let err = ClientOptions::from_args(args).unwrap_err();
+        assert!(
+            err.0.starts_with("invalid value for --path-schedule"),
+            "{}",
+            err.0
+        );
+    }
+
+    let args = ["--path-schedule", "40:0-1s", "hello"].map(String::from);
+    let err = ClientOptions::from_args(args).unwrap_err();
+    assert_eq!(err.0, "--path-schedule paths must be below 20");
+}
+
+#[test]
+fn client_bind_devs() {
+    let args = ["--bind-dev", "0:eth0", "--bind-dev", "1:wlan0", "hello"].map(String::from);
+    let opts = ClientOptions::from_args(args).unwrap();
+
+    assert_eq!(
+        opts.bind_devs,
+        [
+            BindDev {
+                path: 0,
+                ifname: "eth0".to_string()
+            },
+            BindDev {
+                path: 1,
+                ifname: "wlan0".to_string()
+            }
+        ]
+    );
+
+    for dev in ["eth0", "x:eth0", "0:", "0:averylonginterfacename"] {
+        let args = ["--bind-dev", dev, "hello"].map(String::from);
+        let err = ClientOptions::from_args(args).unwrap_err();
+        assert!(
+            err.0.starts_with("invalid value for --bind-dev"),
+            "{}",
+            err.0
+        );
+    }
+}
+
+#[test]
+fn client_run_id() {
+    let args = ["--run-id", "ci-1234.nightly", "hello"].map(String::from);
+    let opts = ClientOptions::from_args(args).unwrap();
+    assert_eq!(
+        opts.run_id,
+        Some(RunId::from_str("ci-1234.nightly").unwrap())
+    );
+
+    let opts = ClientOptions::from_args(["hello".to_string()]).unwrap();
+    assert_eq!(opts.run_id, None);
+
+    for run_id in ["", "with space", "run,1", &"x".repeat(65)] {
+        let args = ["--run-id", run_id, "hello"].map(String::from);
+        let err = ClientOptions::from_args(args).unwrap_err();
+        assert!(err.0.starts_with("invalid value for --run-id"), "{}", err.0);
+    }
+}
+
+#[test]
+fn output_formats() {
+    let args = [
+        "--report-format",
+        "cbor",
+        "--events-format",
+        "cbor-seq",
+        "hello",
+    ]
+    .map(String::from);
+    let opts = ClientOptions::from_args(args).unwrap();
+    assert_eq!(opts.report_format, Format::Cbor);
+    assert_eq!(opts.events_format, Format::Cbor);
+
+    let opts = ClientOptions::from_args(["hello".to_string()]).unwrap();
+    assert_eq!(opts.report_format, Format::Json);
+    assert_eq!(opts.events_format, Format::Json);
+
+    // Each flag takes the name of its own format.
+    let args = ["--events-format", "json", "hello"].map(String::from);
+    let err = ClientOptions::from_args(args).unwrap_err();
+    assert_eq!(err.0, "invalid value 'json' for --events-format");
+}
+
+#[test]
+fn cid_len_bounded() {
+    let args = ["--cid-len", "8", "hello"].map(String::from);
+    assert_eq!(ClientOptions::from_args(args).unwrap().cid_len, 8);
+
+    let args = ["--cid-len", "4"].map(String::from);
+    assert_eq!(ServerOptions::from_args(args).unwrap().cid_len, 4);
+
+    // Both default to the longest IDs.
+    assert_eq!(ClientOptions::default().cid_len, quiche::MAX_CONN_ID_LEN);
+    assert_eq!(ServerOptions::default().cid_len, quiche::MAX_CONN_ID_LEN);
+
+    for len in ["0", "21"] {
+        let args = ["--cid-len", len, "hello"].map(String::from);
+        assert_eq!(
+            ClientOptions::from_args(args).unwrap_err().0,
+            "--cid-len must be between 1 and 20"
+        );
+
+        let args = ["--cid-len", len].map(String::from);
+        assert_eq!(
+            ServerOptions::from_args(args).unwrap_err().0,
+            "--cid-len must be between 1 and 20"
+        );
+    }
+}
+
+#[test]
+fn quiche_config_errors() {
+    let opts = ClientOptions {
+        ca: "certs/missing.crt".to_string(),
+        ..Default::default()
+    };
+
+    let mut customized = false;
+    let err = opts
+        .quiche_config(&Target::default(), false, |_| customized = true)
+        .err()
+        .unwrap();
+
+    assert!(err
+        .0
+        .starts_with("cannot load certificate authorities certs/missing.crt"));
+    // The closure is not called on a configuration that failed.
+    assert!(!customized);
+
+    let opts = ClientOptions::default();
+    opts.quiche_config(&Target::default(), false, |_| customized = true)
+        .unwrap();
+    assert!(customized);
+}
+
+fn warnings<F: FnOnce(&mut ClientOptions)>(set: F) -> Vec<String> {
+    let mut opts = ClientOptions::default();
+    set(&mut opts);
+    opts.warnings()
+}
+
+#[test]
+fn defaults_raise_no_warning() {
+    assert!(ClientOptions::default().warnings().is_empty());
+    assert!(warnings(|o| o.messages = vec![b"hello".to_vec(); 100]).is_empty());
+}
+
+#[test]
+fn stream_larger_than_stream_window() {
+    let warnings = warnings(|o| {
+        o.messages = vec![vec![0; 1_500_000]];
+        o.max_data = 4_000_000;
+    });
+
+    assert_eq!(
+        warnings,
+        [
+            "a stream carries up to 1500000 bytes but initial_max_stream_data (1000000 bytes) \
+             caps each stream to ~160 Mbit/s at 50 ms RTT; consider --max-stream-data on the \
+             client and the server"
+        ]
+    );
+}
+
+#[test]
+fn messages_in_flight_larger_than_connection_window() {
+    let many = |o: &mut ClientOptions| o.messages = vec![vec![0; 100_000]; 20];
+
+    assert_eq!(
+        warnings(many),
+        [
+            "up to 2000000 bytes are in flight at once but initial_max_data (1000000 bytes) \
+             caps the connection to ~160 Mbit/s at 50 ms RTT; consider --max-data on the \
+             client and the server"
+        ]
+    );
+
+    // Padding counts, concurrency limits what is in flight.
+    assert_eq!(
+        warnings(|o| {
+            o.messages = vec![b"hello".to_vec(); 20];
+            o.pad_to = Some(100_000);
+        })
+        .len(),
+        1
+    );
+    assert!(warnings(|o| {
+        many(o);
+        o.max_concurrent_streams = Some(5);
+    })
+    .is_empty());
+}
+
+#[test]
+fn bulk_transfer_capped_by_both_windows() {
+    let warnings = warnings(|o| o.bufferbloat = Some(100_000_000));
+
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].starts_with("a stream carries up to 100000000 bytes"));
+    assert!(warnings[1].starts_with("up to 100000000 bytes are in flight at once"));
+}
+
+#[test]
+fn connection_window_below_stream_window() {
+    assert_eq!(
+        warnings(|o| o.max_data = 500_000),
+        [
+            "initial_max_data (500000 bytes) is below initial_max_stream_data (1000000 bytes), \
+             no stream can fill its window; consider a larger --max-data"
+        ]
+    );
+}
+
+#[test]
+fn flow_blocking_expects_small_windows() {
+    let small = |o: &mut ClientOptions| {
+        o.messages = vec![vec![0; 2_000_000]];
+        o.max_data = 500_000;
+    };
+
+    assert_eq!(warnings(small).len(), 3);
+    assert!(warnings(|o| {
+        small(o);
+        o.flow_blocking = true;
+    })
+    .is_empty());
+}
+
+#[test]
+fn more_messages_at_once_than_streams() {
+    let many = |o: &mut ClientOptions| o.messages = vec![b"hello".to_vec(); 150];
+
+    assert_eq!(
+        warnings(many),
+        [
+            "150 messages are sent at once but the server lets the client open 100 streams, \
+             the others wait for it to allow more; consider --max-concurrent-streams 100"
+        ]
+    );
+
+    assert!(warnings(|o| {
+        many(o);
+        o.multiplex = Some(1);
+    })
+    .is_empty());
+}
+
+#[test]
+fn strict_refuses_warnings() {
+    let args = ["--strict", "--max-data", "500000"].map(String::from);
+    let err = ClientOptions::from_args(args).unwrap_err();
+
+    assert_eq!(
+        err.0,
+        "initial_max_data (500000 bytes) is below initial_max_stream_data (1000000 bytes), no \
+         stream can fill its window; consider a larger --max-data (refused with --strict)"
+    );
+
+    assert!(ClientOptions::from_args(["--strict".to_string()]).is_ok());
+}
@@ -0,0 +1,103 @@
+use quiche_test::shared::control::{
+    Completion, ConnRun, Control, RunId, RunOutcomes, RunTally, MAX_RUN_ID_LEN,
+};
+
+fn run_id(s: &str) -> RunId {
+    s.parse().unwrap()
+}
+
+fn completion(id: &str) -> Completion {
+    Completion {
+        run_id: run_id(id),
+        messages: 3,
+        bytes: 1200,
+    }
+}
+
+#[test]
+fn messages_round_trip() {
+    let start = Control::Start(run_id("ci-42"));
+    assert_eq!(start.encode(), b"run start: run-id ci-42");
+    assert_eq!(Control::parse(&start.encode()), Some(start));
+
+    let complete = Control::Complete(completion("ci-42"));
+    assert_eq!(
+        complete.encode(),
+        b"run complete: 3 messages, 1200 bytes, run-id ci-42"
+    );
+    assert_eq!(Control::parse(&complete.encode()), Some(complete));
+}
+
+#[test]
+fn other_data_is_not_control() {
+    for data in [
+        &b"hello"[..],
+        b"run start: run-id ",
+        b"run start: run-id with space",
+        b"run complete: many messages, 1200 bytes, run-id ci-42",
+        b"run complete: 3 messages, 1200 bytes",
+        b"\xffrun start: run-id ci-42",
+    ] {
+        assert_eq!(Control::parse(data), None, "{:?}", data);
+    }
+}
+
+#[test]
+fn only_whole_uni_streams() {
+    let data = Control::Start(run_id("ci-42")).encode();
+
+    assert!(Control::from_read(2, &data, true).is_some());
+    // Bidirectional streams carry the messages to echo.
+    assert!(Control::from_read(0, &data, true).is_none());
+    assert!(Control::from_read(2, &data, false).is_none());
+}
+
+#[test]
+fn run_id_validation() {
+    assert_eq!(run_id("a.b_c-1").as_str(), "a.b_c-1");
+    assert!("x".repeat(MAX_RUN_ID_LEN).parse::<RunId>().is_ok());
+
+    for s in ["", "a b", "a,b", "é"] {
+        assert!(s.parse::<RunId>().is_err(), "{}", s);
+    }
+    assert!("x".repeat(MAX_RUN_ID_LEN + 1).parse::<RunId>().is_err());
+
+    // Random IDs are valid ones.
+    let random = RunId::random(u64::MAX);
+    assert_eq!(random.as_str(), "ffffffffffffffff");
+    assert_eq!(random.as_str().parse::<RunId>().unwrap(), random);
+}
+
+#[test]
+fn tally_clean_and_aborted() {
+    let mut tally = RunTally::default();
+
+    let mut clean = ConnRun::default();
+    clean.on_control(Control::Start(run_id("a")));
+    clean.on_control(Control::Complete(completion("a")));
+    assert!(clean.is_clean());
+    assert_eq!(clean.to_string(), "a, clean: 3 messages, 1200 bytes");
+
+    let mut aborted = ConnRun::default();
+    aborted.on_control(Control::Start(run_id("a")));
+    assert!(!aborted.is_clean());
+    assert_eq!(
+        aborted.to_string(),
+        "a, ended without completion (client crashed or aborted)"
+    );
+
+    tally.close(&clean);
+    tally.close(&clean);
+    tally.close(&aborted);
+    tally.close(&ConnRun::default());
+
+    assert_eq!(
+        tally.get(&run_id("a")),
+        Some(RunOutcomes {
+            clean: 2,
+            aborted: 1
+        })
+    );
+    assert_eq!(tally.get(&run_id("b")), None);
+    assert_eq!(tally.unknown(), 1);
+}
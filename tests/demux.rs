@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use quiche_test::{
+    client::Workload,
+    config::ClientOptions,
+    report::Outcome,
+    shared::{demux::RejectTracker, file_transfer, testing::SharedSockets},
+};
+
+use common::config;
+
+mod common;
+
+fn shared_sockets(opts: &ClientOptions) -> SharedSockets {
+    let workload = Workload::from_options(opts, 1).unwrap();
+
+    SharedSockets::new(&workload, 2, &mut config(false), || config(true))
+}
+
+fn messages(n: usize) -> Vec<Vec<u8>> {
+    (0..n)
+        .map(|i| format!("message {}", i).into_bytes())
+        .collect()
+}
+
+#[test]
+fn clean_run_rejects_nothing() {
+    let opts = ClientOptions {
+        messages: messages(5),
+        ..Default::default()
+    };
+
+    let mut run = shared_sockets(&opts);
+    run.run_until(Duration::from_secs(10), |_| false);
+
+    for client in run.clients() {
+        let report = client.report();
+        assert_eq!(report.outcome(), Outcome::Delivered);
+        assert_eq!(report.rejected_datagrams, 0);
+        assert!(report.misdelivery.is_none());
+    }
+}
+
+#[test]
+fn misrouted_datagrams_detected() {
+    let opts = ClientOptions {
+        messages: messages(20),
+        keep_open: true,
+        strict: true,
+        ..Default::default()
+    };
+
+    let mut run = shared_sockets(&opts);
+    assert!(run.run_until(Duration::from_secs(10), |clients| {
+        clients.iter().all(|c| c.conn().is_established())
+    }));
+
+    // The datagrams of connection 1 now reach connection 0.
+    let misrouted = file_transfer::hex(&run.clients()[1].conn().source_id());
+    run.demux_mut().misroute(1, 0);
+
+    run.run_until(Duration::from_secs(10), |clients| clients[0].is_finished());
+
+    let report = run.clients()[0].report();
+    assert!(report.rejected_datagrams > 0);
+    assert_eq!(report.outcome(), Outcome::Misdelivered);
+
+    // The diagnostic names the connection ID and the socket.
+    let misdelivery = report.misdelivery.as_deref().unwrap();
+    assert!(
+        misdelivery.contains(&format!("{} on 127.0.0.1:9000", misrouted)),
+        "{}",
+        misdelivery
+    );
+}
+
+#[test]
+fn reject_rate_threshold() {
+    let socket = "127.0.0.1:9000".parse().unwrap();
+    let mut rejects = RejectTracker::default();
+
+    for i in 0..10 {
+        rejects.on_datagram(socket, Some(&[0xaa]), i < 2);
+    }
+
+    // 2 of 10 is not above the threshold.
+    assert!(rejects.check().is_none());
+
+    rejects.on_datagram(socket, Some(&[0xbb]), true);
+    let diagnostic = rejects.check().unwrap();
+    assert!(diagnostic.contains("3 of 11"), "{}", diagnostic);
+    assert!(diagnostic.contains("bb on 127.0.0.1:9000: 1 rejected of 1"));
+
+    // Reported once.
+    assert!(rejects.check().is_none());
+}
+
+#[test]
+fn short_cids_routed() {
+    let opts = ClientOptions {
+        messages: messages(5),
+        cid_len: 4,
+        ..Default::default()
+    };
+
+    let mut run = shared_sockets(&opts);
+    run.run_until(Duration::from_secs(10), |_| false);
+
+    for client in run.clients() {
+        assert_eq!(client.conn().source_id().len(), 4);
+
+        let report = client.report();
+        assert_eq!(report.outcome(), Outcome::Delivered);
+        assert_eq!(report.rejected_datagrams, 0);
+    }
+}
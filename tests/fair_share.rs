@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::fair_share::{Fairness, RoundRobin, WorkBudget};
+
+/// Runs a bulk connection with 5000 packets queued against a prober
+/// sending one packet per millisecond, each packet costing 20µs of virtual
+/// time. Returns the largest echo latency of the prober, the number of
+/// probes and the wakeups of the bulk connection that left packets.
+fn bulk_and_prober(packet_budget: usize) -> (Duration, usize, u64) {
+    let cost = Duration::from_micros(20);
+    let period = Duration::from_millis(1);
+
+    let mut round_robin = RoundRobin::default();
+    let mut conns = [
+        WorkBudget::new(packet_budget, usize::MAX),
+        WorkBudget::new(packet_budget, usize::MAX),
+    ];
+
+    for _ in 0..5000 {
+        conns[0].push(Duration::ZERO);
+    }
+
+    let mut clock = Duration::ZERO;
+    let mut next_probe = Duration::ZERO;
+    let mut latencies = vec![];
+    let mut bulk = 0;
+
+    while conns[0].has_backlog() {
+        while next_probe <= clock {
+            conns[1].push(next_probe);
+            next_probe += period;
+        }
+
+        for id in round_robin.order([0, 1]) {
+            let conn = &mut conns[id as usize];
+            conn.begin();
+
+            while let Some(arrival) = conn.next_packet() {
+                clock += cost;
+
+                if id == 0 {
+                    bulk += 1;
+                } else {
+                    latencies.push(clock - arrival);
+                }
+            }
+        }
+    }
+
+    // The bulk transfer went through.
+    assert_eq!(bulk, 5000);
+
+    let max = latencies.iter().max().cloned().unwrap_or_default();
+    (max, latencies.len(), conns[0].stats().packets_limited)
+}
+
+#[test]
+fn prober_not_held_back_by_bulk() {
+    let cost = Duration::from_micros(20);
+
+    let (max, probes, limited) = bulk_and_prober(32);
+
+    // A probe waits for the wakeup during which it arrived, then for the
+    // share of the bulk connection at most.
+    assert!(max <= cost * 66, "{:?}", max);
+    assert!(probes > 100);
+    assert_eq!(limited, 156);
+
+    // Without a budget, the first probe waits for the whole backlog.
+    let (max, _, limited) = bulk_and_prober(usize::MAX);
+    assert!(max >= cost * 5000, "{:?}", max);
+    assert_eq!(limited, 0);
+}
+
+#[test]
+fn connections_take_turns() {
+    let mut round_robin = RoundRobin::default();
+
+    assert_eq!(round_robin.order([0, 1, 2]), [0, 1, 2]);
+    assert_eq!(round_robin.order([2, 0, 1]), [1, 2, 0]);
+
+    // Connection 1 is gone, the next one in turn goes first.
+    assert_eq!(round_robin.order([0, 2]), [2, 0]);
+    assert_eq!(round_robin.order([0, 2]), [0, 2]);
+}
+
+#[test]
+fn bytes_left_for_next_wakeup() {
+    let mut work = WorkBudget::<()>::new(32, 1000);
+
+    work.begin();
+    assert!(work.can_serve());
+    work.serve(600);
+    assert!(work.can_serve());
+    work.serve(600);
+    assert!(!work.can_serve());
+    assert!(!work.can_serve());
+    assert!(work.has_backlog());
+    assert_eq!(work.stats().bytes_limited, 1);
+
+    work.begin();
+    assert!(work.can_serve());
+    assert!(!work.has_backlog());
+}
+
+#[test]
+fn fairness_ratio() {
+    let start = Instant::now();
+    let mut fairness = Fairness::default();
+
+    fairness.open(0, start);
+    fairness.open(1, start);
+    fairness.open(2, start);
+    fairness.on_served(0, 1000);
+    fairness.on_served(1, 4000);
+
+    let now = start + Duration::from_secs(1);
+    assert_eq!(fairness.rate(0, now), Some(1000.0));
+
+    // Connection 2 asked for nothing, it is left out.
+    assert_eq!(fairness.ratio(now), Some(4.0));
+
+    fairness.close(1);
+    assert_eq!(fairness.ratio(now), None);
+}
@@ -0,0 +1,119 @@
+use quiche_test::shared::file_transfer::{self, FileSink, RangeHeader, Reply, HEADER_LEN};
+
+/// Stream carrying the range of `data` at `offset`, as sent by the client.
+fn stream(transfer: u64, data: &[u8], offset: u64, len: u64) -> Vec<u8> {
+    let header = RangeHeader {
+        transfer,
+        size: data.len() as u64,
+        offset,
+    };
+
+    let range = &data[offset as usize..(offset + len) as usize];
+    [&header.encode()[..], range].concat()
+}
+
+/// Sends the ranges of `data` to `sink` in reverse order, each in reads of
+/// at most 7 bytes, and returns the replies to their fins.
+fn reassemble(sink: &mut FileSink, data: &[u8], n: usize) -> Vec<Reply> {
+    let ranges = file_transfer::split(data.len() as u64, n);
+    let mut replies = vec![];
+
+    for (i, (offset, len)) in ranges.into_iter().enumerate().rev() {
+        let stream = stream(0, data, offset, len);
+        let chunks: Vec<&[u8]> = stream.chunks(7).collect();
+
+        for (j, chunk) in chunks.iter().enumerate() {
+            let fin = j == chunks.len() - 1;
+            let reply = sink.on_stream_data(i as u64 * 4, chunk, fin).unwrap();
+            assert_eq!(reply.is_some(), fin);
+            replies.extend(reply);
+        }
+    }
+
+    replies
+}
+
+#[test]
+fn split_in_nearly_equal_ranges() {
+    assert_eq!(file_transfer::split(10, 3), [(0, 4), (4, 3), (7, 3)]);
+    assert_eq!(file_transfer::split(2, 4), [(0, 1), (1, 1)]);
+    assert_eq!(file_transfer::split(0, 4), [(0, 0)]);
+}
+
+#[test]
+fn split_in_chunks() {
+    assert_eq!(file_transfer::chunks(10, 4), [(0, 4), (4, 4), (8, 2)]);
+    assert_eq!(file_transfer::chunks(8, 4), [(0, 4), (4, 4)]);
+    assert_eq!(file_transfer::chunks(3, 10), [(0, 3)]);
+    assert_eq!(file_transfer::chunks(0, 4), [(0, 0)]);
+}
+
+#[test]
+fn header_roundtrip() {
+    let header = RangeHeader {
+        transfer: 3,
+        size: 1 << 40,
+        offset: 12345,
+    };
+
+    let encoded = header.encode();
+    assert_eq!(encoded.len(), HEADER_LEN);
+    assert_eq!(RangeHeader::decode(&encoded), header);
+}
+
+#[test]
+fn ranges_out_of_order_in_memory() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+    let mut sink = FileSink::new(1 << 20);
+
+    let replies = reassemble(&mut sink, &data, 4);
+
+    // Only the fin completing the file carries the digest.
+    assert_eq!(
+        replies[..3],
+        [Reply::RangeDone, Reply::RangeDone, Reply::RangeDone]
+    );
+    assert_eq!(
+        replies[3],
+        Reply::FileDone {
+            size: 1000,
+            digest: file_transfer::sha256(&data)
+        }
+    );
+    assert_eq!(sink.in_memory(), 0);
+}
+
+#[test]
+fn ranges_out_of_order_spilled() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i % 13) as u8).collect();
+    let dir = std::env::temp_dir().join(format!("quiche-test-spill-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut sink = FileSink::new(100).spill_dir(dir.clone());
+    let replies = reassemble(&mut sink, &data, 3);
+
+    assert_eq!(
+        replies.last().unwrap(),
+        &Reply::FileDone {
+            size: 1000,
+            digest: file_transfer::sha256(&data)
+        }
+    );
+
+    // The spilled file is removed once hashed.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    std::fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn range_beyond_file_size() {
+    let mut sink = FileSink::new(1 << 20);
+    let header = RangeHeader {
+        transfer: 0,
+        size: 4,
+        offset: 2,
+    };
+
+    let stream = [&header.encode()[..], b"abc"].concat();
+    assert!(sink.on_stream_data(0, &stream, true).is_err());
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+use quiche_test::shared::flow_block::{FlowBlocking, PendingStream};
+
+use common::ms;
+
+mod common;
+
+fn stream(stream_id: u64, written: u64, capacity: usize) -> PendingStream {
+    PendingStream {
+        stream_id,
+        written,
+        capacity,
+    }
+}
+
+#[test]
+fn connection_blocked_until_window_raised() {
+    let t0 = Instant::now();
+    let mut flow = FlowBlocking::new(1000, 10_000);
+
+    flow.update(1000, &[stream(0, 1000, 0)], ms(10), t0);
+    assert!(flow.is_blocked());
+    assert_eq!(flow.blocked_streams().count(), 0);
+
+    // Bytes got through, the block ends even if the new window is already
+    // used up.
+    flow.update(1500, &[stream(0, 1500, 0)], ms(10), t0 + ms(20));
+    assert!(flow.is_blocked());
+
+    flow.update(1500, &[stream(0, 1500, 500)], ms(10), t0 + ms(30));
+    assert!(!flow.is_blocked());
+
+    let stats = flow.stats(t0 + ms(40));
+    assert_eq!(stats.connection.blocks, 2);
+    assert_eq!(stats.connection.total, ms(30));
+    assert_eq!(stats.connection.longest, ms(20));
+    assert!(stats.streams.is_empty());
+    assert_eq!(
+        stats.connection.to_string(),
+        "2 blocks, 30ms total, longest 20ms, resumed after 1.5 RTTs on average"
+    );
+}
+
+#[test]
+fn stream_blocked_alone() {
+    let t0 = Instant::now();
+    let mut flow = FlowBlocking::new(10_000, 1000);
+
+    // Stream 4 is held back by the congestion window, not by its limit.
+    flow.update(1500, &[stream(0, 1000, 0), stream(4, 500, 200)], ms(10), t0);
+    assert!(!flow.is_blocked());
+    assert_eq!(flow.blocked_streams().collect::<Vec<_>>(), [0]);
+
+    flow.update(1700, &[stream(4, 700, 0)], ms(10), t0 + ms(20));
+    assert_eq!(flow.blocked_streams().count(), 0);
+
+    let stats = flow.stats(t0 + ms(20));
+    assert_eq!(stats.connection.to_string(), "never blocked");
+    assert_eq!(stats.streams.len(), 1);
+    assert_eq!(stats.streams[&0].total, ms(20));
+    assert_eq!(stats.streams[&0].mean_rtts(), Some(2.0));
+}
+
+#[test]
+fn blocks_end_with_the_connection() {
+    let t0 = Instant::now();
+    let mut flow = FlowBlocking::new(1000, 1000);
+
+    flow.update(1000, &[stream(0, 1000, 0)], ms(10), t0);
+    assert!(flow.is_blocked());
+
+    flow.close(t0 + ms(50));
+    let stats = flow.stats(t0 + ms(100));
+    assert_eq!(stats.connection.total, ms(50));
+    assert_eq!(stats.connection.mean_rtts(), None);
+    assert_eq!(stats.longest_streams()[0].0, 0);
+
+    // The next connection starts with the limits of its handshake.
+    flow.restart(4000, 4000);
+    flow.update(1000, &[stream(0, 1000, 0)], ms(10), t0 + ms(60));
+    assert!(!flow.is_blocked());
+    assert_eq!(flow.stats(t0 + ms(100)).connection.blocks, 1);
+}
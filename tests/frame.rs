@@ -0,0 +1,62 @@
+use quiche_test::shared::frame::{self, FrameDecoder};
+
+#[test]
+fn frame_split_across_three_reads() {
+    let encoded = frame::encode(b"hello multipath");
+    let mut decoder = FrameDecoder::new();
+
+    // The first read ends within the length prefix.
+    decoder.push(&encoded[..2]);
+    assert_eq!(decoder.next_frame(), None);
+
+    decoder.push(&encoded[2..9]);
+    assert_eq!(decoder.next_frame(), None);
+    assert_eq!(decoder.buffered(), 9);
+
+    decoder.push(&encoded[9..]);
+    assert_eq!(decoder.next_frame().unwrap(), b"hello multipath");
+    assert_eq!(decoder.next_frame(), None);
+    assert_eq!(decoder.buffered(), 0);
+}
+
+#[test]
+fn several_frames_in_one_read() {
+    let mut data = frame::encode(b"a");
+    data.extend_from_slice(&frame::encode(b""));
+    data.extend_from_slice(&frame::encode(b"bc"));
+    data.extend_from_slice(&frame::encode(b"def")[..3]);
+
+    let mut decoder = FrameDecoder::new();
+    decoder.push(&data);
+
+    assert_eq!(decoder.next_frame().unwrap(), b"a");
+    assert_eq!(decoder.next_frame().unwrap(), b"");
+    assert_eq!(decoder.next_frame().unwrap(), b"bc");
+    assert_eq!(decoder.next_frame(), None);
+    assert_eq!(decoder.buffered(), 3);
+}
+
+#[test]
+fn interleaved_echoes() {
+    let streams = [
+        [frame::encode(b"one"), frame::encode(b"three")].concat(),
+        [frame::encode(b"two"), frame::encode(b"four")].concat(),
+    ];
+
+    let mut decoders = [FrameDecoder::new(), FrameDecoder::new()];
+    let mut frames: [Vec<Vec<u8>>; 2] = Default::default();
+
+    // The echoes of both streams arrive in small interleaved reads.
+    let reads: Vec<Vec<&[u8]>> = streams.iter().map(|s| s.chunks(3).collect()).collect();
+    for i in 0..reads[0].len().max(reads[1].len()) {
+        for s in 0..2 {
+            if let Some(read) = reads[s].get(i) {
+                decoders[s].push(read);
+                frames[s].extend(std::iter::from_fn(|| decoders[s].next_frame()));
+            }
+        }
+    }
+
+    assert_eq!(frames[0], [b"one".to_vec(), b"three".to_vec()]);
+    assert_eq!(frames[1], [b"two".to_vec(), b"four".to_vec()]);
+}
@@ -0,0 +1,30 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::{keepalive::ConnKeepalive, testing::MockConn};
+
+#[test]
+fn ping_once_idle() {
+    let mut conn = MockConn::default();
+    let start = Instant::now();
+    let ms = Duration::from_millis;
+
+    let mut pings = ConnKeepalive::new(ms(100), start);
+    assert_eq!(pings.next_timeout(), start + ms(100));
+
+    // Traffic postpones the PING.
+    assert_eq!(pings.poll(&mut conn, start + ms(50)), None);
+    pings.on_recv(start + ms(80));
+    assert_eq!(pings.poll(&mut conn, start + ms(100)), None);
+
+    assert_eq!(pings.poll(&mut conn, start + ms(180)), Some(ms(100)));
+    assert_eq!(conn.pings, 1);
+
+    // Unanswered, the next one follows an interval later.
+    assert_eq!(pings.poll(&mut conn, start + ms(200)), None);
+    assert_eq!(pings.poll(&mut conn, start + ms(280)), Some(ms(200)));
+    assert_eq!(pings.sent(), 2);
+
+    // Its acknowledgment restarts the interval.
+    pings.on_recv(start + ms(300));
+    assert_eq!(pings.next_timeout(), start + ms(400));
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::liveness::{Liveness, MIN_SUSPECT_TIMEOUT};
+
+use common::path;
+
+mod common;
+
+const RTT: Duration = Duration::from_millis(20);
+
+#[test]
+fn suspected_after_rtts_without_receiving() {
+    let now = Instant::now();
+    let mut liveness = Liveness::default();
+
+    assert!(!liveness.observe(path(0), 1000, 1000, RTT, now));
+    assert_eq!(liveness.suspect_at(), None);
+
+    // The path sends and receives nothing: suspected 4 RTTs later.
+    assert!(!liveness.observe(path(0), 1000, 1200, RTT, now));
+    assert_eq!(liveness.suspect_at(), Some(now + RTT * 4));
+
+    // Further sends do not push the deadline back.
+    let later = now + RTT * 2;
+    assert!(!liveness.observe(path(0), 1000, 1500, RTT, later));
+    assert_eq!(liveness.suspect_at(), Some(now + RTT * 4));
+
+    assert!(liveness.observe(path(0), 1000, 1500, RTT, now + RTT * 4));
+
+    // Suspected once, then again a timeout later.
+    assert_eq!(liveness.suspect_at(), None);
+    assert!(!liveness.observe(path(0), 1000, 1500, RTT, now + RTT * 4));
+    assert!(!liveness.observe(path(0), 1000, 1600, RTT, now + RTT * 5));
+    assert!(liveness.observe(path(0), 1000, 1600, RTT, now + RTT * 9));
+}
+
+#[test]
+fn receiving_keeps_path_alive() {
+    let now = Instant::now();
+    let mut liveness = Liveness::default();
+
+    liveness.observe(path(0), 1000, 1000, RTT, now);
+    liveness.observe(path(0), 1000, 1200, RTT, now);
+
+    // Anything received starts over.
+    assert!(!liveness.observe(path(0), 1100, 1200, RTT, now + RTT * 4));
+    assert_eq!(liveness.suspect_at(), None);
+    assert!(!liveness.observe(path(0), 1100, 1200, RTT, now + RTT * 10));
+}
+
+#[test]
+fn idle_path_not_suspected() {
+    let now = Instant::now();
+    let mut liveness = Liveness::default();
+
+    // Nothing sent, nothing expected.
+    liveness.observe(path(0), 1000, 1000, RTT, now);
+    assert!(!liveness.observe(path(0), 1000, 1000, RTT, now + RTT * 100));
+    assert_eq!(liveness.suspect_at(), None);
+}
+
+#[test]
+fn new_path_starts_over() {
+    let now = Instant::now();
+    let mut liveness = Liveness::default();
+
+    liveness.observe(path(0), 1000, 1000, RTT, now);
+    liveness.observe(path(0), 1000, 1200, RTT, now);
+
+    assert!(!liveness.observe(path(1), 0, 100, RTT, now + RTT * 4));
+    assert_eq!(liveness.suspect_at(), None);
+
+    liveness.reset();
+    assert!(!liveness.observe(path(1), 0, 200, RTT, now + RTT * 4));
+    assert_eq!(liveness.suspect_at(), None);
+}
+
+#[test]
+fn minimum_timeout_on_short_rtt() {
+    let now = Instant::now();
+    let mut liveness = Liveness::default();
+
+    let rtt = Duration::from_millis(1);
+    liveness.observe(path(0), 0, 0, rtt, now);
+    liveness.observe(path(0), 0, 100, rtt, now);
+    assert_eq!(liveness.suspect_at(), Some(now + MIN_SUSPECT_TIMEOUT));
+
+    assert!(!liveness.observe(path(0), 0, 100, rtt, now + rtt * 4));
+    assert!(liveness.observe(path(0), 0, 100, rtt, now + MIN_SUSPECT_TIMEOUT));
+}
@@ -0,0 +1,49 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use quiche_test::shared::log_sample::PacketLog;
+
+/// Logger enabling every level, packet events are only sampled when their
+/// level is enabled.
+struct Enabled;
+
+impl Log for Enabled {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, _: &Record) {}
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Enabled = Enabled;
+
+fn init() {
+    log::set_logger(&LOGGER).ok();
+    log::set_max_level(LevelFilter::Trace);
+}
+
+#[test]
+fn first_and_every_nth_logged() {
+    init();
+
+    let mut log = PacketLog::new(3);
+    let logged: Vec<bool> = (0..7).map(|i| log.sample(Level::Info, i)).collect();
+    assert_eq!(logged, [true, false, false, true, false, false, true]);
+
+    // The last event was logged.
+    assert_eq!(log.end_burst(), None);
+
+    // The next burst starts over, its last event skipped after another one.
+    let logged: Vec<bool> = (10..13).map(|i| log.sample(Level::Info, i)).collect();
+    assert_eq!(logged, [true, false, false]);
+    assert_eq!(log.end_burst(), Some((12, 1)));
+}
+
+#[test]
+fn unsampled_logs_everything() {
+    init();
+
+    let mut log = PacketLog::new(1);
+    assert!((0..100).all(|i| log.sample(Level::Info, i)));
+    assert_eq!(log.end_burst(), None);
+}
@@ -0,0 +1,65 @@
+use std::time::Instant;
+
+use quiche_test::shared::loop_timing::{LoopTiming, Phase, LOG_INTERVAL};
+
+use common::ms;
+
+mod common;
+
+#[test]
+fn phases_of_every_connection_add_up() {
+    let t0 = Instant::now();
+    let mut timing = LoopTiming::new(t0);
+
+    // Two connections in one wakeup, then a wakeup with a slow write.
+    timing.begin(t0);
+    timing.lap(Phase::Read, t0 + ms(1));
+    timing.lap(Phase::Read, t0 + ms(2));
+    timing.lap(Phase::PathEvents, t0 + ms(3));
+    timing.lap(Phase::Schedule, t0 + ms(5));
+    timing.lap(Phase::Write, t0 + ms(6));
+    timing.lap(Phase::Read, t0 + ms(7));
+    timing.lap(Phase::Write, t0 + ms(8));
+    assert_eq!(timing.end(t0 + ms(8)), None);
+
+    // The wait for events is not timed.
+    timing.begin(t0 + ms(100));
+    timing.lap(Phase::Read, t0 + ms(100));
+    timing.lap(Phase::Write, t0 + ms(110));
+    assert_eq!(timing.end(t0 + ms(110)), None);
+
+    let stats = timing.stats();
+    assert_eq!(stats.wakeups(), 2);
+    assert_eq!(stats.phase(Phase::Read).total, ms(3));
+    assert_eq!(stats.phase(Phase::Read).max, ms(3));
+    assert_eq!(stats.phase(Phase::Schedule).mean(), ms(1));
+    assert_eq!(stats.phase(Phase::Write).mean(), ms(6));
+    assert_eq!(stats.phase(Phase::Write).max, ms(10));
+    assert_eq!(
+        stats.to_string(),
+        "2 wakeups, read mean 1.5ms max 3ms, schedule mean 1ms max 2ms, \
+         path events mean 500µs max 1ms, write mean 6ms max 10ms"
+    );
+}
+
+#[test]
+fn aggregates_logged_per_interval() {
+    let t0 = Instant::now();
+    let mut timing = LoopTiming::new(t0);
+
+    timing.begin(t0);
+    timing.lap(Phase::Read, t0 + ms(4));
+    assert_eq!(timing.end(t0 + ms(4)), None);
+
+    let end = t0 + LOG_INTERVAL;
+    timing.begin(end);
+    timing.lap(Phase::Read, end + ms(2));
+    let window = timing.end(end + ms(2)).unwrap();
+    assert_eq!(window.wakeups(), 2);
+    assert_eq!(window.phase(Phase::Read).mean(), ms(3));
+
+    // The next interval starts afresh, the totals keep every wakeup.
+    timing.begin(end + ms(10));
+    assert_eq!(timing.end(end + ms(10)), None);
+    assert_eq!(timing.stats().wakeups(), 3);
+}
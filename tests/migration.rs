@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::migration::{MigrationStats, Migrations};
+
+use common::path;
+
+mod common;
+
+#[test]
+fn migration_completes_with_bytes_received_on_its_path() {
+    let now = Instant::now();
+    let mut migrations = Migrations::default();
+
+    migrations.on_migrated(path(1), 100, now);
+    assert_eq!(migrations.pending(), Some(path(1)));
+
+    // Nothing new received on the path yet.
+    migrations.on_recv(100, now + Duration::from_millis(10));
+    assert_eq!(migrations.pending(), Some(path(1)));
+
+    migrations.on_recv(150, now + Duration::from_millis(20));
+    assert_eq!(migrations.pending(), None);
+
+    let stats = migrations.stats();
+    assert_eq!(stats.succeeded, 1);
+    assert_eq!(stats.mean_time(), Some(Duration::from_millis(20)));
+}
+
+#[test]
+fn refused_and_lost_migrations_fail() {
+    let now = Instant::now();
+    let mut migrations = Migrations::default();
+
+    // Refused after being announced, then without a prior announcement.
+    migrations.on_migrated(path(1), 0, now);
+    migrations.on_refused(path(1));
+    migrations.on_refused(path(2));
+
+    // The path of the pending migration fails validation, another one
+    // closing does not matter.
+    migrations.on_migrated(path(3), 0, now);
+    migrations.on_path_lost(path(1));
+    migrations.on_path_lost(path(3));
+
+    // Superseded before completing.
+    migrations.on_migrated(path(1), 0, now);
+    migrations.on_migrated(path(2), 0, now);
+
+    let stats = migrations.stats();
+    assert_eq!(
+        stats,
+        MigrationStats {
+            attempted: 5,
+            succeeded: 0,
+            failed: 3,
+            completion_time: Duration::ZERO,
+        }
+    );
+    assert_eq!(stats.unconfirmed(), 2);
+    assert_eq!(stats.mean_time(), None);
+}
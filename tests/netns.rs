@@ -0,0 +1,106 @@
+//! Multipath runs over distinct interfaces, each path through its own veth
+//! pair. Needs the `netns-tests` feature and root (or `CAP_NET_ADMIN` and
+//! `CAP_SYS_ADMIN`), the tests being skipped otherwise:
+//!
+//! > sudo -E cargo test --features netns-tests --test netns
+#![cfg(feature = "netns-tests")]
+
+use std::time::Duration;
+
+use quiche_test::{
+    client::{Completed, Workload},
+    config::{ClientOptions, ServerOptions, Target},
+    shared::{
+        netns::{self, Lab, Netem},
+        testing::UdpRun,
+    },
+};
+
+use common::{ms, options};
+
+mod common;
+
+/// Lab with one veth pair per entry of `links`, if the tests may create
+/// one.
+fn lab(links: &[Netem]) -> Option<Lab> {
+    if !netns::privileged() {
+        eprintln!("skipped: network namespaces need root");
+        return None;
+    }
+
+    Some(Lab::new(links).unwrap())
+}
+
+/// Sends the messages of `opts` over the paths of `lab`, the client sockets
+/// bound to their interface with `by_device`, and returns the completed
+/// messages by index.
+fn run(lab: &Lab, opts: &ClientOptions, by_device: bool) -> Vec<Completed> {
+    let mut workload = Workload::from_options(opts, 1).unwrap();
+    workload.fit_paths(lab.paths());
+
+    let server_config = ServerOptions::default()
+        .quiche_config(false, |_| ())
+        .unwrap();
+    let mut client_config = opts
+        .quiche_config(&Target::default(), false, |_| ())
+        .unwrap();
+
+    let mut run = UdpRun::with_sockets(
+        server_config,
+        &mut client_config,
+        &workload,
+        lab.bind_server().unwrap(),
+        lab.bind_client(by_device).unwrap(),
+    )
+    .unwrap();
+
+    let mut completed = run
+        .run(opts.messages.len(), Duration::from_secs(30))
+        .unwrap();
+    assert_eq!(completed.len(), opts.messages.len());
+
+    completed.sort_by_key(|c| c.record.index);
+    completed
+}
+
+#[test]
+fn latency_follows_interface_delay() {
+    let Some(lab) = lab(&[Netem::new(ms(5)), Netem::new(ms(40))]) else {
+        return;
+    };
+
+    let completed = run(&lab, &options(&["near", "far"]), false);
+
+    // Each end delays what it sends, so the RTT is twice the delay.
+    assert!(completed[1].record.latency >= ms(80));
+    assert!(completed[0].record.latency < completed[1].record.latency);
+}
+
+#[test]
+fn paths_bound_to_interfaces() {
+    let Some(lab) = lab(&[Netem::new(ms(5)), Netem::new(ms(40))]) else {
+        return;
+    };
+
+    let completed = run(&lab, &options(&["near", "far"]), true);
+
+    // Each socket sent through the interface it is bound to.
+    assert!(completed[1].record.latency >= ms(80));
+    assert!(completed[0].record.latency < completed[1].record.latency);
+}
+
+#[test]
+fn lossy_interface_still_delivers() {
+    let Some(lab) = lab(&[Netem::new(ms(5)), Netem::new(ms(10)).loss(0.1)]) else {
+        return;
+    };
+
+    let messages: Vec<String> = (0..20).map(|i| format!("message {}", i)).collect();
+    let messages: Vec<&str> = messages.iter().map(|m| m.as_str()).collect();
+
+    let completed = run(&lab, &options(&messages), false);
+
+    for c in &completed {
+        assert_eq!(c.echo, messages[c.record.index].as_bytes());
+    }
+}
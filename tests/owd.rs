@@ -0,0 +1,51 @@
+use std::time::Instant;
+
+use quiche_test::shared::owd::{self, OwdStats, WallClock};
+
+use common::path;
+
+mod common;
+
+#[test]
+fn stamps_round_trip() {
+    let stamped = owd::stamp(1_700_000_000_123_456, b"hello");
+    assert_eq!(stamped.len(), owd::STAMP_LEN + 5);
+
+    let (micros, rest) = owd::unstamp(&stamped).unwrap();
+    assert_eq!((micros, rest), (1_700_000_000_123_456, &b"hello"[..]));
+
+    assert!(owd::unstamp(b"short").is_none());
+}
+
+#[test]
+fn skewed_clock() {
+    let now = Instant::now();
+    let clock = WallClock::new(now);
+
+    assert_eq!(clock.skewed(5000).micros(now), clock.micros(now) + 5000);
+    assert_eq!(clock.skewed(-5000).micros(now), clock.micros(now) - 5000);
+}
+
+#[test]
+fn delays_per_path() {
+    let mut stats = OwdStats::default();
+
+    // The clock of the server is 2ms ahead.
+    stats.record(path(1), 1_000, 13_000, 22_000);
+    stats.record(path(1), 2_000, 14_000, 23_000);
+    stats.record(path(2), 1_000, 33_000, 62_000);
+
+    let paths = stats.paths();
+    assert_eq!(paths.len(), 2);
+
+    assert_eq!(paths[0].path, path(1));
+    assert_eq!(paths[0].samples, 2);
+    assert_eq!((paths[0].forward, paths[0].reverse), (12_000.0, 9_000.0));
+    assert_eq!(paths[0].asymmetry(), 3_000.0);
+
+    assert_eq!((paths[1].forward, paths[1].reverse), (32_000.0, 29_000.0));
+
+    // The offset between the clocks cancels out across paths.
+    assert_eq!(owd::spread(&paths), Some((20_000.0, 20_000.0)));
+    assert_eq!(owd::spread(&paths[..1]), None);
+}
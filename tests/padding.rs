@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use quiche_test::{
+    client::Workload,
+    config::ClientOptions,
+    shared::{
+        padding::{pad, unpad, PadError, LENGTH_PREFIX_LEN},
+        stream_writer::{Progress, StreamWriter},
+        testing::{MockConn, UdpRun},
+    },
+};
+
+use common::{config, temp_file};
+
+mod common;
+
+#[test]
+fn round_trip() {
+    let frame = pad(b"hello", 32).unwrap();
+
+    assert_eq!(frame.len(), 32);
+    assert_eq!(unpad(&frame), Some(&b"hello"[..]));
+
+    let frame = pad(b"", LENGTH_PREFIX_LEN).unwrap();
+    assert_eq!(unpad(&frame), Some(&b""[..]));
+}
+
+#[test]
+fn too_long() {
+    assert_eq!(pad(b"hello", 8), Err(PadError { len: 5, size: 8 }));
+
+    assert!(pad(b"hello", 9).is_ok());
+}
+
+#[test]
+fn truncated_frame() {
+    let frame = pad(b"hello", 16).unwrap();
+
+    assert_eq!(unpad(&frame[..6]), None);
+    assert_eq!(unpad(&frame[..2]), None);
+}
+
+#[test]
+fn uniform_stream_sizes() {
+    let messages: [&[u8]; 4] = [b"", b"a", b"hello world", &[b'x'; 500]];
+    let frames: Vec<Vec<u8>> = messages.iter().map(|m| pad(m, 512).unwrap()).collect();
+
+    let mut conn = MockConn::default();
+    let mut writers: Vec<_> = frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let stream_id = i as u64 * 4;
+            conn.stream(stream_id).capacity = Some(100);
+            StreamWriter::new(stream_id, &frame[..])
+        })
+        .collect();
+
+    for writer in writers.iter_mut() {
+        while writer.poll_write(&mut conn).unwrap() != Progress::Finished {
+            conn.stream(writer.stream_id()).capacity = Some(100);
+        }
+    }
+
+    let sizes: Vec<usize> = (0..4).map(|i| conn.stream(i * 4).data.len()).collect();
+    assert_eq!(sizes, [512; 4]);
+
+    for (i, message) in messages.iter().enumerate() {
+        assert_eq!(unpad(&conn.stream(i as u64 * 4).data), Some(*message));
+    }
+}
+
+/// Sizes of the datagrams the client sends while echoing `messages` padded
+/// to `pad_to` bytes over the loopback, on a single path.
+fn datagram_sizes(messages: &[&[u8]], pad_to: usize, name: &str) -> Vec<usize> {
+    let opts = ClientOptions {
+        messages: messages.iter().map(|m| m.to_vec()).collect(),
+        pad_to: Some(pad_to),
+        ..Default::default()
+    };
+    let mut workload = Workload::from_options(&opts, 1).unwrap();
+    workload.fit_paths(1);
+
+    let port_file = temp_file(name);
+    let mut run = UdpRun::start(config(true), &mut config(false), &workload, &port_file).unwrap();
+    let completed = run.run(messages.len(), Duration::from_secs(30)).unwrap();
+    std::fs::remove_file(port_file).unwrap();
+
+    assert_eq!(completed.len(), messages.len());
+    for c in &completed {
+        assert_eq!(c.echo, messages[c.record.index]);
+    }
+
+    run.datagram_sizes().to_vec()
+}
+
+#[test]
+fn uniform_datagram_sizes() {
+    const PAD_TO: usize = 1000;
+
+    let short = datagram_sizes(&[b"a", b"b", b"c"], PAD_TO, "pad-short");
+    let long = datagram_sizes(&[b"", b"hello world", &[b'x'; 500]], PAD_TO, "pad-long");
+
+    // Leaving out the datagrams only carrying acknowledgments, whose number
+    // depends on timing, the messages take the same bytes on the wire
+    // whatever their length.
+    let data = |sizes: &[usize]| -> usize { sizes.iter().filter(|s| **s > 100).sum() };
+    let (short, long) = (data(&short), data(&long));
+
+    assert!(short >= 3 * PAD_TO, "{}", short);
+    assert!(short.abs_diff(long) <= 64, "{} against {}", short, long);
+}
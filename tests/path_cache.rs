@@ -0,0 +1,102 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use quiche_test::path_cache::PathCache;
+
+use common::{addr, temp_file};
+
+mod common;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn missing_file_is_empty() {
+    let cache = PathCache::load(temp_file("missing"), HOUR, at(10_000)).unwrap();
+
+    assert!(cache.entries().is_empty());
+}
+
+#[test]
+fn expired_and_invalid_entries_skipped() {
+    let file = temp_file("expired");
+    std::fs::write(
+        &file,
+        "# sni server local peer rtt_us validated_at\n\
+         a 127.0.0.1:8000 127.0.0.1:9000 127.0.0.1:8000 20000 9000\n\
+         a 127.0.0.1:8000 127.0.0.1:9001 127.0.0.1:8001 5000 5000\n\
+         a 127.0.0.1:8000 127.0.0.1:9002 not-an-address 5000 9000\n\
+         a 127.0.0.1:8000 127.0.0.1:9002 127.0.0.1:8002 5000\n",
+    )
+    .unwrap();
+
+    let cache = PathCache::load(&file, HOUR, at(10_000)).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    let rtts = cache.rtts("a", addr(8000));
+    assert_eq!(rtts.len(), 1);
+    assert_eq!(rtts[&(addr(9000), addr(8000))], Duration::from_millis(20));
+}
+
+#[test]
+fn keyed_by_server_name_and_address() {
+    let mut cache = PathCache::new(HOUR);
+    let path = (addr(9001), addr(8001));
+
+    cache.record("a", addr(8000), path, Duration::from_millis(10), at(0));
+    cache.record("b", addr(8000), path, Duration::from_millis(20), at(0));
+    cache.record("a", addr(7000), path, Duration::from_millis(30), at(0));
+
+    assert_eq!(
+        cache.rtts("a", addr(8000))[&path],
+        Duration::from_millis(10)
+    );
+    assert_eq!(
+        cache.rtts("b", addr(8000))[&path],
+        Duration::from_millis(20)
+    );
+    assert_eq!(
+        cache.rtts("a", addr(7000))[&path],
+        Duration::from_millis(30)
+    );
+    assert!(cache.rtts("b", addr(7000)).is_empty());
+}
+
+#[test]
+fn save_and_load() {
+    let file = temp_file("saved");
+    let mut cache = PathCache::new(HOUR);
+
+    cache.record(
+        "a",
+        addr(8000),
+        (addr(9000), addr(8000)),
+        Duration::from_millis(10),
+        at(10_000),
+    );
+    cache.record(
+        "a",
+        addr(8000),
+        (addr(9001), addr(8001)),
+        Duration::from_millis(20),
+        at(10_000),
+    );
+
+    // The new RTT replaces the previous one.
+    cache.record(
+        "a",
+        addr(8000),
+        (addr(9000), addr(8000)),
+        Duration::from_millis(30),
+        at(10_000),
+    );
+
+    cache.save(&file).unwrap();
+    let loaded = PathCache::load(&file, HOUR, at(10_000)).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    assert_eq!(loaded.entries(), cache.entries());
+    assert_eq!(loaded.entries().len(), 2);
+}
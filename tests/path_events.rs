@@ -0,0 +1,159 @@
+use quiche::PathEvent;
+use quiche_test::{
+    client::{handle_path_event, ClientState, PathAction},
+    events::Event,
+};
+
+use common::{addr, path};
+
+mod common;
+
+/// Client with three paths, the last one being the standby path if
+/// `standby`.
+fn state(standby: bool) -> ClientState {
+    let local_addrs = (0..3).map(|i| addr(9000 + i)).collect();
+    let peer_addrs = (0..3).map(|i| addr(8000 + i)).collect();
+
+    ClientState::new(local_addrs, peer_addrs, standby.then_some(2))
+}
+
+/// Handles the events of `script` in order and returns the actions and the
+/// names of the connection events they produced.
+fn replay(state: &mut ClientState, script: Vec<PathEvent>) -> (Vec<PathAction>, Vec<&'static str>) {
+    let mut actions = vec![];
+    let mut events: Vec<Event> = vec![];
+
+    for event in script {
+        actions.extend(handle_path_event(state, event, &mut events));
+    }
+
+    (actions, events.iter().map(|e| e.name()).collect())
+}
+
+#[test]
+fn validated_path_becomes_active() {
+    let mut state = state(false);
+    let (local, peer) = path(1);
+
+    let (actions, events) = replay(&mut state, vec![PathEvent::Validated(local, peer)]);
+
+    assert_eq!(actions, [PathAction::Migrate(local, peer)]);
+    assert_eq!(events, ["path_validated", "migrated"]);
+    assert_eq!(state.active_path, path(1));
+    assert!(state.validated.contains(&path(1)));
+}
+
+#[test]
+fn unknown_path_not_migrated_to() {
+    let mut state = state(false);
+
+    let (actions, _) = replay(
+        &mut state,
+        vec![PathEvent::Validated(addr(9000), addr(8001))],
+    );
+
+    assert!(actions.is_empty());
+    assert_eq!(state.active_path, path(0));
+}
+
+#[test]
+fn standby_path_not_migrated_to() {
+    let mut state = state(true);
+    let (local, peer) = path(2);
+
+    let (actions, events) = replay(&mut state, vec![PathEvent::Validated(local, peer)]);
+
+    assert!(actions.is_empty());
+    assert_eq!(events, ["path_validated"]);
+    assert_eq!(state.active_path, path(0));
+}
+
+#[test]
+fn closed_active_path_fails_over() {
+    let mut state = state(true);
+    let (local, peer) = path(1);
+    let (standby_local, standby_peer) = path(2);
+
+    let (actions, events) = replay(
+        &mut state,
+        vec![
+            PathEvent::Validated(local, peer),
+            PathEvent::Validated(standby_local, standby_peer),
+            PathEvent::Closed(local, peer),
+        ],
+    );
+
+    assert_eq!(
+        actions,
+        [PathAction::Migrate(local, peer), PathAction::FailOver]
+    );
+    assert_eq!(
+        events,
+        [
+            "path_validated",
+            "migrated",
+            "path_validated",
+            "path_closed"
+        ]
+    );
+    assert!(!state.validated.contains(&path(1)));
+    assert!(state.validated.contains(&path(2)));
+}
+
+#[test]
+fn closed_active_path_without_standby() {
+    let mut state = state(false);
+    let (local, peer) = path(1);
+
+    let (actions, _) = replay(
+        &mut state,
+        vec![
+            PathEvent::Validated(local, peer),
+            PathEvent::Closed(local, peer),
+        ],
+    );
+
+    // Nowhere to fail over to, the next message migrates again.
+    assert_eq!(actions, [PathAction::Migrate(local, peer)]);
+    assert_eq!(state.active_path, path(1));
+    assert!(state.validated.is_empty());
+}
+
+#[test]
+fn closed_inactive_path_ignored() {
+    let mut state = state(true);
+    let (local, peer) = path(1);
+
+    let (actions, _) = replay(
+        &mut state,
+        vec![
+            PathEvent::Validated(local, peer),
+            PathEvent::Closed(addr(9000), addr(8000)),
+        ],
+    );
+
+    assert_eq!(actions, [PathAction::Migrate(local, peer)]);
+    assert_eq!(state.active_path, path(1));
+}
+
+#[test]
+fn kept_alive_path_probed_again() {
+    let mut state = state(false);
+    let (local, peer) = path(1);
+
+    replay(&mut state, vec![PathEvent::Validated(local, peer)]);
+    state.kept_alive.insert(path(1));
+    state.active_path = path(0);
+
+    let (actions, _) = replay(
+        &mut state,
+        vec![
+            PathEvent::FailedValidation(local, peer),
+            PathEvent::Validated(local, peer),
+        ],
+    );
+
+    // Validated again, but the path stays idle.
+    assert_eq!(actions, [PathAction::Reprobe(local, peer)]);
+    assert_eq!(state.active_path, path(0));
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use quiche_test::shared::path_quality::{self, PathSample, QualityWeights};
+
+use common::path;
+
+mod common;
+
+fn sample(port: u16, rtt_ms: u64, sent: usize, lost: usize, cwnd: usize) -> PathSample {
+    PathSample {
+        path: path(port),
+        rtt: Duration::from_millis(rtt_ms),
+        sent,
+        lost,
+        cwnd,
+    }
+}
+
+#[test]
+fn best_path_first() {
+    let ranked = path_quality::rank(
+        [sample(1, 40, 100, 10, 6000), sample(2, 20, 100, 0, 12000)],
+        &QualityWeights::default(),
+    );
+
+    assert_eq!(ranked[0].path, path(2));
+    assert_eq!(ranked[0].score, 1.0);
+
+    // Twice as slow, 10% lost and half the window.
+    let slow = ranked[1];
+    assert_eq!((slow.rtt, slow.loss, slow.cwnd), (0.5, 0.9, 0.5));
+    assert!((slow.score - (0.4 * 0.5 + 0.4 * 0.9 + 0.2 * 0.5)).abs() < 1e-9);
+}
+
+#[test]
+fn weights_change_the_ranking() {
+    // Path 1 is fast but lossy, path 2 slow but clean.
+    let paths = [sample(1, 10, 100, 50, 12000), sample(2, 50, 100, 0, 12000)];
+
+    let rtt_only: QualityWeights = "rtt=1".parse().unwrap();
+    let ranked = path_quality::rank(paths, &rtt_only);
+    assert_eq!(ranked[0].path, path(1));
+
+    let loss_only: QualityWeights = "loss=1".parse().unwrap();
+    let ranked = path_quality::rank(paths, &loss_only);
+    assert_eq!(ranked[0].path, path(2));
+}
+
+#[test]
+fn parse_weights() {
+    let weights: QualityWeights = "rtt=2,cwnd=0.5".parse().unwrap();
+    assert_eq!((weights.rtt, weights.loss, weights.cwnd), (2.0, 0.0, 0.5));
+
+    assert!("rtt=0,loss=0".parse::<QualityWeights>().is_err());
+    assert!("rtt=-1".parse::<QualityWeights>().is_err());
+    assert!("jitter=1".parse::<QualityWeights>().is_err());
+    assert!("rtt".parse::<QualityWeights>().is_err());
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use quiche_test::shared::path_schedule::{PathCounters, PathSchedule, ScheduleTracker, Window};
+
+fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+}
+
+fn counters(paths: &[(u64, u64)]) -> Vec<PathCounters> {
+    paths
+        .iter()
+        .map(|(sent_bytes, stream_bytes)| PathCounters {
+            sent_bytes: *sent_bytes,
+            stream_bytes: *stream_bytes,
+        })
+        .collect()
+}
+
+#[test]
+fn parse_windows() {
+    let schedule: PathSchedule = "0:0-5s,1:5s-7500ms,0:10-".parse().unwrap();
+
+    assert_eq!(
+        schedule.windows(),
+        [
+            Window {
+                path: 0,
+                start: secs(0),
+                end: Some(secs(5)),
+            },
+            Window {
+                path: 1,
+                start: secs(5),
+                end: Some(Duration::from_millis(7500)),
+            },
+            Window {
+                path: 0,
+                start: secs(10),
+                end: None,
+            },
+        ]
+    );
+    assert_eq!(schedule.max_path(), 1);
+
+    assert!("0:5s-1s".parse::<PathSchedule>().is_err());
+    assert!("0:0-5s,1:4s-6s".parse::<PathSchedule>().is_err());
+    assert!("0:0-,1:4s-6s".parse::<PathSchedule>().is_err());
+    assert!("0:0".parse::<PathSchedule>().is_err());
+}
+
+#[test]
+fn current_and_upcoming_windows() {
+    let schedule: PathSchedule = "0:0-5s,1:6s-10s,2:10s-".parse().unwrap();
+    let lead = Duration::from_millis(500);
+
+    assert_eq!(schedule.current(secs(0)), Some(0));
+    assert_eq!(schedule.current(Duration::from_millis(5500)), None);
+    assert_eq!(schedule.current(secs(10)), Some(2));
+    assert_eq!(schedule.current(secs(100)), Some(2));
+
+    assert_eq!(schedule.upcoming(secs(5), lead), None);
+    assert_eq!(
+        schedule.upcoming(Duration::from_millis(5500), lead),
+        Some(1)
+    );
+    assert_eq!(
+        schedule.upcoming(Duration::from_millis(9600), lead),
+        Some(2)
+    );
+    assert_eq!(schedule.upcoming(secs(10), lead), None);
+}
+
+#[test]
+fn tracker_accounts_each_window() {
+    let schedule: PathSchedule = "0:0-1s,1:1s-".parse().unwrap();
+    let mut tracker = ScheduleTracker::new(schedule);
+
+    tracker.on_step(
+        secs(0),
+        &counters(&[(0, 0), (0, 0)]),
+        Some(0),
+        &[true, false],
+    );
+    tracker.on_step(
+        Duration::from_millis(900),
+        &counters(&[(1000, 800), (100, 0)]),
+        Some(0),
+        &[true, true],
+    );
+    assert_eq!(tracker.current_path(Duration::from_millis(900)), Some(0));
+
+    // The path of the second window becomes active 50ms into it.
+    tracker.on_step(
+        secs(1),
+        &counters(&[(1200, 900), (100, 0)]),
+        Some(0),
+        &[true, true],
+    );
+    tracker.on_step(
+        Duration::from_millis(1050),
+        &counters(&[(1300, 950), (400, 200)]),
+        Some(1),
+        &[true, true],
+    );
+    tracker.close(&counters(&[(1400, 1000), (2100, 1800)]));
+
+    let usage = tracker.usage();
+    assert_eq!(usage.len(), 2);
+
+    assert_eq!((usage[0].sent_bytes, usage[0].sent_elsewhere), (1200, 100));
+    assert_eq!((usage[0].stream_bytes, usage[0].stream_elsewhere), (900, 0));
+    assert!(usage[0].ready);
+    assert_eq!(usage[0].switch_delay, Some(secs(0)));
+
+    assert_eq!((usage[1].sent_bytes, usage[1].sent_elsewhere), (2000, 200));
+    assert_eq!(
+        (usage[1].stream_bytes, usage[1].stream_elsewhere),
+        (1800, 100)
+    );
+    assert!(usage[1].ready);
+    assert_eq!(usage[1].switch_delay, Some(Duration::from_millis(50)));
+}
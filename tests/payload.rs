@@ -0,0 +1,36 @@
+use quiche_test::shared::payload::{decode_base64, decode_hex, hex_dump, printable, HEX_DUMP_LEN};
+
+#[test]
+fn hex_decoded() {
+    assert_eq!(decode_hex("00ffA5").unwrap(), [0x00, 0xff, 0xa5]);
+    assert_eq!(decode_hex("").unwrap(), Vec::<u8>::new());
+    assert!(decode_hex("abc").is_err());
+    assert!(decode_hex("0g").is_err());
+    assert!(decode_hex("+1").is_err());
+}
+
+#[test]
+fn base64_decoded() {
+    assert_eq!(decode_base64("AP8=").unwrap(), [0x00, 0xff]);
+    assert_eq!(decode_base64("AP8").unwrap(), [0x00, 0xff]);
+    assert_eq!(decode_base64("aGVsbG8K").unwrap(), b"hello\n");
+    assert_eq!(decode_base64("////").unwrap(), [0xff; 3]);
+    assert!(decode_base64("A").is_err());
+    assert!(decode_base64("AP8===").is_err());
+    assert!(decode_base64("AP-8").is_err());
+}
+
+#[test]
+fn binary_payloads_hex_dumped() {
+    assert_eq!(printable(b"hello world"), Some("hello world"));
+    assert_eq!(printable(b"a\0b"), None);
+    assert_eq!(printable(b"line\n"), None);
+    assert_eq!(printable(&[0xff, 0xfe]), None);
+
+    assert_eq!(hex_dump(&[0x00, 0xff]), "2 bytes [00ff]");
+
+    let long = hex_dump(&[0xff; 1 << 20]);
+    assert!(long.starts_with("1048576 bytes [ffff"));
+    assert!(long.ends_with("...]"));
+    assert_eq!(long.matches('f').count(), 2 * HEX_DUMP_LEN);
+}
@@ -0,0 +1,51 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use quiche_test::shared::poll_events;
+
+static SIGNALED: AtomicBool = AtomicBool::new(false);
+
+#[test]
+fn signal_during_poll() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        SIGNALED.store(true, Ordering::Relaxed);
+    }
+
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+    // SAFETY: the handler only stores to an atomic, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler);
+    }
+
+    let mut poll = mio::Poll::new().unwrap();
+    let mut events = mio::Events::with_capacity(8);
+
+    // epoll_wait fails with EINTR when a handled signal reaches the thread
+    // waiting, whatever SA_RESTART.
+    // SAFETY: pthread_self cannot fail.
+    let waiting = unsafe { libc::pthread_self() };
+    let signaler = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+
+        // SAFETY: the waiting thread outlives this one, it joins it.
+        unsafe { libc::pthread_kill(waiting, libc::SIGUSR1) };
+    });
+
+    let start = Instant::now();
+    let timeout = Duration::from_millis(200);
+
+    poll_events(&mut poll, &mut events, Some(timeout)).unwrap();
+
+    signaler.join().unwrap();
+
+    assert!(SIGNALED.load(Ordering::Relaxed));
+    assert!(events.is_empty());
+
+    // The wait went on for the time left after the signal.
+    assert!(start.elapsed() >= timeout);
+}
@@ -0,0 +1,94 @@
+use std::{
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use quiche_test::{
+    client::Workload,
+    config::ClientOptions,
+    shared::{ports, testing::UdpRun},
+};
+
+use common::{addr, config, temp_file};
+
+mod common;
+
+#[test]
+fn consecutive_or_os_picked_ports() {
+    assert_eq!(ports::socket_addr(addr(8000), 3), addr(8003));
+    assert_eq!(ports::socket_addr(addr(0), 3), addr(0));
+
+    let sockets = ports::bind_sockets(addr(0), 3).unwrap();
+    let mut bound: Vec<u16> = sockets
+        .iter()
+        .map(|s| s.local_addr().unwrap().port())
+        .collect();
+    assert!(bound.iter().all(|p| *p != 0));
+
+    bound.sort();
+    bound.dedup();
+    assert_eq!(bound.len(), 3);
+}
+
+#[test]
+fn port_file_round_trip() {
+    let path = temp_file("ports");
+    let addrs = vec![addr(40000), addr(40007)];
+
+    ports::write_port_file(&path, &addrs).unwrap();
+    assert_eq!(ports::read_port_file(&path).unwrap(), addrs);
+
+    std::fs::write(&path, "127.0.0.1:1\nnot an address\n").unwrap();
+    assert!(ports::read_port_file(&path).is_err());
+
+    std::fs::write(&path, "").unwrap();
+    assert!(ports::read_port_file(&path).is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn parallel_runs_do_not_collide() {
+    let runs: Vec<_> = (0..2)
+        .map(|i| {
+            thread::spawn(move || {
+                let opts = ClientOptions {
+                    messages: vec![format!("hello {}", i).into_bytes(), b"world".to_vec()],
+                    ..Default::default()
+                };
+                let workload = Workload::from_options(&opts, i).unwrap();
+                let path = temp_file(&format!("parallel-{}", i));
+
+                let mut run =
+                    UdpRun::start(config(true), &mut config(false), &workload, &path).unwrap();
+                let start = Instant::now();
+                let completed = run.run(2, Duration::from_secs(30)).unwrap();
+
+                let peers = ports::read_port_file(&path).unwrap();
+                std::fs::remove_file(path).unwrap();
+
+                assert_eq!(completed.len(), 2, "run {} after {:?}", i, start.elapsed());
+                for c in &completed {
+                    assert_eq!(c.echo, opts.messages[c.record.index]);
+                }
+
+                (peers, run.client().local_addrs().to_vec())
+            })
+        })
+        .collect();
+
+    let addrs: Vec<(Vec<SocketAddr>, Vec<SocketAddr>)> =
+        runs.into_iter().map(|r| r.join().unwrap()).collect();
+
+    // Every socket got its own port.
+    let mut all: Vec<SocketAddr> = addrs
+        .iter()
+        .flat_map(|(peers, locals)| peers.iter().chain(locals))
+        .copied()
+        .collect();
+    let count = all.len();
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), count);
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::shared::progress::{ProgressMeter, PROGRESS_INTERVAL};
+
+#[test]
+fn at_most_twice_a_second() {
+    let start = Instant::now();
+    let mut meter = ProgressMeter::new(0, 1000, start);
+
+    assert!(meter.update(0, 0, 1, start).is_some());
+    assert!(meter
+        .update(100, 0, 1, start + Duration::from_millis(100))
+        .is_none());
+
+    let progress = meter
+        .update(400, 200, 2, start + PROGRESS_INTERVAL)
+        .unwrap();
+    assert_eq!(
+        (progress.sent, progress.acked, progress.paths),
+        (400, 200, 2)
+    );
+    assert_eq!(progress.rate, 400.0);
+    assert_eq!(progress.eta(), Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn acked_never_goes_back() {
+    let start = Instant::now();
+    let mut meter = ProgressMeter::new(0, 1000, start);
+
+    meter.update(600, 500, 1, start);
+
+    // A lower count, e.g. after a reconnection, does not undo the progress.
+    let progress = meter
+        .update(100, 0, 1, start + Duration::from_secs(1))
+        .unwrap();
+    assert_eq!((progress.sent, progress.acked), (500, 500));
+}
+
+#[test]
+fn last_snapshot_always_given() {
+    let start = Instant::now();
+    let mut meter = ProgressMeter::new(3, 1000, start);
+
+    meter.update(0, 0, 1, start);
+
+    let last = meter
+        .update(1000, 1000, 1, start + Duration::from_millis(10))
+        .unwrap();
+    assert!(last.is_complete());
+    assert_eq!(
+        (last.transfer, last.acked, last.eta()),
+        (3, 1000, Some(Duration::ZERO))
+    );
+
+    assert!(meter
+        .update(1000, 1000, 1, start + Duration::from_secs(5))
+        .is_none());
+}
@@ -0,0 +1,85 @@
+use quiche_test::shared::pto::{PathCounters, PathPtos, PtoCounter};
+
+use common::path;
+
+mod common;
+
+fn counters(port: u16, sent: usize, lost: usize) -> PathCounters {
+    PathCounters {
+        path: path(port),
+        sent,
+        lost,
+    }
+}
+
+#[test]
+fn pto_counted_on_path_sending_probes() {
+    let mut ptos = PtoCounter::default();
+
+    ptos.on_sent([counters(1, 10, 0), counters(2, 10, 0)]);
+
+    // The timer expires: path 1 declares a packet lost, path 2 nothing and
+    // sends probes.
+    ptos.before_timeout([counters(1, 10, 0), counters(2, 10, 0)]);
+    ptos.after_timeout([counters(1, 10, 1), counters(2, 10, 0)]);
+    ptos.on_sent([counters(1, 11, 1), counters(2, 12, 0)]);
+
+    assert_eq!(ptos.ptos(path(1)), 0);
+    assert_eq!(ptos.ptos(path(2)), 1);
+
+    // Steps without a timer expiration count nothing.
+    ptos.on_sent([counters(1, 20, 1), counters(2, 20, 0)]);
+    assert_eq!(ptos.ptos(path(2)), 1);
+
+    // A path that sent nothing after the timer expired fired no PTO.
+    ptos.before_timeout([counters(2, 20, 0)]);
+    ptos.after_timeout([counters(2, 20, 0)]);
+    ptos.on_sent([counters(2, 20, 0)]);
+    assert_eq!(ptos.ptos(path(2)), 1);
+
+    assert_eq!(
+        ptos.paths(),
+        [
+            PathPtos {
+                path: path(1),
+                ptos: 0,
+                lost: 1,
+            },
+            PathPtos {
+                path: path(2),
+                ptos: 1,
+                lost: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn black_hole_across_reconnections() {
+    let mut ptos = PtoCounter::default();
+
+    for sent in [1, 2] {
+        ptos.before_timeout([counters(1, sent, 0)]);
+        ptos.after_timeout([counters(1, sent, 0)]);
+        ptos.on_sent([counters(1, sent + 1, 0)]);
+    }
+
+    // The losses of the new connection add up with those of the previous
+    // one, whose counters started from zero again.
+    ptos.restart();
+    ptos.before_timeout([counters(1, 1, 0)]);
+    ptos.after_timeout([counters(1, 1, 0)]);
+    ptos.on_sent([counters(1, 2, 1)]);
+
+    let paths = ptos.paths();
+    assert_eq!(paths[0].ptos, 3);
+    assert_eq!(paths[0].lost, 1);
+    assert!(paths[0].black_holed());
+
+    let lossy = PathPtos {
+        path: path(1),
+        ptos: 3,
+        lost: 10,
+    };
+    assert!(!lossy.black_holed());
+}
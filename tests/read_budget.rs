@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use quiche_test::shared::{read_budget::ReadBudget, testing::MockConn};
+
+#[test]
+fn five_thousand_streams() {
+    let mut conn = MockConn::default();
+    conn.readable.extend((0..5000).map(|i| i * 4));
+
+    let mut reads = ReadBudget::new(64);
+
+    // Each stream read costs 10µs of virtual time, and a timer is due every
+    // millisecond. It runs between two wakeups, as a driver would.
+    let cost = Duration::from_micros(10);
+    let period = Duration::from_millis(1);
+
+    let mut clock = Duration::ZERO;
+    let mut next_timer = period;
+    let mut max_lateness = Duration::ZERO;
+    let mut read = vec![];
+
+    loop {
+        reads.begin();
+
+        let mut wakeup = 0;
+        while let Some(stream_id) = reads.next(&mut conn) {
+            read.push(stream_id);
+            wakeup += 1;
+            clock += cost;
+        }
+        assert!(wakeup <= 64);
+
+        while next_timer <= clock {
+            max_lateness = max_lateness.max(clock - next_timer);
+            next_timer += period;
+        }
+
+        if !reads.has_backlog() {
+            break;
+        }
+    }
+
+    // Every stream is read once, in order.
+    assert_eq!(read, (0..5000).map(|i| i * 4).collect::<Vec<_>>());
+
+    // Timers wait for one batch of streams at most, not for all of them.
+    assert!(max_lateness <= cost * 64);
+
+    let stats = reads.stats();
+    assert_eq!(stats.wakeups, 79);
+    assert_eq!(stats.streams, 5000);
+    assert_eq!(stats.max_per_wakeup, 64);
+    assert_eq!(stats.budget_reached, 78);
+}
+
+#[test]
+fn deferred_stream_read_next_wakeup() {
+    let mut conn = MockConn::default();
+    conn.readable.extend([0, 4]);
+
+    let mut reads = ReadBudget::new(64);
+    assert!(!reads.begin());
+
+    // Stream 0 cannot be read yet, e.g. its echo is blocked.
+    assert_eq!(reads.next(&mut conn), Some(0));
+    reads.defer(0);
+    assert_eq!(reads.next(&mut conn), Some(4));
+    assert_eq!(reads.next(&mut conn), None);
+    assert!(!reads.has_backlog());
+
+    reads.begin();
+    assert_eq!(reads.next(&mut conn), Some(0));
+    assert_eq!(reads.next(&mut conn), None);
+
+    // Once read, it is not handed out again.
+    reads.begin();
+    assert_eq!(reads.next(&mut conn), None);
+    assert_eq!(reads.stats().wakeups, 2);
+}
@@ -0,0 +1,224 @@
+use std::{net::SocketAddr, time::Duration};
+
+use quiche_test::{
+    events::{Event, JsonEvents, PathSample},
+    report::{
+        CompletionTimes, HandshakeTrials, Outcome, PathDelivery, PathList, Report, TargetReport,
+        Throughput,
+    },
+};
+
+use common::path;
+
+mod common;
+
+#[test]
+fn path_list_after_migration() {
+    let mut paths = PathList::default();
+
+    // Echo started on the old path, then continued on the new one.
+    paths.push(path(0));
+    paths.push(path(0));
+    paths.push(path(1));
+    paths.push(path(1));
+
+    assert_eq!(paths.paths(), [path(0), path(1)]);
+    assert_eq!(
+        paths.to_string(),
+        "(127.0.0.1:9000, 127.0.0.1:8000) -> (127.0.0.1:9001, 127.0.0.1:8001)"
+    );
+}
+
+#[test]
+fn path_list_single() {
+    let mut paths = PathList::default();
+    paths.push(path(2));
+
+    assert_eq!(paths.to_string(), "(127.0.0.1:9002, 127.0.0.1:8002)");
+}
+
+fn target(name: &str, messages: usize, delivered: usize) -> TargetReport {
+    let mut report = TargetReport::new(name, SocketAddr::from(([127, 0, 0, 1], 8000)), messages);
+    report.delivered = delivered;
+    report
+}
+
+#[test]
+fn exit_codes() {
+    let codes: Vec<i32> = [
+        Outcome::Delivered,
+        Outcome::Partial,
+        Outcome::HandshakeFailed,
+        Outcome::PeerError,
+        Outcome::LocalError,
+        Outcome::SendStalled,
+        Outcome::Interrupted,
+    ]
+    .iter()
+    .map(|o| o.exit_code())
+    .collect();
+
+    assert_eq!(codes, [0, 2, 3, 4, 5, 6, 130]);
+}
+
+#[test]
+fn outcome_of_target() {
+    assert_eq!(target("a", 3, 3).outcome(), Outcome::Delivered);
+    assert_eq!(target("a", 3, 3).reason(), "all 3 messages delivered");
+
+    // Messages outstanding without a failure, e.g. a deadline was hit.
+    assert_eq!(target("a", 3, 1).outcome(), Outcome::Partial);
+
+    let mut report = target("a", 3, 0);
+    report.failure = Some(Outcome::PeerError);
+    report.error = Some("closed by peer with error 0x1".to_string());
+    assert_eq!(report.outcome(), Outcome::PeerError);
+    assert_eq!(
+        report.reason(),
+        "0/3 messages delivered, closed by peer with error 0x1"
+    );
+}
+
+#[test]
+fn outcome_of_worst_target() {
+    let mut failed = target("b", 2, 0);
+    failed.failure = Some(Outcome::HandshakeFailed);
+    failed.error = Some("idle timeout".to_string());
+
+    let report = Report {
+        targets: vec![target("a", 2, 2), failed, target("c", 2, 1)],
+        ..Default::default()
+    };
+
+    let (outcome, reason) = report.outcome();
+    assert_eq!(outcome, Outcome::HandshakeFailed);
+    assert_eq!(
+        outcome.banner(&reason),
+        "result: handshake failed (exit 3): b: 0/2 messages delivered, idle timeout"
+    );
+}
+
+#[test]
+fn handshake_percentiles() {
+    let mut trials = HandshakeTrials {
+        times: (1..=10)
+            .rev()
+            .map(|ms| Duration::from_millis(ms * 10))
+            .collect(),
+        failures: vec!["timed out".to_string(); 2],
+    };
+
+    assert_eq!(trials.attempts(), 12);
+    assert!((trials.success_rate() - 10.0 / 12.0).abs() < 1e-9);
+    assert_eq!(trials.percentile(50.0), Some(Duration::from_millis(50)));
+    assert_eq!(trials.percentile(90.0), Some(Duration::from_millis(90)));
+    assert_eq!(trials.percentile(99.0), Some(Duration::from_millis(100)));
+    assert_eq!(trials.outcome(), Outcome::Partial);
+
+    trials.times.clear();
+    assert_eq!(trials.percentile(50.0), None);
+    assert_eq!(trials.outcome(), Outcome::HandshakeFailed);
+}
+
+#[test]
+fn completion_histogram() {
+    let mut times = CompletionTimes::new(4);
+    assert!(times.render().is_empty());
+
+    for ms in [10, 11, 12, 20, 50] {
+        times.record(Duration::from_millis(ms));
+    }
+
+    let ms = Duration::from_millis;
+    assert_eq!(
+        times.buckets(),
+        [(ms(10), 3), (ms(20), 1), (ms(30), 0), (ms(40), 1)]
+    );
+
+    let lines = times.render();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].ends_with(&format!("|{} 3", "#".repeat(40))));
+    assert!(lines[2].ends_with(&format!("|{} 0", " ".repeat(40))));
+
+    // Equal samples fill a single bucket.
+    let mut times = CompletionTimes::new(4);
+    times.record(ms(5));
+    times.record(ms(5));
+    assert_eq!(times.buckets(), [(ms(5), 2)]);
+}
+
+#[test]
+fn throughput_and_goodput() {
+    let t = Throughput {
+        wire_bytes: 3000,
+        app_bytes: 1000,
+        elapsed: Duration::from_millis(500),
+    };
+    assert_eq!(t.throughput(), 6000.0);
+    assert_eq!(t.goodput(), 2000.0);
+
+    let json = JsonEvents::encode(&Event::Throughput(t), 7, None);
+    assert_eq!(
+        json,
+        "{\"time_us\":7,\"event\":\"throughput\",\"wire_bytes\":3000,\"app_bytes\":1000,\
+         \"elapsed_us\":500000,\"throughput_bps\":48000,\"goodput_bps\":16000}"
+    );
+}
+
+#[test]
+fn live_event_schema() {
+    let completed = Event::MessageCompleted {
+        index: 2,
+        stream_id: 8,
+        bytes: 5,
+        latency: Duration::from_micros(1500),
+    };
+    assert_eq!(
+        JsonEvents::encode(&completed, 10, Some("a")),
+        "{\"time_us\":10,\"target\":\"a\",\"event\":\"message_completed\",\"index\":2,\
+         \"stream_id\":8,\"bytes\":5,\"latency_us\":1500}"
+    );
+
+    let (local, peer) = path(0);
+    let sample = PathSample {
+        local,
+        peer,
+        rtt: Duration::from_millis(20),
+        cwnd: 13500,
+        sent: 40,
+        recv: 38,
+        lost: 1,
+        sent_bytes: 42000,
+        recv_bytes: 41000,
+    };
+    assert_eq!(
+        JsonEvents::encode(&Event::PathStats(sample), 20, None),
+        "{\"time_us\":20,\"event\":\"path_stats\",\"local\":\"127.0.0.1:9000\",\
+         \"peer\":\"127.0.0.1:8000\",\"rtt_us\":20000,\"cwnd\":13500,\"sent\":40,\
+         \"recv\":38,\"lost\":1,\"sent_bytes\":42000,\"recv_bytes\":41000}"
+    );
+}
+
+#[test]
+fn path_delivery_ratio() {
+    let delivery = PathDelivery {
+        path: path(0),
+        sent_bytes: 20_000,
+        lost_bytes: 1_000,
+    };
+
+    assert_eq!(delivery.acked_bytes(), 19_000);
+    assert_eq!(delivery.ratio(), 0.95);
+    assert_eq!(
+        delivery.to_string(),
+        "(127.0.0.1:9000, 127.0.0.1:8000): 19000/20000 bytes acked (95.0%), 1000 lost"
+    );
+
+    // A path that sent nothing lost nothing either.
+    let idle = PathDelivery {
+        sent_bytes: 0,
+        lost_bytes: 0,
+        ..delivery
+    };
+    assert_eq!(idle.ratio(), 1.0);
+}
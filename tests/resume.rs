@@ -0,0 +1,73 @@
+use quiche_test::resume::{ResumeState, TargetProgress};
+
+use common::{addr, temp_file};
+
+mod common;
+
+fn progress(name: &str, session: Option<&[u8]>, delivered: &[usize]) -> TargetProgress {
+    TargetProgress {
+        name: name.to_string(),
+        addr: addr(8000),
+        messages: 4,
+        session: session.map(<[u8]>::to_vec),
+        delivered: delivered.to_vec(),
+    }
+}
+
+#[test]
+fn saved_and_loaded() {
+    let file = temp_file("resume");
+
+    let mut state = ResumeState::new();
+    assert!(state.update(progress("a", Some(&[0x00, 0xff, 0x10]), &[0, 3])));
+    assert!(state.update(progress("b", None, &[])));
+    state.save(&file).unwrap();
+
+    let loaded = ResumeState::load(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(loaded.entries(), state.entries());
+
+    let addr = addr(8000);
+    assert_eq!(loaded.get("a", addr, 4).unwrap().delivered, [0, 3]);
+
+    // A run of different messages starts over.
+    assert!(loaded.get("a", addr, 5).is_none());
+    assert!(loaded.get("c", addr, 4).is_none());
+}
+
+#[test]
+fn missing_file_is_new_run() {
+    let file = temp_file("no-resume");
+    assert!(ResumeState::load(file).unwrap().entries().is_empty());
+}
+
+#[test]
+fn invalid_lines_skipped() {
+    let file = temp_file("bad-resume");
+    std::fs::write(
+        &file,
+        "# name addr messages session delivered\n\
+         a 127.0.0.1:8000 4 00ff 0,1\n\
+         b 127.0.0.1:8000 4 0 -\n\
+         c 127.0.0.1:8000 4 - 0,4\n\
+         d 127.0.0.1:8000 4 -\n",
+    )
+    .unwrap();
+
+    let loaded = ResumeState::load(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(
+        loaded.entries(),
+        [progress("a", Some(&[0x00, 0xff]), &[0, 1])]
+    );
+}
+
+#[test]
+fn update_reports_changes() {
+    let mut state = ResumeState::new();
+    assert!(state.update(progress("a", None, &[0])));
+    assert!(!state.update(progress("a", None, &[0])));
+    assert!(state.update(progress("a", Some(b"ticket"), &[0])));
+    assert!(state.update(progress("a", Some(b"ticket"), &[0, 1])));
+    assert_eq!(state.entries().len(), 1);
+}
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use quiche_test::{
+    events::{Event, JsonEvents, PathSample},
+    report::CwndSampler,
+    shared::{pto::PtoCounter, run_clock::RunClock},
+};
+
+use common::{addr, temp_file};
+
+mod common;
+
+#[test]
+fn timestamps_from_the_monotonic_start() {
+    let t0 = Instant::now();
+    let clock = RunClock::with_anchor(t0 + Duration::from_millis(10), 1_000_000);
+
+    let now = t0 + Duration::from_millis(25);
+    assert_eq!(clock.time_us(now), 15_000);
+    assert_eq!(clock.wall_us(now), 1_015_000);
+
+    // Instants before the start, e.g. of an event timed before the clock
+    // was created, are at its start.
+    assert_eq!(clock.time_us(t0), 0);
+    assert_eq!(clock.wall_us(t0), 1_000_000);
+
+    assert_eq!(clock.to_string(), "anchor_us=1000000 at time_us=0");
+}
+
+#[test]
+fn event_and_cwnd_sample_share_the_clock() {
+    let events_path = temp_file("clock-events");
+    let csv_path = temp_file("clock-cwnd");
+    std::fs::remove_file(&events_path).ok();
+
+    let t0 = Instant::now();
+    let clock = RunClock::with_anchor(t0, 1_700_000_000_000_000);
+
+    let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION).unwrap();
+    let scid = quiche::ConnectionId::from_ref(&[0xba; 16]);
+    let conn = quiche::connect(None, &scid, addr(9000), addr(8000), &mut config).unwrap();
+    let stats = conn.path_stats().next().unwrap();
+
+    let sample = PathSample {
+        local: stats.local_addr,
+        peer: stats.peer_addr,
+        rtt: stats.rtt,
+        cwnd: stats.cwnd,
+        sent: stats.sent,
+        recv: stats.recv,
+        lost: stats.lost,
+        sent_bytes: stats.sent_bytes,
+        recv_bytes: stats.recv_bytes,
+    };
+
+    // The same sample, taken at the same instant for both outputs.
+    let now = t0 + Duration::from_micros(1234);
+
+    let mut events = JsonEvents::open(&events_path, clock).unwrap();
+    events.write(&Event::PathStats(sample), now).unwrap();
+    drop(events);
+
+    let mut sampler = CwndSampler::create(&csv_path, clock, Duration::from_millis(100)).unwrap();
+    let ptos = PtoCounter::default();
+    sampler.sample(now, [("a", &conn, &ptos)]).unwrap();
+    drop(sampler);
+
+    let events = std::fs::read_to_string(&events_path).unwrap();
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    std::fs::remove_file(&events_path).ok();
+    std::fs::remove_file(&csv_path).ok();
+
+    let events: Vec<&str> = events.lines().collect();
+    assert_eq!(
+        events[0],
+        "{\"time_us\":0,\"event\":\"run_clock\",\"anchor_us\":1700000000000000}"
+    );
+    assert!(events[1].starts_with("{\"time_us\":1234,\"event\":\"path_stats\""));
+
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "# run clock anchor_us=1700000000000000 at time_us=0"
+    );
+    assert!(rows[1].starts_with("time_us,"));
+    assert!(rows[2].starts_with("1234,a,127.0.0.1:9000,127.0.0.1:8000,"));
+}
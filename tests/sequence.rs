@@ -0,0 +1,34 @@
+use quiche_test::shared::sequence::{self, SequenceCheck};
+
+#[test]
+fn prefix_round_trip() {
+    let data = sequence::prefix(258, b"hello");
+    assert_eq!(data[..8], [0, 0, 0, 0, 0, 0, 1, 2]);
+    assert_eq!(sequence::split(&data), Some((258, &b"hello"[..])));
+
+    assert_eq!(sequence::split(&data[..7]), None);
+}
+
+#[test]
+fn late_echoes_counted() {
+    let mut check = SequenceCheck::default();
+
+    assert_eq!(check.complete(0), None);
+    assert_eq!(check.complete(3), None);
+    assert_eq!(check.complete(1), Some(3));
+    assert_eq!(check.complete(2), Some(3));
+    assert_eq!(check.complete(4), None);
+
+    let stats = check.stats();
+    assert_eq!(stats.checked, 5);
+    assert_eq!(stats.out_of_order, 2);
+    assert_eq!(stats.max_displacement, 2);
+
+    // A new connection numbers its messages from 0 again.
+    check.restart();
+    assert_eq!(check.complete(0), None);
+    assert_eq!(
+        check.stats().to_string(),
+        "6 echoes, 2 out of order (late by up to 2)"
+    );
+}
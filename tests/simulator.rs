@@ -0,0 +1,1860 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    net::SocketAddr,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use quiche::h3::NameValue;
+use quiche_test::{
+    client::{handle_path_event, Client, Completed, Workload},
+    config::{ClientOptions, Confirm, Target},
+    events::{Event, StreamStages},
+    path_cache::PathCache,
+    report::{Comparison, Outcome, PathUsage, TargetReport},
+    shared::{
+        apps::{App, Handler},
+        control::{Completion, RunId},
+        impair::ImpairSpec,
+        owd,
+        shaper::EchoShaper,
+        testing::{EchoServer, Link, Simulator},
+        transform::Transform,
+        GREASE_VERSION,
+    },
+};
+
+use common::{addr, config, config_with_version, ms, options, temp_file};
+
+mod common;
+
+/// Connects a client sending the messages of `opts` to an echo server over
+/// `links`, the server being set up by `server`.
+fn simulator<F>(opts: &ClientOptions, links: Vec<Link>, seed: u64, server: F) -> Simulator
+where
+    F: FnOnce(EchoServer) -> EchoServer,
+{
+    simulator_with_config(opts, links, seed, config(false), server)
+}
+
+/// Like `simulator`, the client connecting with `client_config`.
+fn simulator_with_config<F>(
+    opts: &ClientOptions,
+    links: Vec<Link>,
+    seed: u64,
+    client_config: quiche::Config,
+    server: F,
+) -> Simulator
+where
+    F: FnOnce(EchoServer) -> EchoServer,
+{
+    let workload = Workload::from_options(opts, seed).unwrap();
+    simulate_workload(&workload, links, seed, client_config, server)
+}
+
+/// Like `simulator_with_config`, the client running `workload`.
+fn simulate_workload<F>(
+    workload: &Workload,
+    links: Vec<Link>,
+    seed: u64,
+    client_config: quiche::Config,
+    server: F,
+) -> Simulator
+where
+    F: FnOnce(EchoServer) -> EchoServer,
+{
+    simulate_against(workload, links, seed, client_config, config(true), server)
+}
+
+/// Like `simulate_workload`, the server accepting with `server_config`.
+fn simulate_against<F>(
+    workload: &Workload,
+    links: Vec<Link>,
+    seed: u64,
+    mut client_config: quiche::Config,
+    server_config: quiche::Config,
+    server: F,
+) -> Simulator
+where
+    F: FnOnce(EchoServer) -> EchoServer,
+{
+    let local_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+        .map(|i| addr(9000 + i))
+        .collect();
+    let peer_addrs: Vec<SocketAddr> = (0..workload.path_count() as u16)
+        .map(|i| addr(8000 + i))
+        .collect();
+
+    let scid = quiche::ConnectionId::from_ref(&[0xba; quiche::MAX_CONN_ID_LEN]);
+    let conn = quiche::connect(
+        Some("localhost"),
+        &scid,
+        local_addrs[0],
+        peer_addrs[0],
+        &mut client_config,
+    )
+    .unwrap();
+
+    let now = Instant::now();
+    let report = TargetReport::new("sim", peer_addrs[0], workload.messages.len());
+    let client = Client::new(conn, workload, local_addrs, peer_addrs.clone(), report, now);
+
+    let server = server(EchoServer::new(server_config, peer_addrs));
+
+    Simulator::new(client, server, links, seed, now).reconnect_with(config(false))
+}
+
+/// Runs the messages of `opts` over `links` and returns the latency of each
+/// message, by index.
+fn simulate(opts: &ClientOptions, links: Vec<Link>, seed: u64) -> Vec<Duration> {
+    let mut sim = simulator(opts, links, seed, |s| s);
+    let mut completed: Vec<Completed> = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), opts.messages.len());
+
+    completed.sort_by_key(|c| c.record.index);
+    completed.iter().map(|c| c.record.latency).collect()
+}
+
+#[test]
+fn latency_follows_path_rtt() {
+    let delays = [ms(5), ms(10), ms(20), ms(40)];
+    let links = delays.iter().map(|d| Link::new(*d)).collect();
+
+    let latencies = simulate(&options(&["a", "b", "c", "d"]), links, 1);
+
+    for (latency, delay) in latencies.iter().zip(delays) {
+        assert!(*latency >= 2 * delay, "{:?} < 2 * {:?}", latency, delay);
+    }
+
+    assert!(latencies.windows(2).all(|w| w[0] < w[1]), "{:?}", latencies);
+}
+
+#[test]
+fn lossy_path() {
+    let links = vec![
+        Link::new(ms(5)),
+        Link::new(ms(10)),
+        Link::new(ms(20)),
+        Link::new(ms(40)).loss(0.1),
+    ];
+
+    let latencies = simulate(&options(&["a", "b", "c", "d"]), links, 3);
+
+    assert!(latencies[3] >= ms(80));
+    assert!(latencies[..3].iter().all(|l| *l < latencies[3]));
+}
+
+#[test]
+fn handshake_recovery_under_loss() {
+    let opts = options(&["a", "b"]);
+
+    // The first loss draw of seed 3 is below 0.3: the first Initial of the
+    // client is dropped.
+    let links = vec![Link::new(ms(10)).handshake_loss(0.3); 2];
+    let mut sim = simulator(&opts, links, 3, |s| s);
+
+    let completed = sim.run(2, Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    let recovery = sim.client().report().handshake_recovery.unwrap();
+    assert!(recovery.ptos > 0, "{:?}", recovery);
+    assert!(recovery.retransmissions > 0, "{:?}", recovery);
+}
+
+#[test]
+fn handshake_timeout_against_dead_server() {
+    let mut opts = options(&["a"]);
+    opts.handshake_timeout_ms = 500;
+
+    // Every datagram is dropped, as if nothing listened.
+    let links = vec![Link::new(ms(10)).loss(1.0)];
+    let mut sim = simulator(&opts, links, 7, |s| s);
+    let start = sim.now();
+
+    assert!(sim.run(1, Duration::from_secs(60)).is_empty());
+    assert!(sim.now() - start < Duration::from_secs(1));
+
+    let report = sim.client().report();
+    assert_eq!(report.outcome(), Outcome::HandshakeFailed);
+    assert!(report
+        .error
+        .as_deref()
+        .unwrap()
+        .starts_with("handshake timed out after 500ms (nothing received for"));
+}
+
+#[test]
+fn bandwidth_limited_path() {
+    let mut opts = options(&["a", "b"]);
+    opts.pad_to = Some(1000);
+
+    // 1000 bytes take 100ms each way on the slow path.
+    let links = vec![Link::new(ms(10)), Link::new(ms(10)).bandwidth(10_000)];
+
+    let latencies = simulate(&opts, links, 5);
+
+    assert!(latencies[0] < ms(100), "{:?}", latencies);
+    assert!(latencies[1] >= ms(200), "{:?}", latencies);
+}
+
+#[test]
+fn failover_when_active_path_dies() {
+    let message = "x".repeat(1_000_000);
+    let mut opts = options(&[&message]);
+    opts.standby_path = Some(1);
+
+    // The active path dies halfway through the transfer, without quiche
+    // closing it.
+    let links = vec![
+        Link::new(ms(10)).bandwidth(500_000).down_after(ms(500)),
+        Link::new(ms(10)).bandwidth(500_000),
+    ];
+    let mut sim = simulator(&opts, links, 59, |s| s);
+
+    let completed = sim.run(1, Duration::from_secs(60));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].echo, message.as_bytes());
+
+    let suspect = sim
+        .events()
+        .iter()
+        .position(|e| matches!(e, Event::PathSuspect(..)))
+        .unwrap();
+    assert!(sim.events()[suspect..]
+        .iter()
+        .any(|e| matches!(e, Event::Migrated(local, _) if *local == addr(9001))));
+
+    let failover_time = sim.client().report().failover_time.unwrap();
+    assert!(failover_time < ms(200), "{:?}", failover_time);
+}
+
+#[test]
+fn reproducible() {
+    let links = || vec![Link::new(ms(5)), Link::new(ms(15)), Link::new(ms(25))];
+    let opts = options(&["a", "b", "c"]);
+
+    assert_eq!(simulate(&opts, links(), 9), simulate(&opts, links(), 9));
+}
+
+#[test]
+fn server_restart_with_stateless_reset() {
+    let mut opts = options(&["a", "b", "c", "d"]);
+    opts.reconnect = 1;
+
+    let links = vec![Link::new(ms(10)); 4];
+    let mut sim =
+        simulator(&opts, links, 11, |s| s.with_reset_token(0x5eed)).restart_server_after(ms(60));
+
+    let completed = sim.run(4, Duration::from_secs(60));
+    assert_eq!(completed.len(), 4);
+
+    let report = sim.client().report();
+    assert_eq!(report.interruptions, ["stateless reset by peer"]);
+}
+
+#[test]
+fn server_restart_silent() {
+    let mut opts = options(&["a", "b", "c", "d"]);
+    opts.reconnect = 1;
+    opts.stall_timeout_ms = Some(200);
+
+    let links = vec![Link::new(ms(10)); 4];
+    let mut sim = simulator(&opts, links, 13, |s| s).restart_server_after(ms(60));
+
+    let completed = sim.run(4, Duration::from_secs(60));
+    assert_eq!(completed.len(), 4);
+
+    let report = sim.client().report();
+    assert_eq!(report.interruptions, ["stalled"]);
+}
+
+#[test]
+fn server_restart_without_reconnect() {
+    let links = vec![Link::new(ms(10)); 4];
+    let mut sim = simulator(&options(&["a", "b", "c", "d"]), links, 17, |s| {
+        s.with_reset_token(0x5eed)
+    })
+    .restart_server_after(ms(60));
+
+    let completed = sim.run(4, Duration::from_secs(60));
+    assert!(completed.len() < 4);
+
+    let client = sim.into_client();
+    assert!(client.is_finished());
+    assert_eq!(
+        client.report().error.as_deref(),
+        Some("stateless reset by peer")
+    );
+}
+
+/// Runs three messages against a server that does not allow new paths and
+/// checks that they are all delivered on the initial path.
+fn single_path_fallback<F>(server: F) -> Option<String>
+where
+    F: FnOnce(EchoServer) -> EchoServer,
+{
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&options(&["a", "b", "c"]), links, 19, server);
+
+    let completed = sim.run(3, Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    for c in &completed {
+        assert_eq!(c.record.paths.paths(), [(addr(9000), addr(8000))]);
+    }
+
+    sim.client().report().single_path.clone()
+}
+
+#[test]
+fn fallback_without_peer_cids() {
+    assert_eq!(
+        single_path_fallback(EchoServer::without_cids).as_deref(),
+        Some("peer issued no connection ID")
+    );
+}
+
+#[test]
+fn fallback_without_migration() {
+    assert_eq!(
+        single_path_fallback(EchoServer::without_migration).as_deref(),
+        Some("peer disabled active migration")
+    );
+}
+
+#[test]
+fn fallback_with_low_cid_limit() {
+    assert_eq!(
+        single_path_fallback(|s| s.with_cid_limit(2)).as_deref(),
+        Some("peer advertises active_connection_id_limit=2")
+    );
+}
+
+#[test]
+fn multipath_required() {
+    let links = vec![Link::new(ms(10)); 3];
+    let mut opts = options(&["a", "b", "c"]);
+    opts.require_multipath = true;
+    let mut sim = simulator(&opts, links, 19, |s| s.with_cid_limit(2));
+
+    assert!(sim.run(3, Duration::from_secs(60)).is_empty());
+
+    let report = sim.client().report();
+    assert_eq!(report.outcome(), Outcome::MultipathUnavailable);
+    assert_eq!(
+        report.error.as_deref(),
+        Some("multipath required: peer advertises active_connection_id_limit=2")
+    );
+}
+
+#[test]
+fn migrations_summarized() {
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&options(&["a", "b", "c"]), links, 127, |s| s);
+
+    let completed = sim.run(3, Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    // Moved to the second then the third path, each confirmed by its echo
+    // at the latest.
+    let migrations = sim.client().report().migrations;
+    assert_eq!(migrations.attempted, 2);
+    assert_eq!(migrations.succeeded, 2);
+    assert_eq!(migrations.failed, 0);
+    assert!(migrations.mean_time().unwrap() <= ms(40));
+}
+
+#[test]
+fn paths_opened_with_zero_length_server_cid() {
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&options(&["a", "b", "c"]), links, 113, |s| {
+        s.with_zero_length_cids()
+    });
+
+    let mut completed = sim.run(3, Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    // Each message on its own path, although the server issued no ID.
+    completed.sort_by_key(|c| c.record.index);
+    for (i, c) in completed.iter().enumerate() {
+        assert_eq!(
+            c.record.paths.paths(),
+            [(addr(9000 + i as u16), addr(8000 + i as u16))]
+        );
+    }
+
+    assert_eq!(sim.client().report().single_path, None);
+    assert_eq!(sim.server().conn().unwrap().available_dcids(), 0);
+    assert!(sim.server().conn().unwrap().source_id().is_empty());
+}
+
+#[test]
+fn probe_without_spare_scid_against_zero_length_server_cid() {
+    // The client accepts two active IDs of its own: the one of the handshake
+    // and the one issued for the second path, none is left for the third.
+    let mut client_config = config(false);
+    client_config.set_active_connection_id_limit(2);
+
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim =
+        simulator_with_config(&options(&["a", "b", "c"]), links, 131, client_config, |s| {
+            s.with_zero_length_cids()
+        });
+
+    let completed = sim.run(3, Duration::from_secs(2));
+    assert_eq!(completed.len(), 2);
+
+    // Reported once, the message waiting for a spare ID instead of
+    // panicking.
+    let failed: Vec<&Event> = sim
+        .events()
+        .iter()
+        .filter(|e| matches!(e, Event::PathProbeFailed(..)))
+        .collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].path(), Some((addr(9002), addr(8002))));
+    assert!(!sim.client().is_finished());
+}
+
+/// Runs two messages then leaves both paths idle for a while, the NAT of the
+/// first one forgetting its mapping after 200ms of silence.
+fn idle_nat(path_keepalive_ms: Option<u64>) -> Simulator {
+    let mut opts = options(&["a", "b"]);
+    opts.path_keepalive_ms = path_keepalive_ms;
+
+    let links = vec![Link::new(ms(5)).nat_timeout(ms(200)), Link::new(ms(5))];
+    let mut sim = simulator(&opts, links, 23, |s| s);
+
+    // Keep running once both messages completed.
+    let completed = sim.run(3, ms(600));
+    assert_eq!(completed.len(), 2);
+
+    sim
+}
+
+#[test]
+fn path_keepalive_keeps_nat_mapping() {
+    let sim = idle_nat(Some(50));
+    assert!(sim.is_mapped(0));
+
+    let report = sim.client().report();
+    let stats = report
+        .keepalives
+        .iter()
+        .find(|k| k.path == (addr(9000), addr(8000)))
+        .unwrap();
+    assert!(stats.keepalives > 0);
+    assert_eq!(stats.reprobes, 0);
+}
+
+#[test]
+fn idle_path_loses_nat_mapping() {
+    assert!(!idle_nat(None).is_mapped(0));
+}
+
+#[test]
+fn idle_path_closed() {
+    let opts = ClientOptions {
+        keep_open: true,
+        path_keepalive_ms: Some(50),
+        path_idle_timeout_ms: Some(300),
+        ..options(&["a", "b"])
+    };
+    let links = vec![Link::new(ms(5)), Link::new(ms(5))];
+    let mut sim = simulator(&opts, links, 131, |s| s);
+
+    // Path 1 only carries the second message, at the start.
+    let completed = sim.run(2, Duration::from_secs(2));
+    assert_eq!(completed.len(), 2);
+    sim.run(1, Duration::from_secs(1));
+
+    let closed: Vec<_> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::PathIdleClosed(local, peer) => Some((*local, *peer)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(closed, [(addr(9001), addr(8001))]);
+
+    // The active path is left as it was.
+    let conn = sim.client().conn();
+    assert!(!conn.is_closed());
+    assert!(conn.is_path_validated(addr(9000), addr(8000)).unwrap());
+    assert!(sim.client_mut().migrate(1).is_err());
+
+    // Nothing is sent on the closed path anymore.
+    let keepalives = |sim: &Simulator| {
+        let report = sim.client().report();
+        report
+            .keepalives
+            .iter()
+            .find(|k| k.path == (addr(9001), addr(8001)))
+            .map_or(0, |k| k.keepalives)
+    };
+    let before = keepalives(&sim);
+    sim.run(1, Duration::from_secs(1));
+    assert_eq!(keepalives(&sim), before);
+}
+
+/// Runs the messages of `opts` over two 10ms links against a server echoing
+/// through `shaper`, and returns the latency of each message.
+fn shaped(opts: &ClientOptions, shaper: EchoShaper) -> Vec<Duration> {
+    let links = vec![Link::new(ms(10)), Link::new(ms(10))];
+    let mut sim = simulator(opts, links, 29, |s| s.with_echo_shaper(shaper));
+
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), opts.messages.len());
+
+    completed.iter().map(|c| c.record.latency).collect()
+}
+
+#[test]
+fn echo_delay() {
+    let latencies = shaped(&options(&["a", "b"]), EchoShaper::new(ms(50)));
+
+    for latency in latencies {
+        assert!(latency >= ms(70), "{:?}", latency);
+        assert!(latency < ms(90), "{:?}", latency);
+    }
+}
+
+#[test]
+fn echo_rate() {
+    let mut opts = options(&["a", "b"]);
+    opts.pad_to = Some(10_000);
+
+    // The first 1200 bytes of each echo leave at once, the other 8800 take
+    // 176ms at 50kB/s.
+    let latencies = shaped(&opts, EchoShaper::new(Duration::ZERO).rate(50_000));
+
+    for latency in latencies {
+        assert!(latency >= ms(190), "{:?}", latency);
+        assert!(latency < ms(300), "{:?}", latency);
+    }
+}
+
+/// Sends a message needing several round trips with the send watchdog on,
+/// the client being frozen for 500ms in the middle of the transfer if
+/// `freeze`, as if its driver missed a deadline.
+fn watched_transfer(freeze: bool, strict: bool) -> (Vec<Completed>, Simulator) {
+    let mut opts = options(&["a"]);
+    opts.pad_to = Some(200_000);
+    opts.send_watchdog = Some(3);
+    opts.strict = strict;
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 31, |s| s);
+
+    if freeze {
+        sim = sim.freeze_client(ms(60), ms(500));
+    }
+
+    let completed = sim.run(1, Duration::from_secs(5));
+
+    (completed, sim)
+}
+
+#[test]
+fn send_watchdog_quiet_without_stall() {
+    let (completed, sim) = watched_transfer(false, false);
+
+    assert_eq!(completed.len(), 1);
+    assert!(sim.client().report().send_stalls.is_empty());
+}
+
+#[test]
+fn send_watchdog_reports_frozen_client() {
+    let (completed, sim) = watched_transfer(true, false);
+
+    // The transfer resumes once the client is stepped again.
+    assert_eq!(completed.len(), 1);
+
+    let report = sim.client().report();
+    assert_eq!(report.send_stalls.len(), 1);
+
+    let stall = &report.send_stalls[0];
+    assert!(stall.idle >= ms(500), "{:?}", stall);
+
+    let active = stall.paths.iter().find(|p| p.active).unwrap();
+    assert_eq!(active.blocked_streams, vec![0]);
+}
+
+#[test]
+fn strict_send_watchdog_closes_stalled_connection() {
+    let (completed, sim) = watched_transfer(true, true);
+
+    assert!(completed.is_empty());
+    assert!(sim.client().is_finished());
+    assert_eq!(sim.client().report().outcome(), Outcome::SendStalled);
+}
+
+/// Runs the messages of `opts` over 10ms links until the connection closes
+/// and returns its path usage.
+fn path_usage(opts: &ClientOptions) -> PathUsage {
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(opts, links, 37, |s| s);
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), opts.messages.len());
+    assert!(sim.client().is_finished());
+
+    sim.client().report().path_usage
+}
+
+#[test]
+fn every_path_used() {
+    let usage = path_usage(&options(&["a", "b", "c"]));
+
+    assert_eq!(
+        usage,
+        PathUsage {
+            probed: 3,
+            validated: 3,
+            used: 3,
+        }
+    );
+}
+
+#[test]
+fn standby_path_validated_but_unused() {
+    let mut opts = options(&["a", "b"]);
+    opts.standby_path = Some(2);
+
+    let usage = path_usage(&opts);
+
+    assert_eq!(
+        usage,
+        PathUsage {
+            probed: 3,
+            validated: 3,
+            used: 2,
+        }
+    );
+}
+
+#[test]
+fn no_probe_before_confirmation() {
+    let messages: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    let messages: Vec<&str> = messages.iter().map(|m| m.as_str()).collect();
+    let opts = options(&messages);
+    let links = vec![Link::new(ms(10)); 10];
+    let mut sim = simulator(&opts, links, 43, |s| s);
+
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), opts.messages.len());
+    assert!(sim.client().is_confirmed());
+
+    let events = sim.events();
+    let confirmed = events
+        .iter()
+        .position(|e| matches!(e, Event::HandshakeConfirmed))
+        .unwrap();
+    let probes: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e, Event::PathProbed(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Every path but the initial one is probed, only once confirmed.
+    assert_eq!(probes.len(), 9);
+    assert!(probes.iter().all(|i| *i > confirmed));
+
+    let pre_validation = sim.client().report().pre_validation;
+    assert!(pre_validation.validated);
+    assert!(pre_validation.sent > 0 && pre_validation.received > 0);
+    assert_eq!(pre_validation.lost, 0);
+}
+
+#[test]
+fn dcid_reserve_kept_for_migration() {
+    let opts = ClientOptions {
+        dcid_reserve: 1,
+        ..options(&["a", "b", "c", "d", "e"])
+    };
+    let links = vec![Link::new(ms(10)); 5];
+
+    // The server may issue 3 connection IDs beyond the initial one.
+    let mut client_config = config(false);
+    client_config.set_active_connection_id_limit(4);
+    let mut sim = simulator_with_config(&opts, links, 53, client_config, |s| s);
+
+    // Paths 1 and 2 take a DCID each, path 3 would take the reserved one.
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(2));
+    assert_eq!(completed.len(), 3);
+    assert_eq!(sim.client().conn().available_dcids(), 1);
+
+    // The reserved DCID is still there to migrate.
+    sim.client_mut().migrate(3).unwrap();
+    let completed = sim.run(1, Duration::from_secs(2));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].record.index, 3);
+}
+
+/// Runs three messages against a server that does not echo them.
+fn unechoed(confirm: Confirm) -> Vec<Completed> {
+    let opts = ClientOptions {
+        confirm,
+        ..options(&["a", "b", "c"])
+    };
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&opts, links, 47, |s| s.without_echo());
+
+    sim.run(opts.messages.len(), Duration::from_secs(2))
+}
+
+#[test]
+fn confirm_by_echo() {
+    assert!(unechoed(Confirm::Echo).is_empty());
+}
+
+#[test]
+fn confirm_by_acks() {
+    let mut completed = unechoed(Confirm::Acks);
+    assert_eq!(completed.len(), 3);
+
+    completed.sort_by_key(|c| c.record.index);
+    for (i, c) in completed.iter().enumerate() {
+        // Sent on unidirectional streams, acknowledged after a round trip
+        // on the path of the message.
+        assert_eq!(c.record.stream_id, 2 + 4 * i as u64);
+        assert!(c.echo.is_empty());
+        assert!(c.record.latency >= ms(20));
+        assert_eq!(
+            c.record.paths.paths(),
+            [(addr(9000 + i as u16), addr(8000 + i as u16))]
+        );
+    }
+}
+
+#[test]
+fn custom_path_policy() {
+    let opts = options(&["a", "b", "c"]);
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&opts, links, 41, |s| s);
+
+    // Wraps the default policy, recording the paths validated.
+    let validated = Rc::new(RefCell::new(vec![]));
+    let seen = validated.clone();
+    sim.client_mut()
+        .set_path_policy(move |state, event, events| {
+            if let quiche::PathEvent::Validated(local, peer) = event {
+                seen.borrow_mut().push((local, peer));
+            }
+
+            handle_path_event(state, event, events)
+        });
+
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), opts.messages.len());
+
+    let validated = validated.borrow();
+    let client = sim.client();
+    for path in client.local_addrs().iter().zip(client.peer_addrs()).skip(1) {
+        assert!(validated.contains(&(*path.0, *path.1)));
+    }
+}
+
+#[test]
+fn cached_fastest_path_probed_first() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let file = temp_file("sim-cache");
+
+    // Path 2 is the fastest, path 0 is only cached for another server or
+    // long ago.
+    std::fs::write(
+        &file,
+        format!(
+            "sim 127.0.0.1:8000 127.0.0.1:9002 127.0.0.1:8002 5000 {now}\n\
+             sim 127.0.0.1:8000 127.0.0.1:9001 127.0.0.1:8001 10000 {now}\n\
+             other 127.0.0.1:8000 127.0.0.1:9000 127.0.0.1:8000 1000 {now}\n\
+             sim 127.0.0.1:8000 127.0.0.1:9000 127.0.0.1:8000 1000 {}\n",
+            now - 7200
+        ),
+    )
+    .unwrap();
+
+    let cache = PathCache::load(&file, Duration::from_secs(3600), SystemTime::now()).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    let opts = options(&["a", "b", "c"]);
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&opts, links, 41, |s| s);
+    sim.client_mut()
+        .prioritize_paths(&cache.rtts("sim", addr(8000)));
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    let mut streams: Vec<(u64, usize)> = completed
+        .iter()
+        .map(|c| (c.record.stream_id, c.record.index))
+        .collect();
+    streams.sort();
+
+    assert_eq!(streams, [(0, 2), (4, 1), (8, 0)]);
+
+    let report = sim.client().report();
+    assert_eq!(report.path_cache_hits, Some((2, 3)));
+    assert_eq!(report.path_rtts.len(), 3);
+}
+
+#[test]
+fn chunked_messages_reassembled() {
+    let message = "x".repeat(50_000);
+    let mut opts = options(&[&message, "short"]);
+    opts.chunk_size = Some(1000);
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 2], 43, |s| s);
+    let mut completed = sim.run(2, Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    completed.sort_by_key(|c| c.record.index);
+    assert_eq!(completed[0].echo, message.as_bytes());
+    assert_eq!(completed[1].echo, b"short");
+}
+
+#[test]
+fn both_ends_validate_the_same_paths() {
+    let opts = options(&["a", "b", "c"]);
+    let links = vec![Link::new(ms(10)); 3];
+    let mut sim = simulator(&opts, links, 47, |s| s.with_client_path_probes());
+
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    let client: HashSet<(SocketAddr, SocketAddr)> = sim
+        .client()
+        .validated_paths()
+        .iter()
+        .map(|(local, peer)| (*peer, *local))
+        .collect();
+
+    let server = sim.server().paths().unwrap();
+    assert_eq!(client.len(), 3);
+    assert_eq!(server.validated(), &client);
+    assert_eq!(server.client_addrs(), [addr(9000), addr(9001), addr(9002)]);
+
+    // quiche only lets the client open paths, the server cannot cross them.
+    assert_eq!(server.stats.probes, 2);
+    assert_eq!(server.stats.refused_probes, 6);
+}
+
+#[test]
+fn concurrency_limit_queues_messages() {
+    let mut opts = options(&["a", "b", "c"]);
+    opts.max_concurrent_streams = Some(1);
+
+    // Each path is validated before the echo on the previous one completes.
+    let delays = [ms(30), ms(10), ms(5)];
+    let links = delays.iter().map(|d| Link::new(*d)).collect();
+
+    let mut sim = simulator(&opts, links, 53, |s| s);
+    let mut completed = sim.run(3, Duration::from_secs(60));
+    assert_eq!(completed.len(), 3);
+
+    completed.sort_by_key(|c| c.record.index);
+
+    // Each message waits for the echo of the previous one, which is not
+    // part of its latency.
+    assert_eq!(completed[0].record.queue_wait, Duration::ZERO);
+    for message in &completed[1..] {
+        assert!(message.record.queue_wait > Duration::ZERO);
+    }
+    for (message, delay) in completed.iter().zip(delays) {
+        assert!(
+            message.record.latency < 2 * delay + ms(10),
+            "{:?}",
+            message.record
+        );
+    }
+
+    let queue_wait = sim.client().report().queue_wait.as_ref().unwrap();
+    assert_eq!(queue_wait.count(), 3);
+}
+
+#[test]
+fn multiplexed_messages_share_streams() {
+    // The long message spans several packets.
+    let long = "x".repeat(3000);
+    let mut opts = options(&["a", "b", &long, "d"]);
+    opts.multiplex = Some(2);
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 2], 59, |s| s);
+    let mut completed = sim.run(4, Duration::from_secs(60));
+    assert_eq!(completed.len(), 4);
+
+    completed.sort_by_key(|c| c.record.index);
+    assert_eq!(completed[2].echo, long.as_bytes());
+
+    // One stream per path, each echoing its frames in order.
+    let records: Vec<_> = completed.iter().map(|c| &c.record).collect();
+    assert_eq!(records[0].stream_id, records[2].stream_id);
+    assert_eq!(records[1].stream_id, records[3].stream_id);
+    assert_ne!(records[0].stream_id, records[1].stream_id);
+    assert_eq!(
+        records.iter().map(|r| r.frame).collect::<Vec<_>>(),
+        [Some(0), Some(0), Some(1), Some(1)]
+    );
+}
+
+#[test]
+fn multiplexed_streams_per_path() {
+    let mut opts = options(&["a", "b", "c", "d"]);
+    opts.multiplex = Some(1);
+    opts.multiplex_streams = 2;
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 61, |s| s);
+    let completed = sim.run(4, Duration::from_secs(60));
+    assert_eq!(completed.len(), 4);
+
+    let streams: HashSet<u64> = completed.iter().map(|c| c.record.stream_id).collect();
+    assert_eq!(streams.len(), 2);
+
+    for c in &completed {
+        assert_eq!(c.echo, opts.messages[c.record.index]);
+    }
+}
+
+/// A fast path 0, and a path 1 with 4 times its RTT and a tenth of its
+/// bandwidth.
+fn uneven_links() -> Vec<Link> {
+    vec![
+        Link::new(ms(10)).bandwidth(1_000_000),
+        Link::new(ms(40)).bandwidth(100_000),
+    ]
+}
+
+/// Time until the last of 10 messages of 20 kB multiplexed on the first
+/// `paths` paths in turn, over `uneven_links`, is echoed.
+fn multiplexed_completion(paths: usize) -> Duration {
+    let message = "x".repeat(20_000);
+    let mut opts = options(&[message.as_str(); 10]);
+    opts.multiplex = Some(paths);
+
+    let mut sim = simulator(&opts, uneven_links(), 139, |s| s);
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 10);
+
+    completed.iter().map(|c| c.record.latency).max().unwrap()
+}
+
+#[test]
+fn min_rtt_beats_round_robin_on_uneven_paths() {
+    // Everything on the fastest path, against every other message on the
+    // slow one.
+    let min_rtt = multiplexed_completion(1);
+    let round_robin = multiplexed_completion(2);
+
+    assert!(min_rtt < round_robin, "{:?} >= {:?}", min_rtt, round_robin);
+}
+
+/// Sends binary messages, one of them a multi-megabyte blob, and checks
+/// they are echoed byte for byte.
+fn binary_messages_echoed(multiplex: Option<usize>) {
+    let blob: Vec<u8> = (0..3 << 20)
+        .map(|i: u32| (i.wrapping_mul(2654435761) >> 24) as u8)
+        .collect();
+    let opts = ClientOptions {
+        messages: vec![
+            vec![0x00],
+            b"a\0b\nc".to_vec(),
+            vec![0xff; 4096],
+            [vec![0x00; 100], vec![0xff; 100]].concat(),
+            blob,
+        ],
+        multiplex,
+        ..Default::default()
+    };
+
+    // A path per message, unless multiplexed.
+    let links = vec![Link::new(ms(10)); multiplex.unwrap_or(opts.messages.len())];
+    let mut sim = simulator(&opts, links, 89, |s| s);
+    let completed = sim.run(5, Duration::from_secs(60));
+    assert_eq!(completed.len(), 5);
+
+    for c in &completed {
+        assert!(c.echo == opts.messages[c.record.index]);
+    }
+}
+
+#[test]
+fn binary_messages_per_stream() {
+    binary_messages_echoed(None);
+}
+
+#[test]
+fn binary_messages_multiplexed() {
+    binary_messages_echoed(Some(2));
+}
+
+#[test]
+fn goodput_counts_delivered_messages() {
+    let opts = options(&["hello", "world!"]);
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 2], 97, |s| s);
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+    assert!(sim.client().is_finished());
+
+    // The handshake, probes and headers only count as throughput.
+    let t = sim.client().report().throughput;
+    assert_eq!(t.app_bytes, 11);
+    assert_eq!(t.wire_bytes, sim.client().conn().stats().sent_bytes);
+    assert!(t.throughput() > t.goodput());
+}
+
+#[test]
+fn sequence_order_checked() {
+    let opts = ClientOptions {
+        sequence: true,
+        ..options(&["a", "b"])
+    };
+
+    // The first message takes the slow path, the second one is sent later
+    // on the fast path and completes first.
+    let links = vec![Link::new(ms(80)), Link::new(ms(5))];
+    let mut sim = simulator(&opts, links, 137, |s| s);
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert!(sim.client().is_finished());
+
+    let indexes: Vec<usize> = completed.iter().map(|c| c.record.index).collect();
+    assert_eq!(indexes, [1, 0]);
+    assert_eq!(completed[0].echo, b"b");
+    assert_eq!(completed[1].echo, b"a");
+
+    let late: Vec<_> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::OutOfOrder {
+                index, seq, after, ..
+            } => Some((*index, *seq, *after)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(late, [(0, 0, 1)]);
+
+    let report = sim.client().report();
+    assert_eq!(report.delivered, 2);
+    assert!(report.mismatched.is_empty());
+    assert_eq!(
+        report.ordering.unwrap().to_string(),
+        "2 echoes, 1 out of order (late by up to 1)"
+    );
+}
+
+#[test]
+fn sequence_echo_checked() {
+    let opts = ClientOptions {
+        sequence: true,
+        ..options(&["a"])
+    };
+
+    // The sequence number has no letters, only the message is changed.
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 139, |s| {
+        s.with_transform(Transform::Upper)
+    });
+    sim.run(2, Duration::from_secs(60));
+    assert!(sim.client().is_finished());
+
+    let report = sim.client().report();
+    assert_eq!(report.mismatched, [0]);
+    assert_eq!(report.delivered, 0);
+    assert_eq!(
+        report.error.as_deref(),
+        Some("1 echoes do not carry back their message")
+    );
+}
+
+/// Runs messages against a server answering them with `server`, the client
+/// expecting `expected`.
+fn transformed(server: Option<Transform>, expected: Transform) -> (Vec<Completed>, Simulator) {
+    let large: Vec<u8> = (0..200_000u32).map(|i| b'a' + (i % 26) as u8).collect();
+    let opts = ClientOptions {
+        messages: vec![b"hello".to_vec(), vec![0x00, b'x', 0xff], large],
+        expect_transform: Some(expected),
+        ..Default::default()
+    };
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 3], 101, |s| match server {
+        Some(transform) => s.with_transform(transform),
+        None => s,
+    });
+
+    let mut completed = sim.run_until_closed(Duration::from_secs(60));
+    assert!(sim.client().is_finished());
+    completed.sort_by_key(|c| c.record.index);
+
+    (completed, sim)
+}
+
+#[test]
+fn transformed_echoes_checked() {
+    for transform in [Transform::Upper, Transform::Reverse, Transform::Sha256] {
+        let (completed, sim) = transformed(Some(transform), transform);
+        assert_eq!(completed.len(), 3);
+
+        let report = sim.client().report();
+        assert!(report.mismatched.is_empty(), "{}", transform);
+        assert_eq!(report.delivered, 3);
+    }
+
+    let (completed, _) = transformed(Some(Transform::Upper), Transform::Upper);
+    assert_eq!(completed[0].echo, b"HELLO");
+    assert_eq!(completed[1].echo, [0x00, b'X', 0xff]);
+
+    // The large message is digested over several reads.
+    let (completed, _) = transformed(Some(Transform::Sha256), Transform::Sha256);
+    assert!(completed.iter().all(|c| c.echo.len() == 32));
+}
+
+#[test]
+fn plain_echoes_not_taken_for_transforms() {
+    let (completed, sim) = transformed(None, Transform::Reverse);
+    assert_eq!(completed.len(), 3);
+
+    // A single byte or a palindrome would be its own reverse.
+    let report = sim.client().report();
+    assert_eq!(report.mismatched, [0, 1, 2]);
+    assert_eq!(report.delivered, 0);
+    assert_eq!(report.outcome(), Outcome::Partial);
+}
+
+#[test]
+fn server_discovers_path_mtu() {
+    let opts = options(&["a", "b"]);
+    let links = vec![Link::new(ms(10)); 2];
+    let mut sim = simulator(&opts, links, 67, |s| s.with_pmtud(1350));
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+    assert!(sim.client().is_finished());
+
+    let mtus = sim.server().paths().unwrap().mtus();
+    assert_eq!(mtus[&(addr(8000), addr(9000))], 1350);
+
+    // quiche only probes from the server, the client keeps the minimum.
+    let report = sim.client().report();
+    assert_eq!(report.path_mtus.len(), 2);
+    assert!(report.path_mtus.iter().all(|(_, mtu)| *mtu == 1200));
+}
+
+#[test]
+fn empty_streams_delivered() {
+    let mut opts = options(&["a", "b"]);
+    opts.empty_streams = 3;
+
+    let workload = Workload::from_options(&opts, 0).unwrap();
+    assert_eq!(workload.paths, [0, 1, 0, 1, 0]);
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 2], 71, |s| s);
+
+    let mut completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 5);
+    assert!(sim.client().is_finished());
+    assert_eq!(sim.client().report().outcome(), Outcome::Delivered);
+
+    completed.sort_by_key(|c| c.record.index);
+    assert_eq!(completed[0].echo, b"a");
+    assert_eq!(completed[1].echo, b"b");
+    for empty in &completed[2..] {
+        assert!(empty.echo.is_empty());
+    }
+}
+
+#[test]
+fn parallel_file_transfer() {
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let path = std::env::temp_dir().join(format!("quiche-test-file-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        parallel_streams: Some(4),
+        ..Default::default()
+    };
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 73, |s| {
+        s.with_file_sink(1 << 20)
+    });
+    std::fs::remove_file(&path).unwrap();
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert!(completed.is_empty());
+    assert!(sim.client().is_finished());
+
+    // The single stream baseline comes first.
+    let runs = &sim.client().report().transfer_runs;
+    assert_eq!(runs.iter().map(|r| r.streams).collect::<Vec<_>>(), [1, 4]);
+    assert!(runs.iter().all(|r| r.size == 100_000 && r.digest_ok));
+}
+
+#[test]
+fn one_way_delays_per_path() {
+    let opts = ClientOptions {
+        owd: true,
+        ..options(&["hello", "world"])
+    };
+    let links = vec![Link::new(ms(10)), Link::new(ms(30))];
+
+    // The clock of the server is 5ms ahead of the one of the client.
+    let mut sim = simulator(&opts, links, 131, |s| s.with_timestamps(5000));
+
+    let mut completed = sim.run(2, Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    // The timestamps are stripped from the echoes.
+    completed.sort_by_key(|c| c.record.index);
+    assert_eq!(completed[0].echo, b"hello");
+    assert_eq!(completed[1].echo, b"world");
+
+    let report = sim.client().report();
+    let delays = &report.one_way_delays;
+    assert_eq!(delays.len(), 2);
+
+    // The offset shows up as an asymmetry of the forward and reverse
+    // delays, not across paths.
+    let near = |value: f64, expected: f64| (value - expected).abs() < 1000.0;
+    for (delays, link) in delays.iter().zip([10_000.0, 30_000.0]) {
+        assert!(near(delays.forward, link + 5000.0), "{:?}", delays);
+        assert!(near(delays.reverse, link - 5000.0), "{:?}", delays);
+    }
+
+    let (forward, reverse) = owd::spread(delays).unwrap();
+    assert!(near(forward, 20_000.0) && near(reverse, 20_000.0));
+}
+
+/// Sends a POST carrying `body` to `server` over HTTP/3 from a client of
+/// its own, once its previous connection closed, and returns the status
+/// and the body of the response.
+fn h3_post(server: &mut EchoServer, body: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while server.conn().is_some_and(|c| !c.is_closed()) {
+        assert!(Instant::now() < deadline, "previous connection still open");
+
+        if let Some(timeout) = server.timeout() {
+            std::thread::sleep(timeout.saturating_duration_since(Instant::now()));
+        }
+        server.on_timeout();
+    }
+
+    let mut client_config = config(false);
+    client_config.set_application_protos(&[b"h3"]).unwrap();
+
+    let (local, peer) = (addr(9000), addr(8000));
+    let scid = quiche::ConnectionId::from_ref(&[0xc3; quiche::MAX_CONN_ID_LEN]);
+    let mut conn =
+        quiche::connect(Some("localhost"), &scid, local, peer, &mut client_config).unwrap();
+
+    let mut h3 = None;
+    let mut status = vec![];
+    let mut response = vec![];
+    let mut buf = [0; 65535];
+
+    loop {
+        assert!(Instant::now() < deadline, "no response");
+
+        while let Ok((len, send_info)) = conn.send(&mut buf) {
+            let recv_info = quiche::RecvInfo {
+                from: send_info.from,
+                to: send_info.to,
+            };
+            server.recv(&buf[..len], recv_info);
+        }
+
+        for (mut pkt, recv_info) in server.send() {
+            conn.recv(&mut pkt, recv_info).unwrap();
+        }
+
+        if conn.timeout_instant().is_some_and(|t| t <= Instant::now()) {
+            conn.on_timeout();
+        }
+
+        if !conn.is_established() {
+            continue;
+        }
+
+        let h3 = h3.get_or_insert_with(|| {
+            let config = quiche::h3::Config::new().unwrap();
+            let mut h3 = quiche::h3::Connection::with_transport(&mut conn, &config).unwrap();
+
+            let headers = [
+                quiche::h3::Header::new(b":method", b"POST"),
+                quiche::h3::Header::new(b":scheme", b"https"),
+                quiche::h3::Header::new(b":authority", b"localhost"),
+                quiche::h3::Header::new(b":path", b"/"),
+            ];
+            let stream_id = h3.send_request(&mut conn, &headers, false).unwrap();
+            assert_eq!(
+                h3.send_body(&mut conn, stream_id, body, true).unwrap(),
+                body.len()
+            );
+
+            h3
+        });
+
+        loop {
+            match h3.poll(&mut conn) {
+                Ok((_, quiche::h3::Event::Headers { list, .. })) => {
+                    let header = list.iter().find(|h| h.name() == b":status").unwrap();
+                    status = header.value().to_vec();
+                }
+
+                Ok((stream_id, quiche::h3::Event::Data)) => {
+                    while let Ok(read) = h3.recv_body(&mut conn, stream_id, &mut buf) {
+                        response.extend_from_slice(&buf[..read]);
+                    }
+                }
+
+                Ok((_, quiche::h3::Event::Finished)) => return (status, response),
+
+                Ok(_) => (),
+
+                Err(quiche::h3::Error::Done) => break,
+
+                Err(e) => panic!("HTTP/3 failed: {:?}", e),
+            }
+        }
+    }
+}
+
+#[test]
+fn server_handler_chosen_by_alpn() {
+    let apps = [
+        App::new("echo/1", Handler::Echo),
+        App::new("http/0.9", Handler::File),
+        App::new("h3", Handler::H3),
+    ];
+
+    // A connection negotiating echo/1 gets its message echoed.
+    let mut client_config = config(false);
+    client_config.set_application_protos(&[b"echo/1"]).unwrap();
+
+    let opts = options(&["hello"]);
+    let mut sim = simulator_with_config(&opts, vec![Link::new(ms(10))], 127, client_config, |s| {
+        s.with_apps(&apps, 1 << 20)
+    });
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].echo, b"hello");
+    assert_eq!(sim.server().handler(), Some(Handler::Echo));
+
+    // The next one, to the same server, negotiating http/0.9 gets its file
+    // reassembled.
+    let server = sim.into_server();
+
+    let data: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+    let path = temp_file("apps");
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 127, move |_| server);
+    std::fs::remove_file(&path).unwrap();
+
+    sim.run(1, Duration::from_secs(60));
+    assert!(sim.client().is_finished());
+    assert_eq!(sim.server().handler(), Some(Handler::File));
+
+    let runs = &sim.client().report().transfer_runs;
+    assert!(!runs.is_empty());
+    assert!(runs.iter().all(|r| r.size == 50_000 && r.digest_ok));
+
+    // The last one, negotiating h3, gets its request body back.
+    let mut server = sim.into_server();
+    let (status, body) = h3_post(&mut server, b"hello over h3");
+    assert_eq!(status, b"200");
+    assert_eq!(body, b"hello over h3");
+    assert_eq!(server.handler(), Some(Handler::H3));
+}
+
+#[test]
+fn file_transfer_progress() {
+    let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+    let path = std::env::temp_dir().join(format!("quiche-test-progress-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        parallel_streams: Some(4),
+        ..Default::default()
+    };
+
+    let links = vec![Link::new(ms(20)).bandwidth(250_000).loss(0.02)];
+    let mut sim = simulator(&opts, links, 29, |s| s.with_file_sink(1 << 20));
+    std::fs::remove_file(&path).unwrap();
+
+    sim.run(1, Duration::from_secs(60));
+    assert!(sim.client().is_finished());
+
+    let progress: Vec<_> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::TransferProgress(p) => Some(*p),
+            _ => None,
+        })
+        .collect();
+
+    for transfer in 0..2 {
+        let run: Vec<_> = progress.iter().filter(|p| p.transfer == transfer).collect();
+
+        // Snapshots in between, whatever the retransmissions.
+        assert!(run.len() > 2, "{:?}", run);
+        assert!(run.windows(2).all(|w| w[0].acked <= w[1].acked));
+        assert!(run.iter().all(|p| p.sent >= p.acked && p.sent <= 300_000));
+
+        let last = run.last().unwrap();
+        assert_eq!((last.acked, last.size), (300_000, 300_000));
+        assert!(last.is_complete());
+    }
+}
+
+#[test]
+fn striped_file_transfer() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let path = std::env::temp_dir().join(format!("quiche-test-stripe-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        stripe: Some(2),
+        chunk_size: Some(16_384),
+        ..Default::default()
+    };
+
+    let links = vec![Link::new(ms(10)).bandwidth(1_000_000); 2];
+    let mut sim = simulator(&opts, links, 131, |s| s.with_file_sink(1 << 20));
+    std::fs::remove_file(&path).unwrap();
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert!(completed.is_empty());
+    assert!(sim.client().is_finished());
+
+    // A run on each path alone, then the chunks over both in turn.
+    let report = sim.client().report();
+    let paths: Vec<_> = report
+        .transfer_runs
+        .iter()
+        .map(|r| r.paths.clone())
+        .collect();
+    let (first, second) = ((addr(9000), addr(8000)), (addr(9001), addr(8001)));
+    assert_eq!(paths, [vec![first], vec![second], vec![first, second]]);
+
+    assert_eq!(report.transfer_runs[2].streams, 13);
+    assert!(report
+        .transfer_runs
+        .iter()
+        .all(|r| r.size == 200_000 && r.digest_ok));
+    assert!(report.striping_gain().is_some());
+}
+
+#[test]
+fn striping_between_uneven_paths() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let path = temp_file("stripe-uneven");
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        stripe: Some(2),
+        chunk_size: Some(16_384),
+        ..Default::default()
+    };
+
+    let mut sim = simulator(&opts, uneven_links(), 149, |s| s.with_file_sink(1 << 20));
+    std::fs::remove_file(&path).unwrap();
+
+    sim.run_until_closed(Duration::from_secs(60));
+
+    let report = sim.client().report();
+    let runs = &report.transfer_runs;
+    assert_eq!(runs.len(), 3);
+    assert!(runs.iter().all(|r| r.digest_ok));
+
+    // The chunks on the slow path hold the striped run back behind the
+    // fast path alone, but it still beats the slow path alone.
+    let (fast, slow, striped) = (runs[0].goodput(), runs[1].goodput(), runs[2].goodput());
+    assert!(fast > striped, "{} <= {}", fast, striped);
+    assert!(striped > slow, "{} <= {}", striped, slow);
+    assert!(report.striping_gain().unwrap() < 1.0);
+}
+
+#[test]
+fn tx_impair_drop_nth() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let path = std::env::temp_dir().join(format!("quiche-test-impair-{}", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let opts = ClientOptions {
+        file: Some(path.to_string_lossy().into_owned()),
+        tx_impair: Some(ImpairSpec::DropNth(50)),
+        ..Default::default()
+    };
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 83, |s| {
+        s.with_file_sink(1 << 20)
+    });
+    std::fs::remove_file(&path).unwrap();
+
+    let completed = sim.run_until_closed(Duration::from_secs(60));
+    assert!(completed.is_empty());
+    assert!(sim.client().is_finished());
+
+    let runs = &sim.client().report().transfer_runs;
+    assert!(!runs.is_empty());
+    assert!(runs.iter().all(|r| r.size == 200_000 && r.digest_ok));
+
+    // Every dropped datagram was declared lost by the sender.
+    let impaired = sim.client().report().tx_impaired.unwrap();
+    assert!(impaired.dropped > 0);
+    assert!(sim.client().conn().stats().lost as u64 >= impaired.dropped);
+}
+
+#[test]
+fn grease_version_negotiated() {
+    let opts = options(&["a", "b"]);
+    let client_config = config_with_version(false, GREASE_VERSION);
+    let mut sim =
+        simulator_with_config(&opts, vec![Link::new(ms(10)); 2], 79, client_config, |s| s);
+
+    // The server answers the reserved version with a version negotiation,
+    // and the client starts over with version 1.
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    let handshake = sim.client().report().handshake_time.unwrap();
+    assert!(handshake >= ms(40), "handshake took {:?}", handshake);
+}
+
+#[test]
+fn initial_cwnd_reported_per_path() {
+    let opts = options(&["a", "b"]);
+    let mut client_config = config(false);
+    client_config.set_initial_congestion_window_packets(4);
+
+    let mut sim =
+        simulator_with_config(&opts, vec![Link::new(ms(10)); 2], 103, client_config, |s| s);
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    // 4 packets of quiche's 1200 bytes, before anything was acknowledged.
+    let cwnds = &sim.client().report().initial_cwnds;
+    assert_eq!(cwnds[0], ((addr(9000), addr(8000)), 4 * 1200));
+    assert_eq!(cwnds.len(), 2);
+}
+
+#[test]
+fn bursts_capped_by_override() {
+    let opts = ClientOptions {
+        messages: vec![vec![b'x'; 100_000]],
+        burst_override: Some(2),
+        ..Default::default()
+    };
+
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 109, |s| s);
+    let completed = sim.run(1, Duration::from_secs(60));
+    assert_eq!(completed.len(), 1);
+
+    // The message takes far more than 2 packets, sent 2 at a time.
+    let bursts = sim.client().report().bursts;
+    assert_eq!(bursts.max_burst, 2);
+    assert!(bursts.capped > 0);
+}
+
+#[test]
+fn baseline_runs_same_workload_on_one_path() {
+    let opts = options(&["hello", "multipath", "world"]);
+    let workload = Workload::from_options(&opts, 107).unwrap();
+    let links = vec![Link::new(ms(10)); 3];
+
+    let run = |workload: &Workload| {
+        let mut sim = simulate_workload(workload, links.clone(), 107, config(false), |s| s);
+
+        let completed = sim.run_until_closed(Duration::from_secs(60));
+        assert_eq!(completed.len(), workload.messages.len());
+        assert!(sim.client().is_finished());
+
+        sim.into_client().into_report()
+    };
+
+    let multipath = run(&workload);
+    let single_path = run(&workload.single_path());
+    assert_eq!(multipath.path_usage.used, 3);
+    assert_eq!(single_path.path_usage.used, 1);
+
+    let comparison = Comparison::new(&multipath, &single_path);
+    assert_eq!(comparison.multipath.throughput.app_bytes, 19);
+    assert_eq!(
+        comparison.single_path.throughput.app_bytes,
+        comparison.multipath.throughput.app_bytes
+    );
+    assert_eq!(comparison.single_path.delivered, 3);
+
+    let printed = comparison.to_string();
+    assert!(printed.contains("multipath"));
+    assert!(printed.contains("single-path"));
+    assert!(printed.contains("application bytes delivered: 19 multipath, 19 single-path"));
+}
+
+#[test]
+fn live_events_sample_paths_and_messages() {
+    let opts = ClientOptions {
+        json_events: true,
+        stats_interval_ms: 20,
+        ..options(&["hello", "world"])
+    };
+    let mut sim = simulator(&opts, vec![Link::new(ms(10)); 2], 109, |s| s);
+
+    let completed = sim.run(opts.messages.len(), Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    let mut indices: Vec<usize> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::MessageCompleted { index, bytes, .. } => Some((*index, *bytes)),
+            _ => None,
+        })
+        .map(|(index, bytes)| {
+            assert_eq!(bytes, opts.messages[index].len());
+            index
+        })
+        .collect();
+    indices.sort();
+    assert_eq!(indices, [0, 1]);
+
+    // Every path is sampled from the start.
+    let sampled: HashSet<_> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::PathStats(sample) => Some(sample.local),
+            _ => None,
+        })
+        .collect();
+    assert!(sampled.contains(&addr(9000)));
+}
+
+#[test]
+fn stream_state_freed_over_long_run() {
+    const MESSAGES: usize = 100_000;
+    const IN_FLIGHT: usize = 16;
+
+    let opts = ClientOptions {
+        messages: (0..MESSAGES).map(|i| i.to_string().into_bytes()).collect(),
+        max_concurrent_streams: Some(IN_FLIGHT),
+        keep_open: true,
+        json_events: true,
+        stats_interval_ms: 100,
+        ..Default::default()
+    };
+    let mut workload = Workload::from_options(&opts, 113).unwrap();
+    workload.fit_paths(1);
+
+    let mut sim = simulate_workload(&workload, vec![Link::new(ms(1))], 113, config(false), |s| s);
+    let completed = sim.run(MESSAGES, Duration::from_secs(3600));
+    assert_eq!(completed.len(), MESSAGES);
+
+    // The streams are freed as the messages complete, quiche collecting
+    // them as fast: none pile up waiting for their acknowledgment.
+    let stages: Vec<StreamStages> = sim
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::StreamStages(stages) => Some(*stages),
+            _ => None,
+        })
+        .collect();
+    assert!(stages.len() > 10);
+    assert!(
+        stages.iter().all(|s| s.total() <= 2 * IN_FLIGHT),
+        "{:?}",
+        stages
+    );
+
+    // Nothing is left once the last acknowledgments arrive.
+    sim.run(1, ms(500));
+    assert_eq!(sim.client().stream_stages(), StreamStages::default());
+}
+
+#[test]
+fn path_schedule_followed() {
+    const MESSAGES: usize = 400;
+
+    let opts = ClientOptions {
+        messages: (0..MESSAGES).map(|i| i.to_string().into_bytes()).collect(),
+        max_concurrent_streams: Some(1),
+        path_schedule: Some("0:0-2s,1:2s-4s,0:4s-".parse().unwrap()),
+        ..Default::default()
+    };
+    let mut workload = Workload::from_options(&opts, 137).unwrap();
+    workload.fit_paths(2);
+
+    let links = vec![Link::new(ms(10)), Link::new(ms(10))];
+    let mut sim = simulate_workload(&workload, links, 137, config(false), |s| s);
+
+    let completed = sim.run(MESSAGES, Duration::from_secs(60));
+    assert_eq!(completed.len(), MESSAGES);
+
+    // Nothing stalls at the boundaries: the path of each window is
+    // validated ahead, so no message waits for a probe.
+    let slowest = completed.iter().map(|c| c.record.latency).max().unwrap();
+    assert!(slowest < ms(200), "{:?}", slowest);
+
+    // Path 1 is probed before its window, then migrated to.
+    let position = |matches: &dyn Fn(&Event) -> bool| sim.events().iter().position(matches);
+    let probed = position(&|e| matches!(e, Event::PathProbed(local, _) if *local == addr(9001)));
+    let migrated = position(&|e| matches!(e, Event::Migrated(local, _) if *local == addr(9001)));
+    assert!(probed.unwrap() < migrated.unwrap());
+
+    sim.run(1, Duration::from_secs(5));
+
+    let windows = &sim.client().report().path_windows;
+    assert_eq!(windows.len(), 3, "{:?}", windows);
+
+    for window in windows {
+        assert!(window.ready, "{}", window);
+        assert!(
+            window.switch_delay.is_some_and(|d| d < ms(100)),
+            "{}",
+            window
+        );
+
+        // Only the echoes in flight at the handover arrive on the other
+        // path.
+        assert!(
+            window.stream_bytes > 10 * window.stream_elsewhere,
+            "{}",
+            window
+        );
+    }
+}
+
+#[test]
+fn client_config_customized_after_flags() {
+    let opts = options(&["hello"]);
+
+    // The closure runs once the flags are applied, so it replaces the
+    // protocol of the target.
+    let client_config = opts
+        .quiche_config(&Target::default(), false, |config| {
+            config.set_application_protos(&[b"echo/1"]).unwrap()
+        })
+        .unwrap();
+
+    let apps = [App::new("echo/1", Handler::Echo)];
+    let mut sim = simulator_with_config(&opts, vec![Link::new(ms(10))], 139, client_config, |s| {
+        s.with_apps(&apps, 1 << 20)
+    });
+
+    let completed = sim.run(1, Duration::from_secs(60));
+    assert_eq!(completed.len(), 1);
+    assert_eq!(sim.server().handler(), Some(Handler::Echo));
+}
+
+#[test]
+fn path_delivery_shows_lossy_path() {
+    const MESSAGES: usize = 40;
+
+    let opts = ClientOptions {
+        messages: (0..MESSAGES).map(|i| vec![i as u8; 4000]).collect(),
+        ..Default::default()
+    };
+    let mut workload = Workload::from_options(&opts, 141).unwrap();
+    workload.fit_paths(2);
+
+    let links = vec![Link::new(ms(10)), Link::new(ms(10)).loss(0.2)];
+    let mut sim = simulate_workload(&workload, links, 141, config(false), |s| s);
+
+    let completed = sim.run(MESSAGES, Duration::from_secs(60));
+    assert_eq!(completed.len(), MESSAGES);
+    sim.run(1, Duration::from_secs(5));
+
+    let delivery = &sim.client().report().path_delivery;
+    let clean = delivery.iter().find(|d| d.path.0 == addr(9000)).unwrap();
+    let lossy = delivery.iter().find(|d| d.path.0 == addr(9001)).unwrap();
+
+    assert!(clean.sent_bytes > 0 && lossy.sent_bytes > 0);
+    assert!(lossy.lost_bytes > 0, "{}", lossy);
+    assert!(lossy.ratio() < clean.ratio(), "{} / {}", lossy, clean);
+}
+
+#[test]
+fn run_completion_reaches_server() {
+    let run_id: RunId = "ci-42".parse().unwrap();
+    let opts = ClientOptions {
+        run_id: Some(run_id.clone()),
+        ..options(&["hello", "world!"])
+    };
+    let mut sim = simulator(&opts, vec![Link::new(ms(10))], 143, |s| s);
+
+    let completed = sim.run(2, Duration::from_secs(60));
+    assert_eq!(completed.len(), 2);
+
+    // The server knows the run from its start, and has not been told yet
+    // that it completed.
+    assert_eq!(sim.server().run().run_id, Some(run_id.clone()));
+    assert!(!sim.server().run().is_clean());
+
+    sim.run(1, Duration::from_secs(5));
+    assert!(sim.client().conn().is_closed());
+
+    let run = sim.server().run();
+    assert_eq!(
+        run.completion,
+        Some(Completion {
+            run_id: run_id.clone(),
+            messages: 2,
+            bytes: 11,
+        })
+    );
+
+    let report = sim.client().report();
+    assert_eq!(report.run_id, Some(run_id));
+    assert!(report.completion_acked);
+}
+
+#[test]
+fn flow_blocking_reported() {
+    let opts = ClientOptions {
+        messages: vec![vec![b'x'; 50_000]; 4],
+        flow_blocking: true,
+        ..Default::default()
+    };
+    let workload = Workload::from_options(&opts, 149).unwrap();
+
+    // A window far below the messages, the streams each filling theirs.
+    let mut server_config = config(true);
+    server_config.set_initial_max_data(16_000);
+    server_config.set_initial_max_stream_data_bidi_remote(8_000);
+
+    let links = vec![Link::new(ms(10))];
+    let mut sim = simulate_against(&workload, links, 149, config(false), server_config, |s| s);
+
+    let completed = sim.run(4, Duration::from_secs(60));
+    assert_eq!(completed.len(), 4);
+
+    sim.run(1, Duration::from_secs(5));
+    assert!(sim.client().conn().is_closed());
+
+    let flow = sim.client().report().flow_blocking.clone().unwrap();
+    assert!(flow.connection.blocks > 0, "{}", flow.connection);
+    assert!(!flow.streams.is_empty());
+
+    // The server raises the window once the data reached it, the client
+    // resumes within a few RTTs.
+    let rtts = flow.connection.mean_rtts().unwrap();
+    assert!(rtts > 0.0 && rtts < 10.0, "{}", flow.connection);
+}
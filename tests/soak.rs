@@ -0,0 +1,81 @@
+use std::{
+    io::{BufRead, BufReader},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use quiche_test::{
+    report::{MessageRecord, PathList, RecordWriter, TargetReport, RECENT_RECORDS},
+    shared::run_clock::RunClock,
+};
+
+const MESSAGES: usize = 1_000_000;
+
+/// Resident set size of the process, in kB.
+fn rss_kb() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap()
+}
+
+// The only test of this file, so that nothing else runs in the process
+// while its memory is measured.
+#[test]
+fn report_memory_bounded_over_million_messages() {
+    let path = std::env::temp_dir().join(format!("quiche-test-soak-{}", std::process::id()));
+
+    let mut report = TargetReport::new("soak", SocketAddr::from(([127, 0, 0, 1], 8000)), MESSAGES);
+    report.records = Some(RecordWriter::open(&path, RunClock::new(Instant::now())).unwrap());
+
+    let before = rss_kb();
+
+    for index in 0..MESSAGES {
+        report.record(MessageRecord {
+            index,
+            stream_id: index as u64 * 4,
+            paths: PathList::default(),
+            frame: None,
+            latency: Duration::from_micros(index as u64 % 100_000),
+            queue_wait: Duration::ZERO,
+        });
+    }
+
+    // Keeping every record would take over 50 MB.
+    let grown = rss_kb().saturating_sub(before);
+    assert!(grown < 8 * 1024, "RSS grew by {} kB", grown);
+
+    assert_eq!(report.latency.count(), MESSAGES as u64);
+    assert_eq!(report.recent.len(), RECENT_RECORDS);
+    assert_eq!(report.recent.back().unwrap().index, MESSAGES - 1);
+
+    // Every message was streamed out once, after the header of the clock.
+    drop(report);
+    let mut seen = vec![false; MESSAGES];
+
+    let file = std::fs::File::open(&path).unwrap();
+    let mut lines = BufReader::new(file).lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .unwrap()
+        .contains("\"event\":\"run_clock\""));
+
+    for line in lines {
+        let line = line.unwrap();
+        let index: usize = line
+            .split_once("\"message\":")
+            .and_then(|(_, rest)| rest.split(',').next())
+            .and_then(|v| v.parse().ok())
+            .unwrap();
+
+        assert!(!seen[index], "message {} written twice", index);
+        seen[index] = true;
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(seen.iter().all(|s| *s));
+}
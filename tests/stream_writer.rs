@@ -0,0 +1,100 @@
+use quiche_test::shared::{
+    stream_writer::{Progress, StreamWriter},
+    testing::MockConn,
+};
+
+#[test]
+fn capacity_limited() {
+    let mut conn = MockConn::default();
+    conn.stream(0).capacity = Some(4);
+
+    let mut writer = StreamWriter::new(0, &b"hello world"[..]);
+
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Written(4));
+    assert_eq!(conn.stream(0).data, b"hell");
+    assert!(!conn.stream(0).fin);
+
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Blocked);
+
+    conn.stream(0).capacity = Some(100);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Finished);
+    assert_eq!(conn.stream(0).data, b"hello world");
+    assert!(conn.stream(0).fin);
+    assert_eq!(writer.written(), 11);
+
+    // The fin is only sent once.
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Finished);
+}
+
+#[test]
+fn zero_capacity() {
+    let mut conn = MockConn::default();
+    conn.stream(0).capacity = Some(0);
+    conn.stream(4).capacity = Some(0);
+
+    let mut writer = StreamWriter::new(0, &b"data"[..]);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Blocked);
+    assert!(conn.stream(0).data.is_empty());
+
+    // An empty source only needs to send its fin.
+    let mut empty = StreamWriter::new(4, &b""[..]);
+    assert_eq!(empty.poll_write(&mut conn).unwrap(), Progress::Finished);
+    assert!(conn.stream(4).fin);
+}
+
+#[test]
+fn reset() {
+    let mut conn = MockConn::default();
+    conn.stream(0).capacity = Some(2);
+
+    let mut writer = StreamWriter::new(0, &b"data"[..]);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Written(2));
+
+    conn.stream(0).stopped = Some(42);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Stopped(42));
+    assert!(writer.is_done());
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Stopped(42));
+}
+
+#[test]
+fn kept_open_then_finished() {
+    let mut conn = MockConn::default();
+
+    let mut writer = StreamWriter::new(0, &b"hello "[..]).keep_open();
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Drained);
+    assert_eq!(conn.stream(0).data, b"hello ");
+    assert!(!conn.stream(0).fin);
+    assert!(!writer.is_done());
+
+    // Nothing more to write until resumed.
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Drained);
+
+    writer.resume(&b"world"[..]);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Drained);
+    assert_eq!(conn.stream(0).data, b"hello world");
+    assert!(!conn.stream(0).fin);
+
+    // The fin is sent on its own.
+    writer.finish();
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Finished);
+    assert_eq!(conn.stream(0).data, b"hello world");
+    assert!(conn.stream(0).fin);
+    assert!(writer.is_done());
+    assert_eq!(writer.written(), 11);
+}
+
+#[test]
+fn chunked() {
+    let mut conn = MockConn::default();
+    conn.stream(0).capacity = Some(6);
+
+    let mut writer = StreamWriter::new(0, &b"hello world"[..]).chunk_size(4);
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Written(6));
+    assert_eq!(conn.stream(0).writes, [4, 2]);
+
+    conn.stream(0).capacity = None;
+    assert_eq!(writer.poll_write(&mut conn).unwrap(), Progress::Finished);
+    assert_eq!(conn.stream(0).writes, [4, 2, 4, 1]);
+    assert_eq!(conn.stream(0).data, b"hello world");
+    assert!(conn.stream(0).fin);
+}
@@ -0,0 +1,50 @@
+use quiche_test::shared::{
+    file_transfer::sha256,
+    transform::{StreamTransform, Transform},
+};
+
+#[test]
+fn transforms_parsed() {
+    for transform in [Transform::Upper, Transform::Reverse, Transform::Sha256] {
+        assert_eq!(transform.name().parse(), Ok(transform));
+    }
+
+    assert!("lower".parse::<Transform>().is_err());
+}
+
+#[test]
+fn whole_messages() {
+    assert_eq!(Transform::Upper.apply(b"abc\0\xffZ"), b"ABC\0\xffZ");
+    assert_eq!(Transform::Reverse.apply(b"abc\0"), b"\0cba");
+    assert_eq!(Transform::Sha256.apply(b"abc"), sha256(b"abc"));
+    assert_eq!(Transform::Sha256.apply(b"").len(), 32);
+}
+
+#[test]
+fn upper_streamed_chunk_by_chunk() {
+    let mut stream = StreamTransform::new(Transform::Upper);
+    assert_eq!(stream.update(b"hel"), b"HEL");
+    assert_eq!(stream.update(b"lo"), b"LO");
+    assert!(stream.finish().is_empty());
+}
+
+#[test]
+fn chunked_transforms_match_whole_messages() {
+    let data: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+
+    for transform in [Transform::Upper, Transform::Reverse, Transform::Sha256] {
+        let mut stream = StreamTransform::new(transform);
+        let mut out = Vec::new();
+
+        // Chunks of uneven sizes, as read from a stream.
+        for chunk in data.chunks(1 + 65535 / 3) {
+            out.extend_from_slice(&stream.update(chunk));
+        }
+        out.extend_from_slice(&stream.finish());
+
+        assert_eq!(out, transform.apply(&data), "{}", transform);
+    }
+
+    // The digest of the whole message, not of its last chunk.
+    assert_eq!(Transform::Sha256.apply(&data), sha256(&data));
+}
@@ -0,0 +1,88 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use quiche_test::shared::{
+    testing::Socks5Responder,
+    transport::{DatagramTransport, Socks5},
+};
+
+fn any_port() -> SocketAddr {
+    "127.0.0.1:0".parse().unwrap()
+}
+
+/// Echoes one datagram and returns the address it came from.
+fn echo_once(socket: &UdpSocket) -> SocketAddr {
+    let mut buf = [0; 1500];
+    let (len, from) = socket.recv_from(&mut buf).unwrap();
+    socket.send_to(&buf[..len], from).unwrap();
+    from
+}
+
+/// Waits for a datagram on the non-blocking `transport`.
+fn recv(transport: &dyn DatagramTransport) -> (Vec<u8>, SocketAddr) {
+    let mut buf = [0; 1500];
+    let deadline = Instant::now() + Duration::from_secs(2);
+
+    loop {
+        match transport.recv_from(&mut buf) {
+            Ok((len, from)) => return (buf[..len].to_vec(), from),
+
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                assert!(Instant::now() < deadline, "nothing received");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            Err(e) => panic!("recv failed: {}", e),
+        }
+    }
+}
+
+fn echo_server() -> UdpSocket {
+    let server = UdpSocket::bind(any_port()).unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    server
+}
+
+#[test]
+fn datagrams_relayed_by_socks5() {
+    let proxy = Socks5Responder::start().unwrap();
+    let server = echo_server();
+    let server_addr = server.local_addr().unwrap();
+
+    let transport = Socks5::associate(any_port(), proxy.addr()).unwrap();
+
+    assert_eq!(transport.send_to(b"ping", server_addr).unwrap(), 4);
+
+    // The server only sees the proxy.
+    let relay = echo_once(&server);
+    assert_ne!(relay, transport.local_addr());
+
+    assert_eq!(recv(&transport), (b"ping".to_vec(), server_addr));
+}
+
+#[test]
+fn one_association_per_socket() {
+    let proxy = Socks5Responder::start().unwrap();
+    let server = echo_server();
+    let server_addr = server.local_addr().unwrap();
+
+    let first = Socks5::associate(any_port(), proxy.addr()).unwrap();
+    let second = Socks5::associate(any_port(), proxy.addr()).unwrap();
+    assert_eq!(proxy.associations(), 2);
+
+    first.send_to(b"a", server_addr).unwrap();
+    let first_relay = echo_once(&server);
+
+    second.send_to(b"b", server_addr).unwrap();
+    let second_relay = echo_once(&server);
+
+    // Each path keeps its own address behind the proxy.
+    assert_ne!(first_relay, second_relay);
+
+    assert_eq!(recv(&first), (b"a".to_vec(), server_addr));
+    assert_eq!(recv(&second), (b"b".to_vec(), server_addr));
+}